@@ -1,10 +1,115 @@
+use std::path::PathBuf;
+
 use clap::{Args, Parser, Subcommand, ValueEnum};
 
+use crate::entities::devices::ThermostatMode;
+use crate::entities::manager::DEFAULT_BACKUP_KEEP;
+
 #[derive(Args, Debug)]
 pub struct MakeMeasure {
     /// Device id of the device where the measure will be proceeded
     #[arg(short = 'i', long, value_name = "device_id")]
+    #[arg(required_unless_present = "selector", conflicts_with = "selector")]
+    pub device_id: Option<String>,
+
+    /// Measure by all the devices matching the label selector, e.g. `floor=2,!critical`
+    #[arg(short = 'l', long, value_name = "selector")]
+    pub selector: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct LightCommand {
+    /// Device id of the light to be adjusted
+    #[arg(short = 'i', long, value_name = "device_id")]
+    #[arg(required_unless_present = "selector", conflicts_with = "selector")]
+    pub device_id: Option<String>,
+
+    /// Adjust all the lights matching the label selector, e.g. `floor=2,!critical`
+    #[arg(short = 'l', long, value_name = "selector")]
+    pub selector: Option<String>,
+
+    /// The brightness from 0 to 100 percents
+    #[arg(short = 'b', long, value_name = "percents")]
+    #[arg(value_parser = clap::value_parser!(u8).range(0..=100))]
+    #[arg(required_unless_present = "color_temperature")]
+    pub brightness: Option<u8>,
+
+    /// The colour temperature in kelvins, e.g. 2700 for the warm white
+    #[arg(short = 'k', long, value_name = "kelvins")]
+    pub color_temperature: Option<u16>,
+
+    /// Change the brightness gradually over the given number of seconds
+    #[arg(short = 't', long, value_name = "seconds", requires = "brightness")]
+    pub transition: Option<f64>,
+}
+
+#[derive(Args, Debug)]
+pub struct SocketCommand {
+    /// Device id of the socket to be adjusted
+    #[arg(short = 'i', long, value_name = "device_id")]
+    pub device_id: String,
+
+    /// The power of the load plugged into the socket, in watts
+    #[arg(short = 'p', long, value_name = "watts")]
+    pub power: f32,
+}
+
+#[derive(Args, Debug)]
+pub struct ThermostatCommand {
+    /// Device id of the thermostat to be configured
+    #[arg(short = 'i', long, value_name = "device_id")]
     pub device_id: String,
+
+    /// The temperature to keep, in °C
+    #[arg(short = 't', long, value_name = "celsius")]
+    pub target: Option<f32>,
+
+    /// The allowed deviation from the target, in °C
+    #[arg(long, value_name = "celsius")]
+    pub hysteresis: Option<f32>,
+
+    /// The mode of the thermostat: heat, eco or off
+    #[arg(short = 'm', long, value_name = "mode")]
+    pub mode: Option<ThermostatMode>,
+
+    /// The thermometer or the environment sensor of the same room to read the temperature from
+    #[arg(long, value_name = "device_id")]
+    pub thermometer: Option<String>,
+
+    /// The socket of the same room with a heater plugged in, might be repeated. The given
+    /// sockets replace the linked ones
+    #[arg(long = "heater", value_name = "device_id")]
+    pub heaters: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct EnergyQuery {
+    /// The id or the path of the entity
+    #[arg(short, long, value_name = "id")]
+    pub id: String,
+
+    /// Count only the energy consumed since the given point in time, either RFC 3339 timestamp
+    /// or `YYYY-MM-DD HH:MM:SS` in UTC
+    #[arg(long, value_name = "timestamp")]
+    pub since: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum EnergyCommand {
+    /// The energy consumed by all the metered devices of the home
+    Home(EnergyQuery),
+
+    /// The energy consumed by all the metered devices of the room
+    Room(EnergyQuery),
+
+    /// The energy consumed by the metered device
+    Device(EnergyQuery),
+}
+
+#[derive(Args, Debug)]
+pub struct EnergyCommandWrapper {
+    #[command(subcommand)]
+    pub command: EnergyCommand,
 }
 
 #[derive(Args, Debug)]
@@ -32,15 +137,17 @@ pub struct CreateRoom {
     pub description: Option<String>,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
-pub enum DeviceType {
-    Socket,
-    Thermometer,
-}
+/// The device types are open, see [register_device](crate::entities::devices::register_device),
+/// so the type is parsed against the registered ones
+pub use crate::entities::devices::DeviceType;
+
+/// The options backed by the manager and its storage are defined next to them, the cli only
+/// parses them
+pub use crate::entities::manager::{DataFormat, SortKey, StoreType};
 
 #[derive(Args, Debug)]
 pub struct CreateDevice {
-    /// The type of the creating device
+    /// The type of the creating device, e.g. `socket`, `thermometer` or any registered one
     #[arg(short, long, value_name = "device_type")]
     pub r#type: DeviceType,
 
@@ -74,6 +181,10 @@ pub struct EntityId {
     /// The id of the entity to be deleted
     #[arg(short, long, value_name = "id")]
     pub id: String,
+
+    /// Print the entity and all its content which would be removed, but remove nothing
+    #[arg(long)]
+    pub dry_run: bool,
 }
 
 #[derive(Subcommand, Debug)]
@@ -99,11 +210,28 @@ pub enum DeviceStatusAction {
     Disable,
 }
 
+#[derive(Args, Debug)]
+pub struct EntityStatusCommand {
+    /// The id of the entity to be displayed
+    #[arg(short, long, value_name = "id")]
+    #[arg(required_unless_present = "selector", conflicts_with = "selector")]
+    pub id: Option<String>,
+
+    /// Display all the entities matching the label selector, e.g. `floor=2,!critical`
+    #[arg(short = 'l', long, value_name = "selector")]
+    pub selector: Option<String>,
+}
+
 #[derive(Args, Debug)]
 pub struct DeviceStatusCommand {
     /// The id of the entity to be displayed
     #[arg(short = 'i', long, value_name = "id")]
-    pub device_id: String,
+    #[arg(required_unless_present = "selector", conflicts_with = "selector")]
+    pub device_id: Option<String>,
+
+    /// Display or switch all the devices matching the label selector, e.g. `floor=2,!critical`
+    #[arg(short = 'l', long, value_name = "selector")]
+    pub selector: Option<String>,
 
     /// Enable given device
     #[arg(short = 'e', long)]
@@ -117,10 +245,10 @@ pub struct DeviceStatusCommand {
 #[derive(Subcommand, Debug)]
 pub enum StatusCommand {
     /// Status of the smart home
-    Home(EntityId),
+    Home(EntityStatusCommand),
 
     /// Status of the room in smart home
-    Room(EntityId),
+    Room(EntityStatusCommand),
 
     /// Status of the device in smart home
     Device(DeviceStatusCommand),
@@ -138,16 +266,75 @@ pub struct EntityRemoveCommandWrapper {
     pub command: RemoveEntityCommand,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum StatusFilter {
+    Enabled,
+    Disabled,
+}
+
+/// The options common for all the list commands
+#[derive(Args, Debug, Default)]
+pub struct ListOptions {
+    /// List only the entities matching the label selector, e.g. `floor=2,!critical`
+    #[arg(short = 'l', long, value_name = "selector")]
+    pub selector: Option<String>,
+
+    /// Sort the entities by the given key, by default they are listed in the order they are
+    /// stored
+    #[arg(short, long, value_enum, value_name = "key")]
+    pub sort: Option<SortKey>,
+
+    /// List at most the given number of the entities
+    #[arg(long, value_name = "limit")]
+    pub limit: Option<usize>,
+
+    /// Skip the given number of the entities
+    #[arg(long, value_name = "offset", default_value_t = 0)]
+    pub offset: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct ListRooms {
+    /// List only the rooms of the given home
+    #[arg(long, value_name = "home_id")]
+    pub home: Option<String>,
+
+    #[command(flatten)]
+    pub options: ListOptions,
+}
+
+#[derive(Args, Debug)]
+pub struct ListDevices {
+    /// List only the devices of the given type
+    #[arg(short, long, value_name = "device_type")]
+    pub r#type: Option<DeviceType>,
+
+    /// List only the devices in the given home
+    #[arg(long, value_name = "home_id")]
+    pub home: Option<String>,
+
+    /// List only the devices in the given room
+    #[arg(long, value_name = "room_id")]
+    pub room: Option<String>,
+
+    /// List only the switched on (enabled) or switched off (disabled) devices
+    #[arg(long, value_enum, value_name = "status")]
+    pub status: Option<StatusFilter>,
+
+    #[command(flatten)]
+    pub options: ListOptions,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum ListEntityCommand {
     /// List all homes
-    Homes,
+    Homes(ListOptions),
 
     /// List all rooms
-    Rooms,
+    Rooms(ListRooms),
 
     /// List all devices
-    Devices,
+    Devices(ListDevices),
 }
 
 #[derive(Args, Debug)]
@@ -156,6 +343,242 @@ pub struct ListEntityCommandWrapper {
     pub command: ListEntityCommand,
 }
 
+#[derive(Args, Debug)]
+pub struct RenameCommand {
+    /// The id of the home, room or device to be renamed
+    #[arg(short, long, value_name = "id")]
+    pub id: String,
+
+    /// The new name
+    #[arg(short, long, value_name = "name")]
+    pub name: String,
+}
+
+#[derive(Args, Debug)]
+pub struct EditCommand {
+    /// The id of the home, room or device to be edited
+    #[arg(short, long, value_name = "id")]
+    pub id: String,
+
+    /// The new name
+    #[arg(short, long, value_name = "name")]
+    pub name: Option<String>,
+
+    /// The new description, the empty one removes the description
+    #[arg(short, long, value_name = "description")]
+    pub description: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct LabelCommand {
+    /// The id of the home, room or device to be labeled
+    #[arg(short, long, value_name = "id")]
+    pub id: String,
+
+    /// The labels to be set, either `key=value` or just `key`
+    #[arg(value_name = "label")]
+    pub labels: Vec<String>,
+
+    /// The key of the label to be removed, might be repeated
+    #[arg(short, long, value_name = "key")]
+    pub remove: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct MoveRoom {
+    /// The id of the room to be moved
+    #[arg(short, long, value_name = "id")]
+    pub id: String,
+
+    /// The home where the room will be located
+    #[arg(long, value_name = "home_id")]
+    pub home_id: String,
+}
+
+#[derive(Args, Debug)]
+pub struct MoveDevice {
+    /// The id of the device to be moved
+    #[arg(short, long, value_name = "id")]
+    pub id: String,
+
+    /// The room where the device will be located
+    #[arg(long, value_name = "room_id")]
+    pub room_id: String,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MoveEntityCommand {
+    /// Move a room to another home
+    Room(MoveRoom),
+
+    /// Move a device to another room
+    Device(MoveDevice),
+}
+
+#[derive(Args, Debug)]
+pub struct EntityMoveCommandWrapper {
+    #[command(subcommand)]
+    pub command: MoveEntityCommand,
+}
+
+#[derive(Args, Debug)]
+pub struct HistoryCommand {
+    /// Show the home with given id as it was at the given point in time
+    #[arg(long, value_name = "home_id", requires = "at")]
+    pub home_id: Option<String>,
+
+    /// The point in time, either RFC 3339 timestamp or `YYYY-MM-DD HH:MM:SS` in UTC
+    #[arg(long, value_name = "timestamp")]
+    pub at: Option<String>,
+
+    /// Roll the home back to the state it had at the given point in time
+    #[arg(long, requires = "home_id")]
+    pub restore: bool,
+
+    /// Show only the given number of the latest changes
+    #[arg(short = 'n', long, value_name = "limit")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Args, Debug)]
+pub struct MigrateCommand {
+    /// Only show the migration steps and how many devices each of them would rewrite, nothing
+    /// is written
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Replace the smart home with the state file written by the previous versions, e.g. by the
+    /// hw-006 or hw-007 crates
+    #[arg(long, value_name = "file")]
+    pub import: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct ExportCommand {
+    /// The format of the export, by default it's guessed by the extension of the output file,
+    /// or json
+    #[arg(short, long, value_enum, value_name = "format")]
+    pub format: Option<DataFormat>,
+
+    /// Write the export to the file instead of printing it
+    #[arg(short, long, value_name = "file")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct ImportCommand {
+    /// The file written by the `export` command
+    #[arg(value_name = "file")]
+    pub file: PathBuf,
+
+    /// The format of the file, by default it's guessed by the extension of the file
+    #[arg(short, long, value_enum, value_name = "format")]
+    pub format: Option<DataFormat>,
+
+    /// Add the imported homes to the smart home, the homes with the same ids are replaced. It's
+    /// the default mode
+    #[arg(long, conflicts_with = "replace")]
+    pub merge: bool,
+
+    /// Replace the whole smart home with the imported homes
+    #[arg(long)]
+    pub replace: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct PlanCommand {
+    /// The description of the smart home in the yaml, json or toml format, the format is
+    /// guessed by the extension of the file
+    #[arg(short, long, value_name = "file")]
+    pub file: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct CreateBackup {
+    /// A note to tell the backup apart, e.g. `before cleanup`
+    #[arg(short, long, value_name = "label")]
+    pub label: Option<String>,
+
+    /// The number of the latest backups to keep, the older ones are removed. Zero keeps all of
+    /// them
+    #[arg(long, value_name = "count", default_value_t = DEFAULT_BACKUP_KEEP)]
+    pub keep: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct RestoreBackup {
+    /// The id of the backup, see `backup list`
+    #[arg(value_name = "id")]
+    pub id: String,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BackupCommand {
+    /// Copy all the files of the repository into a new backup
+    Create(CreateBackup),
+
+    /// List the backups from the oldest to the latest one
+    List,
+
+    /// Verify the backup and replace the whole smart home with its state. The restore might be
+    /// undone as any other change
+    Restore(RestoreBackup),
+}
+
+#[derive(Args, Debug)]
+pub struct BackupCommandWrapper {
+    #[command(subcommand)]
+    pub command: BackupCommand,
+}
+
+#[derive(Args, Debug)]
+pub struct TrashId {
+    /// The id of the removed home, room or device
+    #[arg(value_name = "id")]
+    pub id: String,
+}
+
+#[derive(Args, Debug)]
+pub struct HideTrash {
+    /// Hide the entity with the given id only
+    #[arg(value_name = "id")]
+    pub id: Option<String>,
+
+    /// Hide only the entities removed more than the given number of days ago
+    #[arg(long, value_name = "days")]
+    pub older_than: Option<i64>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TrashCommand {
+    /// List the removed entities, they are kept for 30 days
+    List,
+
+    /// Put the removed entity back to its parent with the same id
+    Restore(TrashId),
+
+    /// Take the entities out of the trash list. Their data is still kept in the journal, so
+    /// they might be found by the `history`
+    Hide(HideTrash),
+
+    /// Drop the content of the entities removed more than 30 days ago from the journal and the
+    /// snapshots, the server does it once a day on its own
+    Purge,
+}
+
+#[derive(Args, Debug)]
+pub struct TrashCommandWrapper {
+    #[command(subcommand)]
+    pub command: TrashCommand,
+}
+
+#[derive(Args, Debug)]
+pub struct FsckCommand {
+    /// Repair the found problems and rewrite the state in the current format
+    #[arg(long)]
+    pub fix: bool,
+}
+
 #[derive(Subcommand, Debug)]
 #[non_exhaustive]
 pub enum Command {
@@ -172,17 +595,86 @@ pub enum Command {
     /// Subcommand for removing an entity
     Remove(EntityRemoveCommandWrapper),
 
+    /// Change the name of an entity, the id is kept as is
+    Rename(RenameCommand),
+
+    /// Change the name and/or the description of an entity
+    Edit(EditCommand),
+
+    /// Set or remove the labels of an entity, without the labels just shows them
+    Label(LabelCommand),
+
+    /// Subcommand for moving an entity to another parent, the id is kept as is
+    Move(EntityMoveCommandWrapper),
+
     /// List all entities in the repository
     List(ListEntityCommandWrapper),
 
     /// Request measurement for specific device in the home
     Measure(MakeMeasure),
+
+    /// Set the brightness and the colour temperature of a light
+    Light(LightCommand),
+
+    /// Set the power of the load plugged into a socket
+    Socket(SocketCommand),
+
+    /// Set the target, the mode and the linked devices of a thermostat
+    Thermostat(ThermostatCommand),
+
+    /// Show the energy consumed by the sockets of a home, a room or by a single socket
+    Energy(EnergyCommandWrapper),
+
+    /// Show the journal of changes, or the state of a home at the given point in time
+    History(HistoryCommand),
+
+    /// Upgrade the stored state to the current format
+    Migrate(MigrateCommand),
+
+    /// Write the whole smart home in the json, yaml, toml or csv format
+    Export(ExportCommand),
+
+    /// Read the homes written by the `export` command
+    Import(ImportCommand),
+
+    /// Show the changes needed to make the smart home match the description in the file
+    Plan(PlanCommand),
+
+    /// Create, update and remove the entities to make the smart home match the description in
+    /// the file
+    Apply(PlanCommand),
+
+    /// Create, list and restore the backups of the repository
+    Backup(BackupCommandWrapper),
+
+    /// List, restore and hide the removed entities
+    Trash(TrashCommandWrapper),
+
+    /// Check the integrity of the stored state. The exit code is 1 if the problems were found
+    /// and fixed, and 2 if the problems are left as is
+    #[command(alias = "doctor")]
+    Fsck(FsckCommand),
+
+    /// Revert the latest change of the smart home
+    Undo,
+
+    /// Repeat the latest undone change of the smart home
+    Redo,
 }
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 #[command(propagate_version = true)]
 pub struct Arguments {
+    /// The storage type of the smart home repository, json by default
+    #[arg(long, value_name = "store", value_enum)]
+    pub store: Option<StoreType>,
+
+    /// The root directory of the smart home repository. By default, the nearest repository is
+    /// looked up from the current directory, the same way as git does
+    #[arg(long, value_name = "repo", global = true)]
+    pub repo: Option<PathBuf>,
+
     #[command(subcommand)]
     pub command: Command,
 }
@@ -1,35 +1,220 @@
 use std::io::Write;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 
 use crate::cli::*;
+use crate::entities::devices::Device;
 use crate::entities::manager::*;
+use crate::entities::{
+    format_labels, parse_label, LabelError, LabelSelector, Labels, ValidationError,
+};
 
 pub struct CommandHandler<'a> {
     output: &'a mut dyn Write,
-    smart_home_manager: SmartHomeManager,
+    smart_home_manager: Arc<SmartHomeManager>,
+    /// The backups of the repository, there are none for the remote and in-memory repositories
+    backups: Option<Backups>,
+    /// Whether the errors are prefixed by their codes for the remote clients
+    error_codes: bool,
+    exit_code: i32,
 }
 
 impl<'a> CommandHandler<'a> {
     pub fn new(output: &'a mut dyn Write, path: PathBuf) -> Self {
-        let smart_home_manager = SmartHomeManager::new(path);
+        let smart_home_manager = Arc::new(SmartHomeManager::new(path));
+        Self::with_manager(output, smart_home_manager)
+    }
 
+    pub fn with_manager(
+        output: &'a mut dyn Write,
+        smart_home_manager: Arc<SmartHomeManager>,
+    ) -> Self {
         Self {
             output,
             smart_home_manager,
+            backups: None,
+            error_codes: false,
+            exit_code: 0,
+        }
+    }
+
+    /// Enables the `backup` commands for the repository
+    pub fn with_backups(self, backups: Backups) -> Self {
+        Self {
+            backups: Some(backups),
+            ..self
         }
     }
 
-    pub fn process(&mut self, command: Command) {
+    /// Prefixes the validation errors by their codes, see [ValidationError::code], so the remote
+    /// clients might handle them without parsing the messages
+    pub fn with_error_codes(self) -> Self {
+        Self {
+            error_codes: true,
+            ..self
+        }
+    }
+
+    /// The exit code of the last processed command for the cli. As for now, only the `fsck`
+    /// command returns a non-zero code, all other commands just report their errors.
+    pub fn exit_code(&self) -> i32 {
+        self.exit_code
+    }
+
+    pub fn process(&mut self, mut command: Command) {
+        self.exit_code = 0;
+        if let Err(msg) = self.resolve_references(&mut command) {
+            self.write_response(&msg.to_string()).unwrap();
+            return;
+        }
+
         match command {
             Command::Init => self.initialize_smart_home(),
             Command::Status(wrapper) => self.status_command(wrapper.command),
             Command::New(wrapper) => self.handle_new_command(wrapper.command),
             Command::Remove(wrapper) => self.handle_remove_command(wrapper.command),
-            Command::Measure(wrapper) => self.handle_measure_command(&wrapper.device_id),
+            Command::Measure(wrapper) => self.handle_measure_command(wrapper),
+            Command::Light(command) => self.handle_light_command(command),
+            Command::Socket(command) => self.handle_socket_command(command),
+            Command::Thermostat(command) => self.handle_thermostat_command(command),
+            Command::Energy(wrapper) => self.handle_energy_command(wrapper.command),
+            Command::Rename(command) => self.handle_rename_command(command),
+            Command::Edit(command) => self.handle_edit_command(command),
+            Command::Label(command) => self.handle_label_command(command),
+            Command::Move(wrapper) => self.handle_move_command(wrapper.command),
             Command::List(entity) => self.handle_list_command(entity.command),
+            Command::History(command) => self.handle_history_command(command),
+            Command::Migrate(command) => self.handle_migrate_command(command),
+            Command::Fsck(command) => self.handle_fsck_command(command),
+            Command::Export(command) => self.handle_export_command(command),
+            Command::Import(command) => self.handle_import_command(command),
+            Command::Plan(command) => self.handle_plan_command(command, false),
+            Command::Apply(command) => self.handle_plan_command(command, true),
+            Command::Backup(wrapper) => self.handle_backup_command(wrapper.command),
+            Command::Trash(wrapper) => self.handle_trash_command(wrapper.command),
+            Command::Undo => self.handle_undo_command(false),
+            Command::Redo => self.handle_undo_command(true),
+        }
+    }
+
+    /// Replaces the paths and the patterns given instead of the ids with the ids of the
+    /// entities, see [FindFunctions] for the details of the addressing
+    fn resolve_references(&self, command: &mut Command) -> Result<()> {
+        let manager = &self.smart_home_manager;
+
+        match command {
+            Command::Status(wrapper) => match &mut wrapper.command {
+                StatusCommand::Home(home) => {
+                    if let Some(id) = home.id.as_mut() {
+                        *id = manager.resolve_home(id)?
+                    }
+                }
+                StatusCommand::Room(room) => {
+                    if let Some(id) = room.id.as_mut() {
+                        *id = manager.resolve_room(id)?
+                    }
+                }
+                StatusCommand::Device(device) => {
+                    if let Some(id) = device.device_id.as_mut() {
+                        *id = manager.resolve_device(id)?
+                    }
+                }
+            },
+            Command::New(wrapper) => match &mut wrapper.command {
+                CreateEntity::Home(_) => {}
+                CreateEntity::Room(room) => room.home_id = manager.resolve_home(&room.home_id)?,
+                CreateEntity::Device(device) => {
+                    device.room_id = manager.resolve_room(&device.room_id)?
+                }
+            },
+            Command::Remove(wrapper) => match &mut wrapper.command {
+                RemoveEntityCommand::Home(home) => home.id = manager.resolve_home(&home.id)?,
+                RemoveEntityCommand::Room(room) => room.id = manager.resolve_room(&room.id)?,
+                RemoveEntityCommand::Device(device) => {
+                    device.id = manager.resolve_device(&device.id)?
+                }
+            },
+            Command::Measure(measure) => {
+                if let Some(id) = measure.device_id.as_mut() {
+                    *id = manager.resolve_device(id)?
+                }
+            }
+            Command::Light(light) => {
+                if let Some(id) = light.device_id.as_mut() {
+                    *id = manager.resolve_device(id)?
+                }
+            }
+            Command::Socket(socket) => {
+                socket.device_id = manager.resolve_device(&socket.device_id)?
+            }
+            Command::Thermostat(thermostat) => {
+                thermostat.device_id = manager.resolve_device(&thermostat.device_id)?;
+                if let Some(id) = thermostat.thermometer.as_mut() {
+                    *id = manager.resolve_device(id)?
+                }
+                for id in thermostat.heaters.iter_mut() {
+                    *id = manager.resolve_device(id)?
+                }
+            }
+            Command::Energy(wrapper) => match &mut wrapper.command {
+                EnergyCommand::Home(home) => home.id = manager.resolve_home(&home.id)?,
+                EnergyCommand::Room(room) => room.id = manager.resolve_room(&room.id)?,
+                EnergyCommand::Device(device) => device.id = manager.resolve_device(&device.id)?,
+            },
+            Command::Rename(rename) => rename.id = manager.resolve_entity(&rename.id)?,
+            Command::Edit(edit) => edit.id = manager.resolve_entity(&edit.id)?,
+            Command::Label(label) => label.id = manager.resolve_entity(&label.id)?,
+            Command::Move(wrapper) => match &mut wrapper.command {
+                MoveEntityCommand::Room(room) => {
+                    room.id = manager.resolve_room(&room.id)?;
+                    room.home_id = manager.resolve_home(&room.home_id)?;
+                }
+                MoveEntityCommand::Device(device) => {
+                    device.id = manager.resolve_device(&device.id)?;
+                    device.room_id = manager.resolve_room(&device.room_id)?;
+                }
+            },
+            Command::List(wrapper) => match &mut wrapper.command {
+                ListEntityCommand::Homes(_) => {}
+                ListEntityCommand::Rooms(rooms) => {
+                    if let Some(home) = rooms.home.as_mut() {
+                        *home = manager.resolve_home(home)?
+                    }
+                }
+                ListEntityCommand::Devices(devices) => {
+                    if let Some(home) = devices.home.as_mut() {
+                        *home = manager.resolve_home(home)?
+                    }
+                    if let Some(room) = devices.room.as_mut() {
+                        *room = manager.resolve_room(room)?
+                    }
+                }
+            },
+            Command::History(history) => {
+                // the home might be removed already, so its id is taken as is
+                if let Some(home_id) = history.home_id.as_mut() {
+                    if let Ok(id) = manager.resolve_home(home_id) {
+                        *home_id = id;
+                    }
+                }
+            }
+            _ => {}
         }
+        Ok(())
+    }
+
+    /// Writes the error of the command. The [ValidationError]s are prefixed by their codes, e.g.
+    /// `[duplicate-name] ...`, when the handler serves the remote clients.
+    fn write_error(&mut self, error: &anyhow::Error) {
+        let response = match error.downcast_ref::<ValidationError>() {
+            Some(validation) if self.error_codes => format!("[{}] {error}", validation.code()),
+            _ => error.to_string(),
+        };
+        self.write_response(&response).unwrap();
     }
 
     fn write_response(&mut self, content: &str) -> Result<(), String> {
@@ -43,19 +228,14 @@ impl<'a> CommandHandler<'a> {
     fn initialize_smart_home(&mut self) {
         self.write_response("Initializing a new repo").unwrap();
 
-        let current_dir = env::current_dir().expect("Unable determine the current dir");
-        let path = current_dir.as_path();
-
-        if !path.exists() {
-            let smart_home_manager = SmartHomeManager::new(path.to_path_buf());
-            match smart_home_manager.initialize_smart_home() {
-                Ok(_) => self.smart_home_manager = smart_home_manager,
-                Err(msg) => self
-                    .write_response(&format!("Unable initialize repository {msg}"))
-                    .unwrap(),
-            };
-        } else {
+        if self.smart_home_manager.is_smart_home_repo_exists() {
             self.write_response("Repository already exists").unwrap();
+            return;
+        }
+
+        if let Err(msg) = self.smart_home_manager.initialize_smart_home() {
+            self.write_response(&format!("Unable initialize repository {msg}"))
+                .unwrap();
         }
     }
 
@@ -118,8 +298,9 @@ impl<'a> CommandHandler<'a> {
             .smart_home_manager
             .change_device_status(device_id, status)
         {
-            Ok(_) => {
-                self.write_response(device_id).unwrap();
+            Ok(state) => {
+                self.write_response(&format!("{device_id}: {state}"))
+                    .unwrap();
             }
             Err(msg) => {
                 self.write_response(&msg.to_string()).unwrap();
@@ -127,8 +308,95 @@ impl<'a> CommandHandler<'a> {
         }
     }
 
+    /// Parses the label selector given in the command, the parsing error is written as the
+    /// response
+    fn parse_selector(&mut self, selector: &str) -> Option<LabelSelector> {
+        match selector.parse() {
+            Ok(selector) => Some(selector),
+            Err(msg) => {
+                self.write_response(&msg.to_string()).unwrap();
+                None
+            }
+        }
+    }
+
+    /// Writes the entities one after another, or `Not found` if there is nothing to write
+    fn write_entities<T: ToString>(&mut self, entities: Result<Vec<T>>) {
+        match entities {
+            Ok(entities) if entities.is_empty() => self.write_response("Not found").unwrap(),
+            Ok(entities) => {
+                let blocks: Vec<String> = entities.iter().map(T::to_string).collect();
+                self.write_response(&blocks.join("\n\n")).unwrap();
+            }
+            Err(msg) => self.write_response(&msg.to_string()).unwrap(),
+        }
+    }
+
+    fn print_selected_homes(&mut self, selector: &str) {
+        if let Some(selector) = self.parse_selector(selector) {
+            let homes = self.smart_home_manager.select_homes(&selector);
+            self.write_entities(homes);
+        }
+    }
+
+    fn print_selected_rooms(&mut self, selector: &str) {
+        if let Some(selector) = self.parse_selector(selector) {
+            let rooms = self.smart_home_manager.select_rooms(&selector);
+            self.write_entities(rooms);
+        }
+    }
+
+    /// Finds the devices matching the selector, writes the response if there are no such
+    /// devices
+    fn selected_devices(&mut self, selector: &str) -> Option<Vec<Device>> {
+        let selector = self.parse_selector(selector)?;
+
+        match self.smart_home_manager.select_devices(&selector) {
+            Ok(devices) if devices.is_empty() => self.write_response("Not found").unwrap(),
+            Ok(devices) => return Some(devices),
+            Err(msg) => self.write_response(&msg.to_string()).unwrap(),
+        }
+        None
+    }
+
+    /// The selected devices are switched one by one, so a device which can't be switched
+    /// doesn't stop the others. Each device gets its own line in the response.
+    fn handle_selected_devices_status(&mut self, selector: &str, status: Option<bool>) {
+        let Some(devices) = self.selected_devices(selector) else {
+            return;
+        };
+
+        let lines: Vec<String> = devices
+            .iter()
+            .map(|device| {
+                let id = device.id();
+                let state = match status {
+                    Some(status) => self.smart_home_manager.change_device_status(id, status),
+                    None => Ok(device.state().clone()),
+                };
+                match state {
+                    Ok(state) => format!("{id}: {state}"),
+                    Err(msg) => format!("{id}: {msg}"),
+                }
+            })
+            .collect();
+        self.write_response(&lines.join("\n")).unwrap();
+    }
+
     fn handle_device_status_command(&mut self, command: DeviceStatusCommand) {
-        let device_id = &command.device_id;
+        if let Some(selector) = &command.selector {
+            let status = match (command.disable, command.enable) {
+                (Some(_), Some(_)) => {
+                    self.write_response("Wrong command parameters").unwrap();
+                    return;
+                }
+                (None, Some(true)) => Some(true),
+                (Some(true), None) => Some(false),
+                _ => None,
+            };
+            return self.handle_selected_devices_status(selector, status);
+        }
+        let device_id = &command.device_id.unwrap_or_default();
 
         match (command.disable, command.enable) {
             (Some(_), Some(_)) => self.write_response("Wrong command parameters").unwrap(),
@@ -157,8 +425,14 @@ impl<'a> CommandHandler<'a> {
 
     fn status_command(&mut self, command: StatusCommand) {
         match command {
-            StatusCommand::Home(id) => self.print_home_status(id.id),
-            StatusCommand::Room(id) => self.print_room_status(id.id),
+            StatusCommand::Home(command) => match (command.id, command.selector) {
+                (Some(id), _) => self.print_home_status(id),
+                (None, selector) => self.print_selected_homes(&selector.unwrap_or_default()),
+            },
+            StatusCommand::Room(command) => match (command.id, command.selector) {
+                (Some(id), _) => self.print_room_status(id),
+                (None, selector) => self.print_selected_rooms(&selector.unwrap_or_default()),
+            },
             StatusCommand::Device(command) => self.handle_device_status_command(command),
         }
     }
@@ -169,7 +443,7 @@ impl<'a> CommandHandler<'a> {
             .create_home(create_home.name, create_home.description)
         {
             Ok(home_id) => self.write_response(&home_id).unwrap(),
-            Err(msg) => self.write_response(&msg.to_string()).unwrap(),
+            Err(msg) => self.write_error(&msg),
         }
     }
 
@@ -179,7 +453,7 @@ impl<'a> CommandHandler<'a> {
             .create_room(room.home_id, room.name, room.description)
         {
             Ok(room_id) => self.write_response(&room_id).unwrap(),
-            Err(msg) => self.write_response(&msg.to_string()).unwrap(),
+            Err(msg) => self.write_error(&msg),
         }
     }
 
@@ -191,7 +465,7 @@ impl<'a> CommandHandler<'a> {
             device.description,
         ) {
             Ok(device_id) => self.write_response(&device_id).unwrap(),
-            Err(msg) => self.write_response(&msg.to_string()).unwrap(),
+            Err(msg) => self.write_error(&msg),
         }
     }
 
@@ -225,6 +499,19 @@ impl<'a> CommandHandler<'a> {
     }
 
     fn handle_remove_command(&mut self, command: RemoveEntityCommand) {
+        let (RemoveEntityCommand::Home(entity)
+        | RemoveEntityCommand::Room(entity)
+        | RemoveEntityCommand::Device(entity)) = &command;
+        if entity.dry_run {
+            match self.smart_home_manager.removal_impact(&entity.id) {
+                Ok(impact) => self
+                    .write_response(&format!("{impact}\nNothing is removed in the dry run mode"))
+                    .unwrap(),
+                Err(msg) => self.write_response(&msg.to_string()).unwrap(),
+            }
+            return;
+        }
+
         match command {
             RemoveEntityCommand::Home(home) => self.remove_home_by_id(&home.id),
             RemoveEntityCommand::Room(room) => self.remove_room_by_id(&room.id),
@@ -232,54 +519,531 @@ impl<'a> CommandHandler<'a> {
         }
     }
 
-    fn handle_measure_command(&mut self, device_id: &str) {
-        match self.smart_home_manager.make_measure(&device_id.to_string()) {
-            Ok(ms_result) => self.write_response(&ms_result).unwrap(),
-            Err(msg) => self.write_response(&msg.to_string()).unwrap(),
+    fn handle_rename_command(&mut self, command: RenameCommand) {
+        match self.smart_home_manager.rename(&command.id, command.name) {
+            Ok(_) => self.write_response(&command.id).unwrap(),
+            Err(msg) => self.write_error(&msg),
         }
     }
 
-    fn print_device_ids(&mut self) {
-        match self.smart_home_manager.list_all_devices() {
-            Ok(devices) => {
-                let ids: Vec<String> = devices.iter().map(|d| d.id().to_string()).collect();
+    fn handle_edit_command(&mut self, command: EditCommand) {
+        match self
+            .smart_home_manager
+            .edit(&command.id, command.name, command.description)
+        {
+            Ok(_) => self.write_response(&command.id).unwrap(),
+            Err(msg) => self.write_error(&msg),
+        }
+    }
 
-                let response = ids.join("\n");
-                self.write_response(&response).unwrap();
+    fn handle_label_command(&mut self, command: LabelCommand) {
+        let labels: Result<Labels, LabelError> = command
+            .labels
+            .iter()
+            .map(|label| parse_label(label))
+            .collect();
+        let labels = match labels {
+            Ok(labels) => labels,
+            Err(msg) => {
+                self.write_response(&msg.to_string()).unwrap();
+                return;
             }
+        };
+
+        match self
+            .smart_home_manager
+            .label(&command.id, labels, &command.remove)
+        {
+            Ok(labels) if labels.is_empty() => self
+                .write_response(&format!("{}: no labels", command.id))
+                .unwrap(),
+            Ok(labels) => self
+                .write_response(&format!("{}: {}", command.id, format_labels(&labels)))
+                .unwrap(),
             Err(msg) => self.write_response(&msg.to_string()).unwrap(),
         }
     }
 
-    fn print_room_ids(&mut self) {
-        match self.smart_home_manager.list_all_rooms() {
-            Ok(rooms) => {
-                let ids: Vec<String> = rooms.iter().map(|r| r.id.to_string()).collect();
+    fn handle_move_command(&mut self, command: MoveEntityCommand) {
+        let (id, result) = match command {
+            MoveEntityCommand::Room(room) => {
+                let result = self.smart_home_manager.move_room(&room.id, &room.home_id);
+                (room.id, result)
+            }
+            MoveEntityCommand::Device(device) => {
+                let result = self
+                    .smart_home_manager
+                    .move_device(&device.id, &device.room_id);
+                (device.id, result)
+            }
+        };
+
+        match result {
+            Ok(_) => self.write_response(&id).unwrap(),
+            Err(msg) => self.write_error(&msg),
+        }
+    }
+
+    fn handle_measure_command(&mut self, command: MakeMeasure) {
+        let Some(selector) = command.selector else {
+            let device_id = command.device_id.unwrap_or_default();
+            match self.smart_home_manager.make_measure(&device_id) {
+                Ok(reading) => self.write_response(&reading.to_string()).unwrap(),
+                Err(msg) => self.write_response(&msg.to_string()).unwrap(),
+            }
+            return;
+        };
+
+        let Some(devices) = self.selected_devices(&selector) else {
+            return;
+        };
+        let lines: Vec<String> = devices
+            .iter()
+            .map(
+                |device| match self.smart_home_manager.make_measure(device.id()) {
+                    Ok(reading) => format!("{}: {reading}", device.id()),
+                    Err(msg) => format!("{}: {msg}", device.id()),
+                },
+            )
+            .collect();
+        self.write_response(&lines.join("\n")).unwrap();
+    }
 
-                let response = ids.join("\n");
-                self.write_response(&response).unwrap();
+    /// Adjusts the light with the given id and writes out the adjusted light, or adjusts all the
+    /// selected lights and writes their current levels one per line
+    fn handle_light_command(&mut self, command: LightCommand) {
+        let transition = match command.transition {
+            Some(seconds) => match Duration::try_from_secs_f64(seconds) {
+                Ok(duration) => Some(duration),
+                Err(_) => {
+                    let msg = format!("Wrong transition duration: {seconds}");
+                    self.write_response(&msg).unwrap();
+                    return;
+                }
+            },
+            None => None,
+        };
+        let settings = LightSettings {
+            brightness: command.brightness,
+            color_temperature: command.color_temperature,
+            transition,
+        };
+
+        let Some(selector) = command.selector else {
+            let device_id = command.device_id.unwrap_or_default();
+            match self.smart_home_manager.adjust_light(&device_id, settings) {
+                Ok(light) => self.write_response(&light.to_string()).unwrap(),
+                Err(msg) => self.write_error(&msg),
             }
-            Err(msg) => self.write_response(&msg.to_string()).unwrap(),
+            return;
+        };
+
+        let Some(devices) = self.selected_devices(&selector) else {
+            return;
+        };
+        let lines: Vec<String> = devices
+            .iter()
+            .map(|device| {
+                let adjusted = self
+                    .smart_home_manager
+                    .adjust_light(device.id(), settings.clone());
+                match adjusted {
+                    Ok(light) => format!("{}: {}%", device.id(), light.brightness),
+                    Err(msg) => format!("{}: {msg}", device.id()),
+                }
+            })
+            .collect();
+        self.write_response(&lines.join("\n")).unwrap();
+    }
+
+    fn handle_socket_command(&mut self, command: SocketCommand) {
+        let manager = &self.smart_home_manager;
+        match manager.set_socket_power(&command.device_id, command.power) {
+            Ok(socket) => self.write_response(&socket.to_string()).unwrap(),
+            Err(msg) => self.write_error(&msg),
         }
     }
 
-    fn print_home_ids(&mut self) {
-        match self.smart_home_manager.list_all_homes() {
-            Ok(homes) => {
-                let ids: Vec<String> = homes.iter().map(|h| h.id.to_string()).collect();
+    fn handle_thermostat_command(&mut self, command: ThermostatCommand) {
+        let settings = ThermostatSettings {
+            target: command.target,
+            hysteresis: command.hysteresis,
+            mode: command.mode,
+            thermometer: command.thermometer,
+            heaters: (!command.heaters.is_empty()).then_some(command.heaters),
+        };
+        let manager = &self.smart_home_manager;
+        match manager.configure_thermostat(&command.device_id, settings) {
+            Ok(thermostat) => self.write_response(&thermostat.to_string()).unwrap(),
+            Err(msg) => self.write_error(&msg),
+        }
+    }
+
+    /// Writes the energy consumed by each metered device, one per line, and the total for the
+    /// homes and the rooms
+    fn handle_energy_command(&mut self, command: EnergyCommand) {
+        let (query, single) = match command {
+            EnergyCommand::Home(query) | EnergyCommand::Room(query) => (query, false),
+            EnergyCommand::Device(query) => (query, true),
+        };
+        let since = match query.since.as_deref().map(parse_timestamp) {
+            Some(Ok(since)) => Some(since),
+            Some(Err(msg)) => {
+                self.write_response(&msg.to_string()).unwrap();
+                return;
+            }
+            None => None,
+        };
+
+        let usage = match self.smart_home_manager.energy(&query.id, since) {
+            Ok(usage) => usage,
+            Err(msg) => {
+                self.write_response(&msg.to_string()).unwrap();
+                return;
+            }
+        };
+        let total: f64 = usage.iter().map(|device| device.kwh).sum();
+        let response = if single {
+            format!("{total:.3} kWh")
+        } else {
+            let mut lines: Vec<String> = usage
+                .iter()
+                .map(|device| {
+                    format!(
+                        "{} ({}): {:.3} kWh",
+                        device.device_id, device.name, device.kwh
+                    )
+                })
+                .collect();
+            lines.push(format!("Total: {total:.3} kWh"));
+            lines.join("\n")
+        };
+        self.write_response(&response).unwrap();
+    }
+
+    /// Builds the query of the list command, the invalid label selector is written as the
+    /// response
+    fn list_query(&mut self, options: ListOptions) -> Option<ListQuery> {
+        let selector = match options.selector {
+            Some(selector) => self.parse_selector(&selector)?,
+            None => LabelSelector::default(),
+        };
+
+        Some(ListQuery {
+            selector,
+            sort: options.sort,
+            offset: options.offset,
+            limit: options.limit,
+            ..ListQuery::default()
+        })
+    }
 
-                let response = ids.join("\n");
-                self.write_response(&response).unwrap();
+    fn write_page<T: ToString>(&mut self, page: Result<Page<T>>) {
+        match page {
+            Ok(page) => {
+                let page = page.map(|entity| entity.to_string());
+                self.write_response(&page.to_string()).unwrap();
             }
             Err(msg) => self.write_response(&msg.to_string()).unwrap(),
         }
     }
 
+    fn print_home_ids(&mut self, options: ListOptions) {
+        if let Some(query) = self.list_query(options) {
+            let homes = self.smart_home_manager.query_homes(&query);
+            self.write_page(homes.map(|page| page.map(|home| home.id)));
+        }
+    }
+
+    fn print_room_ids(&mut self, command: ListRooms) {
+        if let Some(query) = self.list_query(command.options) {
+            let query = ListQuery {
+                home_id: command.home,
+                ..query
+            };
+            let rooms = self.smart_home_manager.query_rooms(&query);
+            self.write_page(rooms.map(|page| page.map(|room| room.id)));
+        }
+    }
+
+    fn print_device_ids(&mut self, command: ListDevices) {
+        if let Some(query) = self.list_query(command.options) {
+            let query = ListQuery {
+                device_type: command.r#type,
+                home_id: command.home,
+                room_id: command.room,
+                status: command.status.map(|status| status == StatusFilter::Enabled),
+                ..query
+            };
+            let devices = self.smart_home_manager.query_devices(&query);
+            self.write_page(devices.map(|page| page.map(|device| device.id().clone())));
+        }
+    }
+
     fn handle_list_command(&mut self, command: ListEntityCommand) {
         match command {
-            ListEntityCommand::Homes => self.print_home_ids(),
-            ListEntityCommand::Rooms => self.print_room_ids(),
-            ListEntityCommand::Devices => self.print_device_ids(),
+            ListEntityCommand::Homes(options) => self.print_home_ids(options),
+            ListEntityCommand::Rooms(command) => self.print_room_ids(command),
+            ListEntityCommand::Devices(command) => self.print_device_ids(command),
+        }
+    }
+
+    fn print_history(&mut self, limit: Option<usize>) {
+        match self.smart_home_manager.history() {
+            Ok(entries) => {
+                let skip = limit.map_or(0, |limit| entries.len().saturating_sub(limit));
+                let lines: Vec<String> = entries
+                    .iter()
+                    .skip(skip)
+                    .flat_map(|entry| {
+                        let kind = match entry.kind {
+                            EntryKind::Change => String::new(),
+                            EntryKind::Undo(revision) => format!("undo #{revision}: "),
+                            EntryKind::Redo(revision) => format!("redo #{revision}: "),
+                            EntryKind::Automatic => "automatic: ".to_string(),
+                        };
+                        entry.events.iter().map(move |event| {
+                            format!(
+                                "#{} [{}] {}: {kind}{event}",
+                                entry.revision,
+                                entry.timestamp.to_rfc3339(),
+                                entry.actor
+                            )
+                        })
+                    })
+                    .collect();
+
+                if lines.is_empty() {
+                    self.write_response("No changes recorded").unwrap();
+                } else {
+                    self.write_response(&lines.join("\n")).unwrap();
+                }
+            }
+            Err(msg) => self.write_response(&msg.to_string()).unwrap(),
+        }
+    }
+
+    fn print_home_at(&mut self, id: &str, at: DateTime<Utc>) {
+        match self.smart_home_manager.find_home_at(&id.to_string(), at) {
+            Ok(Some(home)) => {
+                let mut blocks = vec![home.to_string()];
+                for room in &home.rooms {
+                    blocks.push(room.to_string());
+                    blocks.extend(room.devices.iter().map(|d| d.to_string()));
+                }
+                self.write_response(&blocks.join("\n\n")).unwrap();
+            }
+            Ok(None) => self.write_response("Not found").unwrap(),
+            Err(msg) => self.write_response(&msg.to_string()).unwrap(),
+        }
+    }
+
+    fn restore_home(&mut self, id: &str, at: DateTime<Utc>) {
+        match self.smart_home_manager.restore_home(&id.to_string(), at) {
+            Ok(id) => self.write_response(&id).unwrap(),
+            Err(msg) => self.write_response(&msg.to_string()).unwrap(),
+        }
+    }
+
+    fn handle_history_command(&mut self, command: HistoryCommand) {
+        let at = match command.at.as_deref().map(parse_timestamp) {
+            Some(Ok(at)) => Some(at),
+            Some(Err(msg)) => {
+                self.write_response(&msg.to_string()).unwrap();
+                return;
+            }
+            None => None,
+        };
+
+        match (command.home_id, at) {
+            (Some(id), Some(at)) if command.restore => self.restore_home(&id, at),
+            (Some(id), Some(at)) => self.print_home_at(&id, at),
+            _ => self.print_history(command.limit),
+        }
+    }
+
+    fn handle_migrate_command(&mut self, command: MigrateCommand) {
+        let result = match (command.import, command.dry_run) {
+            (Some(path), false) => self.smart_home_manager.import_state(&path),
+            (Some(_), true) => {
+                self.write_response("Import can't be done in the dry run mode")
+                    .unwrap();
+                return;
+            }
+            (None, dry_run) => self.smart_home_manager.migrate(dry_run),
+        };
+
+        match result {
+            Ok(plan) if command.dry_run && !plan.is_empty() => self
+                .write_response(&format!("{plan}\nNothing is written in the dry run mode"))
+                .unwrap(),
+            Ok(plan) => self.write_response(&plan.to_string()).unwrap(),
+            Err(msg) => self.write_response(&msg.to_string()).unwrap(),
+        }
+    }
+
+    fn handle_export_command(&mut self, command: ExportCommand) {
+        let format = command
+            .format
+            .or_else(|| command.output.as_deref().and_then(DataFormat::from_path))
+            .unwrap_or(DataFormat::Json);
+
+        let result =
+            self.smart_home_manager
+                .export(format)
+                .and_then(|text| match &command.output {
+                    Some(path) => {
+                        std::fs::write(path, text).map_err(|e| {
+                            anyhow::anyhow!("Unable write the file {}: {e}", path.display())
+                        })?;
+                        Ok(format!("Exported to {}", path.display()))
+                    }
+                    None => Ok(text),
+                });
+
+        match result {
+            Ok(text) => self.write_response(&text).unwrap(),
+            Err(msg) => self.write_response(&msg.to_string()).unwrap(),
+        }
+    }
+
+    fn handle_import_command(&mut self, command: ImportCommand) {
+        let Some(format) = command
+            .format
+            .or_else(|| DataFormat::from_path(&command.file))
+        else {
+            self.write_response("Unknown format of the file, provide it with --format")
+                .unwrap();
+            return;
+        };
+        let mode = match command.replace {
+            true => ImportMode::Replace,
+            false => ImportMode::Merge,
+        };
+
+        let result = std::fs::read_to_string(&command.file)
+            .map_err(|e| anyhow::anyhow!("Unable read the file {}: {e}", command.file.display()))
+            .and_then(|text| self.smart_home_manager.import(&text, format, mode));
+
+        match result {
+            Ok(summary) => self.write_response(&summary.to_string()).unwrap(),
+            Err(msg) => self.write_response(&msg.to_string()).unwrap(),
+        }
+    }
+
+    fn handle_plan_command(&mut self, command: PlanCommand, apply: bool) {
+        let format = DataFormat::from_path(&command.file).unwrap_or(DataFormat::Yaml);
+        let result = std::fs::read_to_string(&command.file)
+            .map_err(|e| anyhow::anyhow!("Unable read the file {}: {e}", command.file.display()))
+            .and_then(|text| HouseSpec::parse(&text, format))
+            .and_then(|spec| match apply {
+                true => self.smart_home_manager.apply(&spec),
+                false => self.smart_home_manager.plan(&spec),
+            });
+
+        match result {
+            Ok(plan) => self.write_response(&plan.to_string()).unwrap(),
+            Err(msg) => self.write_response(&msg.to_string()).unwrap(),
+        }
+    }
+
+    fn handle_backup_command(&mut self, command: BackupCommand) {
+        let Some(backups) = self.backups.clone() else {
+            self.write_response("The backups are not available for this repository")
+                .unwrap();
+            return;
+        };
+
+        let result = match command {
+            BackupCommand::Create(create) => backups
+                .keep(create.keep)
+                .create(create.label)
+                .map(|backup| format!("Created {backup}")),
+            BackupCommand::List => backups.list().map(|list| match list.is_empty() {
+                true => "No backups found".to_string(),
+                false => list
+                    .iter()
+                    .map(Backup::to_string)
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            }),
+            BackupCommand::Restore(restore) => self
+                .smart_home_manager
+                .restore_backup(&backups, &restore.id)
+                .map(|backup| format!("Restored {backup}")),
+        };
+
+        match result {
+            Ok(text) => self.write_response(&text).unwrap(),
+            Err(msg) => self.write_response(&msg.to_string()).unwrap(),
+        }
+    }
+
+    fn handle_trash_command(&mut self, command: TrashCommand) {
+        let manager = &self.smart_home_manager;
+        let result = match command {
+            TrashCommand::List => manager.trash().map(|trash| match trash.is_empty() {
+                true => "The trash is empty".to_string(),
+                false => trash
+                    .iter()
+                    .map(TrashEntry::to_string)
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            }),
+            TrashCommand::Restore(restore) => manager
+                .restore_from_trash(&restore.id)
+                .map(|entry| format!("Restored {}", entry.entity.id())),
+            TrashCommand::Hide(hide) => manager
+                .hide_from_trash(
+                    hide.id.as_deref(),
+                    hide.older_than.map(chrono::Duration::days),
+                )
+                .map(|hidden| format!("Hidden {} entities", hidden.len())),
+            TrashCommand::Purge => manager
+                .purge_trash(chrono::Utc::now())
+                .map(|purged| format!("Purged {} entities", purged.len())),
+        };
+
+        match result {
+            Ok(text) => self.write_response(&text).unwrap(),
+            Err(msg) => self.write_response(&msg.to_string()).unwrap(),
+        }
+    }
+
+    fn handle_fsck_command(&mut self, command: FsckCommand) {
+        match self.smart_home_manager.check(command.fix) {
+            Ok(report) => {
+                self.exit_code = match (report.is_clean(), report.fixed) {
+                    (true, _) => 0,
+                    (false, Some(_)) => 1,
+                    (false, None) => 2,
+                };
+                self.write_response(&report.to_string()).unwrap();
+            }
+            Err(msg) => {
+                self.exit_code = 2;
+                self.write_response(&msg.to_string()).unwrap();
+            }
+        }
+    }
+
+    fn handle_undo_command(&mut self, redo: bool) {
+        let (result, action) = if redo {
+            (self.smart_home_manager.redo(), "Redone")
+        } else {
+            (self.smart_home_manager.undo(), "Undone")
+        };
+
+        match result {
+            Ok(entry) => {
+                let lines: Vec<String> = entry
+                    .events
+                    .iter()
+                    .map(|event| format!("{action} #{}: {event}", entry.revision))
+                    .collect();
+                self.write_response(&lines.join("\n")).unwrap();
+            }
+            Err(msg) => self.write_response(&msg.to_string()).unwrap(),
         }
     }
 }
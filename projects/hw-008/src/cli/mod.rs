@@ -4,15 +4,42 @@ mod command_handler;
 pub use args::*;
 pub use command_handler::*;
 
-use std::{env, io};
+use crate::entities::manager::{open_store, repository_root, Backups, SmartHomeManager};
+use std::io;
+use std::process;
+use std::sync::Arc;
 
 pub struct Cli {}
 
 impl Cli {
     pub fn run(args: Arguments) {
+        // each run of the cli is a separate process, so the in-memory store would be empty for
+        // every command
+        let store = args.store.unwrap_or_default();
+        if store == StoreType::Memory {
+            eprintln!(
+                "The memory store keeps nothing between the commands, use it with the server"
+            );
+            process::exit(1);
+        }
+
         let mut output = io::stdout();
-        let path = env::current_dir().unwrap();
-        let mut handler = CommandHandler::new(&mut output, path);
-        handler.process(args.command)
+        // a new repository is always created right where it's asked for
+        let discover = !matches!(args.command, Command::Init);
+        let path = match repository_root(args.repo, discover) {
+            Ok(path) => path,
+            Err(msg) => {
+                eprintln!("{msg}");
+                process::exit(1);
+            }
+        };
+        let backups = Backups::new(path.clone(), store);
+        let manager = Arc::new(SmartHomeManager::with_store(open_store(store, path)));
+        let mut handler = CommandHandler::with_manager(&mut output, manager).with_backups(backups);
+        handler.process(args.command);
+
+        if handler.exit_code() != 0 {
+            process::exit(handler.exit_code());
+        }
     }
 }
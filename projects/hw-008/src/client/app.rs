@@ -26,6 +26,23 @@ impl SelectedTable {
     }
 }
 
+/// The number of the entities requested at once for each table
+const PAGE_SIZE: usize = 50;
+
+/// The position of the page shown in the table among all the entities
+#[derive(Clone, Copy, Default)]
+pub struct TablePage {
+    pub offset: usize,
+    pub total: usize,
+}
+
+impl TablePage {
+    /// Reads the position from the last line of the `list` reply, e.g. `# 51-100 of 153`
+    fn parse(footer: &str) -> Option<usize> {
+        footer.rsplit(" of ").next()?.trim().parse().ok()
+    }
+}
+
 pub struct ApplicationState {
     tcp_client: TcpClient,
     udp_client: UdpClient,
@@ -37,6 +54,9 @@ pub struct ApplicationState {
     pub homes: Vec<String>,
     pub rooms: Vec<String>,
     pub devices: Vec<String>,
+    pub homes_page: TablePage,
+    pub rooms_page: TablePage,
+    pub devices_page: TablePage,
     pub current_selected_table: SelectedTable,
     pub homes_table_select_state: TableState,
     pub rooms_table_select_state: TableState,
@@ -59,6 +79,9 @@ impl ApplicationState {
             homes: vec![],
             rooms: vec![],
             devices: vec![],
+            homes_page: TablePage::default(),
+            rooms_page: TablePage::default(),
+            devices_page: TablePage::default(),
             current_selected_table: SelectedTable::Homes,
             homes_table_select_state: firs_element_selected.clone(),
             rooms_table_select_state: firs_element_selected.clone(),
@@ -113,6 +136,24 @@ impl ApplicationState {
         }
     }
 
+    /// Moves the current table to the next or the previous page. Returns `false` if there is
+    /// no such page.
+    pub fn turn_page(&mut self, forward: bool) -> bool {
+        let page = match self.current_selected_table {
+            SelectedTable::Homes => &mut self.homes_page,
+            SelectedTable::Rooms => &mut self.rooms_page,
+            SelectedTable::Devices => &mut self.devices_page,
+        };
+
+        let offset = match forward {
+            true if page.offset + PAGE_SIZE < page.total => page.offset + PAGE_SIZE,
+            false if page.offset > 0 => page.offset.saturating_sub(PAGE_SIZE),
+            _ => return false,
+        };
+        page.offset = offset;
+        true
+    }
+
     pub fn highlight_previous(&mut self) {
         match self.current_selected_table {
             SelectedTable::Homes => ApplicationState::highlight_previous_element(
@@ -159,25 +200,66 @@ impl ApplicationStateUpdater {
 
                     match command {
                         ClientCommand::GetAllHomes => {
-                            let homes = ApplicationStateUpdater::handle_execute_command(
-                                "list homes".to_string(),
+                            let mut page = app_state.homes_page;
+                            let homes = ApplicationStateUpdater::handle_list_command(
+                                "homes",
+                                &mut page,
                                 &mut app_state,
                             );
                             app_state.homes = homes;
+                            app_state.homes_page = page;
                         }
                         ClientCommand::GetAllRooms => {
-                            let rooms = ApplicationStateUpdater::handle_execute_command(
-                                "list rooms".to_string(),
+                            let mut page = app_state.rooms_page;
+                            let rooms = ApplicationStateUpdater::handle_list_command(
+                                "rooms",
+                                &mut page,
                                 &mut app_state,
                             );
                             app_state.rooms = rooms;
+                            app_state.rooms_page = page;
                         }
                         ClientCommand::GetAllDevices => {
-                            let devices = ApplicationStateUpdater::handle_execute_command(
-                                "list devices".to_string(),
+                            let mut page = app_state.devices_page;
+                            let devices = ApplicationStateUpdater::handle_list_command(
+                                "devices",
+                                &mut page,
                                 &mut app_state,
                             );
                             app_state.devices = devices;
+                            app_state.devices_page = page;
+                        }
+                        ClientCommand::NextPage | ClientCommand::PreviousPage => {
+                            let forward = matches!(command, ClientCommand::NextPage);
+                            if app_state.turn_page(forward) {
+                                let (name, mut page) = match app_state.current_selected_table {
+                                    SelectedTable::Homes => ("homes", app_state.homes_page),
+                                    SelectedTable::Rooms => ("rooms", app_state.rooms_page),
+                                    SelectedTable::Devices => ("devices", app_state.devices_page),
+                                };
+                                let ids = ApplicationStateUpdater::handle_list_command(
+                                    name,
+                                    &mut page,
+                                    &mut app_state,
+                                );
+                                match app_state.current_selected_table {
+                                    SelectedTable::Homes => {
+                                        app_state.homes = ids;
+                                        app_state.homes_page = page;
+                                        app_state.homes_table_select_state.select(Some(0));
+                                    }
+                                    SelectedTable::Rooms => {
+                                        app_state.rooms = ids;
+                                        app_state.rooms_page = page;
+                                        app_state.rooms_table_select_state.select(Some(0));
+                                    }
+                                    SelectedTable::Devices => {
+                                        app_state.devices = ids;
+                                        app_state.devices_page = page;
+                                        app_state.devices_table_select_state.select(Some(0));
+                                    }
+                                }
+                            }
                         }
 
                         ClientCommand::GetHomeInfo => {
@@ -228,6 +310,30 @@ impl ApplicationStateUpdater {
         });
     }
 
+    /// Requests the current page of the entities, and keeps the total number of the entities
+    /// told by the server. The ids are returned without the page description.
+    fn handle_list_command(
+        entities: &str,
+        page: &mut TablePage,
+        app_state: &mut MutexGuard<ApplicationState>,
+    ) -> Vec<String> {
+        let command = format!(
+            "list {entities} --offset {} --limit {PAGE_SIZE}",
+            page.offset
+        );
+        let mut lines = ApplicationStateUpdater::handle_execute_command(command, app_state);
+
+        if let Some(total) = lines.last().and_then(|line| {
+            line.starts_with('#')
+                .then(|| TablePage::parse(line))
+                .flatten()
+        }) {
+            page.total = total;
+            lines.pop();
+        }
+        lines
+    }
+
     fn handle_execute_command(
         command: String,
         app_state: &mut MutexGuard<ApplicationState>,
@@ -112,11 +112,7 @@ impl UdpClient {
     }
 
     pub fn read_data(&mut self) -> Result<Vec<String>, String> {
-        if self.connection.is_none() {
-            self.connect().unwrap();
-            self.read_data()
-        } else {
-            let socket = self.connection.as_mut().unwrap();
+        if let Some(socket) = self.connection.as_mut() {
             let mut buff = vec![0u8; 1024];
 
             let mut content = String::new();
@@ -139,6 +135,9 @@ impl UdpClient {
                 .split('\n')
                 .map(|s| s.to_string())
                 .collect::<Vec<String>>())
+        } else {
+            self.connect().unwrap();
+            self.read_data()
         }
     }
 }
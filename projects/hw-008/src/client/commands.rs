@@ -8,5 +8,7 @@ pub enum ClientCommand {
     GetHomeInfo,
     GetRoomInfo,
     GetDeviceInfo,
+    NextPage,
+    PreviousPage,
     ExecuteCommand(Command),
 }
@@ -102,6 +102,8 @@ fn run_app<B: Backend>(
                         app.current_selected_table = selected.next();
                     }
                     KeyCode::Esc => return Ok(()),
+                    KeyCode::PageDown => commands_sender.send(ClientCommand::NextPage).unwrap(),
+                    KeyCode::PageUp => commands_sender.send(ClientCommand::PreviousPage).unwrap(),
                     KeyCode::Down => {
                         app.highlight_next();
                         let event_type = match app.current_selected_table {
@@ -187,7 +189,12 @@ fn draw<B: Backend>(f: &mut Frame<B>, app_lock: Arc<Mutex<ApplicationState>>) {
             "UP (↑) / DOWN (↓)",
             Style::default().add_modifier(Modifier::BOLD),
         ),
-        Span::raw(" to select element from the table "),
+        Span::raw(" to select element from the table, "),
+        Span::styled(
+            "PAGE UP / PAGE DOWN",
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        Span::raw(" to scroll the table by pages "),
     ];
     let text = Text::from(Spans::from(msg));
     let help_message = Paragraph::new(text);
@@ -1,59 +1,172 @@
-use super::socket::Socket;
-use super::thermometer::Thermometer;
-use crate::entities::devices::DeviceId;
-use crate::entities::{ReportError, Reportable};
-use serde_derive::{Deserialize, Serialize};
-use std::fmt::{Display, Formatter, Result as FmtResult};
-
-/// This is a common Trait representing variety of devices in Smart Home project. Currently it
-/// has only two elements, but it the future implementation it may have more.
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+
+use chrono::{DateTime, Utc};
+use serde::de::{Deserializer, Error as DeError};
+use serde::ser::{Error as SerError, SerializeMap, Serializer};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::entities::devices::registry::deserialize_device;
+use crate::entities::devices::{DeviceId, DeviceKind, DeviceState, DeviceType};
+use crate::entities::{Labels, MeasureError, Measurement, ReportError, Reportable};
+
+/// This is a common wrapper representing variety of devices in Smart Home project. It keeps any
+/// device implementing the [DeviceKind] trait, the socket and the thermometer out of the box.
 ///
 /// There are two approaches how to handle problem of storing dynamically typed elements in
 /// one typed container. There is no any java-like `Object` classes, and runtime at all.
 ///
-/// The first approach is to use `dyn MyTrait` type as element type of container. It means,
-/// developer will be forced to implement `MyTrait` trait for each device. And the main drawback
-/// of this approach is the type erasure. Users of the `dyn MyTrait` will not have an access for
-/// the fields and methods for the type behind the `dyn MyTrait` type. Instead, all internal
-/// features will be hidden by Trait public API, which will lead to down-cast the trait.
+/// The first approach is to have enum for each device in the project. It's much easier to deal
+/// with enum, but the enum does not support easy inheritance: a new device type means updating
+/// the enum, as well as all places where this enum was used, it's not cool at all. So, the
+/// project has moved away from it.
 ///
-/// Another approach, and this is the implementation of this approach, is to have enum for
-/// each [Device] in the project. It's much easier to deal with enum, which incapsulates each
-/// device types within the element of Enum. A negative side of this approach is that this enum
-/// does not support easy inheritance. If user want to introduce a new device type to the
-/// project, he/she will forced to update this enum, as well as update all places where this
-/// enum was used, it's not cool at all. A classical `composition` pattern might be considered as
-/// one of possible solution for the `extension` limitation
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub enum Device {
-    Socket(Socket),
-    Thermometer(Thermometer),
-}
+/// Another approach, and this is the implementation of this approach, is to use `dyn MyTrait`
+/// type as element type of container. The main drawback of this approach is the type erasure,
+/// the fields of the exact device are hidden behind the [DeviceKind] API. Whenever they are
+/// needed, the device is down-casted by [Device::downcast_ref]. The device types are known by
+/// the registry, see [register_device](super::register_device), so the devices are stored in
+/// the same externally tagged form the enum had, e.g. `{"Socket": {...}}`.
+pub struct Device(Box<dyn DeviceKind>);
 
 impl Device {
+    pub fn new(device: impl DeviceKind) -> Self {
+        Self(Box::new(device))
+    }
+
+    /// The device behind the wrapper
+    pub fn kind(&self) -> &dyn DeviceKind {
+        self.0.as_ref()
+    }
+
+    pub fn kind_mut(&mut self) -> &mut dyn DeviceKind {
+        self.0.as_mut()
+    }
+
+    /// Gives the exact device, if the device is of the type `T`
+    pub fn downcast_ref<T: DeviceKind>(&self) -> Option<&T> {
+        self.0.as_any().downcast_ref()
+    }
+
+    pub fn downcast_mut<T: DeviceKind>(&mut self) -> Option<&mut T> {
+        self.0.as_any_mut().downcast_mut()
+    }
+
     pub fn id(&self) -> &DeviceId {
-        match self {
-            Device::Socket(socket) => &socket.id,
-            Device::Thermometer(ther) => &ther.id,
-        }
+        self.0.id()
+    }
+
+    pub fn device_type(&self) -> DeviceType {
+        DeviceType(self.0.tag())
+    }
+
+    /// The prefix of the ids of this device type, e.g. `sock` for `sock_01GNNA1J00C3FE9ZJ6S2JXNZ8K`
+    pub fn id_prefix(&self) -> &'static str {
+        self.0.id_prefix()
+    }
+
+    /// Gives the device a new id. It's used only to resolve the colliding ids.
+    pub(crate) fn set_id(&mut self, id: DeviceId) {
+        self.0.set_id(id)
+    }
+
+    pub fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.0.description()
+    }
+
+    pub fn labels(&self) -> &Labels {
+        self.0.labels()
+    }
+
+    pub fn labels_mut(&mut self) -> &mut Labels {
+        self.0.labels_mut()
+    }
+
+    pub fn state(&self) -> &DeviceState {
+        self.0.state()
+    }
+
+    pub fn state_mut(&mut self) -> &mut DeviceState {
+        self.0.state_mut()
+    }
+
+    /// Changes the name and the description of the device, the id is kept as is
+    pub fn edit(&mut self, name: String, description: Option<String>) {
+        self.0.edit(name, description)
+    }
+
+    /// Switches the device on or off at the given moment, see [DeviceKind::switch]
+    pub fn switch(&mut self, on: bool, at: Option<DateTime<Utc>>) {
+        self.0.switch(on, at)
+    }
+
+    /// The energy consumed by the metered device up to the given moment, in kWh
+    pub fn energy_at(&self, at: DateTime<Utc>) -> Option<f64> {
+        self.0.energy_at(at)
+    }
+
+    /// Makes a measurement, if the device is able to measure anything
+    pub fn measure(&self) -> Result<Vec<Measurement>, MeasureError> {
+        self.0.measure()
     }
 }
 
-impl Display for Device {
+impl<T: DeviceKind> From<T> for Device {
+    fn from(device: T) -> Self {
+        Device::new(device)
+    }
+}
+
+impl Clone for Device {
+    fn clone(&self) -> Self {
+        Self(self.0.clone_box())
+    }
+}
+
+impl Debug for Device {
     fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
-        match self {
-            Device::Socket(s) => formatter.write_str(&format!("{s}")),
+        Debug::fmt(&self.0, formatter)
+    }
+}
 
-            Device::Thermometer(t) => formatter.write_str(&format!("{t}")),
-        }
+impl Display for Device {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        Display::fmt(&self.0, formatter)
     }
 }
 
 impl Reportable for Device {
     fn report(&self) -> Result<String, ReportError> {
-        match self {
-            Device::Socket(s) => s.report(),
-            Device::Thermometer(t) => t.report(),
+        self.0.report()
+    }
+}
+
+/// The device is written as a map with the single entry: the tag of its type and its fields
+impl Serialize for Device {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let fields = self.0.to_value().map_err(S::Error::custom)?;
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(self.0.tag(), &fields)?;
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Device {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let tagged = BTreeMap::<String, Value>::deserialize(deserializer)?;
+        let mut entries = tagged.into_iter();
+        match (entries.next(), entries.next()) {
+            (Some((tag, fields)), None) => {
+                deserialize_device(&tag, fields).map_err(D::Error::custom)
+            }
+            _ => Err(D::Error::custom(
+                "The device must have exactly one type tag, e.g. {\"Socket\": {...}}",
+            )),
         }
     }
 }
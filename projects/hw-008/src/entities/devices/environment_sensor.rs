@@ -0,0 +1,168 @@
+use crate::entities::devices::{DeviceId, DeviceKind, DeviceState, Reading, RoomTemperature};
+use crate::entities::reportable::{ReportError, Reportable};
+use crate::entities::{
+    format_labels, generate_id, Labels, Measure, MeasureError, Measurement, Quality, Quantity,
+};
+use chrono::{Duration, Utc};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// The CO2 sensors need some time to warm up after they are switched on, the CO2 measured
+/// earlier is [Quality::Uncertain]
+pub const CO2_WARM_UP_SECONDS: i64 = 180;
+
+/// A sensor of the air in the room. A single measure gives the temperature, the humidity, the
+/// CO2 level and the pressure at once. As the thermometer, it's a dummy one and gives random,
+/// but at least plausible values.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EnvironmentSensor {
+    pub id: DeviceId,
+    pub name: String,
+    pub description: Option<String>,
+    /// The labels like `floor=2` or `critical`, see [LabelSelector](crate::entities::LabelSelector)
+    #[serde(default, skip_serializing_if = "Labels::is_empty")]
+    pub labels: Labels,
+    pub state: DeviceState,
+    /// The simulated temperature of the room, it's measured instead of a random one since the
+    /// sensor was linked to a thermostat
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub room: Option<RoomTemperature>,
+}
+
+impl EnvironmentSensor {
+    /// Creates a switched on sensor, it starts measuring right away
+    pub fn new(name: &str) -> Self {
+        Self {
+            id: generate_id("env"),
+            name: name.to_string(),
+            description: None,
+            labels: Labels::new(),
+            state: DeviceState::switched_on(),
+            room: None,
+        }
+    }
+
+    pub fn new_with_description(name: &str, description: &str) -> Self {
+        Self {
+            description: Some(description.to_string()),
+            ..Self::new(name)
+        }
+    }
+
+    /// `true` if the sensor was switched on so recently, that its CO2 level can't be trusted
+    pub fn is_warming_up(&self) -> bool {
+        self.state.changed_at.is_some_and(|changed_at| {
+            Utc::now() - changed_at < Duration::seconds(CO2_WARM_UP_SECONDS)
+        })
+    }
+}
+
+/// Writes out the full information about the sensor together with its last reading
+impl Display for EnvironmentSensor {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            formatter,
+            "Environment sensor: {},\nId: {},\nStatus: {},\nDescription: {}",
+            self.name,
+            self.id,
+            if self.state.on { "On" } else { "Off" },
+            self.description.as_deref().unwrap_or("[No description]")
+        )?;
+        if let Some(reading) = &self.state.last_reading {
+            write!(formatter, ",\nLast reading: {reading}")?;
+        }
+        if !self.labels.is_empty() {
+            write!(formatter, ",\nLabels: {}", format_labels(&self.labels))?;
+        }
+        Ok(())
+    }
+}
+
+impl Measure for EnvironmentSensor {
+    /// All four values are measured at once. The sensor linked to a thermostat measures the
+    /// simulated temperature of its room. The switched off sensor measures nothing.
+    fn measure(&self) -> Result<Vec<Measurement>, MeasureError> {
+        if !self.state.on {
+            return Err(MeasureError::DeviceIsOff);
+        }
+
+        let within = |from: f32, to: f32| from + (to - from) * rand::random::<f32>();
+        let temperature = match &self.room {
+            Some(room) => room.celsius_at(Utc::now()),
+            None => within(18.0, 26.0),
+        };
+        let co2_quality = if self.is_warming_up() {
+            Quality::Uncertain
+        } else {
+            Quality::Good
+        };
+        Ok(vec![
+            Measurement::new(Quantity::Temperature, temperature),
+            Measurement::new(Quantity::Humidity, within(30.0, 60.0)),
+            Measurement::new(Quantity::Co2, within(400.0, 1200.0)).with_quality(co2_quality),
+            Measurement::new(Quantity::Pressure, within(990.0, 1030.0)),
+        ])
+    }
+}
+
+/// The report makes a measurement, the same way as the thermometer does
+impl Reportable for EnvironmentSensor {
+    fn report(&self) -> Result<String, ReportError> {
+        let reading = Reading::new(Measure::measure(self)?);
+        Ok(format!(
+            "Environment sensor: {}, Measure: {reading}",
+            self.name
+        ))
+    }
+}
+
+impl DeviceKind for EnvironmentSensor {
+    fn tag(&self) -> &'static str {
+        "EnvironmentSensor"
+    }
+
+    fn id_prefix(&self) -> &'static str {
+        "env"
+    }
+
+    fn id(&self) -> &DeviceId {
+        &self.id
+    }
+
+    fn set_id(&mut self, id: DeviceId) {
+        self.id = id;
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    fn labels(&self) -> &Labels {
+        &self.labels
+    }
+
+    fn labels_mut(&mut self) -> &mut Labels {
+        &mut self.labels
+    }
+
+    fn state(&self) -> &DeviceState {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut DeviceState {
+        &mut self.state
+    }
+
+    fn edit(&mut self, name: String, description: Option<String>) {
+        self.name = name;
+        self.description = description;
+    }
+
+    fn measure(&self) -> Result<Vec<Measurement>, MeasureError> {
+        Measure::measure(self)
+    }
+}
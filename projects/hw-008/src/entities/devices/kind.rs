@@ -0,0 +1,99 @@
+use std::any::Any;
+use std::fmt::{Debug, Display};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::entities::devices::{DeviceId, DeviceState};
+use crate::entities::{Labels, MeasureError, Measurement, Reportable};
+
+/// The behaviour every device shares, whatever it is. The [Device](super::Device) keeps any
+/// device behind this trait, so a new device type is added by implementing the trait and
+/// registering the type by [register_device](super::register_device), without touching the
+/// manager, the servers or the cli.
+///
+/// The device must be [Clone] and [Serialize] as well, the [DeviceObject] is implemented for
+/// such devices automatically.
+pub trait DeviceKind: DeviceObject + Debug + Display + Reportable + Send + Sync + 'static {
+    /// The tag of the device type, e.g. `Socket`. The device is stored as `{"Socket": {...}}`,
+    /// and the tag in the lowercase is the name of the type in the cli, e.g. `socket`.
+    fn tag(&self) -> &'static str;
+
+    /// The prefix of the ids of this device type, e.g. `sock` for `sock_01GNNA1J00C3FE9ZJ6S2JXNZ8K`
+    fn id_prefix(&self) -> &'static str;
+
+    fn id(&self) -> &DeviceId;
+
+    /// Gives the device a new id. It's used only to resolve the colliding ids.
+    fn set_id(&mut self, id: DeviceId);
+
+    fn name(&self) -> &str;
+
+    fn description(&self) -> Option<&str>;
+
+    fn labels(&self) -> &Labels;
+
+    fn labels_mut(&mut self) -> &mut Labels;
+
+    fn state(&self) -> &DeviceState;
+
+    fn state_mut(&mut self) -> &mut DeviceState;
+
+    /// Changes the name and the description of the device, the id is kept as is
+    fn edit(&mut self, name: String, description: Option<String>);
+
+    /// Switches the device on or off at the given moment. The devices which track the time they
+    /// are switched on, e.g. the energy metering sockets, bring their counters up to date here.
+    fn switch(&mut self, on: bool, at: Option<DateTime<Utc>>) {
+        let state = self.state_mut();
+        state.on = on;
+        state.changed_at = at;
+    }
+
+    /// The energy consumed by the device up to the given moment, in kWh. Only the metered
+    /// devices know it, the rest have nothing.
+    fn energy_at(&self, _at: DateTime<Utc>) -> Option<f64> {
+        None
+    }
+
+    /// Makes a measurement, all the values the device measures at once. Most of the devices
+    /// measure nothing, so that's the default.
+    fn measure(&self) -> Result<Vec<Measurement>, MeasureError> {
+        Err(MeasureError::WrongDeviceStateError(format!(
+            "{} measures nothing",
+            self.tag()
+        )))
+    }
+}
+
+/// The object-safe parts of the [DeviceKind], which can't be written once for all the devices
+/// in the trait itself: cloning, serialization and downcasting of the boxed device.
+pub trait DeviceObject {
+    fn clone_box(&self) -> Box<dyn DeviceKind>;
+
+    /// The fields of the device, without the tag of its type
+    fn to_value(&self) -> serde_json::Result<Value>;
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: DeviceKind + Clone + Serialize> DeviceObject for T {
+    fn clone_box(&self) -> Box<dyn DeviceKind> {
+        Box::new(self.clone())
+    }
+
+    fn to_value(&self) -> serde_json::Result<Value> {
+        serde_json::to_value(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
@@ -0,0 +1,209 @@
+use crate::entities::devices::{DeviceId, DeviceKind, DeviceState};
+use crate::entities::reportable::{ReportError, Reportable};
+use crate::entities::{format_labels, generate_id, Labels, MeasureError, Measurement, Quantity};
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// The brightness of the freshly created light
+pub const DEFAULT_BRIGHTNESS: u8 = 100;
+
+/// The warmest and the coldest colour temperatures a light might have, in kelvins
+pub const COLOR_TEMPERATURE_RANGE: (u16, u16) = (1000, 10000);
+
+/// A gradual change of the brightness. The light goes from the `from` level to its target
+/// brightness linearly, starting at the `started_at` moment and for `duration_ms` milliseconds.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct Transition {
+    pub from: u8,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+}
+
+/// A dimmable light. It's switched on and off as any other device, and when it's on it shines
+/// with the given brightness, in percents, and optionally the given colour temperature. The
+/// brightness might be changed at once, or gradually over a [Transition].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Light {
+    pub id: DeviceId,
+    pub name: String,
+    pub description: Option<String>,
+    /// The labels like `floor=2` or `critical`, see [LabelSelector](crate::entities::LabelSelector)
+    #[serde(default, skip_serializing_if = "Labels::is_empty")]
+    pub labels: Labels,
+    pub state: DeviceState,
+    /// The target brightness from 0 to 100 percents. During a transition the light is still on
+    /// its way to it, see [Light::level_at].
+    pub brightness: u8,
+    /// The colour temperature in kelvins, the lights without the white tuning have none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_temperature: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transition: Option<Transition>,
+}
+
+impl Light {
+    /// Creates a switched off light with the full brightness
+    pub fn new(name: &str) -> Self {
+        Self {
+            id: generate_id("light"),
+            name: name.to_string(),
+            description: None,
+            labels: Labels::new(),
+            state: DeviceState::default(),
+            brightness: DEFAULT_BRIGHTNESS,
+            color_temperature: None,
+            transition: None,
+        }
+    }
+
+    pub fn new_with_description(name: &str, description: &str) -> Self {
+        Self {
+            description: Some(description.to_string()),
+            ..Self::new(name)
+        }
+    }
+
+    /// Changes the brightness. Without the duration the light gets the new brightness at once,
+    /// otherwise it starts a transition from the level it has at the given moment, so a
+    /// transition might be interrupted by another one smoothly.
+    pub fn set_brightness(&mut self, brightness: u8, duration_ms: Option<u64>, at: DateTime<Utc>) {
+        let from = self.brightness_at(at);
+        self.brightness = brightness;
+        self.transition = duration_ms
+            .filter(|duration| *duration > 0 && from != brightness)
+            .map(|duration_ms| Transition {
+                from,
+                started_at: at,
+                duration_ms,
+            });
+    }
+
+    /// The brightness at the given moment regardless of whether the light is on. It's the
+    /// target brightness when there is no transition or it's over.
+    pub fn brightness_at(&self, at: DateTime<Utc>) -> u8 {
+        let Some(transition) = &self.transition else {
+            return self.brightness;
+        };
+
+        let elapsed = (at - transition.started_at).num_milliseconds().max(0) as u64;
+        if elapsed >= transition.duration_ms {
+            return self.brightness;
+        }
+        let progress = elapsed as f64 / transition.duration_ms as f64;
+        let from = f64::from(transition.from);
+        let level = from + (f64::from(self.brightness) - from) * progress;
+        level.round() as u8
+    }
+
+    /// The level the light actually shines with at the given moment, the switched off light
+    /// doesn't shine at all
+    pub fn level_at(&self, at: DateTime<Utc>) -> u8 {
+        if self.state.on {
+            self.brightness_at(at)
+        } else {
+            0
+        }
+    }
+
+    /// The level the light shines with right now
+    pub fn level(&self) -> u8 {
+        self.level_at(Utc::now())
+    }
+
+    /// `true` if the light is still on its way to the target brightness
+    pub fn in_transition(&self, at: DateTime<Utc>) -> bool {
+        self.brightness_at(at) != self.brightness
+    }
+}
+
+/// Writes out the full information about the light, the same way as for the other devices
+impl Display for Light {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        let now = Utc::now();
+        write!(
+            formatter,
+            "Light: {},\nId: {},\nStatus: {},\nBrightness: {}%",
+            self.name,
+            self.id,
+            if self.state.on { "On" } else { "Off" },
+            self.brightness
+        )?;
+        if self.in_transition(now) {
+            write!(formatter, " (now {}%)", self.brightness_at(now))?;
+        }
+        if let Some(kelvins) = self.color_temperature {
+            write!(formatter, ",\nColour temperature: {kelvins}K")?;
+        }
+        write!(
+            formatter,
+            ",\nDescription: {}",
+            self.description.as_deref().unwrap_or("[No description]")
+        )?;
+        if !self.labels.is_empty() {
+            write!(formatter, ",\nLabels: {}", format_labels(&self.labels))?;
+        }
+        Ok(())
+    }
+}
+
+/// The short report of the light is its current level, e.g. `Light: Desk, Level: 40%`
+impl Reportable for Light {
+    fn report(&self) -> Result<String, ReportError> {
+        Ok(format!("Light: {}, Level: {}%", self.name, self.level()))
+    }
+}
+
+impl DeviceKind for Light {
+    fn tag(&self) -> &'static str {
+        "Light"
+    }
+
+    fn id_prefix(&self) -> &'static str {
+        "light"
+    }
+
+    fn id(&self) -> &DeviceId {
+        &self.id
+    }
+
+    fn set_id(&mut self, id: DeviceId) {
+        self.id = id;
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    fn labels(&self) -> &Labels {
+        &self.labels
+    }
+
+    fn labels_mut(&mut self) -> &mut Labels {
+        &mut self.labels
+    }
+
+    fn state(&self) -> &DeviceState {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut DeviceState {
+        &mut self.state
+    }
+
+    fn edit(&mut self, name: String, description: Option<String>) {
+        self.name = name;
+        self.description = description;
+    }
+
+    /// The light "measures" the level it shines with, so the telemetry shows how bright it's
+    /// right now, including the switched off lights with the zero level
+    fn measure(&self) -> Result<Vec<Measurement>, MeasureError> {
+        let level = f32::from(self.level());
+        Ok(vec![Measurement::new(Quantity::Brightness, level)])
+    }
+}
@@ -1,7 +1,7 @@
 /// An additional sub module devoted the smart socket device. It contains the socket struct, as
 /// well as some helper and utility structs.
 mod socket;
-pub use socket::{Socket, SocketStatus};
+pub use socket::{EnergyMeter, Socket, SocketStatus};
 
 /// I'm tired to write stub text here, hopefully in  the production code I will not be so boiled
 /// with such kind of dummy documentation writing part. Again, as the name of the module states -
@@ -9,9 +9,43 @@ pub use socket::{Socket, SocketStatus};
 mod thermometer;
 pub use thermometer::Thermometer;
 
-/// This is a module stores a common enum [Device], which will handle the variety of devices in
-/// the project. Current implementation of this enum contains only two elements inside the enum,
-/// but in the future it may have more.
+/// The dimmable [Light] with the brightness, the colour temperature and the smooth transitions
+/// between the brightness levels
+mod light;
+pub use light::{Light, Transition, COLOR_TEMPERATURE_RANGE, DEFAULT_BRIGHTNESS};
+
+/// The [EnvironmentSensor] measuring the temperature, the humidity, the CO2 level and the
+/// pressure of the air at once
+mod environment_sensor;
+pub use environment_sensor::{EnvironmentSensor, CO2_WARM_UP_SECONDS};
+
+/// The [Thermostat] switching the heaters of the room by the temperature of its thermometer
+mod thermostat;
+pub use thermostat::{
+    Decision, RoomTemperature, Thermostat, ThermostatConfig, ThermostatMode, COOLING_RATE,
+    DECISION_LOG_SIZE, DEFAULT_HYSTERESIS, DEFAULT_TARGET, ECO_SETBACK, HEATING_RATE,
+    ROOM_TEMPERATURE, ROOM_TEMPERATURE_RANGE,
+};
+
+/// The runtime state of the devices: whether the device is switched on, what it measured the
+/// last time, and when it was changed.
+mod state;
+pub(crate) use state::legacy_quantity;
+pub use state::{DeviceState, Reading};
+
+/// The [DeviceKind] trait every device implements, so the devices of any type might be kept
+/// together
+mod kind;
+pub use kind::{DeviceKind, DeviceObject};
+
+/// The registry of the device types. The socket, the thermometer, the light, the environment
+/// sensor and the thermostat are registered from the start, the other types are registered by
+/// the crates which bring them.
+mod registry;
+pub use registry::{device_types, register_device, DeviceRegistration, DeviceType};
+
+/// This is a module stores a common wrapper [Device], which will handle the variety of devices
+/// in the project, whatever [DeviceKind] they are.
 mod device;
 pub use device::Device;
 
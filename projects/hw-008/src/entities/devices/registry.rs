@@ -0,0 +1,195 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+use std::sync::{Arc, LazyLock, RwLock};
+
+use anyhow::{anyhow, Result};
+use serde::de::{DeserializeOwned, Deserializer, Error as DeError};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::entities::devices::{
+    Device, DeviceKind, EnvironmentSensor, Light, Socket, Thermometer, Thermostat,
+};
+
+/// Creates a new device of the registered type by its name and optional description
+type Constructor = Arc<dyn Fn(&str, Option<&str>) -> Device + Send + Sync>;
+
+/// A registered device type: the tag it's stored with, and the ways to create and to read the
+/// device of this type
+#[derive(Clone)]
+pub struct DeviceRegistration {
+    tag: &'static str,
+    create: Constructor,
+    deserialize: fn(Value) -> serde_json::Result<Device>,
+}
+
+impl DeviceRegistration {
+    /// Describes the device type `T` stored with the given tag. The tag must be the same as the
+    /// [DeviceKind::tag] of the devices of this type.
+    ///
+    /// ```
+    /// use hw_008::entities::devices::{DeviceRegistration, Socket};
+    /// let registration = DeviceRegistration::of("Socket", |name, description| {
+    ///     match description {
+    ///         Some(description) => Socket::new_with_description(name, description),
+    ///         None => Socket::new(name),
+    ///     }
+    /// });
+    ///
+    /// assert_eq!(registration.device_type().to_string(), "socket");
+    /// ```
+    pub fn of<T: DeviceKind + DeserializeOwned>(
+        tag: &'static str,
+        create: impl Fn(&str, Option<&str>) -> T + Send + Sync + 'static,
+    ) -> Self {
+        fn deserialize<T: DeviceKind + DeserializeOwned>(
+            value: Value,
+        ) -> serde_json::Result<Device> {
+            serde_json::from_value::<T>(value).map(Device::new)
+        }
+
+        Self {
+            tag,
+            create: Arc::new(move |name, description| Device::new(create(name, description))),
+            deserialize: deserialize::<T>,
+        }
+    }
+
+    pub fn device_type(&self) -> DeviceType {
+        DeviceType(self.tag)
+    }
+}
+
+/// The registered device types. The socket, the thermometer, the light, the environment sensor
+/// and the thermostat are always there, the rest are added by [register_device].
+static REGISTRY: LazyLock<RwLock<Vec<DeviceRegistration>>> = LazyLock::new(|| {
+    RwLock::new(vec![
+        DeviceRegistration::of("Socket", |name, description| match description {
+            Some(description) => Socket::new_with_description(name, description),
+            None => Socket::new(name),
+        }),
+        DeviceRegistration::of("Thermometer", |name, description| match description {
+            Some(description) => Thermometer::new_with_description(name, description),
+            None => Thermometer::new(name),
+        }),
+        DeviceRegistration::of("Light", |name, description| match description {
+            Some(description) => Light::new_with_description(name, description),
+            None => Light::new(name),
+        }),
+        DeviceRegistration::of("EnvironmentSensor", |name, description| match description {
+            Some(description) => EnvironmentSensor::new_with_description(name, description),
+            None => EnvironmentSensor::new(name),
+        }),
+        DeviceRegistration::of("Thermostat", |name, description| match description {
+            Some(description) => Thermostat::new_with_description(name, description),
+            None => Thermostat::new(name),
+        }),
+    ])
+});
+
+/// Registers a new device type, e.g. by a crate built on top of this one. The type becomes
+/// known everywhere at once: the devices of this type are stored and read back, created by the
+/// `new device --type <name>` command, listed, exported and described in the house
+/// descriptions. The type has to be registered before the repository is read, so the best
+/// place to do it is the very beginning of the `main` function.
+///
+/// It fails if the type with the same tag (in any case) is registered already.
+pub fn register_device(registration: DeviceRegistration) -> Result<DeviceType> {
+    let mut registry = REGISTRY.write().unwrap();
+    if registry
+        .iter()
+        .any(|known| known.tag.eq_ignore_ascii_case(registration.tag))
+    {
+        return Err(anyhow!(
+            "The device type {} is registered already",
+            registration.tag
+        ));
+    }
+    let device_type = registration.device_type();
+    registry.push(registration);
+    Ok(device_type)
+}
+
+/// All the registered device types, in the order of the registration
+pub fn device_types() -> Vec<DeviceType> {
+    let registry = REGISTRY.read().unwrap();
+    registry
+        .iter()
+        .map(DeviceRegistration::device_type)
+        .collect()
+}
+
+fn registration(tag: &str) -> Option<DeviceRegistration> {
+    let registry = REGISTRY.read().unwrap();
+    registry.iter().find(|known| known.tag == tag).cloned()
+}
+
+/// Reads the device of the registered type with the given tag from its fields
+pub(crate) fn deserialize_device(tag: &str, fields: Value) -> Result<Device, String> {
+    let registration = registration(tag).ok_or_else(|| format!("Unknown device type {tag}"))?;
+    let mut device = (registration.deserialize)(fields).map_err(|e| e.to_string())?;
+    if let Some(reading) = device.state_mut().last_reading.as_mut() {
+        reading.upgrade_legacy(tag);
+    }
+    Ok(device)
+}
+
+/// The type of the device, one of the registered ones. The type is written and parsed as the
+/// lowercase tag, e.g. `socket`, the parsing ignores the case and the dashes and underscores
+/// between the words, so `environment-sensor` is the same as `environmentsensor`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DeviceType(pub(super) &'static str);
+
+impl DeviceType {
+    pub const SOCKET: DeviceType = DeviceType("Socket");
+    pub const THERMOMETER: DeviceType = DeviceType("Thermometer");
+    pub const LIGHT: DeviceType = DeviceType("Light");
+    pub const ENVIRONMENT_SENSOR: DeviceType = DeviceType("EnvironmentSensor");
+    pub const THERMOSTAT: DeviceType = DeviceType("Thermostat");
+
+    /// The tag the devices of this type are stored with, e.g. `Socket`
+    pub fn tag(&self) -> &'static str {
+        self.0
+    }
+
+    /// Creates a new device of this type with a freshly generated id. It fails only for the
+    /// type of the device which was never registered.
+    pub fn create(&self, name: &str, description: Option<&str>) -> Result<Device> {
+        let registration = registration(self.0)
+            .ok_or_else(|| anyhow!("The device type {} is not registered", self.0))?;
+        Ok((registration.create)(name, description))
+    }
+}
+
+impl Display for DeviceType {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        write!(formatter, "{}", self.0.to_lowercase())
+    }
+}
+
+impl FromStr for DeviceType {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let types = device_types();
+        let wanted: String = name.trim().replace(['-', '_'], "");
+        types
+            .iter()
+            .find(|device_type| device_type.0.eq_ignore_ascii_case(&wanted))
+            .copied()
+            .ok_or_else(|| {
+                let known: Vec<String> = types.iter().map(DeviceType::to_string).collect();
+                format!(
+                    "Unknown device type '{name}', the known types are: {}",
+                    known.join(", ")
+                )
+            })
+    }
+}
+
+impl<'de> Deserialize<'de> for DeviceType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(D::Error::custom)
+    }
+}
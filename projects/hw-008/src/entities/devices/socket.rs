@@ -1,6 +1,7 @@
-use crate::entities::devices::DeviceId;
-use crate::entities::generate_id;
+use crate::entities::devices::{DeviceId, DeviceKind, DeviceState};
 use crate::entities::reportable::{ReportError, Reportable};
+use crate::entities::{format_labels, generate_id, Labels, MeasureError, Measurement, Quantity};
+use chrono::{DateTime, Utc};
 use serde_derive::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter, Result as FmtResult};
 
@@ -47,16 +48,78 @@ impl Display for SocketStatus {
     }
 }
 
+/// The cumulative counter of the energy consumed by the socket. The counter isn't updated on
+/// every tick: it keeps the energy consumed up to the `metered_at` moment, and the energy
+/// consumed since then is derived from the power of the socket, see [Socket::energy_at]. The
+/// counter is brought up to date each time the socket is switched or its power is changed.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct EnergyMeter {
+    /// The energy consumed up to the `metered_at` moment, in kWh
+    pub total_kwh: f64,
+    /// The moment the counter was brought up to date the last time. The sockets created before
+    /// the metering was introduced are metered since their latest switch.
+    pub metered_at: Option<DateTime<Utc>>,
+}
+
 /// A representation of the smart socket. Each device entity in this project must have the name
 /// and description. Socket entity also has two additional fields such as `power_consumption` and
 /// `status`. I guess, there is no need to write it down the meaning of these additional fields
+///
+/// The on/off status of the socket is kept in its [DeviceState], as for any other device.
+///
+/// The `power_consumption` is the power of the load plugged into the socket, in watts. The
+/// enabled socket consumes it all the time, and the consumed energy is counted by the
+/// [EnergyMeter].
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(from = "SocketRecord")]
 pub struct Socket {
     pub id: DeviceId,
     pub name: String,
     pub description: Option<String>,
+    /// The labels like `floor=2` or `critical`, see [LabelSelector](crate::entities::LabelSelector)
+    #[serde(default, skip_serializing_if = "Labels::is_empty")]
+    pub labels: Labels,
     pub power_consumption: f32,
-    pub status: SocketStatus,
+    pub state: DeviceState,
+    pub energy: EnergyMeter,
+}
+
+/// The socket as it's stored. The sockets written before the [DeviceState] was introduced have
+/// the `status` field instead of the `state`. The stored state is upgraded by the schema
+/// migration, but such sockets are still kept by the journal, so they are upgraded on read too.
+#[derive(Deserialize)]
+struct SocketRecord {
+    id: DeviceId,
+    name: String,
+    description: Option<String>,
+    #[serde(default)]
+    labels: Labels,
+    power_consumption: f32,
+    #[serde(default)]
+    state: Option<DeviceState>,
+    #[serde(default)]
+    status: Option<SocketStatus>,
+    #[serde(default)]
+    energy: EnergyMeter,
+}
+
+impl From<SocketRecord> for Socket {
+    fn from(record: SocketRecord) -> Self {
+        let state = record.state.unwrap_or_else(|| DeviceState {
+            on: matches!(record.status, Some(SocketStatus::Enabled)),
+            ..DeviceState::default()
+        });
+
+        Self {
+            id: record.id,
+            name: record.name,
+            description: record.description,
+            labels: record.labels,
+            power_consumption: record.power_consumption,
+            state,
+            energy: record.energy,
+        }
+    }
 }
 
 /// An implementation of the Socket struct. All of these methods and functions are super obvious,
@@ -69,7 +132,7 @@ impl Socket {
     /// the [Default] implementation of the Socket struct.
     pub fn new(name: &str) -> Self {
         Self {
-            id: generate_id("sock_"),
+            id: generate_id("sock"),
             name: name.to_string(),
             ..Self::default()
         }
@@ -81,7 +144,7 @@ impl Socket {
     /// other fields will be derived from the [Default] implementation of the Socket struct.
     pub fn new_with_description(name: &str, description: &str) -> Self {
         Self {
-            id: generate_id("sock_"),
+            id: generate_id("sock"),
             name: name.to_string(),
             description: Some(description.to_string()),
             ..Self::default()
@@ -96,22 +159,60 @@ impl Socket {
         self.description.as_ref().cloned()
     }
 
+    /// Returns the current status of the socket, it's derived from the socket state
+    pub fn status(&self) -> SocketStatus {
+        SocketStatus::from_bool(self.state.on)
+    }
+
     /// Method for enabling the socket. It needs a mutable reference and it overwrites the
-    /// current socket status.
+    /// current socket status. The energy counter is brought up to date first.
     pub fn enable(&mut self) {
-        self.status = SocketStatus::Enabled
+        self.switch(true, Some(Utc::now()))
     }
 
     /// Method for disabling the socket. It needs a mutable reference and it overwrites the
-    /// current socket status.
+    /// current socket status. The energy counter is brought up to date first.
     pub fn disable(&mut self) {
-        self.status = SocketStatus::Disabled
+        self.switch(false, Some(Utc::now()))
+    }
+
+    /// As the name of this function claims, it returns the current power consumption in watts.
+    /// The disabled socket consumes nothing.
+    pub fn get_current_power_consumption(&self) -> f32 {
+        if self.state.on {
+            self.power_consumption
+        } else {
+            0.0
+        }
+    }
+
+    /// The energy consumed by the socket up to the given moment, in kWh. The moments before the
+    /// latest update of the counter give the counter as is.
+    pub fn energy_at(&self, at: DateTime<Utc>) -> f64 {
+        let since = self.energy.metered_at.or(self.state.changed_at);
+        let running = match since {
+            Some(since) if self.state.on && at > since => {
+                let hours = (at - since).num_milliseconds() as f64 / 3_600_000.0;
+                f64::from(self.power_consumption) * hours / 1000.0
+            }
+            _ => 0.0,
+        };
+        self.energy.total_kwh + running
+    }
+
+    /// Brings the energy counter up to date at the given moment
+    pub fn settle_energy(&mut self, at: DateTime<Utc>) {
+        self.energy = EnergyMeter {
+            total_kwh: self.energy_at(at),
+            metered_at: Some(at),
+        };
     }
 
-    /// As the name of this function claims, it returns the current power consumption in milli
-    /// amperes (or whatever it's consumes)
-    pub fn get_current_power_consumption(&self) -> Option<f32> {
-        rand::random()
+    /// Changes the power of the load at the given moment. The energy consumed with the previous
+    /// power is counted up to this moment.
+    pub fn set_power(&mut self, watts: f32, at: DateTime<Utc>) {
+        self.settle_energy(at);
+        self.power_consumption = watts;
     }
 }
 
@@ -121,11 +222,13 @@ impl Socket {
 impl Default for Socket {
     fn default() -> Self {
         Self {
-            id: generate_id("socket_"),
+            id: generate_id("sock"),
             name: "Default socket".to_string(),
             description: None,
+            labels: Labels::new(),
             power_consumption: 0.0,
-            status: SocketStatus::Disabled,
+            state: DeviceState::default(),
+            energy: EnergyMeter::default(),
         }
     }
 }
@@ -136,17 +239,23 @@ impl Default for Socket {
 impl Display for Socket {
     fn fmt(&self, formatter: &mut Formatter) -> FmtResult {
         let txt = format!(
-            "Socket: {},\nId: {},\nStatus: {},\nPower consumption: {},\nDescription: {}",
+            "Socket: {},\nId: {},\nStatus: {},\nPower consumption: {},\nEnergy: {:.3} kWh,\n\
+             Description: {}",
             self.name,
             self.id,
-            self.status,
+            self.status(),
             self.power_consumption,
+            self.energy_at(Utc::now()),
             self.description
                 .clone()
                 .unwrap_or_else(|| "[No description]".to_string())
         );
 
-        write!(formatter, "{txt}")
+        write!(formatter, "{txt}")?;
+        if !self.labels.is_empty() {
+            write!(formatter, ",\nLabels: {}", format_labels(&self.labels))?;
+        }
+        Ok(())
     }
 }
 
@@ -156,6 +265,76 @@ impl Display for Socket {
 /// usage of `power_consumption` field.
 impl Reportable for Socket {
     fn report(&self) -> Result<String, ReportError> {
-        Ok(format!("Socket: {}, Status: {}", self.name, self.status))
+        Ok(format!("Socket: {}, Status: {}", self.name, self.status()))
+    }
+}
+
+impl DeviceKind for Socket {
+    fn tag(&self) -> &'static str {
+        "Socket"
+    }
+
+    fn id_prefix(&self) -> &'static str {
+        "sock"
+    }
+
+    fn id(&self) -> &DeviceId {
+        &self.id
+    }
+
+    fn set_id(&mut self, id: DeviceId) {
+        self.id = id;
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    fn labels(&self) -> &Labels {
+        &self.labels
+    }
+
+    fn labels_mut(&mut self) -> &mut Labels {
+        &mut self.labels
+    }
+
+    fn state(&self) -> &DeviceState {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut DeviceState {
+        &mut self.state
+    }
+
+    fn edit(&mut self, name: String, description: Option<String>) {
+        self.name = name;
+        self.description = description;
+    }
+
+    /// The counter is brought up to date before the switch, so the energy is counted only
+    /// while the socket is enabled
+    fn switch(&mut self, on: bool, at: Option<DateTime<Utc>>) {
+        if let Some(at) = at {
+            self.settle_energy(at);
+        }
+        self.state.on = on;
+        self.state.changed_at = at;
+    }
+
+    /// The socket measures the power it consumes right now and the energy it has consumed
+    fn measure(&self) -> Result<Vec<Measurement>, MeasureError> {
+        let energy = self.energy_at(Utc::now()) as f32;
+        Ok(vec![
+            Measurement::new(Quantity::Power, self.get_current_power_consumption()),
+            Measurement::new(Quantity::Energy, energy),
+        ])
+    }
+
+    fn energy_at(&self, at: DateTime<Utc>) -> Option<f64> {
+        Some(Socket::energy_at(self, at))
     }
 }
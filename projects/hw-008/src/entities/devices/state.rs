@@ -0,0 +1,136 @@
+use crate::entities::{Measurement, Quantity};
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// All the values measured by the device at once together with the time of the measurement
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(from = "ReadingRecord")]
+pub struct Reading {
+    pub measurements: Vec<Measurement>,
+    pub taken_at: DateTime<Utc>,
+}
+
+impl Reading {
+    /// The reading of the given measurements taken right now
+    pub fn new(measurements: Vec<Measurement>) -> Self {
+        Self {
+            measurements,
+            taken_at: Utc::now(),
+        }
+    }
+
+    /// The measured value of the given quantity, if the device measures it
+    pub fn get(&self, quantity: Quantity) -> Option<&Measurement> {
+        self.measurements
+            .iter()
+            .find(|measurement| measurement.quantity == quantity)
+    }
+
+    /// Gives the bare values of the old readings the quantity measured by the device with the
+    /// given tag. The reading itself doesn't know its device, e.g. in the journaled events, so
+    /// it's done by the one who knows.
+    pub(crate) fn upgrade_legacy(&mut self, tag: &str) {
+        let quantity = legacy_quantity(tag);
+        let bare = self
+            .measurements
+            .iter_mut()
+            .filter(|measurement| measurement.quantity == Quantity::Unspecified);
+        for measurement in bare {
+            measurement.quantity = quantity;
+            measurement.unit = quantity.unit();
+        }
+    }
+}
+
+/// The quantity measured by the devices with the given tag before the [Measurement]s were
+/// introduced. Only the thermometers and the lights measured anything back then.
+pub(crate) fn legacy_quantity(tag: &str) -> Quantity {
+    match tag {
+        "Thermometer" => Quantity::Temperature,
+        "Light" => Quantity::Brightness,
+        _ => Quantity::Unspecified,
+    }
+}
+
+/// The values joined by the commas, e.g. `temperature 21.5°C, humidity 40%`
+impl Display for Reading {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        let values: Vec<String> = self.measurements.iter().map(|m| m.to_string()).collect();
+        write!(formatter, "{}", values.join(", "))
+    }
+}
+
+/// The reading as it's stored. The readings taken before the [Measurement]s were introduced have
+/// a single bare `value`. The stored state is upgraded by the schema migration, but such
+/// readings are still kept by the journal and the snapshots, so they are upgraded on read too:
+/// the bare value is read as the unspecified measurement, and it gets its quantity by
+/// [Reading::upgrade_legacy] as soon as the device is known.
+#[derive(Deserialize)]
+struct ReadingRecord {
+    #[serde(default)]
+    measurements: Vec<Measurement>,
+    #[serde(default)]
+    value: Option<f32>,
+    taken_at: DateTime<Utc>,
+}
+
+impl From<ReadingRecord> for Reading {
+    fn from(record: ReadingRecord) -> Self {
+        let mut measurements = record.measurements;
+        if let Some(value) = record.value {
+            measurements.push(Measurement {
+                taken_at: record.taken_at,
+                ..Measurement::new(Quantity::Unspecified, value)
+            });
+        }
+        Self {
+            measurements,
+            taken_at: record.taken_at,
+        }
+    }
+}
+
+/// The runtime state of the device. Unlike the name or the description, it's changed by the
+/// device itself, or by the commands sent to the device. The state is stored together with the
+/// device, so it survives the restarts of the server.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct DeviceState {
+    /// `true` if the device is switched on
+    pub on: bool,
+    /// The latest value measured by the device, if the device is able to measure anything
+    pub last_reading: Option<Reading>,
+    /// The moment of the latest change of the state. The devices created before the state was
+    /// introduced have no such moment.
+    pub changed_at: Option<DateTime<Utc>>,
+}
+
+impl DeviceState {
+    /// The state of a freshly switched on device
+    pub fn switched_on() -> Self {
+        Self {
+            on: true,
+            ..Self::default()
+        }
+    }
+}
+
+/// A short one-line description of the state, e.g.
+/// `on, last reading temperature 21.5°C at 2023-01-31 ...`
+impl Display for DeviceState {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        write!(formatter, "{}", if self.on { "on" } else { "off" })?;
+
+        if let Some(reading) = &self.last_reading {
+            write!(
+                formatter,
+                ", last reading {reading} at {}",
+                reading.taken_at.to_rfc3339()
+            )?;
+        }
+        if let Some(changed_at) = &self.changed_at {
+            write!(formatter, ", changed at {}", changed_at.to_rfc3339())?;
+        }
+        Ok(())
+    }
+}
@@ -1,6 +1,9 @@
-use crate::entities::devices::DeviceId;
+use crate::entities::devices::{DeviceId, DeviceKind, DeviceState, RoomTemperature};
 use crate::entities::reportable::{ReportError, Reportable};
-use crate::entities::{generate_id, Measure, MeasureError};
+use crate::entities::{
+    format_labels, generate_id, Labels, Measure, MeasureError, Measurement, Quantity,
+};
+use chrono::Utc;
 use serde_derive::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter, Result as FmtResult};
 
@@ -12,6 +15,17 @@ pub struct Thermometer {
     pub id: DeviceId,
     pub name: String,
     pub description: Option<String>,
+    /// The labels like `floor=2` or `critical`, see [LabelSelector](crate::entities::LabelSelector)
+    #[serde(default, skip_serializing_if = "Labels::is_empty")]
+    pub labels: Labels,
+    /// The thermometers are switched on right after creation, so the ones stored before the
+    /// state was introduced are switched on as well
+    #[serde(default = "DeviceState::switched_on")]
+    pub state: DeviceState,
+    /// The simulated temperature of the room, it's measured instead of a random one since the
+    /// sensor was linked to a thermostat
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub room: Option<RoomTemperature>,
 }
 
 /// A thermometer struct implementation, it mostly wrapper and dummy stub-logic inside each method.
@@ -20,9 +34,12 @@ impl Thermometer {
     /// real life we don't need the name of our thermometer.
     pub fn new(name: &str) -> Self {
         Self {
-            id: generate_id("ther_"),
+            id: generate_id("ther"),
             name: name.to_string(),
             description: None,
+            labels: Labels::new(),
+            state: DeviceState::switched_on(),
+            room: None,
         }
     }
 
@@ -30,9 +47,12 @@ impl Thermometer {
     /// struct, because in the real life we don't need the name of our thermometer.
     pub fn new_with_description(name: &str, description: &str) -> Self {
         Self {
-            id: generate_id("ther_"),
+            id: generate_id("ther"),
             name: name.to_string(),
             description: Some(description.to_string()),
+            labels: Labels::new(),
+            state: DeviceState::switched_on(),
+            room: None,
         }
     }
 }
@@ -50,17 +70,30 @@ impl Display for Thermometer {
                 .unwrap_or_else(|| "[No description]".to_string())
         );
 
-        write!(formatter, "{txt}")
+        write!(formatter, "{txt}")?;
+        if !self.labels.is_empty() {
+            write!(formatter, ",\nLabels: {}", format_labels(&self.labels))?;
+        }
+        Ok(())
     }
 }
 
-impl Measure<f32> for Thermometer {
+impl Measure for Thermometer {
     /// A fake implementation of the `measure` function for the given thermometer instance. The
-    /// current implementation gives a random number from [0.. 1.0). I don't expect that the logic
-    /// behind this method will be changed in scope of this project at all. So, please consider
-    /// to use it if you want to get some random number in your use case
-    fn measure(&self) -> Result<Option<f32>, MeasureError> {
-        Ok(Some(rand::random()))
+    /// current implementation gives a random temperature from [0.. 1.0) degrees. I don't expect
+    /// that the logic behind this method will be changed in scope of this project at all. So,
+    /// please consider to use it if you want to get some random number in your use case. The
+    /// thermometer linked to a thermostat measures the simulated temperature of its room, and
+    /// the switched off thermometer measures nothing.
+    fn measure(&self) -> Result<Vec<Measurement>, MeasureError> {
+        if !self.state.on {
+            return Err(MeasureError::DeviceIsOff);
+        }
+        let temperature = match &self.room {
+            Some(room) => room.celsius_at(Utc::now()),
+            None => rand::random(),
+        };
+        Ok(vec![Measurement::new(Quantity::Temperature, temperature)])
     }
 }
 
@@ -68,12 +101,66 @@ impl Measure<f32> for Thermometer {
 /// representation of the current status. It makes measurement for building the status report.
 impl Reportable for Thermometer {
     fn report(&self) -> Result<String, ReportError> {
-        match self.measure() {
-            Ok(result) => match result {
-                Some(value) => Ok(format!("Thermometer: {}, Measure: {value}", self.name)),
+        match Measure::measure(self) {
+            Ok(result) => match result.first() {
+                Some(measurement) => Ok(format!(
+                    "Thermometer: {}, Measure: {}",
+                    self.name, measurement.value
+                )),
                 None => Ok(format!("Thermometer: {}, No measure value", self.name)),
             },
             Err(msg) => Err(msg.into()),
         }
     }
 }
+
+impl DeviceKind for Thermometer {
+    fn tag(&self) -> &'static str {
+        "Thermometer"
+    }
+
+    fn id_prefix(&self) -> &'static str {
+        "ther"
+    }
+
+    fn id(&self) -> &DeviceId {
+        &self.id
+    }
+
+    fn set_id(&mut self, id: DeviceId) {
+        self.id = id;
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    fn labels(&self) -> &Labels {
+        &self.labels
+    }
+
+    fn labels_mut(&mut self) -> &mut Labels {
+        &mut self.labels
+    }
+
+    fn state(&self) -> &DeviceState {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut DeviceState {
+        &mut self.state
+    }
+
+    fn edit(&mut self, name: String, description: Option<String>) {
+        self.name = name;
+        self.description = description;
+    }
+
+    fn measure(&self) -> Result<Vec<Measurement>, MeasureError> {
+        Measure::measure(self)
+    }
+}
@@ -0,0 +1,388 @@
+use crate::entities::devices::{DeviceId, DeviceKind, DeviceState};
+use crate::entities::reportable::{ReportError, Reportable};
+use crate::entities::{format_labels, generate_id, Labels};
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+
+/// The target temperature of the freshly created thermostat, in °C
+pub const DEFAULT_TARGET: f32 = 21.0;
+
+/// The hysteresis of the freshly created thermostat, in °C
+pub const DEFAULT_HYSTERESIS: f32 = 0.5;
+
+/// How much lower the temperature is kept in the [ThermostatMode::Eco] mode, in °C
+pub const ECO_SETBACK: f32 = 3.0;
+
+/// The number of the latest switching decisions the thermostat keeps for its report
+pub const DECISION_LOG_SIZE: usize = 20;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ThermostatMode {
+    /// The temperature is kept around the target
+    #[default]
+    Heat,
+    /// The temperature is kept [ECO_SETBACK] degrees lower than the target
+    Eco,
+    /// The heaters are kept switched off
+    Off,
+}
+
+impl Display for ThermostatMode {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            ThermostatMode::Heat => formatter.write_str("heat"),
+            ThermostatMode::Eco => formatter.write_str("eco"),
+            ThermostatMode::Off => formatter.write_str("off"),
+        }
+    }
+}
+
+impl FromStr for ThermostatMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode.trim().to_lowercase().as_str() {
+            "heat" => Ok(ThermostatMode::Heat),
+            "eco" => Ok(ThermostatMode::Eco),
+            "off" => Ok(ThermostatMode::Off),
+            _ => Err(format!(
+                "Unknown mode '{mode}', the known modes are: heat, eco, off"
+            )),
+        }
+    }
+}
+
+/// The temperature the simulated room starts from, in °C
+pub const ROOM_TEMPERATURE: f32 = 18.0;
+
+/// How fast the simulated room warms up while its heaters are on, in °C per hour
+pub const HEATING_RATE: f32 = 2.0;
+
+/// How fast the simulated room cools down while its heaters are off, in °C per hour
+pub const COOLING_RATE: f32 = 1.0;
+
+/// The simulated room never gets colder or warmer than these, in °C
+pub const ROOM_TEMPERATURE_RANGE: (f32, f32) = (10.0, 30.0);
+
+/// The simulated temperature of the room a thermostat keeps. The sensor linked to the thermostat
+/// measures it instead of a random value: the room warms up while the heaters are on, and cools
+/// down while they are off. The temperature is kept as of the `at` moment, the later ones are
+/// derived from it, the same way the energy of the socket is, see
+/// [EnergyMeter](crate::entities::devices::EnergyMeter).
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct RoomTemperature {
+    pub celsius: f32,
+    pub at: DateTime<Utc>,
+    pub heating: bool,
+}
+
+impl RoomTemperature {
+    /// The room starts at [ROOM_TEMPERATURE] with the heaters switched off
+    pub fn new(at: DateTime<Utc>) -> Self {
+        Self {
+            celsius: ROOM_TEMPERATURE,
+            at,
+            heating: false,
+        }
+    }
+
+    /// The temperature of the room at the given moment
+    pub fn celsius_at(&self, at: DateTime<Utc>) -> f32 {
+        let hours = (at - self.at).num_milliseconds().max(0) as f32 / 3_600_000.0;
+        let (coldest, warmest) = ROOM_TEMPERATURE_RANGE;
+        if self.heating {
+            (self.celsius + HEATING_RATE * hours).min(warmest)
+        } else {
+            (self.celsius - COOLING_RATE * hours).max(coldest)
+        }
+    }
+
+    /// Brings the temperature up to date at the given moment, the room warms up or cools down
+    /// from there by the state of the heaters
+    pub fn set_heating(&mut self, heating: bool, at: DateTime<Utc>) {
+        *self = Self {
+            celsius: self.celsius_at(at),
+            at,
+            heating,
+        };
+    }
+}
+
+/// A single switching decision of the thermostat: at the given moment the heaters were switched
+/// on or off, because the temperature crossed the threshold. The decisions made without any
+/// temperature are the ones made by switching the thermostat off.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct Decision {
+    pub at: DateTime<Utc>,
+    pub temperature: Option<f32>,
+    pub threshold: Option<f32>,
+    pub heating: bool,
+}
+
+/// A short description of the decision, e.g. `2023-01-31T10:00:00+00:00: 19.8°C is below
+/// 20.5°C, the heaters are switched on`
+impl Display for Decision {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        let heaters = if self.heating { "on" } else { "off" };
+        write!(formatter, "{}: ", self.at.to_rfc3339())?;
+        match (self.temperature, self.threshold) {
+            (Some(temperature), Some(threshold)) => write!(
+                formatter,
+                "{temperature:.1}°C is {} {threshold:.1}°C, the heaters are switched {heaters}",
+                if self.heating { "below" } else { "above" }
+            ),
+            _ => write!(
+                formatter,
+                "the thermostat is off, the heaters are switched {heaters}"
+            ),
+        }
+    }
+}
+
+/// The settings and the links of the [Thermostat], everything about it but the decisions of the
+/// control loop
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ThermostatConfig {
+    pub mode: ThermostatMode,
+    pub target: f32,
+    pub hysteresis: f32,
+    pub thermometer: Option<DeviceId>,
+    pub heaters: Vec<DeviceId>,
+}
+
+/// A thermostat keeping the temperature of the room. It reads the temperature from the linked
+/// thermometer, and switches the linked sockets with the heaters on when the temperature falls
+/// below the target minus the hysteresis, and off when it rises above the target plus the
+/// hysteresis. The thermostat itself only decides, the server runs the control loop, see
+/// [ThermostatFunctions](crate::entities::manager::ThermostatFunctions).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Thermostat {
+    pub id: DeviceId,
+    pub name: String,
+    pub description: Option<String>,
+    /// The labels like `floor=2` or `critical`, see [LabelSelector](crate::entities::LabelSelector)
+    #[serde(default, skip_serializing_if = "Labels::is_empty")]
+    pub labels: Labels,
+    pub state: DeviceState,
+    pub mode: ThermostatMode,
+    /// The target temperature, in °C
+    pub target: f32,
+    /// The allowed deviation from the target, in °C, so the heaters aren't switched too often
+    pub hysteresis: f32,
+    /// The device the temperature is read from, a thermometer or an environment sensor of the
+    /// same room
+    #[serde(default)]
+    pub thermometer: Option<DeviceId>,
+    /// The sockets of the same room the heaters are plugged into
+    #[serde(default)]
+    pub heaters: Vec<DeviceId>,
+    /// `true` if the thermostat switched the heaters on the last time
+    #[serde(default)]
+    pub heating: bool,
+    /// The latest [DECISION_LOG_SIZE] switching decisions, the oldest first
+    #[serde(default)]
+    pub decisions: Vec<Decision>,
+}
+
+impl Thermostat {
+    /// Creates a switched on thermostat in the heat mode with the default target. It has
+    /// nothing linked yet, so it doesn't switch anything until the thermometer and the heaters
+    /// are linked.
+    pub fn new(name: &str) -> Self {
+        Self {
+            id: generate_id("stat"),
+            name: name.to_string(),
+            description: None,
+            labels: Labels::new(),
+            state: DeviceState::switched_on(),
+            mode: ThermostatMode::default(),
+            target: DEFAULT_TARGET,
+            hysteresis: DEFAULT_HYSTERESIS,
+            thermometer: None,
+            heaters: vec![],
+            heating: false,
+            decisions: vec![],
+        }
+    }
+
+    pub fn new_with_description(name: &str, description: &str) -> Self {
+        Self {
+            description: Some(description.to_string()),
+            ..Self::new(name)
+        }
+    }
+
+    pub fn config(&self) -> ThermostatConfig {
+        ThermostatConfig {
+            mode: self.mode,
+            target: self.target,
+            hysteresis: self.hysteresis,
+            thermometer: self.thermometer.clone(),
+            heaters: self.heaters.clone(),
+        }
+    }
+
+    /// Changes the settings and the links, the decisions made so far are kept
+    pub fn set_config(&mut self, config: ThermostatConfig) {
+        self.mode = config.mode;
+        self.target = config.target;
+        self.hysteresis = config.hysteresis;
+        self.thermometer = config.thermometer;
+        self.heaters = config.heaters;
+    }
+
+    /// The temperature the thermostat keeps right now, there is none when it's off
+    pub fn setpoint(&self) -> Option<f32> {
+        if !self.state.on {
+            return None;
+        }
+        match self.mode {
+            ThermostatMode::Heat => Some(self.target),
+            ThermostatMode::Eco => Some(self.target - ECO_SETBACK),
+            ThermostatMode::Off => None,
+        }
+    }
+
+    /// Decides whether the heaters have to be switched by the measured temperature. There is
+    /// nothing to decide while the temperature stays within the hysteresis, or when there is no
+    /// temperature at all. The switched off thermostat only switches its heaters off.
+    pub fn decide(&self, temperature: Option<f32>, at: DateTime<Utc>) -> Option<Decision> {
+        let Some(setpoint) = self.setpoint() else {
+            return self.heating.then_some(Decision {
+                at,
+                temperature: None,
+                threshold: None,
+                heating: false,
+            });
+        };
+
+        let temperature = temperature?;
+        let (low, high) = (setpoint - self.hysteresis, setpoint + self.hysteresis);
+        let (heating, threshold) = if !self.heating && temperature < low {
+            (true, low)
+        } else if self.heating && temperature > high {
+            (false, high)
+        } else {
+            return None;
+        };
+        Some(Decision {
+            at,
+            temperature: Some(temperature),
+            threshold: Some(threshold),
+            heating,
+        })
+    }
+
+    /// Remembers the decision, only the latest [DECISION_LOG_SIZE] ones are kept
+    pub fn record(&mut self, decision: Decision) {
+        self.heating = decision.heating;
+        self.decisions.push(decision);
+        let excess = self.decisions.len().saturating_sub(DECISION_LOG_SIZE);
+        self.decisions.drain(..excess);
+    }
+
+    /// The mode and the target, e.g. `heat, target 21°C ±0.5°C`
+    fn settings(&self) -> String {
+        match self.setpoint() {
+            Some(setpoint) => format!("{}, target {setpoint}°C ±{}°C", self.mode, self.hysteresis),
+            None => "off".to_string(),
+        }
+    }
+}
+
+/// Writes out the full information about the thermostat together with its links and decisions
+impl Display for Thermostat {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            formatter,
+            "Thermostat: {},\nId: {},\nStatus: {},\nMode: {},\nTarget: {}°C ±{}°C,\n\
+             Thermometer: {},\nHeaters: {},\nHeating: {},\nDescription: {}",
+            self.name,
+            self.id,
+            if self.state.on { "On" } else { "Off" },
+            self.mode,
+            self.target,
+            self.hysteresis,
+            self.thermometer.as_deref().unwrap_or("[Not linked]"),
+            if self.heaters.is_empty() {
+                "[Not linked]".to_string()
+            } else {
+                self.heaters.join(", ")
+            },
+            if self.heating { "yes" } else { "no" },
+            self.description.as_deref().unwrap_or("[No description]")
+        )?;
+        if !self.labels.is_empty() {
+            write!(formatter, ",\nLabels: {}", format_labels(&self.labels))?;
+        }
+        for decision in &self.decisions {
+            write!(formatter, "\n\t{decision}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The report gives the settings and all the kept switching decisions, one per line
+impl Reportable for Thermostat {
+    fn report(&self) -> Result<String, ReportError> {
+        let mut report = format!(
+            "Thermostat: {}, Mode: {}, Heating: {}",
+            self.name,
+            self.settings(),
+            if self.heating { "yes" } else { "no" }
+        );
+        for decision in &self.decisions {
+            report.push_str(&format!("\n\t\t\t{decision}"));
+        }
+        Ok(report)
+    }
+}
+
+impl DeviceKind for Thermostat {
+    fn tag(&self) -> &'static str {
+        "Thermostat"
+    }
+
+    fn id_prefix(&self) -> &'static str {
+        "stat"
+    }
+
+    fn id(&self) -> &DeviceId {
+        &self.id
+    }
+
+    fn set_id(&mut self, id: DeviceId) {
+        self.id = id;
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    fn labels(&self) -> &Labels {
+        &self.labels
+    }
+
+    fn labels_mut(&mut self) -> &mut Labels {
+        &mut self.labels
+    }
+
+    fn state(&self) -> &DeviceState {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut DeviceState {
+        &mut self.state
+    }
+
+    fn edit(&mut self, name: String, description: Option<String>) {
+        self.name = name;
+        self.description = description;
+    }
+}
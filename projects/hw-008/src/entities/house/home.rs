@@ -1,7 +1,9 @@
 use crate::entities::devices::Device;
 use crate::entities::house::room::Room;
 use crate::entities::reportable::Reportable;
-use crate::entities::{generate_id, ReportError};
+use crate::entities::{
+    format_labels, generate_id, Labels, NamingRules, ReportError, ValidationError,
+};
 use serde_derive::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter, Result as FmtResult};
 
@@ -16,6 +18,9 @@ pub struct Home {
     pub id: HomeId,
     pub name: String,
     pub description: Option<String>,
+    /// The labels like `floor=2` or `critical`, see [LabelSelector](crate::entities::LabelSelector)
+    #[serde(default, skip_serializing_if = "Labels::is_empty")]
+    pub labels: Labels,
     pub rooms: Vec<Room>,
 }
 
@@ -38,12 +43,13 @@ impl Home {
     /// An unobvious function, which returns an option of Vector of [Reportable]s. It tries to
     /// find devices for the `room` in the belonging list of [Room]s in the Home. It will return
     /// [None] in case, there is no room in the Home or the list of rooms doesn't contain the
-    /// room with the provided room name. The signature of this function looks ugly, but hopefully
-    /// it will be refactored soon
+    /// room with the provided room id. The rooms are matched by their ids, since the names are
+    /// not unique with the [permissive](crate::entities::NamingRules::permissive) rules. The
+    /// signature of this function looks ugly, but hopefully it will be refactored soon
     pub fn get_devices(&self, room: &Room) -> Option<&Vec<Device>> {
         self.rooms
             .iter()
-            .find(|r| r.name == room.name)
+            .find(|r| r.id == room.id)
             .map(|r| &r.devices)
     }
 }
@@ -64,7 +70,11 @@ impl Display for Home {
                 .unwrap_or_else(|| "[No description]".to_string()),
             rooms.join(",")
         );
-        write!(formatter, "{txt}")
+        write!(formatter, "{txt}")?;
+        if !self.labels.is_empty() {
+            write!(formatter, ",\nLabels: {}", format_labels(&self.labels))?;
+        }
+        Ok(())
     }
 }
 
@@ -101,6 +111,7 @@ pub struct HomeBuilder {
     name: Option<String>,
     description: Option<String>,
     rooms: Option<Vec<Room>>,
+    rules: NamingRules,
 }
 
 impl HomeBuilder {
@@ -108,31 +119,45 @@ impl HomeBuilder {
     /// provided fields, and populate these values to the final Home instance. It requires an
     /// exclusive ownership for the self instance.
     ///
-    /// It will return [Err] in case, when the `name` for the home is not provided. In any other
-    /// case, it should return a valid home instance
+    /// It will return [Err] in case, when the `name` for the home is not provided, or the name
+    /// breaks the [NamingRules], or two rooms have the same name. In any other case, it should
+    /// return a valid home instance
     ///
     /// ```
     /// use hw_008::entities::house::HomeBuilder;
+    /// use hw_008::entities::ValidationError;
     /// let builder = HomeBuilder::default();
     /// let home = builder.build();
     ///
-    /// assert!(home.is_err());
+    /// assert_eq!(home.unwrap_err(), ValidationError::MissingName);
     /// ```
-    pub fn build(self) -> Result<Home, String> {
-        if self.name.is_none() {
-            return Err("Please, provide house name".to_string());
+    pub fn build(self) -> Result<Home, ValidationError> {
+        let name = self.name.ok_or(ValidationError::MissingName)?;
+        self.rules.check_name(&name)?;
+
+        let rooms = self.rooms.unwrap_or_default();
+        for (i, room) in rooms.iter().enumerate() {
+            let siblings = rooms[..i].iter().map(|r| r.name.as_str());
+            self.rules.check_unique(&room.name, siblings, Some(&name))?;
         }
 
         let h = Home {
             id: generate_id("home"),
-            name: self.name.unwrap(),
+            name,
             description: self.description,
-            rooms: self.rooms.unwrap_or_default(),
+            labels: Labels::new(),
+            rooms,
         };
 
         Ok(h)
     }
 
+    /// An owned method which will return a copy of the HomeBuilder with the [NamingRules] the
+    /// home is checked by. The [NamingRules::default] are used unless other rules are given.
+    pub fn with_rules(self, rules: NamingRules) -> HomeBuilder {
+        HomeBuilder { rules, ..self }
+    }
+
     /// An owned method which will return a copy of the HomeBuilder with name of Home set. It
     /// will clone the provided `name` parameter. In the future implementation it might be
     /// changed for lean and effective implementation
@@ -141,6 +166,7 @@ impl HomeBuilder {
             name: Some(name.to_string()),
             description: self.description,
             rooms: self.rooms,
+            rules: self.rules,
         }
     }
 
@@ -152,6 +178,7 @@ impl HomeBuilder {
             name: self.name,
             description: Some(description.to_string()),
             rooms: self.rooms,
+            rules: self.rules,
         }
     }
 
@@ -165,6 +192,7 @@ impl HomeBuilder {
             name: self.name,
             description: self.description,
             rooms: Some(rooms),
+            rules: self.rules,
         }
     }
 
@@ -177,6 +205,7 @@ impl HomeBuilder {
             name: self.name,
             description: self.description,
             rooms: Some(rooms),
+            rules: self.rules,
         }
     }
 }
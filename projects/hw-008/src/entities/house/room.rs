@@ -1,6 +1,6 @@
 use crate::entities::devices::Device;
-use crate::entities::generate_id;
 use crate::entities::reportable::{ReportError, Reportable};
+use crate::entities::{format_labels, generate_id, Labels, NamingRules, ValidationError};
 use serde_derive::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter, Result as FmtResult};
 
@@ -14,6 +14,9 @@ pub struct Room {
     pub id: RoomId,
     pub name: String,
     pub description: Option<String>,
+    /// The labels like `floor=2` or `critical`, see [LabelSelector](crate::entities::LabelSelector)
+    #[serde(default, skip_serializing_if = "Labels::is_empty")]
+    pub labels: Labels,
     pub devices: Vec<Device>,
 }
 
@@ -43,7 +46,11 @@ impl Display for Room {
             devices_report.join(", ")
         );
 
-        write!(formatter, "{txt}")
+        write!(formatter, "{txt}")?;
+        if !self.labels.is_empty() {
+            write!(formatter, ",\nLabels: {}", format_labels(&self.labels))?;
+        }
+        Ok(())
     }
 }
 
@@ -79,6 +86,7 @@ pub struct RoomBuilder {
     name: Option<String>,
     description: Option<String>,
     devices: Option<Vec<Device>>,
+    rules: NamingRules,
 }
 
 impl RoomBuilder {
@@ -86,8 +94,9 @@ impl RoomBuilder {
     /// provided fields, and populate these values to the final Room instance. It requires an
     /// exclusive ownership for the self instance.
     ///
-    /// It will return [Err] in case, when the `name` for the home is not provided. In any other
-    /// case, it should return a valid home instance
+    /// It will return [Err] in case, when the `name` for the room is not provided, or the name
+    /// breaks the [NamingRules], or two devices have the same name. In any other case, it
+    /// should return a valid room instance
     ///
     /// ```
     /// use hw_008::entities::house::RoomBuilder;
@@ -96,21 +105,34 @@ impl RoomBuilder {
     ///
     /// assert!(room.is_err());
     /// ```
-    pub fn build(self) -> Result<Room, String> {
-        if self.name.is_none() {
-            return Err("Please, provide room name".to_string());
+    pub fn build(self) -> Result<Room, ValidationError> {
+        let name = self.name.ok_or(ValidationError::MissingName)?;
+        self.rules.check_name(&name)?;
+
+        let devices = self.devices.unwrap_or_default();
+        for (i, device) in devices.iter().enumerate() {
+            let siblings = devices[..i].iter().map(|d| d.name());
+            self.rules
+                .check_unique(device.name(), siblings, Some(&name))?;
         }
 
         let r = Room {
             id: generate_id("room"),
-            name: self.name.unwrap(),
+            name,
             description: self.description,
-            devices: self.devices.unwrap_or_default(),
+            labels: Labels::new(),
+            devices,
         };
 
         Ok(r)
     }
 
+    /// An owned method which will return a copy of the RoomBuilder with the [NamingRules] the
+    /// room is checked by. The [NamingRules::default] are used unless other rules are given.
+    pub fn with_rules(self, rules: NamingRules) -> RoomBuilder {
+        RoomBuilder { rules, ..self }
+    }
+
     /// An owned method which will return a copy of the RoomBuilder with name of Room set. It
     /// will clone the provided `name` parameter. In the future implementation it might be
     /// changed for lean and effective implementation
@@ -119,6 +141,7 @@ impl RoomBuilder {
             name: Some(name.to_string()),
             description: self.description,
             devices: self.devices,
+            rules: self.rules,
         }
     }
 
@@ -130,6 +153,7 @@ impl RoomBuilder {
             name: self.name,
             description: Some(description.to_string()),
             devices: self.devices,
+            rules: self.rules,
         }
     }
 
@@ -144,6 +168,7 @@ impl RoomBuilder {
             name: self.name,
             description: self.description,
             devices: Some(devices),
+            rules: self.rules,
         }
     }
 
@@ -156,6 +181,7 @@ impl RoomBuilder {
             name: self.name,
             description: self.description,
             devices: Some(devices),
+            rules: self.rules,
         }
     }
 }
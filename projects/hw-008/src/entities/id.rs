@@ -0,0 +1,128 @@
+use std::cell::RefCell;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// The alphabet of the Crockford's base32, the same as ULID uses. It has no ambiguous letters,
+/// and the encoded ids sort in the same order as the encoded numbers.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// The number of the random bits of the id, the rest 48 bits are the timestamp in milliseconds
+const RANDOM_BITS: u32 = 80;
+
+/// A source of the entity ids. The generator gives the unique part of the id only, the prefix
+/// of the entity type (e.g. `home_`) is added by [generate_id].
+pub trait IdGenerator {
+    fn next_id(&mut self) -> String;
+}
+
+/// The default generator. It gives ULID-like ids: 48 bits of the current time in milliseconds
+/// followed by 80 random bits, encoded into 26 characters. So, the ids sort by the creation
+/// time, and the ids created within the same millisecond by the same thread still sort in the
+/// order of creation, since the random part is just incremented for them.
+#[derive(Debug, Default)]
+pub struct SortableIdGenerator {
+    last: Option<(u64, u128)>,
+}
+
+impl IdGenerator for SortableIdGenerator {
+    fn next_id(&mut self) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+
+        let (timestamp, random) = match self.last {
+            Some((last, random)) if last >= now => (last, random + 1),
+            _ => (now, rand::thread_rng().gen::<u128>() >> (128 - RANDOM_BITS)),
+        };
+        self.last = Some((timestamp, random));
+        encode(timestamp, random)
+    }
+}
+
+/// A deterministic generator for the tests. The same seed always gives the same sequence of
+/// the ids. The ids are sortable as well: each next id is one millisecond "later" than the
+/// previous one, starting from the first of January of 2023.
+#[derive(Debug)]
+pub struct SeededIdGenerator {
+    rng: StdRng,
+    timestamp: u64,
+}
+
+impl SeededIdGenerator {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            timestamp: 1_672_531_200_000,
+        }
+    }
+}
+
+impl IdGenerator for SeededIdGenerator {
+    fn next_id(&mut self) -> String {
+        self.timestamp += 1;
+        let random = self.rng.gen::<u128>() >> (128 - RANDOM_BITS);
+        encode(self.timestamp, random)
+    }
+}
+
+fn encode(timestamp: u64, random: u128) -> String {
+    let value = (u128::from(timestamp) << RANDOM_BITS) | (random & ((1 << RANDOM_BITS) - 1));
+
+    // 26 characters by 5 bits, the first character keeps the 3 highest bits only
+    (0..26)
+        .rev()
+        .map(|i| ALPHABET[((value >> (i * 5)) & 0x1f) as usize] as char)
+        .collect()
+}
+
+thread_local! {
+    static GENERATOR: RefCell<Box<dyn IdGenerator>> =
+        RefCell::new(Box::<SortableIdGenerator>::default());
+}
+
+/// Replaces the id generator of the current thread, e.g. with the [SeededIdGenerator] to get
+/// the predictable ids in the tests. Each thread starts with the [SortableIdGenerator].
+pub fn set_id_generator(generator: impl IdGenerator + 'static) {
+    GENERATOR.with(|current| *current.borrow_mut() = Box::new(generator));
+}
+
+/// Generates a new id for the entity of the given type, e.g. `home_01GNNA1J00C3FE9ZJ6S2JXNZ8K`.
+/// The id is unique with a very high probability, but the manager checks it against the
+/// repository anyway before the entity is created.
+pub fn generate_id(entity_type: &str) -> String {
+    let id = GENERATOR.with(|generator| generator.borrow_mut().next_id());
+    format!("{entity_type}_{id}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IdGenerator, SeededIdGenerator, SortableIdGenerator};
+
+    #[test]
+    fn ids_sort_by_creation_time() {
+        let mut generator = SortableIdGenerator::default();
+        let ids: Vec<String> = (0..100).map(|_| generator.next_id()).collect();
+
+        let mut sorted = ids.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(ids, sorted);
+        assert!(ids.iter().all(|id| id.len() == 26));
+    }
+
+    #[test]
+    fn seeded_ids_are_reproducible() {
+        let first: Vec<String> = {
+            let mut generator = SeededIdGenerator::new(42);
+            (0..10).map(|_| generator.next_id()).collect()
+        };
+        let mut generator = SeededIdGenerator::new(42);
+        let second: Vec<String> = (0..10).map(|_| generator.next_id()).collect();
+
+        assert_eq!(first, second);
+        assert_ne!(SeededIdGenerator::new(7).next_id(), first[0]);
+    }
+}
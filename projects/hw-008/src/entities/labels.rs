@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+
+/// The labels of the entity. The label is either a `key=value` pair, or just a `key` without a
+/// value (e.g. `critical`), such labels are kept with the empty value. The map is ordered, so
+/// the labels are always stored and printed in the same order.
+pub type Labels = BTreeMap<String, String>;
+
+/// The errors of parsing the labels and the label selectors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LabelError {
+    /// The key is empty or has a character which is not allowed
+    InvalidKey(String),
+    /// The `key=` form has no value
+    MissingValue(String),
+}
+
+impl Error for LabelError {}
+
+impl Display for LabelError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            LabelError::InvalidKey(key) => write!(
+                formatter,
+                "Invalid label key '{key}', only letters, digits and '-', '_', '.', '/' are allowed"
+            ),
+            LabelError::MissingValue(label) => {
+                write!(formatter, "The label '{label}' has no value")
+            }
+        }
+    }
+}
+
+fn check_key(key: &str) -> Result<String, LabelError> {
+    let valid = key
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-_./".contains(c));
+    if key.is_empty() || !valid {
+        return Err(LabelError::InvalidKey(key.to_string()));
+    }
+    Ok(key.to_string())
+}
+
+/// Parses a single label, either `key=value` or just `key`
+pub fn parse_label(text: &str) -> Result<(String, String), LabelError> {
+    match text.trim().split_once('=') {
+        Some((_, value)) if value.trim().is_empty() => {
+            Err(LabelError::MissingValue(text.to_string()))
+        }
+        Some((key, value)) => Ok((check_key(key.trim())?, value.trim().to_string())),
+        None => Ok((check_key(text.trim())?, String::new())),
+    }
+}
+
+/// Writes the labels in the same form they are given in the cli, e.g. `critical, floor=2`
+pub fn format_labels(labels: &Labels) -> String {
+    labels
+        .iter()
+        .map(|(key, value)| match value.is_empty() {
+            true => key.clone(),
+            false => format!("{key}={value}"),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Requirement {
+    Equals(String, String),
+    NotEquals(String, String),
+    Exists(String),
+    Missing(String),
+}
+
+/// A selector of the entities by their labels, e.g. `floor=2,!critical`. The selector is a
+/// comma separated list of the requirements, and the entity matches the selector only if all
+/// the requirements are met:
+///
+/// * `key=value` - the entity has the label with the given value
+/// * `key!=value` - the entity has no such label, or the label has another value
+/// * `key` - the entity has the label with any value
+/// * `!key` - the entity has no label with the given key
+///
+/// The empty selector matches any entity.
+///
+/// ```
+/// use hw_008::entities::{LabelSelector, Labels};
+///
+/// let selector: LabelSelector = "floor=2,!critical".parse().unwrap();
+/// let labels = Labels::from([("floor".to_string(), "2".to_string())]);
+///
+/// assert!(selector.matches(&labels));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LabelSelector {
+    requirements: Vec<Requirement>,
+}
+
+impl LabelSelector {
+    pub fn matches(&self, labels: &Labels) -> bool {
+        self.requirements
+            .iter()
+            .all(|requirement| match requirement {
+                Requirement::Equals(key, value) => labels.get(key) == Some(value),
+                Requirement::NotEquals(key, value) => labels.get(key) != Some(value),
+                Requirement::Exists(key) => labels.contains_key(key),
+                Requirement::Missing(key) => !labels.contains_key(key),
+            })
+    }
+}
+
+impl FromStr for LabelSelector {
+    type Err = LabelError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut requirements = vec![];
+        for part in text.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let requirement = if let Some((key, value)) = part.split_once("!=") {
+                let (key, value) = parse_label(&format!("{key}={value}"))?;
+                Requirement::NotEquals(key, value)
+            } else if let Some(key) = part.strip_prefix('!') {
+                Requirement::Missing(check_key(key.trim())?)
+            } else {
+                match parse_label(part)? {
+                    (key, value) if value.is_empty() => Requirement::Exists(key),
+                    (key, value) => Requirement::Equals(key, value),
+                }
+            };
+            requirements.push(requirement);
+        }
+        Ok(Self { requirements })
+    }
+}
+
+impl Display for LabelSelector {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        let parts: Vec<String> = self
+            .requirements
+            .iter()
+            .map(|requirement| match requirement {
+                Requirement::Equals(key, value) => format!("{key}={value}"),
+                Requirement::NotEquals(key, value) => format!("{key}!={value}"),
+                Requirement::Exists(key) => key.clone(),
+                Requirement::Missing(key) => format!("!{key}"),
+            })
+            .collect();
+        write!(formatter, "{}", parts.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_label, LabelError, LabelSelector, Labels};
+
+    fn labels(pairs: &[(&str, &str)]) -> Labels {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn selector_requirements() {
+        let selector: LabelSelector = "floor=2, zone!=garage, !critical, powered".parse().unwrap();
+        assert_eq!(
+            selector.to_string(),
+            "floor=2,zone!=garage,!critical,powered"
+        );
+
+        assert!(selector.matches(&labels(&[("floor", "2"), ("powered", "")])));
+        assert!(!selector.matches(&labels(&[("floor", "2")])));
+        assert!(!selector.matches(&labels(&[("floor", "3"), ("powered", "")])));
+        assert!(!selector.matches(&labels(&[
+            ("floor", "2"),
+            ("powered", ""),
+            ("critical", "")
+        ])));
+        assert!(!selector.matches(&labels(&[
+            ("floor", "2"),
+            ("powered", ""),
+            ("zone", "garage")
+        ])));
+        assert!(LabelSelector::default().matches(&Labels::new()));
+    }
+
+    #[test]
+    fn invalid_labels() {
+        assert_eq!(
+            parse_label("critical").unwrap(),
+            ("critical".into(), "".into())
+        );
+        assert_eq!(
+            parse_label("floor = 2").unwrap(),
+            ("floor".into(), "2".into())
+        );
+        assert!(matches!(
+            parse_label("floor="),
+            Err(LabelError::MissingValue(_))
+        ));
+        assert!(matches!(parse_label("=2"), Err(LabelError::InvalidKey(_))));
+        assert!(matches!(
+            parse_label("two words"),
+            Err(LabelError::InvalidKey(_))
+        ));
+        assert!("floor=2,!".parse::<LabelSelector>().is_err());
+    }
+}
@@ -0,0 +1,24 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+
+use crate::entities::manager::events::SmartHomeEvent;
+use crate::entities::manager::smart_home::SmartHomeManager;
+use crate::entities::manager::store::{Backup, Backups};
+
+pub trait BackupFunctions {
+    /// Replaces the whole smart home with the state kept by the backup. The backup is verified
+    /// first, so nothing is changed if any of its files is damaged. The files of the repository
+    /// are not overwritten: the restore is journaled as a regular change, so the history is
+    /// kept, the other clients see the restored state right away, and the restore might be
+    /// undone.
+    fn restore_backup(&self, backups: &Backups, id: &str) -> Result<Backup>;
+}
+
+impl BackupFunctions for SmartHomeManager {
+    fn restore_backup(&self, backups: &Backups, id: &str) -> Result<Backup> {
+        let (backup, state) = backups.read_state(id)?;
+        let event = SmartHomeEvent::StateReplaced { state }.restamped(Utc::now());
+        self.commit(|_| Ok((vec![event], backup)))
+            .map_err(|msg| anyhow!("Unable restore the backup {id}: {msg}"))
+    }
+}
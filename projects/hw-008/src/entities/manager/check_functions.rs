@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use anyhow::{anyhow, Result};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::entities::devices::Device;
+use crate::entities::generate_id;
+use crate::entities::house::{Home, Room};
+use crate::entities::manager::events::SmartHomeEvent;
+use crate::entities::manager::journal::JournalEntry;
+use crate::entities::manager::smart_home::SmartHomeManager;
+use crate::entities::manager::store::{
+    detect_version, upgrade_document, Revision, SchemaVersion, StoreError, SCHEMA_VERSION,
+};
+use crate::entities::manager::SavedSmartHome;
+
+/// A structural problem of the stored smart home found by [CheckFunctions::check]. The
+/// `location` tells where exactly the problem is, e.g. `homes[0].rooms[2]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// The entity can't be read, e.g. the device doesn't match any known device type. The fix
+    /// drops such entity together with all its content.
+    Unreadable { location: String, reason: String },
+    /// The very same entity is stored twice, e.g. the room exists in two homes. The fix keeps
+    /// the first copy only.
+    DuplicateEntity {
+        id: String,
+        location: String,
+        first: String,
+    },
+    /// Different entities share the same id. The fix gives a new id to the later one.
+    DuplicateId {
+        id: String,
+        location: String,
+        first: String,
+    },
+}
+
+impl Display for Problem {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Problem::Unreadable { location, reason } => {
+                write!(formatter, "{location}: unreadable entity, {reason}")
+            }
+            Problem::DuplicateEntity {
+                id,
+                location,
+                first,
+            } => write!(formatter, "{location}: {id} is a copy of {first}"),
+            Problem::DuplicateId {
+                id,
+                location,
+                first,
+            } => write!(formatter, "{location}: id {id} is already used by {first}"),
+        }
+    }
+}
+
+/// The result of the integrity check
+#[derive(Debug)]
+pub struct CheckReport {
+    /// The version of the format the state is stored in
+    pub schema_version: SchemaVersion,
+    pub problems: Vec<Problem>,
+    /// The revision of the repaired state, if the state was rewritten by the fix
+    pub fixed: Option<Revision>,
+}
+
+impl CheckReport {
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl Display for CheckReport {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        if self.is_clean() {
+            write!(formatter, "No problems found")?;
+        } else {
+            write!(formatter, "{} problem(s) found:", self.problems.len())?;
+            for problem in &self.problems {
+                write!(formatter, "\n  {problem}")?;
+            }
+        }
+
+        if self.schema_version < SCHEMA_VERSION {
+            write!(
+                formatter,
+                "\nThe state is stored in the outdated format (version {})",
+                self.schema_version
+            )?;
+        }
+        if let Some(revision) = self.fixed {
+            write!(formatter, "\nThe state is rewritten as revision {revision}")?;
+        }
+        Ok(())
+    }
+}
+
+pub trait CheckFunctions {
+    /// Walks through the stored state and reports its structural problems. The state is read
+    /// as a raw document, so the check works even if the smart home can't be loaded at all.
+    ///
+    /// With the `fix` the repaired state is written back in the current format, even if the
+    /// only problem is the outdated format. The repair is journaled as a single change, but it
+    /// can't be undone, since the broken state can't be represented by the events.
+    fn check(&self, fix: bool) -> Result<CheckReport>;
+}
+
+impl CheckFunctions for SmartHomeManager {
+    fn check(&self, fix: bool) -> Result<CheckReport> {
+        let store = self.store();
+        if !store.is_initialized() {
+            return Err(StoreError::NotInitialized.into());
+        }
+
+        let document = store.load_document()?;
+        let schema_version = detect_version(&document)?;
+        let mut document = upgrade_document(document)?;
+        let homes = document
+            .get_mut("homes")
+            .map(Value::take)
+            .unwrap_or_default();
+
+        let mut checker = Checker::default();
+        let repaired = checker.check_homes(homes);
+
+        let mut report = CheckReport {
+            schema_version,
+            problems: checker.problems,
+            fixed: None,
+        };
+
+        if fix && (!report.is_clean() || schema_version < SCHEMA_VERSION) {
+            let base = match store.revision() {
+                Ok(revision) => revision,
+                // the json file store has to parse the whole state to tell the revision
+                Err(_) => document["revision"].as_u64().unwrap_or_default(),
+            };
+
+            let entry = JournalEntry::new(
+                base + 1,
+                vec![SmartHomeEvent::StateReplaced {
+                    state: repaired.clone(),
+                }],
+            );
+            let record = serde_json::to_string(&entry)?;
+            let revision = store
+                .save_with_journal(&repaired, base, &[record])
+                .map_err(|msg| anyhow!("Unable write the repaired state: {msg}"))?;
+            report.fixed = Some(revision);
+        }
+
+        Ok(report)
+    }
+}
+
+/// Walks through the raw state document and builds the repaired state along the way. The import
+/// uses it as well to validate the imported homes.
+#[derive(Default)]
+pub(crate) struct Checker {
+    /// All the ids seen so far with the location and the raw content of the entity
+    seen: HashMap<String, (String, Value)>,
+    pub(crate) problems: Vec<Problem>,
+}
+
+impl Checker {
+    pub(crate) fn check_homes(&mut self, homes: Value) -> SavedSmartHome {
+        let homes = match homes {
+            Value::Null => return None,
+            Value::Array(homes) => homes,
+            _ => {
+                self.problems.push(Problem::Unreadable {
+                    location: "homes".into(),
+                    reason: "the homes are not a list".into(),
+                });
+                return None;
+            }
+        };
+
+        let mut repaired = vec![];
+        for (h, mut raw) in homes.into_iter().enumerate() {
+            let location = format!("homes[{h}]");
+            let rooms = detach(&mut raw, "rooms");
+
+            let Some(mut home) = self.read::<Home>(&location, &raw) else {
+                continue;
+            };
+            let Some(id) = self.unique_id(&home.id, "home", &location, &raw, &rooms) else {
+                continue;
+            };
+            home.id = id;
+
+            home.rooms = self.check_rooms(&location, rooms);
+            repaired.push(home);
+        }
+        Some(repaired)
+    }
+
+    fn check_rooms(&mut self, parent: &str, rooms: Value) -> Vec<Room> {
+        let mut repaired = vec![];
+        for (r, mut raw) in list(rooms).into_iter().enumerate() {
+            let location = format!("{parent}.rooms[{r}]");
+            let devices = detach(&mut raw, "devices");
+
+            let Some(mut room) = self.read::<Room>(&location, &raw) else {
+                continue;
+            };
+            let Some(id) = self.unique_id(&room.id, "room", &location, &raw, &devices) else {
+                continue;
+            };
+            room.id = id;
+
+            room.devices = self.check_devices(&location, devices);
+            repaired.push(room);
+        }
+        repaired
+    }
+
+    fn check_devices(&mut self, parent: &str, devices: Value) -> Vec<Device> {
+        let mut repaired = vec![];
+        for (d, raw) in list(devices).into_iter().enumerate() {
+            let location = format!("{parent}.devices[{d}]");
+
+            let Some(mut device) = self.read::<Device>(&location, &raw) else {
+                continue;
+            };
+            let prefix = device.id_prefix();
+            let id = device.id().clone();
+            let Some(id) = self.unique_id(&id, prefix, &location, &raw, &Value::Null) else {
+                continue;
+            };
+            device.set_id(id);
+
+            repaired.push(device);
+        }
+        repaired
+    }
+
+    /// Reads the entity without its children, the children are checked separately
+    fn read<T: DeserializeOwned>(&mut self, location: &str, raw: &Value) -> Option<T> {
+        match serde_json::from_value(raw.clone()) {
+            Ok(entity) => Some(entity),
+            Err(e) => {
+                self.problems.push(Problem::Unreadable {
+                    location: location.to_string(),
+                    reason: e.to_string(),
+                });
+                None
+            }
+        }
+    }
+
+    /// Returns the id the entity should have in the repaired state, or [None] if the entity is
+    /// just a copy of another one, so it must be dropped.
+    fn unique_id(
+        &mut self,
+        id: &str,
+        prefix: &str,
+        location: &str,
+        raw: &Value,
+        children: &Value,
+    ) -> Option<String> {
+        let mut content = raw.clone();
+        if let Some(fields) = content.as_object_mut() {
+            fields.insert("children".into(), children.clone());
+        }
+
+        if let Some((first, first_content)) = self.seen.get(id) {
+            if *first_content == content {
+                self.problems.push(Problem::DuplicateEntity {
+                    id: id.to_string(),
+                    location: location.to_string(),
+                    first: first.clone(),
+                });
+                return None;
+            }
+
+            self.problems.push(Problem::DuplicateId {
+                id: id.to_string(),
+                location: location.to_string(),
+                first: first.clone(),
+            });
+            let mut new_id = generate_id(prefix);
+            while self.seen.contains_key(&new_id) {
+                new_id = generate_id(prefix);
+            }
+            self.seen
+                .insert(new_id.clone(), (location.to_string(), content));
+            return Some(new_id);
+        }
+
+        self.seen
+            .insert(id.to_string(), (location.to_string(), content));
+        Some(id.to_string())
+    }
+}
+
+/// Takes the list of the children out of the entity and leaves the empty list instead of it
+fn detach(raw: &mut Value, key: &str) -> Value {
+    match raw.get_mut(key) {
+        Some(children) => std::mem::replace(children, Value::Array(vec![])),
+        None => Value::Null,
+    }
+}
+
+fn list(children: Value) -> Vec<Value> {
+    match children {
+        Value::Array(children) => children,
+        _ => vec![],
+    }
+}
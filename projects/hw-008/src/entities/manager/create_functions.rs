@@ -1,11 +1,13 @@
 use anyhow::{anyhow, Result};
 
-use crate::cli::DeviceType;
-use crate::entities::devices::{Device, DeviceId, Socket, Thermometer};
+use crate::entities::devices::{DeviceId, DeviceType};
 use crate::entities::house::{Home, HomeId, Room, RoomId};
+use crate::entities::manager::events::SmartHomeEvent;
 use crate::entities::manager::smart_home::SmartHomeManager;
-use crate::entities::manager::{FindFunctions, UpdateFunctions};
 
+/// The names of the new entities are checked by the
+/// [naming rules](SmartHomeManager::naming_rules) of the manager, and the broken rule is
+/// returned as the [ValidationError](crate::entities::ValidationError)
 pub trait CreateFunctions {
     fn create_home(&self, name: String, description: Option<String>) -> Result<HomeId>;
 
@@ -27,22 +29,24 @@ pub trait CreateFunctions {
 
 impl CreateFunctions for SmartHomeManager {
     fn create_home(&self, name: String, description: Option<String>) -> Result<HomeId> {
-        let home = if let Some(ref description) = description {
-            Home::build()
-                .with_name(&name)
-                .with_description(description)
-                .build()
+        let builder = Home::build()
+            .with_name(&name)
+            .with_rules(self.naming_rules().clone());
+        let mut home = if let Some(ref description) = description {
+            builder.with_description(description).build()
         } else {
-            Home::build().with_name(&name).build()
-        }
-        .map_err(|msg| anyhow!(msg))?;
-
-        let id = home.id.clone();
-
-        match self.update_home_state(home) {
-            Ok(_) => Ok(id),
-            Err(msg) => Err(anyhow!("Unable to create home: {msg}")),
-        }
+            builder.build()
+        }?;
+
+        // the validation errors are returned as is, so the callers might tell them apart
+        self.commit(|state| {
+            let siblings = state.child_names(None).into_iter();
+            self.naming_rules().check_unique(&name, siblings, None)?;
+
+            home.id = state.unique_id(home.id.clone(), "home");
+            let id = home.id.clone();
+            Ok((vec![SmartHomeEvent::HomeCreated { home }], id))
+        })
     }
 
     fn create_room(
@@ -51,25 +55,31 @@ impl CreateFunctions for SmartHomeManager {
         name: String,
         description: Option<RoomId>,
     ) -> Result<String> {
-        match self.find_home_by_id(&home_id) {
-            Some(mut home) => {
-                let new_room = if let Some(ref description) = description {
-                    Room::build()
-                        .with_name(&name)
-                        .with_description(description)
-                        .build()
-                } else {
-                    Room::build().with_name(&name).build()
-                }
-                .map_err(|msg| anyhow!(msg))?;
+        let builder = Room::build()
+            .with_name(&name)
+            .with_rules(self.naming_rules().clone());
+        let mut new_room = if let Some(ref description) = description {
+            builder.with_description(description).build()
+        } else {
+            builder.build()
+        }?;
 
+        self.commit(|state| match state.home(&home_id) {
+            Some(_) => {
+                let siblings = state.child_names(Some(&home_id)).into_iter();
+                self.naming_rules()
+                    .check_unique(&name, siblings, Some(&home_id))?;
+
+                new_room.id = state.unique_id(new_room.id.clone(), "room");
                 let id = new_room.id.clone();
-                home.rooms.push(new_room);
-                self.update_home_state(home)?;
-                Ok(id)
+                let event = SmartHomeEvent::RoomCreated {
+                    home_id: home_id.clone(),
+                    room: new_room,
+                };
+                Ok((vec![event], id))
             }
-            _ => Err(anyhow!(format!("Home with id: {home_id} not found"))),
-        }
+            None => Err(anyhow!(format!("Home with id: {home_id} not found"))),
+        })
     }
 
     fn create_device(
@@ -79,54 +89,23 @@ impl CreateFunctions for SmartHomeManager {
         name: String,
         description: Option<DeviceId>,
     ) -> Result<String> {
-        fn create_socket(name: &str, description: &Option<String>) -> Device {
-            let socket = match description.as_ref() {
-                None => Socket::new(name),
-                Some(dsc) => Socket::new_with_description(name, dsc),
-            };
-
-            Device::Socket(socket)
-        }
-
-        fn create_thermometer(name: &str, description: &Option<String>) -> Device {
-            let thermometer = match description.as_ref() {
-                None => Thermometer::new(name),
-                Some(dsc) => Thermometer::new_with_description(name, dsc),
-            };
-
-            Device::Thermometer(thermometer)
-        }
-
-        match self.find_room_by_id(&room_id) {
-            Some(mut room) => {
-                let device = match device_type {
-                    DeviceType::Socket => create_socket(&name, &description),
-                    DeviceType::Thermometer => create_thermometer(&name, &description),
+        self.naming_rules().check_name(&name)?;
+        let mut device = device_type.create(&name, description.as_deref())?;
+        self.commit(|state| match state.room(&room_id) {
+            Some(_) => {
+                let siblings = state.child_names(Some(&room_id)).into_iter();
+                self.naming_rules()
+                    .check_unique(&name, siblings, Some(&room_id))?;
+
+                let id = state.unique_id(device.id().clone(), device.id_prefix());
+                device.set_id(id.clone());
+                let event = SmartHomeEvent::DeviceCreated {
+                    room_id: room_id.clone(),
+                    device,
                 };
-                let id = device.id().clone();
-                room.devices.push(device);
-
-                match self.find_home_by_room_id(&room.id) {
-                    None => Err(anyhow!("Unable to find associated home to {room_id} room")),
-                    Some(mut home) => {
-                        let mut rooms: Vec<Room> = home
-                            .rooms
-                            .iter()
-                            .filter(|r| r.id != room.id)
-                            .cloned()
-                            .collect();
-
-                        rooms.push(room);
-                        home.rooms = rooms;
-
-                        match self.update_home_state(home) {
-                            Ok(_) => Ok(id),
-                            Err(msg) => Err(anyhow!("Unable to save changes: {msg}")),
-                        }
-                    }
-                }
+                Ok((vec![event], id))
             }
             None => Err(anyhow!(format!("Room with id: {room_id} not found"))),
-        }
+        })
     }
 }
@@ -0,0 +1,128 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+
+use crate::entities::devices::{Device, DeviceId, Socket};
+use crate::entities::manager::events::SmartHomeEvent;
+use crate::entities::manager::indexed_state::IndexedState;
+use crate::entities::manager::smart_home::SmartHomeManager;
+
+/// The energy consumed by a single metered device
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnergyUsage {
+    pub device_id: DeviceId,
+    pub name: String,
+    pub kwh: f64,
+}
+
+pub trait EnergyFunctions {
+    /// The energy consumed by the metered devices of the home, the room or just by the device
+    /// with the given id, one entry per device. Without the `since` moment it's all the energy
+    /// consumed by the devices so far, otherwise only the energy consumed since that moment.
+    /// The counters at that moment are taken from the journal, see
+    /// [HistoryFunctions](super::HistoryFunctions).
+    fn energy(&self, id: &str, since: Option<DateTime<Utc>>) -> Result<Vec<EnergyUsage>>;
+
+    /// Changes the power of the load plugged into the socket, in watts. The energy consumed with
+    /// the previous power is counted up to this moment. Returns the changed socket.
+    fn set_socket_power(&self, device_id: &str, watts: f32) -> Result<Socket>;
+}
+
+impl EnergyFunctions for SmartHomeManager {
+    fn energy(&self, id: &str, since: Option<DateTime<Utc>>) -> Result<Vec<EnergyUsage>> {
+        let now = Utc::now();
+        let devices = self.with_state(|state| metered_devices(state, id))??;
+
+        let baseline = match since {
+            Some(since) => Some((state_since(self, since)?, since)),
+            None => None,
+        };
+        let usage = devices
+            .into_iter()
+            .map(|device| {
+                let counted = baseline
+                    .as_ref()
+                    .and_then(|(state, since)| state.device(device.id())?.energy_at(*since))
+                    .unwrap_or_default();
+                EnergyUsage {
+                    device_id: device.id().clone(),
+                    name: device.name().to_string(),
+                    kwh: device.energy_at(now).unwrap_or_default() - counted,
+                }
+            })
+            .collect();
+        Ok(usage)
+    }
+
+    fn set_socket_power(&self, device_id: &str, watts: f32) -> Result<Socket> {
+        if !watts.is_finite() || watts < 0.0 {
+            return Err(anyhow!(
+                "The power must be a non-negative number of watts, got {watts}"
+            ));
+        }
+
+        self.commit(|state| {
+            let device = state
+                .device(&device_id.to_string())
+                .ok_or_else(|| anyhow!("Device with id: {device_id} not found"))?;
+            let mut socket = device
+                .downcast_ref::<Socket>()
+                .ok_or_else(|| {
+                    anyhow!(
+                        "The device {device_id} is a {}, not a socket",
+                        device.device_type()
+                    )
+                })?
+                .clone();
+            if socket.power_consumption == watts {
+                return Ok((vec![], socket));
+            }
+
+            let at = Utc::now();
+            socket.set_power(watts, at);
+            let event = SmartHomeEvent::SocketPowerChanged {
+                device_id: socket.id.clone(),
+                watts,
+                at,
+            };
+            Ok((vec![event], socket))
+        })
+    }
+}
+
+/// The state the counters are compared with. Before the very first change there was nothing
+/// to count at all.
+fn state_since(manager: &SmartHomeManager, since: DateTime<Utc>) -> Result<IndexedState> {
+    let journal = manager.journal();
+    match journal.entries()?.first() {
+        Some(first) if first.revision == 1 && since < first.timestamp => {
+            Ok(IndexedState::default())
+        }
+        _ => journal.state_at(since),
+    }
+}
+
+/// The metered devices of the home, the room or the device itself with the given id
+fn metered_devices(state: &IndexedState, id: &str) -> Result<Vec<Device>> {
+    let id = id.to_string();
+    let devices: Vec<&Device> = if let Some(home) = state.home(&id) {
+        home.rooms.iter().flat_map(|room| &room.devices).collect()
+    } else if let Some(room) = state.room(&id) {
+        room.devices.iter().collect()
+    } else if let Some(device) = state.device(&id) {
+        if device.energy_at(Utc::now()).is_none() {
+            return Err(anyhow!(
+                "The device {id} is a {}, it has no energy meter",
+                device.device_type()
+            ));
+        }
+        vec![device]
+    } else {
+        return Err(anyhow!("Entity with id: {id} not found"));
+    };
+
+    Ok(devices
+        .into_iter()
+        .filter(|device| device.energy_at(Utc::now()).is_some())
+        .cloned()
+        .collect())
+}
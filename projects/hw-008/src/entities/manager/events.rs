@@ -0,0 +1,519 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::entities::devices::{
+    Device, DeviceId, DeviceStatus, Reading, Socket, Thermostat, ThermostatConfig,
+};
+use crate::entities::house::{Home, HomeId, Room, RoomId};
+use crate::entities::manager::indexed_state::IndexedState;
+use crate::entities::manager::store::SchemaVersion;
+use crate::entities::manager::SavedSmartHome;
+use crate::entities::{format_labels, Labels};
+
+/// A single change of the smart home. All the mutations made by the manager are expressed as a
+/// list of events, and [SmartHomeEvent::apply] is the only place where the state is actually
+/// changed. The same events are written to the journal, so the state might be rebuilt at any
+/// moment in the past by applying the journaled events one by one.
+///
+/// The removal events keep the whole removed entity, not only its id. It's not needed for the
+/// replaying, but it's super handy for the audit: the journal always tells what exactly was
+/// removed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SmartHomeEvent {
+    HomeCreated {
+        home: Home,
+    },
+    HomeRemoved {
+        home: Home,
+    },
+    /// The home was replaced with the given one as a whole, or added if there was no such home
+    HomeReplaced {
+        home: Home,
+    },
+    RoomCreated {
+        home_id: HomeId,
+        room: Room,
+    },
+    RoomRemoved {
+        home_id: HomeId,
+        room: Room,
+    },
+    DeviceCreated {
+        room_id: RoomId,
+        device: Device,
+    },
+    DeviceRemoved {
+        room_id: RoomId,
+        device: Device,
+    },
+    /// The device was switched on or off at the given moment. The events journaled before the
+    /// device state was introduced have no moment.
+    DeviceStatusChanged {
+        device_id: DeviceId,
+        status: DeviceStatus,
+        #[serde(default)]
+        at: Option<DateTime<Utc>>,
+    },
+    /// The device measured the given value, or the last reading was cleared by the undo
+    DeviceMeasured {
+        device_id: DeviceId,
+        reading: Option<Reading>,
+    },
+    /// The settings of the device, e.g. the brightness of a light, were changed, so the device
+    /// with the same id was replaced with the given one as a whole
+    DeviceReplaced {
+        device: Device,
+    },
+    /// The power of the load plugged into the socket was changed at the given moment, the energy
+    /// consumed with the previous power is counted up to then
+    SocketPowerChanged {
+        device_id: DeviceId,
+        watts: f32,
+        at: DateTime<Utc>,
+    },
+    /// The settings or the links of the thermostat were changed. Unlike the replacement of the
+    /// whole device, it keeps the decisions of the control loop, so reverting the settings
+    /// doesn't revert the heaters the thermostat switched since then.
+    ThermostatConfigured {
+        device_id: DeviceId,
+        config: ThermostatConfig,
+    },
+    /// The name and the description of the home, room or device with the given id were changed
+    EntityEdited {
+        id: String,
+        name: String,
+        description: Option<String>,
+    },
+    /// The labels of the home, room or device with the given id were replaced with the given ones
+    LabelsChanged {
+        id: String,
+        labels: Labels,
+    },
+    /// The room was moved to the given home with all its devices
+    RoomMoved {
+        room_id: RoomId,
+        home_id: HomeId,
+    },
+    /// The device was moved to the given room
+    DeviceMoved {
+        device_id: DeviceId,
+        room_id: RoomId,
+    },
+    /// The whole smart home was replaced with the given state
+    StateReplaced {
+        state: SavedSmartHome,
+    },
+    /// The stored state was rewritten in the newer format. The state itself is not changed.
+    SchemaMigrated {
+        from: SchemaVersion,
+        to: SchemaVersion,
+    },
+}
+
+impl SmartHomeEvent {
+    /// Applies the event to the state. The indexes of the state are rebuilt, so the next event
+    /// might be applied right away. Fails if the event doesn't match the state, e.g. the room is
+    /// created in a home which doesn't exist.
+    pub(crate) fn apply(&self, state: &mut IndexedState) -> Result<()> {
+        match self {
+            SmartHomeEvent::HomeCreated { home } => {
+                ensure_new_ids(state, home_ids(home))?;
+                state.homes_mut().push(home.clone());
+            }
+            SmartHomeEvent::HomeRemoved { home } => {
+                let h = state
+                    .home_position(&home.id)
+                    .ok_or_else(|| anyhow!("Home {} not found", home.id))?;
+                state.homes_mut().remove(h);
+            }
+            SmartHomeEvent::HomeReplaced { home } => match state.home_position(&home.id) {
+                Some(h) => state.homes_mut()[h] = home.clone(),
+                None => state.homes_mut().push(home.clone()),
+            },
+            SmartHomeEvent::RoomCreated { home_id, room } => {
+                ensure_new_ids(state, room_ids(room))?;
+                state
+                    .home_mut(home_id)
+                    .ok_or_else(|| anyhow!("Home with id: {home_id} not found"))?
+                    .rooms
+                    .push(room.clone());
+            }
+            SmartHomeEvent::RoomRemoved { room, .. } => {
+                let (h, r) = state
+                    .room_position(&room.id)
+                    .ok_or_else(|| anyhow!("Home not found for room {}", room.id))?;
+                state.homes_mut()[h].rooms.remove(r);
+            }
+            SmartHomeEvent::DeviceCreated { room_id, device } => {
+                ensure_new_ids(state, [device.id()])?;
+                state
+                    .room_mut(room_id)
+                    .ok_or_else(|| anyhow!("Room with id: {room_id} not found"))?
+                    .devices
+                    .push(device.clone());
+            }
+            SmartHomeEvent::DeviceRemoved { device, .. } => {
+                let (h, r, d) = state.device_position(device.id()).ok_or_else(|| {
+                    anyhow!("Unable find associated room for device: {}", device.id())
+                })?;
+                state.homes_mut()[h].rooms[r].devices.remove(d);
+            }
+            SmartHomeEvent::DeviceStatusChanged {
+                device_id,
+                status,
+                at,
+            } => {
+                let device = state.device_mut(device_id).ok_or_else(|| {
+                    anyhow!("Unable find associated room for device: {device_id}")
+                })?;
+                device.switch(*status, *at);
+            }
+            SmartHomeEvent::SocketPowerChanged {
+                device_id,
+                watts,
+                at,
+            } => {
+                let device = state.device_mut(device_id).ok_or_else(|| {
+                    anyhow!("Unable find associated room for device: {device_id}")
+                })?;
+                let socket = device
+                    .downcast_mut::<Socket>()
+                    .ok_or_else(|| anyhow!("The device {device_id} is not a socket"))?;
+                socket.set_power(*watts, *at);
+            }
+            SmartHomeEvent::ThermostatConfigured { device_id, config } => {
+                let device = state.device_mut(device_id).ok_or_else(|| {
+                    anyhow!("Unable find associated room for device: {device_id}")
+                })?;
+                let thermostat = device
+                    .downcast_mut::<Thermostat>()
+                    .ok_or_else(|| anyhow!("The device {device_id} is not a thermostat"))?;
+                thermostat.set_config(config.clone());
+            }
+            SmartHomeEvent::DeviceMeasured { device_id, reading } => {
+                let device = state.device_mut(device_id).ok_or_else(|| {
+                    anyhow!("Unable find associated room for device: {device_id}")
+                })?;
+                let mut reading = reading.clone();
+                if let Some(reading) = reading.as_mut() {
+                    reading.upgrade_legacy(device.kind().tag());
+                }
+                device.state_mut().last_reading = reading;
+            }
+            SmartHomeEvent::DeviceReplaced { device } => {
+                let id = device.id();
+                *state
+                    .device_mut(id)
+                    .ok_or_else(|| anyhow!("Unable find associated room for device: {id}"))? =
+                    device.clone();
+            }
+            SmartHomeEvent::EntityEdited {
+                id,
+                name,
+                description,
+            } => {
+                if let Some(home) = state.home_mut(id) {
+                    home.name = name.clone();
+                    home.description = description.clone();
+                } else if let Some(room) = state.room_mut(id) {
+                    room.name = name.clone();
+                    room.description = description.clone();
+                } else if let Some(device) = state.device_mut(id) {
+                    device.edit(name.clone(), description.clone());
+                } else {
+                    return Err(anyhow!("Entity with id: {id} not found"));
+                }
+            }
+            SmartHomeEvent::LabelsChanged { id, labels } => {
+                *state
+                    .labels_mut(id)
+                    .ok_or_else(|| anyhow!("Entity with id: {id} not found"))? = labels.clone();
+            }
+            SmartHomeEvent::RoomMoved { room_id, home_id } => {
+                let target = state
+                    .home_position(home_id)
+                    .ok_or_else(|| anyhow!("Home with id: {home_id} not found"))?;
+                let (h, r) = state
+                    .room_position(room_id)
+                    .ok_or_else(|| anyhow!("Room with id: {room_id} not found"))?;
+                if h != target {
+                    let room = state.homes_mut()[h].rooms.remove(r);
+                    state.homes_mut()[target].rooms.push(room);
+                }
+            }
+            SmartHomeEvent::DeviceMoved { device_id, room_id } => {
+                let (th, tr) = state
+                    .room_position(room_id)
+                    .ok_or_else(|| anyhow!("Room with id: {room_id} not found"))?;
+                let (h, r, d) = state.device_position(device_id).ok_or_else(|| {
+                    anyhow!("Unable find associated room for device: {device_id}")
+                })?;
+                if (h, r) != (th, tr) {
+                    let device = state.homes_mut()[h].rooms[r].devices.remove(d);
+                    state.homes_mut()[th].rooms[tr].devices.push(device);
+                }
+            }
+            SmartHomeEvent::StateReplaced { state: new_state } => {
+                state.replace(new_state.clone());
+                return Ok(());
+            }
+            SmartHomeEvent::SchemaMigrated { .. } => return Ok(()),
+        }
+
+        state.reindex();
+        Ok(())
+    }
+
+    /// Returns the event which reverts this one. It must be called before the event is applied,
+    /// because the reverting event is built from the previous state of the changed entity. The
+    /// events which don't change the smart home have nothing to revert.
+    pub(crate) fn inverse(&self, before: &IndexedState) -> Option<SmartHomeEvent> {
+        let inverse = match self {
+            SmartHomeEvent::HomeCreated { home } => {
+                SmartHomeEvent::HomeRemoved { home: home.clone() }
+            }
+            SmartHomeEvent::HomeRemoved { home } => {
+                SmartHomeEvent::HomeCreated { home: home.clone() }
+            }
+            SmartHomeEvent::HomeReplaced { home } => match before.home(&home.id) {
+                Some(previous) => SmartHomeEvent::HomeReplaced {
+                    home: previous.clone(),
+                },
+                None => SmartHomeEvent::HomeRemoved { home: home.clone() },
+            },
+            SmartHomeEvent::RoomCreated { home_id, room } => SmartHomeEvent::RoomRemoved {
+                home_id: home_id.clone(),
+                room: room.clone(),
+            },
+            SmartHomeEvent::RoomRemoved { home_id, room } => SmartHomeEvent::RoomCreated {
+                home_id: home_id.clone(),
+                room: room.clone(),
+            },
+            SmartHomeEvent::DeviceCreated { room_id, device } => SmartHomeEvent::DeviceRemoved {
+                room_id: room_id.clone(),
+                device: device.clone(),
+            },
+            SmartHomeEvent::DeviceRemoved { room_id, device } => SmartHomeEvent::DeviceCreated {
+                room_id: room_id.clone(),
+                device: device.clone(),
+            },
+            // the switch back is stamped with the moment of the undo, see [Self::restamped], so
+            // the metered devices count the energy up to the undo rather than up to the switch
+            SmartHomeEvent::DeviceStatusChanged { device_id, at, .. } => {
+                SmartHomeEvent::DeviceStatusChanged {
+                    device_id: device_id.clone(),
+                    status: before.device(device_id)?.state().on,
+                    at: *at,
+                }
+            }
+            // a measurement is a read of the device rather than a change of the smart home, so
+            // it's never undone, and it doesn't clear the changes to redo either
+            SmartHomeEvent::DeviceMeasured { .. } => return None,
+            SmartHomeEvent::DeviceReplaced { device } => SmartHomeEvent::DeviceReplaced {
+                device: before.device(device.id())?.clone(),
+            },
+            // the same as the switch, the power is changed back at the moment of the undo
+            SmartHomeEvent::SocketPowerChanged { device_id, at, .. } => {
+                let device = before.device(device_id)?;
+                SmartHomeEvent::SocketPowerChanged {
+                    device_id: device_id.clone(),
+                    watts: device.downcast_ref::<Socket>()?.power_consumption,
+                    at: *at,
+                }
+            }
+            SmartHomeEvent::ThermostatConfigured { device_id, .. } => {
+                let device = before.device(device_id)?;
+                SmartHomeEvent::ThermostatConfigured {
+                    device_id: device_id.clone(),
+                    config: device.downcast_ref::<Thermostat>()?.config(),
+                }
+            }
+            SmartHomeEvent::EntityEdited { id, .. } => {
+                let (name, description) = if let Some(home) = before.home(id) {
+                    (home.name.clone(), home.description.clone())
+                } else if let Some(room) = before.room(id) {
+                    (room.name.clone(), room.description.clone())
+                } else {
+                    let device = before.device(id)?;
+                    (
+                        device.name().to_string(),
+                        device.description().map(str::to_string),
+                    )
+                };
+                SmartHomeEvent::EntityEdited {
+                    id: id.clone(),
+                    name,
+                    description,
+                }
+            }
+            SmartHomeEvent::LabelsChanged { id, .. } => SmartHomeEvent::LabelsChanged {
+                id: id.clone(),
+                labels: before.labels(id)?.clone(),
+            },
+            SmartHomeEvent::RoomMoved { room_id, .. } => SmartHomeEvent::RoomMoved {
+                room_id: room_id.clone(),
+                home_id: before.home_by_room_id(room_id)?.id.clone(),
+            },
+            SmartHomeEvent::DeviceMoved { device_id, .. } => SmartHomeEvent::DeviceMoved {
+                device_id: device_id.clone(),
+                room_id: before.room_by_device_id(device_id)?.id.clone(),
+            },
+            SmartHomeEvent::StateReplaced { .. } => SmartHomeEvent::StateReplaced {
+                state: before.state().clone(),
+            },
+            SmartHomeEvent::SchemaMigrated { .. } => return None,
+        };
+        Some(inverse)
+    }
+
+    /// Returns the same event happening at the given moment. The undo and the redo are made
+    /// later than the original change, so the switches and the power changes they repeat are
+    /// stamped with the moment of the undo or the redo. The sockets are metered only while they
+    /// are in the smart home: the removed ones are counted up to the removal, and the ones
+    /// brought back are counted from the moment they are back, so the time they were gone is
+    /// never counted.
+    pub(crate) fn restamped(&self, now: DateTime<Utc>) -> SmartHomeEvent {
+        let mut event = self.clone();
+        let settle = |socket: &mut Socket| socket.settle_energy(now);
+        let meter_from_now = |socket: &mut Socket| socket.energy.metered_at = Some(now);
+        match &mut event {
+            SmartHomeEvent::DeviceStatusChanged { at, .. } => *at = Some(now),
+            SmartHomeEvent::SocketPowerChanged { at, .. } => *at = now,
+            SmartHomeEvent::HomeRemoved { home } => each_socket(home_devices(home), settle),
+            SmartHomeEvent::RoomRemoved { room, .. } => each_socket(&mut room.devices, settle),
+            SmartHomeEvent::DeviceRemoved { device, .. } => each_socket([device], settle),
+            SmartHomeEvent::HomeCreated { home } => each_socket(home_devices(home), meter_from_now),
+            SmartHomeEvent::RoomCreated { room, .. } => {
+                each_socket(&mut room.devices, meter_from_now)
+            }
+            SmartHomeEvent::DeviceCreated { device, .. } => each_socket([device], meter_from_now),
+            SmartHomeEvent::StateReplaced { state: Some(homes) } => {
+                each_socket(homes.iter_mut().flat_map(home_devices), meter_from_now)
+            }
+            _ => {}
+        }
+        event
+    }
+}
+
+/// The ids must be unique across all the entities, so the entity can't be created with an id
+/// which is already taken by something else, even if the other entity has a different type.
+fn ensure_new_ids<'a>(
+    state: &IndexedState,
+    ids: impl IntoIterator<Item = &'a String>,
+) -> Result<()> {
+    match ids.into_iter().find(|id| state.contains_id(id)) {
+        Some(id) => Err(anyhow!("The id {id} is already taken")),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn home_devices(home: &mut Home) -> impl Iterator<Item = &mut Device> {
+    home.rooms
+        .iter_mut()
+        .flat_map(|room| room.devices.iter_mut())
+}
+
+pub(crate) fn each_socket<'a>(
+    devices: impl IntoIterator<Item = &'a mut Device>,
+    f: impl Fn(&mut Socket),
+) {
+    devices
+        .into_iter()
+        .filter_map(|device| device.downcast_mut::<Socket>())
+        .for_each(f);
+}
+
+fn home_ids(home: &Home) -> impl Iterator<Item = &String> {
+    std::iter::once(&home.id).chain(home.rooms.iter().flat_map(room_ids))
+}
+
+fn room_ids(room: &Room) -> impl Iterator<Item = &String> {
+    std::iter::once(&room.id).chain(room.devices.iter().map(Device::id))
+}
+
+/// A short human readable description of the event for the history listings
+impl Display for SmartHomeEvent {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            SmartHomeEvent::HomeCreated { home } => {
+                write!(formatter, "HomeCreated {} ({})", home.id, home.name)
+            }
+            SmartHomeEvent::HomeRemoved { home } => {
+                write!(formatter, "HomeRemoved {} ({})", home.id, home.name)
+            }
+            SmartHomeEvent::HomeReplaced { home } => {
+                write!(formatter, "HomeReplaced {} ({})", home.id, home.name)
+            }
+            SmartHomeEvent::RoomCreated { home_id, room } => write!(
+                formatter,
+                "RoomCreated {} ({}) in home {home_id}",
+                room.id, room.name
+            ),
+            SmartHomeEvent::RoomRemoved { home_id, room } => write!(
+                formatter,
+                "RoomRemoved {} ({}) from home {home_id}",
+                room.id, room.name
+            ),
+            SmartHomeEvent::DeviceCreated { room_id, device } => write!(
+                formatter,
+                "DeviceCreated {} ({}) in room {room_id}",
+                device.id(),
+                device.name()
+            ),
+            SmartHomeEvent::DeviceRemoved { room_id, device } => write!(
+                formatter,
+                "DeviceRemoved {} ({}) from room {room_id}",
+                device.id(),
+                device.name()
+            ),
+            SmartHomeEvent::DeviceStatusChanged {
+                device_id, status, ..
+            } => {
+                let status = if *status { "enabled" } else { "disabled" };
+                write!(formatter, "DeviceStatusChanged {device_id} {status}")
+            }
+            SmartHomeEvent::DeviceMeasured { device_id, reading } => match reading {
+                Some(reading) => write!(formatter, "DeviceMeasured {device_id} {reading}"),
+                None => write!(formatter, "DeviceMeasured {device_id} reading cleared"),
+            },
+            SmartHomeEvent::DeviceReplaced { device } => write!(
+                formatter,
+                "DeviceReplaced {} ({})",
+                device.id(),
+                device.name()
+            ),
+            SmartHomeEvent::SocketPowerChanged {
+                device_id, watts, ..
+            } => write!(formatter, "SocketPowerChanged {device_id} {watts} W"),
+            SmartHomeEvent::ThermostatConfigured { device_id, config } => write!(
+                formatter,
+                "ThermostatConfigured {device_id} {} {}°C ±{}°C",
+                config.mode, config.target, config.hysteresis
+            ),
+            SmartHomeEvent::EntityEdited { id, name, .. } => {
+                write!(formatter, "EntityEdited {id} ({name})")
+            }
+            SmartHomeEvent::LabelsChanged { id, labels } => {
+                write!(formatter, "LabelsChanged {id} [{}]", format_labels(labels))
+            }
+            SmartHomeEvent::RoomMoved { room_id, home_id } => {
+                write!(formatter, "RoomMoved {room_id} to home {home_id}")
+            }
+            SmartHomeEvent::DeviceMoved { device_id, room_id } => {
+                write!(formatter, "DeviceMoved {device_id} to room {room_id}")
+            }
+            SmartHomeEvent::StateReplaced { state } => write!(
+                formatter,
+                "StateReplaced with {} home(s)",
+                state.as_ref().map(|homes| homes.len()).unwrap_or_default()
+            ),
+            SmartHomeEvent::SchemaMigrated { from, to } => {
+                write!(formatter, "SchemaMigrated from version {from} to {to}")
+            }
+        }
+    }
+}
@@ -0,0 +1,482 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::entities::devices::{DeviceState, DeviceType, Socket};
+use crate::entities::house::Home;
+use crate::entities::manager::check_functions::Checker;
+use crate::entities::manager::events::{each_socket, home_devices, SmartHomeEvent};
+use crate::entities::manager::indexed_state::IndexedState;
+use crate::entities::manager::smart_home::SmartHomeManager;
+use crate::entities::manager::store::{upgrade_document, SCHEMA_VERSION};
+use crate::entities::manager::thermostat_functions::unlinking_thermostats;
+use crate::entities::{format_labels, parse_label, Labels};
+
+/// The formats of the exported smart home
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum DataFormat {
+    Json,
+    Yaml,
+    Toml,
+    /// A flat table with a row per entity. It keeps the structure, the names, the descriptions,
+    /// the labels and the on/off status only
+    Csv,
+}
+
+impl DataFormat {
+    /// Guesses the format by the extension of the file
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "json" => Some(DataFormat::Json),
+            "yaml" | "yml" => Some(DataFormat::Yaml),
+            "toml" => Some(DataFormat::Toml),
+            "csv" => Some(DataFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// What to do with the homes which are already in the smart home
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum ImportMode {
+    /// The imported homes replace the homes with the same ids, the rest of the homes are kept
+    #[default]
+    Merge,
+    /// The whole smart home is replaced with the imported homes
+    Replace,
+}
+
+/// How the import changed a single entity
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Changed,
+    Removed,
+}
+
+impl Display for Change {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Change::Added => write!(formatter, "added"),
+            Change::Changed => write!(formatter, "changed"),
+            Change::Removed => write!(formatter, "removed"),
+        }
+    }
+}
+
+/// The home, room or device changed by the import
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedEntity {
+    /// Either `home`, `room` or `device`
+    pub kind: &'static str,
+    pub id: String,
+    pub name: String,
+    pub change: Change,
+}
+
+/// The result of the import: the entities which were added, changed or removed in the order
+/// they are stored. An entity is changed if any of its own fields differs, e.g. the name or the
+/// labels, the changes of the children are reported for the children only.
+#[derive(Debug, Clone, Default)]
+pub struct ImportSummary {
+    pub entities: Vec<ChangedEntity>,
+    /// The number of the entities left exactly as they were
+    pub unchanged: usize,
+}
+
+impl ImportSummary {
+    pub fn count(&self, change: Change) -> usize {
+        self.entities.iter().filter(|e| e.change == change).count()
+    }
+
+    /// Compares the entities of the smart home before and after the import
+    fn between(before: &[Home], after: &[Home]) -> Self {
+        let before = flatten(before);
+        let after = flatten(after);
+        let before_ids: BTreeMap<&str, &Value> = before
+            .iter()
+            .map(|(_, id, _, content)| (id.as_str(), content))
+            .collect();
+        let after_ids: BTreeSet<&str> = after.iter().map(|(_, id, _, _)| id.as_str()).collect();
+
+        let mut summary = Self::default();
+        for (kind, id, name, content) in &after {
+            let change = match before_ids.get(id.as_str()) {
+                None => Change::Added,
+                Some(previous) if *previous != content => Change::Changed,
+                Some(_) => {
+                    summary.unchanged += 1;
+                    continue;
+                }
+            };
+            summary.entities.push(ChangedEntity {
+                kind,
+                id: id.clone(),
+                name: name.clone(),
+                change,
+            });
+        }
+
+        for (kind, id, name, _) in &before {
+            if !after_ids.contains(id.as_str()) {
+                summary.entities.push(ChangedEntity {
+                    kind,
+                    id: id.clone(),
+                    name: name.clone(),
+                    change: Change::Removed,
+                });
+            }
+        }
+        summary
+    }
+}
+
+/// Writes an entity per line, e.g. `added room room_01GNNA1J00... (Kitchen)`, followed by the
+/// totals
+impl Display for ImportSummary {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        for entity in &self.entities {
+            writeln!(
+                formatter,
+                "{} {} {} ({})",
+                entity.change, entity.kind, entity.id, entity.name
+            )?;
+        }
+        write!(
+            formatter,
+            "{} added, {} changed, {} removed, {} unchanged",
+            self.count(Change::Added),
+            self.count(Change::Changed),
+            self.count(Change::Removed),
+            self.unchanged
+        )
+    }
+}
+
+pub trait ExchangeFunctions {
+    /// Writes the whole smart home in the given format. The json, yaml and toml exports keep
+    /// everything, while the csv one keeps the structure, the names, the descriptions, the
+    /// labels and the on/off status of the devices only.
+    fn export(&self, format: DataFormat) -> Result<String>;
+
+    /// Reads the homes written by [ExchangeFunctions::export] and puts them into the smart home.
+    /// The imported homes are validated the same way as the stored state is checked by the
+    /// `fsck`, and nothing is imported if any problem is found. The import is journaled as a
+    /// single change, so it can be undone.
+    fn import(&self, text: &str, format: DataFormat, mode: ImportMode) -> Result<ImportSummary>;
+}
+
+impl ExchangeFunctions for SmartHomeManager {
+    fn export(&self, format: DataFormat) -> Result<String> {
+        let mut homes = self.read_smart_home_status()?.unwrap_or_default();
+        // the counters are exported as they are at the moment of the export
+        let now = Utc::now();
+        for home in &mut homes {
+            each_socket(home_devices(home), |socket| socket.settle_energy(now));
+        }
+
+        let text = match format {
+            DataFormat::Json => serde_json::to_string_pretty(&document(&homes)?)?,
+            DataFormat::Yaml => serde_yaml::to_string(&document(&homes)?)?,
+            // toml has no nulls, so the omitted fields are just skipped
+            DataFormat::Toml => toml::to_string_pretty(&without_nulls(document(&homes)?))?,
+            DataFormat::Csv => write_csv(&homes)?,
+        };
+        Ok(text)
+    }
+
+    fn import(&self, text: &str, format: DataFormat, mode: ImportMode) -> Result<ImportSummary> {
+        let imported = read_homes(text, format)?;
+
+        self.commit(|state| {
+            let mut imported = imported.clone();
+            continue_counters(&mut imported, state, Utc::now());
+            let before = state.homes();
+            let after = match mode {
+                ImportMode::Replace => imported.clone(),
+                ImportMode::Merge => {
+                    let mut merged = before.to_vec();
+                    for home in &imported {
+                        match merged.iter().position(|h| h.id == home.id) {
+                            Some(h) => merged[h] = home.clone(),
+                            None => merged.push(home.clone()),
+                        }
+                    }
+                    // the imported rooms and devices might clash with the kept homes
+                    validate(serde_json::to_value(&merged)?)?
+                }
+            };
+
+            let summary = ImportSummary::between(before, &after);
+            let events = match mode {
+                _ if summary.entities.is_empty() => vec![],
+                ImportMode::Replace => vec![SmartHomeEvent::StateReplaced { state: Some(after) }],
+                // only the homes which differ from the stored ones are journaled
+                ImportMode::Merge => imported
+                    .into_iter()
+                    .filter(|home| {
+                        let stored = before.iter().find(|h| h.id == home.id);
+                        stored.map(serde_json::to_value).transpose().ok().flatten()
+                            != serde_json::to_value(home).ok()
+                    })
+                    .map(|home| SmartHomeEvent::HomeReplaced { home })
+                    .collect(),
+            };
+            Ok((unlinking_thermostats(state, events)?, summary))
+        })
+        .map_err(|msg| anyhow!("Unable import the smart home: {msg}"))
+    }
+}
+
+/// The imported sockets, which are in the smart home already, go on with their stored counters,
+/// so importing the same homes once again changes nothing. The counters are brought up to now
+/// only if the import changes the power or the status of the socket. The new sockets are
+/// metered from now, so the time between the export and the import isn't counted.
+fn continue_counters(homes: &mut [Home], state: &IndexedState, now: DateTime<Utc>) {
+    for home in homes {
+        each_socket(home_devices(home), |socket| {
+            let stored = state
+                .device(&socket.id)
+                .and_then(|device| device.downcast_ref::<Socket>());
+            match stored {
+                Some(stored) => {
+                    let mut stored = stored.clone();
+                    if stored.power_consumption != socket.power_consumption
+                        || stored.state.on != socket.state.on
+                    {
+                        stored.settle_energy(now);
+                    }
+                    socket.energy = stored.energy;
+                }
+                None => socket.energy.metered_at = Some(now),
+            }
+        });
+    }
+}
+
+/// The exported document has the same envelope as the stored state, so the older exports are
+/// upgraded by the same migrations
+fn document(homes: &[Home]) -> Result<Value> {
+    Ok(json!({
+        "schema_version": SCHEMA_VERSION,
+        "homes": serde_json::to_value(homes)?,
+    }))
+}
+
+fn without_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key, without_nulls(value)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(without_nulls).collect()),
+        value => value,
+    }
+}
+
+fn read_homes(text: &str, format: DataFormat) -> Result<Vec<Home>> {
+    let document: Value = match format {
+        DataFormat::Json => serde_json::from_str(text)?,
+        DataFormat::Yaml => serde_yaml::from_str(text)?,
+        DataFormat::Toml => toml::from_str(text)?,
+        DataFormat::Csv => read_csv(text)?,
+    };
+
+    let mut document = upgrade_document(document)?;
+    let homes = document
+        .get_mut("homes")
+        .map(Value::take)
+        .unwrap_or_default();
+    validate(homes)
+}
+
+/// Reads the homes with the [Checker], so the import rejects exactly what the `fsck` reports
+fn validate(homes: Value) -> Result<Vec<Home>> {
+    let mut checker = Checker::default();
+    let homes = checker.check_homes(homes).unwrap_or_default();
+    if !checker.problems.is_empty() {
+        let problems: Vec<String> = checker.problems.iter().map(|p| p.to_string()).collect();
+        return Err(anyhow!(
+            "{} problem(s) found:\n  {}",
+            problems.len(),
+            problems.join("\n  ")
+        ));
+    }
+    Ok(homes)
+}
+
+/// Lists the entities in the order they are stored as `(kind, id, name, content)`. The content
+/// is the entity without its children.
+fn flatten(homes: &[Home]) -> Vec<(&'static str, String, String, Value)> {
+    let mut entities = vec![];
+    for home in homes {
+        let mut content = serde_json::to_value(home).unwrap_or_default();
+        content["rooms"] = Value::Null;
+        entities.push(("home", home.id.clone(), home.name.clone(), content));
+
+        for room in &home.rooms {
+            let mut content = serde_json::to_value(room).unwrap_or_default();
+            content["devices"] = Value::Null;
+            entities.push(("room", room.id.clone(), room.name.clone(), content));
+
+            for device in &room.devices {
+                let content = serde_json::to_value(device).unwrap_or_default();
+                let (id, name) = (device.id().clone(), device.name().to_string());
+                entities.push(("device", id, name, content));
+            }
+        }
+    }
+    entities
+}
+
+/// A row of the csv export. The parent is the id of the home for the rooms, and the id of the
+/// room for the devices.
+#[derive(Debug, Serialize, Deserialize)]
+struct CsvRow {
+    kind: String,
+    id: String,
+    parent: String,
+    name: String,
+    description: String,
+    labels: String,
+    on: String,
+}
+
+fn write_csv(homes: &[Home]) -> Result<String> {
+    let labels = |labels: &Labels| format_labels(labels).replace(", ", ";");
+    let mut writer = csv::Writer::from_writer(vec![]);
+
+    for home in homes {
+        writer.serialize(CsvRow {
+            kind: "home".into(),
+            id: home.id.clone(),
+            parent: String::new(),
+            name: home.name.clone(),
+            description: home.description.clone().unwrap_or_default(),
+            labels: labels(&home.labels),
+            on: String::new(),
+        })?;
+
+        for room in &home.rooms {
+            writer.serialize(CsvRow {
+                kind: "room".into(),
+                id: room.id.clone(),
+                parent: home.id.clone(),
+                name: room.name.clone(),
+                description: room.description.clone().unwrap_or_default(),
+                labels: labels(&room.labels),
+                on: String::new(),
+            })?;
+
+            for device in &room.devices {
+                writer.serialize(CsvRow {
+                    kind: device.device_type().to_string(),
+                    id: device.id().clone(),
+                    parent: room.id.clone(),
+                    name: device.name().to_string(),
+                    description: device.description().unwrap_or_default().to_string(),
+                    labels: labels(device.labels()),
+                    on: device.state().on.to_string(),
+                })?;
+            }
+        }
+    }
+
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+/// Builds the same document as the other formats have out of the csv rows, so the homes are
+/// validated the same way. The rows might go in any order, but the parents must exist.
+fn read_csv(text: &str) -> Result<Value> {
+    let mut reader = csv::Reader::from_reader(text.as_bytes());
+    let rows = reader
+        .deserialize()
+        .collect::<Result<Vec<CsvRow>, _>>()
+        .map_err(|e| anyhow!("Unable read the csv: {e}"))?;
+
+    let entity = |row: &CsvRow| -> Result<Map<String, Value>> {
+        let mut labels = Labels::new();
+        for label in row.labels.split(';').filter(|l| !l.trim().is_empty()) {
+            let (key, value) = parse_label(label)?;
+            labels.insert(key, value);
+        }
+        let description = Some(row.description.clone()).filter(|d| !d.is_empty());
+
+        let mut fields = Map::new();
+        fields.insert("id".into(), json!(row.id));
+        fields.insert("name".into(), json!(row.name));
+        fields.insert("description".into(), json!(description));
+        fields.insert("labels".into(), json!(labels));
+        Ok(fields)
+    };
+
+    for row in &rows {
+        let parent_kind = match row.kind.as_str() {
+            "home" => continue,
+            "room" => "home",
+            kind if kind.parse::<DeviceType>().is_ok() => "room",
+            kind => return Err(anyhow!("Unknown kind {kind} of the entity {}", row.id)),
+        };
+        if !rows
+            .iter()
+            .any(|r| r.kind == parent_kind && r.id == row.parent)
+        {
+            return Err(anyhow!(
+                "The {} {} has no {parent_kind} {}",
+                row.kind,
+                row.id,
+                row.parent
+            ));
+        }
+    }
+
+    let mut homes = vec![];
+    for home in rows.iter().filter(|r| r.kind == "home") {
+        let mut rooms = vec![];
+        for room in rows
+            .iter()
+            .filter(|r| r.kind == "room" && r.parent == home.id)
+        {
+            let mut devices = vec![];
+            for device in rows
+                .iter()
+                .filter(|r| r.kind != "home" && r.kind != "room" && r.parent == room.id)
+            {
+                let mut fields = entity(device)?;
+                let state = DeviceState {
+                    on: device.on.trim().parse().unwrap_or_default(),
+                    ..DeviceState::default()
+                };
+                fields.insert("state".into(), serde_json::to_value(state)?);
+                // the fields the csv doesn't keep are taken from a new device of the same type
+                let device_type: DeviceType =
+                    device.kind.parse().map_err(|msg: String| anyhow!(msg))?;
+                let mut value = serde_json::to_value(device_type.create(&device.name, None)?)?;
+                if let Some(Value::Object(defaults)) = value.get_mut(device_type.tag()) {
+                    defaults.extend(fields);
+                }
+                devices.push(value);
+            }
+
+            let mut fields = entity(room)?;
+            fields.insert("devices".into(), Value::Array(devices));
+            rooms.push(Value::Object(fields));
+        }
+
+        let mut fields = entity(home)?;
+        fields.insert("rooms".into(), Value::Array(rooms));
+        homes.push(Value::Object(fields));
+    }
+
+    Ok(json!({ "schema_version": SCHEMA_VERSION, "homes": homes }))
+}
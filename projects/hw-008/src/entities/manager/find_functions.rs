@@ -1,81 +1,280 @@
+use anyhow::{anyhow, Result};
+
 use crate::entities::devices::{Device, DeviceId};
 use crate::entities::house::{Home, HomeId, Room, RoomId};
+use crate::entities::manager::indexed_state::IndexedState;
 use crate::entities::manager::smart_home::SmartHomeManager;
 
+/// An entity found by its path, see [FindFunctions::find_by_path]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathMatch {
+    pub id: String,
+    /// The full path of the entity made of the names, e.g. `My Home/Kitchen/Main light`
+    pub path: String,
+}
+
+/// The kind of the entity is defined by the number of the path segments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntityKind {
+    Home = 1,
+    Room = 2,
+    Device = 3,
+}
+
+impl EntityKind {
+    fn name(&self) -> &'static str {
+        match self {
+            EntityKind::Home => "home",
+            EntityKind::Room => "room",
+            EntityKind::Device => "device",
+        }
+    }
+}
+
+/// Besides the ids, the entities might be addressed by their human-readable paths like
+/// `My Home/Kitchen/Main light`: the home name, the room name and the device name separated by
+/// `/`. Each segment of the path is either the name or the id of the entity, and it might be a
+/// glob pattern with `*` (any number of any characters) and `?` (any single character), e.g.
+/// `*/Kitchen/*`. The names containing `/` can't be addressed by the path, use their ids.
+///
+/// The `resolve_*` functions turn any reference (an id, a path or a pattern) into the id of a
+/// single entity. The reference matching several entities is rejected with the list of the
+/// matched paths, so the user is able to pick the right one.
 pub trait FindFunctions {
     fn find_home_by_id(&self, id: &HomeId) -> Option<Home>;
     fn find_home_by_room_id(&self, id: &RoomId) -> Option<Home>;
     fn find_room_by_id(&self, id: &RoomId) -> Option<Room>;
     fn find_device_by_id(&self, id: &DeviceId) -> Option<Device>;
     fn find_room_by_device_id(&self, id: &DeviceId) -> Option<Room>;
+
+    /// Returns all the entities matching the path pattern. The kind of the entities is defined
+    /// by the number of the segments: one for homes, two for rooms and three for devices.
+    fn find_by_path(&self, pattern: &str) -> Result<Vec<PathMatch>>;
+
+    fn resolve_home(&self, reference: &str) -> Result<HomeId>;
+    fn resolve_room(&self, reference: &str) -> Result<RoomId>;
+    fn resolve_device(&self, reference: &str) -> Result<DeviceId>;
+
+    /// Resolves the reference to the entity of any kind
+    fn resolve_entity(&self, reference: &str) -> Result<String>;
 }
 
+/// All the lookups are served by the indexes of the cached state, so they don't touch the
+/// store unless the stored state was changed since the last access.
 impl FindFunctions for SmartHomeManager {
     fn find_home_by_id(&self, id: &HomeId) -> Option<Home> {
-        match self.read_smart_home_status() {
-            Ok(smart_home) => smart_home.and_then(|homes| homes.into_iter().find(|h| h.id == *id)),
-            Err(_) => None,
-        }
+        self.with_state(|state| state.home(id).cloned())
+            .ok()
+            .flatten()
     }
 
     fn find_home_by_room_id(&self, id: &RoomId) -> Option<Home> {
-        match self.read_smart_home_status() {
-            Ok(smart_home) => smart_home.and_then(|homes| {
-                homes
-                    .into_iter()
-                    .find(|home| home.rooms.iter().any(|r| r.id == *id))
-            }),
-            Err(_) => None,
-        }
+        self.with_state(|state| state.home_by_room_id(id).cloned())
+            .ok()
+            .flatten()
     }
 
     fn find_room_by_id(&self, id: &RoomId) -> Option<Room> {
-        match self.read_smart_home_status() {
-            Ok(smart_home) => smart_home.and_then(|homes| {
-                for home in homes {
-                    let option = home.rooms.into_iter().find(|room| room.id == *id);
-
-                    if option.is_some() {
-                        return option;
-                    }
-                }
-                None
-            }),
-            Err(_) => None,
-        }
+        self.with_state(|state| state.room(id).cloned())
+            .ok()
+            .flatten()
     }
 
     fn find_device_by_id(&self, id: &DeviceId) -> Option<Device> {
-        match self.read_smart_home_status() {
-            Ok(smart_home) => smart_home.and_then(|homes| {
-                let mut devices: Vec<Device> = vec![];
-                for home in homes.into_iter() {
-                    for mut room in home.rooms.into_iter() {
-                        devices.append(&mut room.devices)
-                    }
-                }
+        self.with_state(|state| state.device(id).cloned())
+            .ok()
+            .flatten()
+    }
+
+    fn find_room_by_device_id(&self, id: &DeviceId) -> Option<Room> {
+        self.with_state(|state| state.room_by_device_id(id).cloned())
+            .ok()
+            .flatten()
+    }
 
-                devices.into_iter().find(|d| d.id() == id)
-            }),
-            Err(_) => None,
+    fn find_by_path(&self, pattern: &str) -> Result<Vec<PathMatch>> {
+        let segments: Vec<&str> = pattern.split('/').collect();
+        if segments.len() > EntityKind::Device as usize {
+            return Err(anyhow!(
+                "Invalid path {pattern}, expected home/room/device at most"
+            ));
         }
+        self.with_state(|state| find_in(state, &segments))
     }
 
-    fn find_room_by_device_id(&self, id: &DeviceId) -> Option<Room> {
-        match self.read_smart_home_status() {
-            Ok(smart_home) => smart_home.and_then(|homes| {
-                for home in homes {
-                    for room in home.rooms.into_iter() {
-                        let option = room.devices.iter().find(|d| d.id() == id);
-
-                        if option.is_some() {
-                            return Some(room);
-                        }
-                    }
+    fn resolve_home(&self, reference: &str) -> Result<HomeId> {
+        resolve(self, reference, Some(EntityKind::Home))
+    }
+
+    fn resolve_room(&self, reference: &str) -> Result<RoomId> {
+        resolve(self, reference, Some(EntityKind::Room))
+    }
+
+    fn resolve_device(&self, reference: &str) -> Result<DeviceId> {
+        resolve(self, reference, Some(EntityKind::Device))
+    }
+
+    fn resolve_entity(&self, reference: &str) -> Result<String> {
+        resolve(self, reference, None)
+    }
+}
+
+fn resolve(
+    manager: &SmartHomeManager,
+    reference: &str,
+    kind: Option<EntityKind>,
+) -> Result<String> {
+    let id = reference.to_string();
+    let is_id = manager.with_state(|state| match kind {
+        Some(EntityKind::Home) => state.home(&id).is_some(),
+        Some(EntityKind::Room) => state.room(&id).is_some(),
+        Some(EntityKind::Device) => state.device(&id).is_some(),
+        None => {
+            state.home(&id).is_some() || state.room(&id).is_some() || state.device(&id).is_some()
+        }
+    })?;
+    if is_id {
+        return Ok(id);
+    }
+
+    let segments = reference.split('/').count();
+    let kind_name = match kind {
+        Some(kind) if kind as usize != segments => {
+            let expected = ["<home>", "<home>/<room>", "<home>/<room>/<device>"][kind as usize - 1];
+            return Err(anyhow!(
+                "The {} {reference} not found, expected the id or the path like {expected}",
+                kind.name()
+            ));
+        }
+        Some(kind) => kind.name(),
+        None => "entity",
+    };
+
+    let mut matches = manager.find_by_path(reference)?;
+    match matches.len() {
+        0 => Err(anyhow!("The {kind_name} {reference} not found")),
+        1 => Ok(matches.remove(0).id),
+        _ => {
+            let found: Vec<String> = matches
+                .iter()
+                .map(|m| format!("  {} ({})", m.path, m.id))
+                .collect();
+            Err(anyhow!(
+                "The {kind_name} {reference} is ambiguous, it matches:\n{}",
+                found.join("\n")
+            ))
+        }
+    }
+}
+
+fn find_in(state: &IndexedState, segments: &[&str]) -> Vec<PathMatch> {
+    let mut found = vec![];
+
+    for home in state.homes() {
+        if !matches_segment(segments[0], &home.name, &home.id) {
+            continue;
+        }
+        if segments.len() == EntityKind::Home as usize {
+            found.push(PathMatch {
+                id: home.id.clone(),
+                path: home.name.clone(),
+            });
+            continue;
+        }
+
+        for room in &home.rooms {
+            if !matches_segment(segments[1], &room.name, &room.id) {
+                continue;
+            }
+            if segments.len() == EntityKind::Room as usize {
+                found.push(PathMatch {
+                    id: room.id.clone(),
+                    path: format!("{}/{}", home.name, room.name),
+                });
+                continue;
+            }
+
+            for device in &room.devices {
+                if matches_segment(segments[2], device.name(), device.id()) {
+                    found.push(PathMatch {
+                        id: device.id().clone(),
+                        path: format!("{}/{}/{}", home.name, room.name, device.name()),
+                    });
                 }
-                None
-            }),
-            Err(_) => None,
+            }
         }
     }
+
+    found
+}
+
+fn matches_segment(pattern: &str, name: &str, id: &str) -> bool {
+    pattern == id || glob_matches(pattern, name)
+}
+
+/// A tiny glob matcher which supports `*` and `?` wildcards only. It works with the characters,
+/// so `?` stands for a single letter of any alphabet. On a mismatch it goes back to the latest
+/// `*` only, which keeps the matching linear for any number of the stars.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // the position of the latest star in the pattern and the text position it's tried from
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                // let the star take one more character
+                Some((star_p, star_t)) => {
+                    star = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glob_matches;
+
+    #[test]
+    fn glob_patterns() {
+        assert!(glob_matches("Kitchen", "Kitchen"));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("Main*", "Main light"));
+        assert!(glob_matches("*light", "Main light"));
+        assert!(glob_matches("M?in*t", "Main light"));
+        assert!(!glob_matches("Main", "Main light"));
+        assert!(!glob_matches("?", ""));
+        assert!(glob_matches("*a*b", "aab"));
+        assert!(!glob_matches("*a*b", "aba"));
+    }
+
+    #[test]
+    fn glob_patterns_match_characters() {
+        assert!(glob_matches("Кухн?", "Кухня"));
+        assert!(glob_matches("?ухня", "Кухня"));
+        assert!(!glob_matches("Кухн??", "Кухня"));
+        assert!(glob_matches("*ня", "Кухня"));
+    }
+
+    #[test]
+    fn many_stars_are_matched_quickly() {
+        let text = "a".repeat(200);
+        let pattern = format!("{}*b", "*a".repeat(30));
+        assert!(!glob_matches(&pattern, &text));
+    }
 }
@@ -0,0 +1,44 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+
+use crate::entities::house::{Home, HomeId};
+use crate::entities::manager::events::SmartHomeEvent;
+use crate::entities::manager::journal::JournalEntry;
+use crate::entities::manager::smart_home::SmartHomeManager;
+
+pub trait HistoryFunctions {
+    /// Returns all the journaled changes ordered by revision
+    fn history(&self) -> Result<Vec<JournalEntry>>;
+
+    /// Returns the home as it was at the given point in time, or [None] if the home didn't
+    /// exist at that moment.
+    fn find_home_at(&self, id: &HomeId, at: DateTime<Utc>) -> Result<Option<Home>>;
+
+    /// Rolls the home back to the state it had at the given point in time. The rollback is just
+    /// another change, so it's journaled as well and might be rolled back too.
+    fn restore_home(&self, id: &HomeId, at: DateTime<Utc>) -> Result<HomeId>;
+}
+
+impl HistoryFunctions for SmartHomeManager {
+    fn history(&self) -> Result<Vec<JournalEntry>> {
+        self.journal().entries()
+    }
+
+    fn find_home_at(&self, id: &HomeId, at: DateTime<Utc>) -> Result<Option<Home>> {
+        let state = self.journal().state_at(at)?;
+        Ok(state.home(id).cloned())
+    }
+
+    fn restore_home(&self, id: &HomeId, at: DateTime<Utc>) -> Result<HomeId> {
+        let past = self.find_home_at(id, at)?;
+
+        self.commit(|state| {
+            let event = match (past, state.home(id)) {
+                (Some(home), _) => SmartHomeEvent::HomeReplaced { home },
+                (None, Some(home)) => SmartHomeEvent::HomeRemoved { home: home.clone() },
+                (None, None) => return Err(anyhow!("Home {id} not found")),
+            };
+            Ok((vec![event], id.clone()))
+        })
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use crate::entities::devices::{Device, DeviceId};
+use crate::entities::house::{Home, HomeId, Room, RoomId};
+use crate::entities::manager::store::{Revision, VersionedState};
+use crate::entities::manager::SavedSmartHome;
+use crate::entities::{generate_id, Labels};
+
+/// A position of the room in the state: the index of the home and the index of the room in it
+type RoomPosition = (usize, usize);
+
+/// A position of the device in the state: indexes of the home, the room and the device itself
+type DevicePosition = (usize, usize, usize);
+
+/// A loaded smart home state with the indexes for fast lookups. Each entity id is mapped to the
+/// position of the entity in the tree, so the entity and all its parents might be found
+/// without scanning the whole state.
+///
+/// The indexes are rebuilt by [IndexedState::reindex] after each modification of the tree. The
+/// modifications are much more rare than lookups, so it's a fair price.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct IndexedState {
+    revision: Revision,
+    state: SavedSmartHome,
+    homes: HashMap<HomeId, usize>,
+    rooms: HashMap<RoomId, RoomPosition>,
+    devices: HashMap<DeviceId, DevicePosition>,
+}
+
+impl IndexedState {
+    pub fn new(loaded: VersionedState) -> Self {
+        let mut state = IndexedState {
+            revision: loaded.revision,
+            state: loaded.state,
+            ..Self::default()
+        };
+        state.reindex();
+        state
+    }
+
+    pub fn revision(&self) -> Revision {
+        self.revision
+    }
+
+    pub fn set_revision(&mut self, revision: Revision) {
+        self.revision = revision;
+    }
+
+    pub fn state(&self) -> &SavedSmartHome {
+        &self.state
+    }
+
+    pub fn homes(&self) -> &[Home] {
+        self.state.as_deref().unwrap_or_default()
+    }
+
+    /// A mutable access to the list of homes. Don't forget to call [IndexedState::reindex]
+    /// after the tree modification.
+    pub fn homes_mut(&mut self) -> &mut Vec<Home> {
+        self.state.get_or_insert_with(Vec::new)
+    }
+
+    /// Replaces the whole state, the indexes are rebuilt automatically
+    pub fn replace(&mut self, state: SavedSmartHome) {
+        self.state = state;
+        self.reindex();
+    }
+
+    /// Rebuilds all the indexes from scratch. If several entities have the same id, the first
+    /// one wins, which is the same behaviour as the linear search had.
+    pub fn reindex(&mut self) {
+        self.homes.clear();
+        self.rooms.clear();
+        self.devices.clear();
+
+        let homes = self.state.as_deref().unwrap_or_default();
+        for (h, home) in homes.iter().enumerate() {
+            self.homes.entry(home.id.clone()).or_insert(h);
+
+            for (r, room) in home.rooms.iter().enumerate() {
+                self.rooms.entry(room.id.clone()).or_insert((h, r));
+
+                for (d, device) in room.devices.iter().enumerate() {
+                    self.devices.entry(device.id().clone()).or_insert((h, r, d));
+                }
+            }
+        }
+    }
+
+    pub fn home_position(&self, id: &HomeId) -> Option<usize> {
+        self.homes.get(id).copied()
+    }
+
+    pub fn room_position(&self, id: &RoomId) -> Option<RoomPosition> {
+        self.rooms.get(id).copied()
+    }
+
+    pub fn device_position(&self, id: &DeviceId) -> Option<DevicePosition> {
+        self.devices.get(id).copied()
+    }
+
+    pub fn home(&self, id: &HomeId) -> Option<&Home> {
+        self.home_position(id).map(|h| &self.homes()[h])
+    }
+
+    pub fn room(&self, id: &RoomId) -> Option<&Room> {
+        self.room_position(id)
+            .map(|(h, r)| &self.homes()[h].rooms[r])
+    }
+
+    pub fn device(&self, id: &DeviceId) -> Option<&Device> {
+        self.device_position(id)
+            .map(|(h, r, d)| &self.homes()[h].rooms[r].devices[d])
+    }
+
+    /// Checks whether any entity, a home, a room or a device, already has the id
+    pub fn contains_id(&self, id: &str) -> bool {
+        self.homes.contains_key(id) || self.rooms.contains_key(id) || self.devices.contains_key(id)
+    }
+
+    /// Returns the id itself, or a freshly generated one if the id is already taken
+    pub fn unique_id(&self, id: String, prefix: &str) -> String {
+        let mut id = id;
+        while self.contains_id(&id) {
+            id = generate_id(prefix);
+        }
+        id
+    }
+
+    /// The labels of the home, room or device with the given id
+    pub fn labels(&self, id: &str) -> Option<&Labels> {
+        let id = id.to_string();
+        self.home(&id)
+            .map(|home| &home.labels)
+            .or_else(|| self.room(&id).map(|room| &room.labels))
+            .or_else(|| self.device(&id).map(Device::labels))
+    }
+
+    pub fn labels_mut(&mut self, id: &str) -> Option<&mut Labels> {
+        let id = id.to_string();
+        if self.home_position(&id).is_some() {
+            return self.home_mut(&id).map(|home| &mut home.labels);
+        }
+        if self.room_position(&id).is_some() {
+            return self.room_mut(&id).map(|room| &mut room.labels);
+        }
+        self.device_mut(&id).map(Device::labels_mut)
+    }
+
+    /// The names of the homes for [None], the names of the rooms of the home, or the names of
+    /// the devices of the room with the given id
+    pub fn child_names(&self, parent: Option<&str>) -> Vec<&str> {
+        let Some(parent) = parent.map(str::to_string) else {
+            return self.homes().iter().map(|h| h.name.as_str()).collect();
+        };
+        if let Some(home) = self.home(&parent) {
+            home.rooms.iter().map(|r| r.name.as_str()).collect()
+        } else if let Some(room) = self.room(&parent) {
+            room.devices.iter().map(Device::name).collect()
+        } else {
+            vec![]
+        }
+    }
+
+    pub fn home_by_room_id(&self, id: &RoomId) -> Option<&Home> {
+        self.room_position(id).map(|(h, _)| &self.homes()[h])
+    }
+
+    pub fn room_by_device_id(&self, id: &DeviceId) -> Option<&Room> {
+        self.device_position(id)
+            .map(|(h, r, _)| &self.homes()[h].rooms[r])
+    }
+
+    pub fn home_mut(&mut self, id: &HomeId) -> Option<&mut Home> {
+        let h = self.home_position(id)?;
+        Some(&mut self.homes_mut()[h])
+    }
+
+    pub fn room_mut(&mut self, id: &RoomId) -> Option<&mut Room> {
+        let (h, r) = self.room_position(id)?;
+        Some(&mut self.homes_mut()[h].rooms[r])
+    }
+
+    pub fn device_mut(&mut self, id: &DeviceId) -> Option<&mut Device> {
+        let (h, r, d) = self.device_position(id)?;
+        Some(&mut self.homes_mut()[h].rooms[r].devices[d])
+    }
+
+    pub fn rooms(&self) -> impl Iterator<Item = &Room> {
+        self.homes().iter().flat_map(|h| h.rooms.iter())
+    }
+
+    pub fn devices(&self) -> impl Iterator<Item = &Device> {
+        self.rooms().flat_map(|r| r.devices.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IndexedState;
+    use crate::entities::devices::{Device, Socket};
+    use crate::entities::house::{Home, Room};
+    use crate::entities::manager::store::VersionedState;
+
+    #[test]
+    fn lookup_entities_and_parents() {
+        let socket = Socket::new("Socket");
+        let socket_id = socket.id.clone();
+        let room = Room::build()
+            .with_name("Kitchen")
+            .with_device(Device::new(socket))
+            .build()
+            .unwrap();
+        let room_id = room.id.clone();
+        let home = Home::build()
+            .with_name("Home")
+            .with_room(room)
+            .build()
+            .unwrap();
+        let home_id = home.id.clone();
+
+        let mut state = IndexedState::new(VersionedState {
+            revision: 7,
+            state: Some(vec![home]),
+        });
+
+        assert_eq!(state.revision(), 7);
+        assert_eq!(state.home(&home_id).unwrap().name, "Home");
+        assert_eq!(state.room_by_device_id(&socket_id).unwrap().id, room_id);
+        assert_eq!(state.home_by_room_id(&room_id).unwrap().id, home_id);
+        assert_eq!(state.devices().count(), 1);
+
+        state.room_mut(&room_id).unwrap().devices.clear();
+        state.reindex();
+        assert!(state.device(&socket_id).is_none());
+        assert!(state.room(&room_id).is_some());
+    }
+}
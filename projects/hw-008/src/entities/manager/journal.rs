@@ -0,0 +1,343 @@
+use std::cell::RefCell;
+use std::env;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::entities::manager::events::SmartHomeEvent;
+use crate::entities::manager::indexed_state::IndexedState;
+use crate::entities::manager::store::{Revision, StateStore, VersionedState, JOURNAL_LOG};
+use crate::entities::manager::SavedSmartHome;
+
+/// The name of the log with the snapshots of the whole state
+pub(crate) const SNAPSHOTS_LOG: &str = "snapshots";
+
+/// A snapshot of the state is taken each time the revision is divisible by this number, so the
+/// point-in-time queries never need to replay more than this number of changes.
+pub const SNAPSHOT_INTERVAL: Revision = 50;
+
+thread_local! {
+    static ACTOR: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Sets the name of the one who makes the changes in the current thread. The name is written to
+/// the journal together with each change. The servers handle each client in a separate thread,
+/// so each session is able to introduce itself, e.g. by the address of the client.
+pub fn set_current_actor(actor: impl Into<String>) {
+    ACTOR.with(|current| *current.borrow_mut() = Some(actor.into()));
+}
+
+/// Returns the actor set by [set_current_actor], or the name of the user who runs the process
+pub fn current_actor() -> String {
+    ACTOR
+        .with(|current| current.borrow().clone())
+        .or_else(|| env::var("USER").ok())
+        .or_else(|| env::var("USERNAME").ok())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// The reason of the journaled change. The undo and redo changes refer to the revision of the
+/// original change they revert or repeat.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EntryKind {
+    #[default]
+    Change,
+    Undo(Revision),
+    Redo(Revision),
+    /// The change made by the smart home itself, e.g. by the control loop of the thermostats.
+    /// It's never undone, and it doesn't clear the changes to redo, so the users undo their own
+    /// changes only.
+    Automatic,
+}
+
+/// A record of the journal: all the events of a single change, and who made it and when. The
+/// `revision` is the revision of the state produced by the change.
+///
+/// The `inverse` events revert the change, they are recorded by [JournalEntry::apply_recording]
+/// together with the change itself. The entries written before the undo was introduced have
+/// no inverse events, so they can't be undone.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub revision: Revision,
+    pub timestamp: DateTime<Utc>,
+    pub actor: String,
+    #[serde(default)]
+    pub kind: EntryKind,
+    pub events: Vec<SmartHomeEvent>,
+    #[serde(default)]
+    pub inverse: Vec<SmartHomeEvent>,
+}
+
+impl JournalEntry {
+    pub fn new(revision: Revision, events: Vec<SmartHomeEvent>) -> Self {
+        Self {
+            revision,
+            timestamp: Utc::now(),
+            actor: current_actor(),
+            kind: EntryKind::Change,
+            events,
+            inverse: vec![],
+        }
+    }
+
+    pub fn with_kind(self, kind: EntryKind) -> Self {
+        Self { kind, ..self }
+    }
+
+    /// Returns `true` if the entry might be reverted by the undo
+    pub fn is_undoable(&self) -> bool {
+        !self.inverse.is_empty()
+    }
+
+    pub(crate) fn apply(&self, state: &mut IndexedState) -> Result<()> {
+        for event in &self.events {
+            event.apply(state)?;
+        }
+        state.set_revision(self.revision);
+        Ok(())
+    }
+
+    /// The same as [JournalEntry::apply], but it also records the inverse events of the entry
+    pub(crate) fn apply_recording(&mut self, state: &mut IndexedState) -> Result<()> {
+        self.inverse.clear();
+        for event in &self.events {
+            if let Some(inverse) = event.inverse(state) {
+                self.inverse.push(inverse);
+            }
+            event.apply(state)?;
+        }
+        // the latest change must be reverted first
+        self.inverse.reverse();
+        state.set_revision(self.revision);
+        Ok(())
+    }
+}
+
+/// A copy of the whole state at the given revision
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub revision: Revision,
+    pub timestamp: DateTime<Utc>,
+    pub state: SavedSmartHome,
+}
+
+/// A journal of all the changes made by the managers, kept by the [StateStore] next to the
+/// state itself. The journal entries are written atomically with the state, and the snapshots
+/// are written afterwards in the best effort manner, they are just a shortcut for the replaying.
+///
+/// The repositories created before the journal was introduced have some history which was never
+/// journaled. So, the very first journaled change of such repository takes a snapshot of the
+/// state it's based on, and the history starts from this snapshot.
+pub(crate) struct Journal<'a> {
+    store: &'a dyn StateStore,
+}
+
+impl<'a> Journal<'a> {
+    pub fn new(store: &'a dyn StateStore) -> Self {
+        Self { store }
+    }
+
+    /// Returns all the journal entries ordered by revision. An entry could be written twice for
+    /// the same revision only if the first writer crashed before writing the state, so the
+    /// later entry always wins.
+    pub fn entries(&self) -> Result<Vec<JournalEntry>> {
+        let mut entries: Vec<JournalEntry> = vec![];
+
+        for record in self.store.read_log(JOURNAL_LOG)? {
+            let entry: JournalEntry = serde_json::from_str(&record)
+                .map_err(|e| anyhow!("Unable read the journal entry: {e}"))?;
+            entries.retain(|e| e.revision != entry.revision);
+            entries.push(entry);
+        }
+
+        entries.sort_by_key(|e| e.revision);
+        Ok(entries)
+    }
+
+    pub fn snapshots(&self) -> Result<Vec<Snapshot>> {
+        let mut snapshots = self
+            .store
+            .read_log(SNAPSHOTS_LOG)?
+            .iter()
+            .map(|record| serde_json::from_str(record))
+            .collect::<serde_json::Result<Vec<Snapshot>>>()
+            .map_err(|e| anyhow!("Unable read the snapshot: {e}"))?;
+
+        snapshots.sort_by_key(|s| s.revision);
+        Ok(snapshots)
+    }
+
+    /// Replays the journal to find out which changes might be undone and which undone changes
+    /// might be redone. Both stacks have the latest change at the end. Any new change makes the
+    /// undone changes impossible to redo, as it's usual for the undo.
+    pub fn undo_stacks(&self) -> Result<(Vec<JournalEntry>, Vec<JournalEntry>)> {
+        let mut undo: Vec<JournalEntry> = vec![];
+        let mut redo: Vec<JournalEntry> = vec![];
+
+        for entry in self.entries()? {
+            match entry.kind {
+                EntryKind::Change if entry.is_undoable() => {
+                    undo.push(entry);
+                    redo.clear();
+                }
+                // e.g. the migration of the format changes nothing in the smart home
+                EntryKind::Change | EntryKind::Automatic => {}
+                EntryKind::Undo(revision) => {
+                    if let Some(i) = undo.iter().rposition(|e| e.revision == revision) {
+                        redo.push(undo.remove(i));
+                    }
+                }
+                EntryKind::Redo(revision) => {
+                    if let Some(i) = redo.iter().rposition(|e| e.revision == revision) {
+                        undo.push(redo.remove(i));
+                    }
+                }
+            }
+        }
+
+        Ok((undo, redo))
+    }
+
+    /// Returns the revision of the last journaled change
+    pub fn last_revision(&self) -> Result<Option<Revision>> {
+        match self.store.last_log_record(JOURNAL_LOG)? {
+            Some(record) => {
+                let entry: JournalEntry = serde_json::from_str(&record)
+                    .map_err(|e| anyhow!("Unable read the journal entry: {e}"))?;
+                Ok(Some(entry.revision))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn snapshot(&self, state: &IndexedState) -> Result<()> {
+        let snapshot = Snapshot {
+            revision: state.revision(),
+            timestamp: Utc::now(),
+            state: state.state().clone(),
+        };
+        self.store
+            .append_log(SNAPSHOTS_LOG, &[serde_json::to_string(&snapshot)?])
+    }
+
+    /// Writes the changes made on top of the `base` state to the store. Returns the revision of
+    /// the written state.
+    pub fn save(
+        &self,
+        base: &IndexedState,
+        changed: &IndexedState,
+        entry: &JournalEntry,
+    ) -> Result<Revision> {
+        if base.revision() > 0 && self.last_revision()?.is_none() {
+            self.snapshot(base)?;
+        }
+
+        let record = serde_json::to_string(entry)?;
+        let revision = self
+            .store
+            .save_with_journal(changed.state(), base.revision(), &[record])?;
+
+        if revision % SNAPSHOT_INTERVAL == 0 {
+            // the snapshots are optional, the change itself is already saved
+            let _ = self.snapshot(changed);
+        }
+        Ok(revision)
+    }
+
+    /// Brings the loaded state up to date with the journal. The journal might be ahead of the
+    /// state only if a writer crashed after writing the journal, but before writing the state.
+    /// Each missing change is replayed and saved with its own revision, so the revisions of the
+    /// state and the journal are kept in sync.
+    pub fn catch_up(&self, mut state: IndexedState) -> Result<IndexedState> {
+        match self.last_revision()? {
+            Some(last) if last > state.revision() => {}
+            _ => return Ok(state),
+        }
+
+        for entry in self.entries()? {
+            if entry.revision <= state.revision() {
+                continue;
+            }
+
+            let mut next = state.clone();
+            if entry.revision != state.revision() + 1 || entry.apply(&mut next).is_err() {
+                // the rest of the journal doesn't match the state, nothing to replay
+                break;
+            }
+
+            match self.store.save(next.state(), state.revision()) {
+                Ok(revision) => {
+                    next.set_revision(revision);
+                    state = next;
+                }
+                // somebody else is fixing the state right now
+                Err(_) => return Ok(IndexedState::new(self.store.load()?)),
+            }
+        }
+
+        Ok(state)
+    }
+
+    /// Rebuilds the state as it was at the given point in time. The latest snapshot taken before
+    /// that time is used as a starting point, and the rest of the changes are replayed on top
+    /// of it.
+    pub fn state_at(&self, at: DateTime<Utc>) -> Result<IndexedState> {
+        let entries = self.entries()?;
+        let snapshot = self
+            .snapshots()?
+            .into_iter()
+            .rev()
+            .find(|s| s.timestamp <= at);
+
+        let mut state = match snapshot {
+            Some(snapshot) => IndexedState::new(VersionedState {
+                revision: snapshot.revision,
+                state: snapshot.state,
+            }),
+            None => match entries.first() {
+                Some(first) if first.revision == 1 && first.timestamp <= at => {
+                    IndexedState::default()
+                }
+                Some(first) => {
+                    return Err(anyhow!(
+                        "No history recorded before {}",
+                        first.timestamp.to_rfc3339()
+                    ))
+                }
+                None => return Err(anyhow!("No history recorded yet")),
+            },
+        };
+
+        for entry in entries {
+            if entry.timestamp > at {
+                break;
+            }
+            if entry.revision > state.revision() {
+                entry.apply(&mut state)?;
+            }
+        }
+
+        Ok(state)
+    }
+}
+
+/// Parses a point in time given by the user. Both RFC 3339 timestamps and the short forms like
+/// `2023-01-31 18:30:00` or `2023-01-31` are accepted, the short forms are considered as UTC.
+pub fn parse_timestamp(value: &str) -> Result<DateTime<Utc>> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(timestamp) = NaiveDateTime::parse_from_str(value, format) {
+            return Ok(DateTime::from_utc(timestamp, Utc));
+        }
+    }
+
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|timestamp| DateTime::from_utc(timestamp, Utc))
+        .ok_or_else(|| anyhow!("Unable parse the timestamp: {value}"))
+}
@@ -0,0 +1,61 @@
+use anyhow::{anyhow, Result};
+
+use crate::entities::devices::Device;
+use crate::entities::house::{Home, Room};
+use crate::entities::manager::events::SmartHomeEvent;
+use crate::entities::manager::smart_home::SmartHomeManager;
+use crate::entities::{LabelSelector, Labels};
+
+pub trait LabelFunctions {
+    /// Sets the given labels of the home, room or device, and removes the labels with the given
+    /// keys. The existing labels with the same keys are overwritten. Returns all the labels the
+    /// entity has after the change.
+    fn label(&self, id: &str, set: Labels, remove: &[String]) -> Result<Labels>;
+
+    /// The selectors don't look at the parents, so the device in the room labeled `floor=2`
+    /// isn't matched by `floor=2` unless the device itself has such label.
+    fn select_homes(&self, selector: &LabelSelector) -> Result<Vec<Home>>;
+    fn select_rooms(&self, selector: &LabelSelector) -> Result<Vec<Room>>;
+    fn select_devices(&self, selector: &LabelSelector) -> Result<Vec<Device>>;
+}
+
+impl LabelFunctions for SmartHomeManager {
+    fn label(&self, id: &str, set: Labels, remove: &[String]) -> Result<Labels> {
+        self.commit(|state| {
+            let current = state
+                .labels(id)
+                .ok_or_else(|| anyhow!("Entity with id: {id} not found"))?;
+
+            let mut labels = current.clone();
+            labels.extend(set);
+            labels.retain(|key, _| !remove.contains(key));
+
+            if labels == *current {
+                return Ok((vec![], labels));
+            }
+            let event = SmartHomeEvent::LabelsChanged {
+                id: id.to_string(),
+                labels: labels.clone(),
+            };
+            Ok((vec![event], labels))
+        })
+    }
+
+    fn select_homes(&self, selector: &LabelSelector) -> Result<Vec<Home>> {
+        let mut homes = self.list_all_homes()?;
+        homes.retain(|home| selector.matches(&home.labels));
+        Ok(homes)
+    }
+
+    fn select_rooms(&self, selector: &LabelSelector) -> Result<Vec<Room>> {
+        let mut rooms = self.list_all_rooms()?;
+        rooms.retain(|room| selector.matches(&room.labels));
+        Ok(rooms)
+    }
+
+    fn select_devices(&self, selector: &LabelSelector) -> Result<Vec<Device>> {
+        let mut devices = self.list_all_devices()?;
+        devices.retain(|device| selector.matches(device.labels()));
+        Ok(devices)
+    }
+}
@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+
+use crate::entities::devices::{Device, Light, COLOR_TEMPERATURE_RANGE};
+use crate::entities::manager::events::SmartHomeEvent;
+use crate::entities::manager::smart_home::SmartHomeManager;
+
+/// The changes of a light, the omitted ones are kept as is
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LightSettings {
+    /// The new brightness from 0 to 100 percents
+    pub brightness: Option<u8>,
+    /// The new colour temperature in kelvins, see [COLOR_TEMPERATURE_RANGE]
+    pub color_temperature: Option<u16>,
+    /// The time the light goes to the new brightness for, it changes at once without it
+    pub transition: Option<Duration>,
+}
+
+pub trait LightFunctions {
+    /// Changes the brightness and/or the colour temperature of the light with the given id.
+    /// Returns the light after the change. The light is not switched on or off by the change,
+    /// so the brightness of a switched off light is just kept for the moment it's switched on.
+    fn adjust_light(&self, device_id: &str, settings: LightSettings) -> Result<Light>;
+}
+
+impl LightFunctions for SmartHomeManager {
+    fn adjust_light(&self, device_id: &str, settings: LightSettings) -> Result<Light> {
+        if settings.brightness.is_none() && settings.color_temperature.is_none() {
+            return Err(anyhow!(
+                "Nothing to change, provide the brightness or the colour temperature"
+            ));
+        }
+        if let Some(brightness) = settings.brightness.filter(|b| *b > 100) {
+            return Err(anyhow!(
+                "The brightness must be from 0 to 100%, got {brightness}%"
+            ));
+        }
+        let (warmest, coldest) = COLOR_TEMPERATURE_RANGE;
+        if let Some(kelvins) = settings
+            .color_temperature
+            .filter(|k| !(warmest..=coldest).contains(k))
+        {
+            return Err(anyhow!(
+                "The colour temperature must be from {warmest}K to {coldest}K, got {kelvins}K"
+            ));
+        }
+
+        self.commit(|state| {
+            let device = state
+                .device(&device_id.to_string())
+                .ok_or_else(|| anyhow!("Device with id: {device_id} not found"))?;
+            let mut light = device
+                .downcast_ref::<Light>()
+                .ok_or_else(|| {
+                    anyhow!(
+                        "The device {device_id} is a {}, not a light",
+                        device.device_type()
+                    )
+                })?
+                .clone();
+
+            let now = Utc::now();
+            if let Some(brightness) = settings.brightness {
+                let duration = settings.transition.map(|d| d.as_millis() as u64);
+                light.set_brightness(brightness, duration, now);
+            }
+            if let Some(kelvins) = settings.color_temperature {
+                light.color_temperature = Some(kelvins);
+            }
+
+            let event = SmartHomeEvent::DeviceReplaced {
+                device: Device::new(light.clone()),
+            };
+            Ok((vec![event], light))
+        })
+    }
+}
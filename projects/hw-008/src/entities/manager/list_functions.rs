@@ -0,0 +1,191 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+
+use crate::entities::devices::{Device, DeviceStatus, DeviceType};
+use crate::entities::house::{Home, HomeId, Room, RoomId};
+use crate::entities::manager::smart_home::SmartHomeManager;
+use crate::entities::LabelSelector;
+
+/// The keys the listed entities might be sorted by
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum SortKey {
+    Id,
+    Name,
+}
+
+/// The filters, the order and the page of the listed entities. The filters which don't make
+/// sense for the listed entities are ignored, e.g. the device type for the rooms. All the
+/// filters are optional, so the default query lists all the entities in the order they are
+/// stored.
+///
+/// ```
+/// use hw_008::cli::DeviceType;
+/// use hw_008::entities::manager::{ListQuery, SortKey};
+///
+/// let query = ListQuery {
+///     device_type: Some(DeviceType::THERMOMETER),
+///     sort: Some(SortKey::Name),
+///     limit: Some(20),
+///     offset: 40,
+///     ..ListQuery::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ListQuery {
+    pub device_type: Option<DeviceType>,
+    /// The rooms and the devices of the given home only
+    pub home_id: Option<HomeId>,
+    /// The devices of the given room only
+    pub room_id: Option<RoomId>,
+    /// The switched on or switched off devices only
+    pub status: Option<DeviceStatus>,
+    pub selector: LabelSelector,
+    pub sort: Option<SortKey>,
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+impl ListQuery {
+    /// Sorts the matched entities and cuts the requested page out of them
+    fn page<T>(&self, mut items: Vec<T>, key: impl Fn(&T) -> (&str, &str)) -> Page<T> {
+        match self.sort {
+            Some(SortKey::Id) => items.sort_by(|a, b| key(a).1.cmp(key(b).1)),
+            Some(SortKey::Name) => items.sort_by(|a, b| key(a).cmp(&key(b))),
+            None => {}
+        }
+
+        let total = items.len();
+        let items = items
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect();
+        Page {
+            items,
+            offset: self.offset,
+            total,
+            paged: self.limit.is_some() || self.offset > 0,
+        }
+    }
+}
+
+/// A single page of the listed entities
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// The number of the matched entities skipped before this page
+    pub offset: usize,
+    /// The number of all the matched entities, not only the ones on this page
+    pub total: usize,
+    paged: bool,
+}
+
+impl<T> Page<T> {
+    /// Checks whether there are more entities after this page
+    pub fn has_more(&self) -> bool {
+        self.offset + self.items.len() < self.total
+    }
+
+    /// Describes the position of the page, e.g. `# 41-60 of 153`. The description starts with
+    /// `#`, so it can't be confused with the ids. There is no description if all the matched
+    /// entities were requested.
+    pub fn footer(&self) -> Option<String> {
+        if !self.paged {
+            return None;
+        }
+        Some(match self.items.len() {
+            0 => format!("# nothing at offset {} of {}", self.offset, self.total),
+            len => format!(
+                "# {}-{} of {}",
+                self.offset + 1,
+                self.offset + len,
+                self.total
+            ),
+        })
+    }
+
+    pub fn map<R>(self, f: impl FnMut(T) -> R) -> Page<R> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            offset: self.offset,
+            total: self.total,
+            paged: self.paged,
+        }
+    }
+}
+
+/// Writes the entities line by line followed by the [Page::footer]
+impl<T: Display> Display for Page<T> {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        let mut lines: Vec<String> = self.items.iter().map(T::to_string).collect();
+        lines.extend(self.footer());
+        write!(formatter, "{}", lines.join("\n"))
+    }
+}
+
+pub trait ListFunctions {
+    fn query_homes(&self, query: &ListQuery) -> Result<Page<Home>>;
+    fn query_rooms(&self, query: &ListQuery) -> Result<Page<Room>>;
+    fn query_devices(&self, query: &ListQuery) -> Result<Page<Device>>;
+}
+
+impl ListFunctions for SmartHomeManager {
+    fn query_homes(&self, query: &ListQuery) -> Result<Page<Home>> {
+        let homes = self
+            .with_state(|state| {
+                state
+                    .homes()
+                    .iter()
+                    .filter(|home| query.selector.matches(&home.labels))
+                    .cloned()
+                    .collect()
+            })
+            .map_err(|msg| anyhow!(format!("Unable list of homes: {msg}")))?;
+
+        Ok(query.page(homes, |home: &Home| (&home.name, &home.id)))
+    }
+
+    fn query_rooms(&self, query: &ListQuery) -> Result<Page<Room>> {
+        let rooms = self
+            .with_state(|state| {
+                state
+                    .homes()
+                    .iter()
+                    .filter(|home| query.home_id.as_ref().is_none_or(|id| *id == home.id))
+                    .flat_map(|home| home.rooms.iter())
+                    .filter(|room| query.selector.matches(&room.labels))
+                    .cloned()
+                    .collect()
+            })
+            .map_err(|msg| anyhow!(format!("Unable list of rooms: {msg}")))?;
+
+        Ok(query.page(rooms, |room: &Room| (&room.name, &room.id)))
+    }
+
+    fn query_devices(&self, query: &ListQuery) -> Result<Page<Device>> {
+        let devices = self
+            .with_state(|state| {
+                state
+                    .homes()
+                    .iter()
+                    .filter(|home| query.home_id.as_ref().is_none_or(|id| *id == home.id))
+                    .flat_map(|home| home.rooms.iter())
+                    .filter(|room| query.room_id.as_ref().is_none_or(|id| *id == room.id))
+                    .flat_map(|room| room.devices.iter())
+                    .filter(|device| matches_device(query, device))
+                    .cloned()
+                    .collect()
+            })
+            .map_err(|msg| anyhow!(format!("Unable list of devices: {msg}")))?;
+
+        Ok(query.page(devices, |device: &Device| (device.name(), device.id())))
+    }
+}
+
+fn matches_device(query: &ListQuery, device: &Device) -> bool {
+    query.device_type.is_none_or(|t| t == device.device_type())
+        && query.status.is_none_or(|on| on == device.state().on)
+        && query.selector.matches(device.labels())
+}
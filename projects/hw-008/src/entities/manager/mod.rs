@@ -1,11 +1,56 @@
+mod backup_functions;
+mod check_functions;
 mod create_functions;
+mod energy_functions;
+mod events;
+mod exchange_functions;
 mod find_functions;
+mod history_functions;
+mod indexed_state;
+mod journal;
+mod label_functions;
+mod light_functions;
+mod list_functions;
+mod plan_functions;
 mod remove_functions;
 mod smart_home;
+mod store;
+mod thermostat_functions;
+mod transaction_functions;
+mod trash_functions;
+mod undo_functions;
 mod update_functions;
 
+pub use backup_functions::BackupFunctions;
+pub use check_functions::{CheckFunctions, CheckReport, Problem};
 pub use create_functions::CreateFunctions;
-pub use find_functions::FindFunctions;
+pub use energy_functions::{EnergyFunctions, EnergyUsage};
+pub use events::SmartHomeEvent;
+pub use exchange_functions::{
+    Change, ChangedEntity, DataFormat, ExchangeFunctions, ImportMode, ImportSummary,
+};
+pub use find_functions::{FindFunctions, PathMatch};
+pub use history_functions::HistoryFunctions;
+pub use journal::{
+    current_actor, parse_timestamp, set_current_actor, EntryKind, JournalEntry, Snapshot,
+    SNAPSHOT_INTERVAL,
+};
+pub use label_functions::LabelFunctions;
+pub use light_functions::{LightFunctions, LightSettings};
+pub use list_functions::{ListFunctions, ListQuery, Page, SortKey};
+pub use plan_functions::{
+    Action, DeviceSpec, HomeSpec, HouseSpec, Plan, PlanFunctions, PlannedChange, RoomSpec,
+};
 pub use remove_functions::RemoveFunctions;
-pub use smart_home::SmartHomeManager;
+pub use smart_home::{SavedSmartHome, SmartHomeManager};
+pub use store::{
+    find_repository, open_store, repository_root, Backup, BackupFile, Backups, InMemoryStore,
+    JsonFileStore, Migration, MigrationPlan, Revision, SchemaVersion, SqliteStore, StateStore,
+    StoreError, StoreType, VersionedState, BACKUP_DIR, DEFAULT_BACKUP_KEEP, JOURNAL_LOG,
+    MIGRATIONS, REPO_ENV, SCHEMA_VERSION,
+};
+pub use thermostat_functions::{ThermostatFunctions, ThermostatSettings, TARGET_RANGE};
+pub use transaction_functions::{Transaction, TransactionFunctions};
+pub use trash_functions::{TrashEntry, TrashFunctions, Trashed, TRASH_RETENTION_DAYS};
+pub use undo_functions::UndoFunctions;
 pub use update_functions::UpdateFunctions;
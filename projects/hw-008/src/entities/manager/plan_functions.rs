@@ -0,0 +1,514 @@
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use serde_derive::Deserialize;
+
+use crate::entities::devices::{Device, DeviceType};
+use crate::entities::house::{Home, Room};
+use crate::entities::manager::events::SmartHomeEvent;
+use crate::entities::manager::exchange_functions::DataFormat;
+use crate::entities::manager::indexed_state::IndexedState;
+use crate::entities::manager::smart_home::SmartHomeManager;
+use crate::entities::manager::thermostat_functions::unlinking_thermostats;
+use crate::entities::{format_labels, generate_id, Labels, NamingRules};
+
+/// The desired state of the smart home described in a file, e.g.
+///
+/// ```yaml
+/// homes:
+///   - name: My Home
+///     rooms:
+///       - name: Kitchen
+///         description: The one with the fridge
+///         devices:
+///           - name: Kettle
+///             type: socket
+///             labels:
+///               critical: ""
+/// ```
+///
+/// The entities are matched with the stored ones by their names, so the file never mentions
+/// the ids. The omitted description means no description, while the omitted labels mean the
+/// labels are not managed by the file and are kept as they are.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HouseSpec {
+    #[serde(default)]
+    pub homes: Vec<HomeSpec>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HomeSpec {
+    pub name: String,
+    pub description: Option<String>,
+    pub labels: Option<Labels>,
+    #[serde(default)]
+    pub rooms: Vec<RoomSpec>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoomSpec {
+    pub name: String,
+    pub description: Option<String>,
+    pub labels: Option<Labels>,
+    #[serde(default)]
+    pub devices: Vec<DeviceSpec>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceSpec {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub device_type: DeviceType,
+    pub description: Option<String>,
+    pub labels: Option<Labels>,
+}
+
+impl HouseSpec {
+    /// Reads the description of the smart home in the yaml, json or toml format
+    pub fn parse(text: &str, format: DataFormat) -> Result<Self> {
+        let spec: Self = match format {
+            DataFormat::Yaml => serde_yaml::from_str(text)?,
+            DataFormat::Json => serde_json::from_str(text)?,
+            DataFormat::Toml => toml::from_str(text)?,
+            DataFormat::Csv => return Err(anyhow!("The csv format can't describe the homes")),
+        };
+        spec.check()?;
+        Ok(spec)
+    }
+
+    /// The names are the only way to match the entities, so they must be unique among the
+    /// siblings
+    fn check(&self) -> Result<()> {
+        check_names("", self.homes.iter().map(|home| home.name.as_str()))?;
+        for home in &self.homes {
+            check_names(&home.name, home.rooms.iter().map(|room| room.name.as_str()))?;
+            for room in &home.rooms {
+                let path = format!("{}/{}", home.name, room.name);
+                check_names(
+                    &path,
+                    room.devices.iter().map(|device| device.name.as_str()),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_names<'a>(parent: &str, names: impl Iterator<Item = &'a str>) -> Result<()> {
+    let mut seen = BTreeSet::new();
+    for name in names {
+        if name.trim().is_empty() {
+            return Err(anyhow!("The empty name in {}", join(parent, "")));
+        }
+        if !seen.insert(name) {
+            return Err(anyhow!("The name {} is used twice", join(parent, name)));
+        }
+    }
+    Ok(())
+}
+
+/// What the plan does with a single entity
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Action {
+    Create,
+    Update,
+    /// The device of another type is removed, and the new one is created instead
+    Replace,
+    Remove,
+}
+
+impl Display for Action {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Action::Create => write!(formatter, "+"),
+            Action::Update => write!(formatter, "~"),
+            Action::Replace => write!(formatter, "-/+"),
+            Action::Remove => write!(formatter, "-"),
+        }
+    }
+}
+
+/// A single step of the [Plan]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedChange {
+    pub action: Action,
+    /// Either `home`, `room` or `device`
+    pub kind: &'static str,
+    /// The path of the entity made of the names, e.g. `My Home/Kitchen/Kettle`
+    pub path: String,
+    /// What exactly is changed, e.g. `description: none -> "The one with the fridge"`
+    pub details: Vec<String>,
+}
+
+/// The changes needed to bring the smart home to the state described by the [HouseSpec]. The
+/// removed home or room takes all its content with it, so its content is not listed.
+#[derive(Debug, Clone, Default)]
+pub struct Plan {
+    pub changes: Vec<PlannedChange>,
+    events: Vec<SmartHomeEvent>,
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn count(&self, action: Action) -> usize {
+        self.changes.iter().filter(|c| c.action == action).count()
+    }
+
+    fn push(&mut self, action: Action, kind: &'static str, path: String, details: Vec<String>) {
+        self.changes.push(PlannedChange {
+            action,
+            kind,
+            path,
+            details,
+        });
+    }
+}
+
+/// Writes a change per line, e.g. `~ room My Home/Kitchen`, with the details indented below,
+/// followed by the totals
+impl Display for Plan {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        if self.is_empty() {
+            return write!(
+                formatter,
+                "No changes, the smart home matches the description"
+            );
+        }
+
+        for change in &self.changes {
+            writeln!(
+                formatter,
+                "{} {} {}",
+                change.action, change.kind, change.path
+            )?;
+            for detail in &change.details {
+                writeln!(formatter, "    {detail}")?;
+            }
+        }
+        let replaced = self.count(Action::Replace);
+        write!(
+            formatter,
+            "Plan: {} to create, {} to update, {} to remove",
+            self.count(Action::Create) + replaced,
+            self.count(Action::Update),
+            self.count(Action::Remove) + replaced
+        )
+    }
+}
+
+pub trait PlanFunctions {
+    /// Compares the smart home with the description and returns the changes needed to make
+    /// them match. Nothing is changed.
+    fn plan(&self, spec: &HouseSpec) -> Result<Plan>;
+
+    /// Makes the smart home match the description: creates, updates and removes the entities.
+    /// The changes are journaled as a single change, so a single `undo` reverts them. Applying
+    /// the same description once again changes nothing. Returns the applied plan.
+    fn apply(&self, spec: &HouseSpec) -> Result<Plan>;
+}
+
+impl PlanFunctions for SmartHomeManager {
+    fn plan(&self, spec: &HouseSpec) -> Result<Plan> {
+        self.with_state(|state| Planner::new(state, self.naming_rules()).plan(spec))?
+    }
+
+    fn apply(&self, spec: &HouseSpec) -> Result<Plan> {
+        self.commit(|state| {
+            let plan = Planner::new(state, self.naming_rules()).plan(spec)?;
+            // the same as the removals of the users, the removed sockets are metered up to now,
+            // and the removed or replaced devices are unlinked from the thermostats
+            let now = Utc::now();
+            let events = plan.events.iter().map(|event| event.restamped(now));
+            let events = unlinking_thermostats(state, events.collect())?;
+            Ok((events, plan))
+        })
+        .map_err(|msg| anyhow!("Unable apply the description: {msg}"))
+    }
+}
+
+/// Builds the plan against the given state. The ids of the new entities are unique both among
+/// the stored entities and among the entities created by the plan.
+struct Planner<'a> {
+    state: &'a IndexedState,
+    rules: &'a NamingRules,
+    plan: Plan,
+    new_ids: BTreeSet<String>,
+}
+
+impl<'a> Planner<'a> {
+    fn new(state: &'a IndexedState, rules: &'a NamingRules) -> Self {
+        Self {
+            state,
+            rules,
+            plan: Plan::default(),
+            new_ids: BTreeSet::new(),
+        }
+    }
+
+    fn plan(mut self, spec: &HouseSpec) -> Result<Plan> {
+        let homes = self.state.homes();
+        for home_spec in &spec.homes {
+            match find(homes, &home_spec.name, |home| &home.name, "")? {
+                Some(home) => self.update_home(home, home_spec)?,
+                None => {
+                    let home = self.new_home(home_spec)?;
+                    self.plan.events.push(SmartHomeEvent::HomeCreated { home });
+                }
+            }
+        }
+        for home in homes {
+            if !spec.homes.iter().any(|h| h.name == home.name) {
+                self.plan
+                    .push(Action::Remove, "home", home.name.clone(), vec![]);
+                let home = home.clone();
+                self.plan.events.push(SmartHomeEvent::HomeRemoved { home });
+            }
+        }
+        Ok(self.plan)
+    }
+
+    fn update_home(&mut self, home: &Home, spec: &HomeSpec) -> Result<()> {
+        let details = self.edit(
+            &home.id,
+            (&home.name, &home.description, &home.labels),
+            (&spec.description, &spec.labels),
+        );
+        if !details.is_empty() {
+            self.plan
+                .push(Action::Update, "home", home.name.clone(), details);
+        }
+
+        for room_spec in &spec.rooms {
+            match find(&home.rooms, &room_spec.name, |room| &room.name, &home.name)? {
+                Some(room) => self.update_room(&home.name, room, room_spec)?,
+                None => {
+                    let room = self.new_room(&home.name, room_spec)?;
+                    let home_id = home.id.clone();
+                    self.plan
+                        .events
+                        .push(SmartHomeEvent::RoomCreated { home_id, room });
+                }
+            }
+        }
+        for room in &home.rooms {
+            if !spec.rooms.iter().any(|r| r.name == room.name) {
+                let path = format!("{}/{}", home.name, room.name);
+                self.plan.push(Action::Remove, "room", path, vec![]);
+                self.plan.events.push(SmartHomeEvent::RoomRemoved {
+                    home_id: home.id.clone(),
+                    room: room.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn update_room(&mut self, parent: &str, room: &Room, spec: &RoomSpec) -> Result<()> {
+        let path = format!("{parent}/{}", room.name);
+        let details = self.edit(
+            &room.id,
+            (&room.name, &room.description, &room.labels),
+            (&spec.description, &spec.labels),
+        );
+        if !details.is_empty() {
+            self.plan
+                .push(Action::Update, "room", path.clone(), details);
+        }
+
+        for device_spec in &spec.devices {
+            let device = find(&room.devices, &device_spec.name, |d| d.name(), &path)?;
+            let device_path = format!("{path}/{}", device_spec.name);
+            match device {
+                Some(device) if device.device_type() != device_spec.device_type => {
+                    let details = vec![format!(
+                        "type: {} -> {}",
+                        device.device_type(),
+                        device_spec.device_type
+                    )];
+                    self.plan
+                        .push(Action::Replace, "device", device_path, details);
+                    self.plan.events.push(SmartHomeEvent::DeviceRemoved {
+                        room_id: room.id.clone(),
+                        device: device.clone(),
+                    });
+                    let device = self.build_device(device_spec)?;
+                    self.plan.events.push(SmartHomeEvent::DeviceCreated {
+                        room_id: room.id.clone(),
+                        device,
+                    });
+                }
+                Some(device) => {
+                    let description = device.description().map(str::to_string);
+                    let details = self.edit(
+                        device.id(),
+                        (device.name(), &description, device.labels()),
+                        (&device_spec.description, &device_spec.labels),
+                    );
+                    if !details.is_empty() {
+                        self.plan
+                            .push(Action::Update, "device", device_path, details);
+                    }
+                }
+                None => {
+                    let device = self.new_device(&path, device_spec)?;
+                    self.plan.events.push(SmartHomeEvent::DeviceCreated {
+                        room_id: room.id.clone(),
+                        device,
+                    });
+                }
+            }
+        }
+        for device in &room.devices {
+            if !spec.devices.iter().any(|d| d.name == device.name()) {
+                let device_path = format!("{path}/{}", device.name());
+                self.plan
+                    .push(Action::Remove, "device", device_path, vec![]);
+                self.plan.events.push(SmartHomeEvent::DeviceRemoved {
+                    room_id: room.id.clone(),
+                    device: device.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Plans the edit of the description and the labels of the matched entity, the name is the
+    /// same already. Returns the details of the change, nothing if there is no change.
+    fn edit(
+        &mut self,
+        id: &str,
+        (name, description, labels): (&str, &Option<String>, &Labels),
+        (new_description, new_labels): (&Option<String>, &Option<Labels>),
+    ) -> Vec<String> {
+        let mut details = vec![];
+        if description != new_description {
+            details.push(format!(
+                "description: {} -> {}",
+                quoted(description),
+                quoted(new_description)
+            ));
+            self.plan.events.push(SmartHomeEvent::EntityEdited {
+                id: id.to_string(),
+                name: name.to_string(),
+                description: new_description.clone(),
+            });
+        }
+        if let Some(new_labels) = new_labels.as_ref().filter(|l| *l != labels) {
+            details.push(format!(
+                "labels: [{}] -> [{}]",
+                format_labels(labels),
+                format_labels(new_labels)
+            ));
+            self.plan.events.push(SmartHomeEvent::LabelsChanged {
+                id: id.to_string(),
+                labels: new_labels.clone(),
+            });
+        }
+        details
+    }
+
+    fn new_home(&mut self, spec: &HomeSpec) -> Result<Home> {
+        self.plan
+            .push(Action::Create, "home", spec.name.clone(), vec![]);
+
+        let mut builder = Home::build().with_name(&spec.name);
+        if let Some(description) = &spec.description {
+            builder = builder.with_description(description);
+        }
+        let mut home = builder.with_rules(self.rules.clone()).build()?;
+        home.id = self.unique_id(home.id, "home");
+        home.labels = spec.labels.clone().unwrap_or_default();
+
+        for room_spec in &spec.rooms {
+            let room = self.new_room(&spec.name, room_spec)?;
+            home.rooms.push(room);
+        }
+        Ok(home)
+    }
+
+    fn new_room(&mut self, parent: &str, spec: &RoomSpec) -> Result<Room> {
+        let path = format!("{parent}/{}", spec.name);
+        self.plan.push(Action::Create, "room", path.clone(), vec![]);
+
+        let mut builder = Room::build().with_name(&spec.name);
+        if let Some(description) = &spec.description {
+            builder = builder.with_description(description);
+        }
+        let mut room = builder.with_rules(self.rules.clone()).build()?;
+        room.id = self.unique_id(room.id, "room");
+        room.labels = spec.labels.clone().unwrap_or_default();
+
+        for device_spec in &spec.devices {
+            let device = self.new_device(&path, device_spec)?;
+            room.devices.push(device);
+        }
+        Ok(room)
+    }
+
+    fn new_device(&mut self, parent: &str, spec: &DeviceSpec) -> Result<Device> {
+        let path = format!("{parent}/{}", spec.name);
+        self.plan.push(Action::Create, "device", path, vec![]);
+        self.build_device(spec)
+    }
+
+    fn build_device(&mut self, spec: &DeviceSpec) -> Result<Device> {
+        self.rules.check_name(&spec.name)?;
+        let mut device = spec
+            .device_type
+            .create(&spec.name, spec.description.as_deref())?;
+        let id = self.unique_id(device.id().clone(), device.id_prefix());
+        device.set_id(id);
+        *device.labels_mut() = spec.labels.clone().unwrap_or_default();
+        Ok(device)
+    }
+
+    fn unique_id(&mut self, id: String, prefix: &str) -> String {
+        let mut id = self.state.unique_id(id, prefix);
+        while !self.new_ids.insert(id.clone()) {
+            id = self.state.unique_id(generate_id(prefix), prefix);
+        }
+        id
+    }
+}
+
+/// Finds the sibling with the given name. The stored siblings might share the name, but then
+/// the description can't tell which one is meant.
+fn find<'s, T>(
+    siblings: &'s [T],
+    name: &str,
+    name_of: impl Fn(&T) -> &str,
+    parent: &str,
+) -> Result<Option<&'s T>> {
+    let mut matched = siblings.iter().filter(|s| name_of(s) == name);
+    let found = matched.next();
+    if found.is_some() && matched.next().is_some() {
+        return Err(anyhow!(
+            "Several entities are named {}, rename them to make the names unique",
+            join(parent, name)
+        ));
+    }
+    Ok(found)
+}
+
+fn join(parent: &str, name: &str) -> String {
+    match parent {
+        "" => name.to_string(),
+        parent => format!("{parent}/{name}"),
+    }
+}
+
+fn quoted(text: &Option<String>) -> String {
+    match text {
+        Some(text) => format!("{text:?}"),
+        None => "none".to_string(),
+    }
+}
@@ -1,11 +1,21 @@
 use crate::entities::devices::DeviceId;
 use anyhow::{anyhow, Result};
+use chrono::Utc;
 
-use crate::entities::house::{Home, HomeId, RoomId};
+use crate::entities::house::{HomeId, RoomId};
+use crate::entities::manager::events::SmartHomeEvent;
 use crate::entities::manager::smart_home::SmartHomeManager;
-use crate::entities::manager::{FindFunctions, UpdateFunctions};
+use crate::entities::manager::thermostat_functions::unlinking_thermostats;
+use crate::entities::manager::trash_functions::Trashed;
 
+/// The removed entities are moved to the trash together with all their content, see
+/// [TrashFunctions](crate::entities::manager::TrashFunctions). The removed devices are unlinked
+/// from the thermostats, the undo of the removal links them back.
 pub trait RemoveFunctions {
+    /// Returns the home, room or device with the given id together with everything the removal
+    /// would take with it. Nothing is removed.
+    fn removal_impact(&self, id: &str) -> Result<Trashed>;
+
     fn remove_home(&self, id: &HomeId) -> Result<HomeId>;
 
     fn remove_room(&self, id: &RoomId) -> Result<RoomId>;
@@ -14,61 +24,70 @@ pub trait RemoveFunctions {
 }
 
 impl RemoveFunctions for SmartHomeManager {
-    fn remove_home(&self, id: &HomeId) -> Result<HomeId> {
-        let current_state = self.read_smart_home_status();
+    fn removal_impact(&self, id: &str) -> Result<Trashed> {
+        let id = id.to_string();
+        self.with_state(|state| {
+            if let Some(home) = state.home(&id) {
+                Some(Trashed::Home(home.clone()))
+            } else if let (Some(home), Some(room)) = (state.home_by_room_id(&id), state.room(&id)) {
+                Some(Trashed::Room {
+                    home_id: home.id.clone(),
+                    room: room.clone(),
+                })
+            } else if let (Some(room), Some(device)) =
+                (state.room_by_device_id(&id), state.device(&id))
+            {
+                Some(Trashed::Device {
+                    room_id: room.id.clone(),
+                    device: device.clone(),
+                })
+            } else {
+                None
+            }
+        })?
+        .ok_or_else(|| anyhow!("Entity with id: {id} not found"))
+    }
 
-        match current_state {
-            Ok(state) => {
-                if let Some(homes) = state {
-                    let new_state: Vec<Home> = homes.into_iter().filter(|h| h.id != *id).collect();
-                    match self.update_state(Some(new_state)) {
-                        Ok(_) => Ok(id.clone()),
-                        Err(msg) => Err(anyhow!(format!("Unable to save changes: {msg}"))),
-                    }
-                } else {
-                    Err(anyhow!(format!("Home {id} not found")))
-                }
+    fn remove_home(&self, id: &HomeId) -> Result<HomeId> {
+        self.commit(|state| match state.home(id) {
+            Some(home) => {
+                let event = SmartHomeEvent::HomeRemoved { home: home.clone() };
+                let events = unlinking_thermostats(state, vec![event.restamped(Utc::now())])?;
+                Ok((events, id.clone()))
             }
-            Err(msg) => Err(anyhow!(format!("Unable remove home: {msg}"))),
-        }
+            None => Err(anyhow!(format!("Home {id} not found"))),
+        })
     }
 
     fn remove_room(&self, id: &RoomId) -> Result<RoomId> {
-        match self.find_home_by_room_id(id) {
-            None => Err(anyhow!(format!("Home not found for room {id}"))),
-            Some(mut home) => {
-                home.rooms.retain(|r| r.id != *id);
-
-                match self.update_home_state(home) {
-                    Ok(_) => Ok(id.clone()),
-                    Err(msg) => Err(anyhow!(format!("Unable to save changes: {msg}"))),
-                }
+        self.commit(|state| match (state.home_by_room_id(id), state.room(id)) {
+            (Some(home), Some(room)) => {
+                let event = SmartHomeEvent::RoomRemoved {
+                    home_id: home.id.clone(),
+                    room: room.clone(),
+                };
+                let events = unlinking_thermostats(state, vec![event.restamped(Utc::now())])?;
+                Ok((events, id.clone()))
             }
-        }
+            _ => Err(anyhow!(format!("Home not found for room {id}"))),
+        })
     }
 
     fn remove_device(&self, id: &DeviceId) -> Result<DeviceId> {
-        if let Some(mut room) = self.find_room_by_device_id(id) {
-            room.devices.retain(|d| d.id() != id);
-
-            if let Some(mut home) = self.find_home_by_room_id(&room.id) {
-                home.rooms.retain(|r| r.id != room.id);
-                home.rooms.push(room);
-
-                match self.update_home_state(home) {
-                    Ok(_) => Ok(id.clone()),
-                    Err(msg) => Err(anyhow!(format!("Unable to save changes: {msg}"))),
+        self.commit(
+            |state| match (state.room_by_device_id(id), state.device(id)) {
+                (Some(room), Some(device)) => {
+                    let event = SmartHomeEvent::DeviceRemoved {
+                        room_id: room.id.clone(),
+                        device: device.clone(),
+                    };
+                    let events = unlinking_thermostats(state, vec![event.restamped(Utc::now())])?;
+                    Ok((events, id.clone()))
                 }
-            } else {
-                Err(anyhow!(format!(
-                    "Unable find associated home for room: {}",
-                    room.id
-                )))
-            }
-        } else {
-            Err(anyhow!(format!(
-                "Unable find associated room for device: {id}"
-            )))
-        }
+                _ => Err(anyhow!(format!(
+                    "Unable find associated room for device: {id}"
+                ))),
+            },
+        )
     }
 }
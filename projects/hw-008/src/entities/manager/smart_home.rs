@@ -1,139 +1,267 @@
-use std::fs;
 use std::fs::File;
 use std::io::BufReader;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
 use anyhow::{anyhow, Result};
+use serde_json::Value;
 
-use crate::entities::devices::{Device, DeviceId};
+use crate::entities::devices::{Device, DeviceId, Reading};
 use crate::entities::house::{Home, Room};
-use crate::entities::manager::FindFunctions;
-use crate::entities::Measure;
+use crate::entities::manager::events::SmartHomeEvent;
+use crate::entities::manager::indexed_state::IndexedState;
+use crate::entities::manager::journal::{EntryKind, Journal, JournalEntry};
+use crate::entities::manager::store::{
+    decode_state, detect_version, JsonFileStore, MigrationPlan, StateStore, StoreError,
+};
+use crate::entities::NamingRules;
 
-const SMART_HOME_FILE: &str = "smart-home.json";
-
-const REPO_DIR: &str = ".smart-home";
-
-pub(crate) type SavedSmartHome = Option<Vec<Home>>;
+pub type SavedSmartHome = Option<Vec<Home>>;
 
 pub struct SmartHomeManager {
-    path: PathBuf,
+    store: Arc<dyn StateStore>,
+    /// The loaded state with the indexes for fast lookups. It's loaded lazily on the first
+    /// access and reloaded each time the stored revision differs from the cached one. All the
+    /// changes made by the manager are written through to the store, and they are based on the
+    /// cached revision, so the store will reject them in case the state was changed by someone
+    /// else in between.
+    cache: Mutex<Option<IndexedState>>,
+    /// The rules the names of the created and renamed entities are checked by
+    rules: NamingRules,
 }
 
 impl SmartHomeManager {
+    /// Creates a manager for the json file repository located in the given directory
     pub fn new(path: PathBuf) -> Self {
-        Self { path }
+        Self::with_store(Arc::new(JsonFileStore::new(path)))
     }
 
-    pub fn initialize_smart_home(&self) -> Result<()> {
-        if !self.path.exists() {
-            fs::create_dir(self.path.clone()).expect("Unable create repository");
-
-            let mut path = self.path.clone();
-            path.push(REPO_DIR);
-            path.push(SMART_HOME_FILE);
-
-            let init: SavedSmartHome = None;
-            let content = serde_json::to_string(&init).unwrap();
-
-            fs::write(path, content).expect("Unable write initial data");
-            Ok(())
-        } else {
-            Err(anyhow!(format!(
-                "Path: {} is not empty",
-                self.path.display()
-            )))
+    /// Creates a manager on top of any [StateStore] implementation. The store might be shared
+    /// between several managers, e.g. one manager per each client session of the server.
+    pub fn with_store(store: Arc<dyn StateStore>) -> Self {
+        Self {
+            store,
+            cache: Mutex::new(None),
+            rules: NamingRules::default(),
         }
     }
 
-    pub fn get_state_file(&self) -> Result<PathBuf> {
-        let mut current_dir = self.path.clone();
-        current_dir.push(REPO_DIR);
-        current_dir.push(SMART_HOME_FILE);
-        Ok(current_dir)
+    /// Replaces the [NamingRules::default] the names of the new and renamed entities are checked
+    /// by. The entities already stored are not checked again.
+    pub fn with_naming_rules(self, rules: NamingRules) -> Self {
+        Self { rules, ..self }
+    }
+
+    pub fn naming_rules(&self) -> &NamingRules {
+        &self.rules
+    }
+
+    pub fn initialize_smart_home(&self) -> Result<()> {
+        self.store.initialize()
     }
 
     pub fn is_smart_home_repo_exists(&self) -> bool {
-        self.path.exists()
+        self.store.is_initialized()
     }
 
     pub fn read_smart_home_status(&self) -> Result<SavedSmartHome> {
-        let mut current_dir = self.path.clone();
-
-        current_dir.push(REPO_DIR);
-        current_dir.push(SMART_HOME_FILE);
+        self.with_state(|state| state.state().clone())
+    }
 
-        let path = current_dir.as_path();
+    /// Rewrites the stored state in the current format. The older formats are readable anyway,
+    /// but they are written in the current format only with the next change, so the migration
+    /// might be done in advance. Nothing is written in the `dry_run` mode, the migration is run
+    /// on a copy of the stored document instead, so the plan tells how many devices each step
+    /// would rewrite.
+    pub fn migrate(&self, dry_run: bool) -> Result<MigrationPlan> {
+        if !self.store.is_initialized() {
+            return Err(StoreError::NotInitialized.into());
+        }
 
-        let file = File::open(path)?;
-        let reader = BufReader::new(file);
+        let plan = MigrationPlan::new(self.store.schema_version()?)?;
+        if dry_run {
+            return Ok(plan.preview(self.store.load_document()?)?);
+        }
+        if !plan.is_empty() {
+            let event = SmartHomeEvent::SchemaMigrated {
+                from: plan.from,
+                to: plan.to,
+            };
+            self.commit(|_| Ok((vec![event], ())))?;
+        }
+        Ok(plan)
+    }
 
-        let state: SavedSmartHome = serde_json::from_reader(reader)
-            .map_err(|e| e.to_string())
-            .expect("Unable deserialize the smart-home state");
+    /// Replaces the whole smart home with the state from the given file. The file might be of
+    /// any known format, e.g. the state file written by the hw-006 or hw-007 crates.
+    pub fn import_state(&self, path: &Path) -> Result<MigrationPlan> {
+        let file = File::open(path)
+            .map_err(|e| anyhow!("Unable open the file {}: {e}", path.display()))?;
+        let document: Value = serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| StoreError::MalformedState(e.to_string()))?;
 
-        Ok(state)
+        let plan = MigrationPlan::new(detect_version(&document)?)?;
+        let imported = decode_state(document)?;
+        self.commit(|_| {
+            let event = SmartHomeEvent::StateReplaced {
+                state: imported.state,
+            };
+            Ok((vec![event], ()))
+        })?;
+        Ok(plan)
     }
 
-    pub fn make_measure(&self, device_id: &DeviceId) -> Result<String> {
-        match self.find_device_by_id(device_id) {
-            None => Err(anyhow!("Not found")),
-            Some(device) => match device {
-                Device::Socket(_) => Err(anyhow!("Not supported")),
-                Device::Thermometer(th) => match th.measure() {
-                    Ok(measurement) => match measurement {
-                        None => Err(anyhow!("N/A")),
-                        Some(v) => Ok(v.to_string()),
-                    },
-                    Err(msg) => Err(anyhow!(msg.to_string())),
-                },
-            },
+    /// Reloads the cached state if the stored one has a different revision. The freshly loaded
+    /// state is brought up to date with the journal, see [Journal::catch_up].
+    fn refresh(&self, cache: &mut Option<IndexedState>) -> Result<()> {
+        if !self.store.is_initialized() {
+            return Err(StoreError::NotInitialized.into());
+        }
+
+        let revision = self.store.revision()?;
+        if cache.as_ref().map(|c| c.revision()) != Some(revision) {
+            let loaded = IndexedState::new(self.store.load()?);
+            *cache = Some(self.journal().catch_up(loaded)?);
         }
+        Ok(())
     }
 
-    pub fn list_all_devices(&self) -> Result<Vec<Device>> {
-        match self.read_smart_home_status() {
-            Ok(state) => {
-                let mut devices: Vec<Device> = vec![];
-                if let Some(homes) = state {
-                    for home in homes {
-                        for room in home.rooms {
-                            for device in room.devices {
-                                devices.push(device.clone())
-                            }
-                        }
-                    }
-                }
-                Ok(devices)
+    /// Gives a read access to the up-to-date state
+    pub(crate) fn with_state<R>(&self, f: impl FnOnce(&IndexedState) -> R) -> Result<R> {
+        let mut cache = self.cache.lock().unwrap();
+        self.refresh(&mut cache)?;
+
+        // It's safe to unwrap, the cache is always populated by `refresh` function
+        Ok(f(cache.as_ref().unwrap()))
+    }
+
+    /// Builds the events of a change from the up-to-date state, applies them to a copy of the
+    /// state and writes the result through to the store together with the journal entry. The
+    /// cached state is replaced only if the store accepted the changes, so a failed change
+    /// leaves no traces. A change without any events is not written at all.
+    pub(crate) fn commit<R>(
+        &self,
+        f: impl FnOnce(&IndexedState) -> Result<(Vec<SmartHomeEvent>, R)>,
+    ) -> Result<R> {
+        self.commit_as(EntryKind::Change, f)
+    }
+
+    /// The same as [SmartHomeManager::commit], but the change is journaled with the given kind
+    pub(crate) fn commit_as<R>(
+        &self,
+        kind: EntryKind,
+        f: impl FnOnce(&IndexedState) -> Result<(Vec<SmartHomeEvent>, R)>,
+    ) -> Result<R> {
+        self.commit_journaled(|state| f(state).map(|(events, result)| (kind, events, result)))
+    }
+
+    /// The same as [SmartHomeManager::commit_as], but the kind of the change is chosen together
+    /// with its events under the same lock, e.g. the undo picks the change to revert from the
+    /// journal, so no other change might slip in between
+    pub(crate) fn commit_journaled<R>(
+        &self,
+        f: impl FnOnce(&IndexedState) -> Result<(EntryKind, Vec<SmartHomeEvent>, R)>,
+    ) -> Result<R> {
+        let mut cache = self.cache.lock().unwrap();
+        self.refresh(&mut cache)?;
+        self.write_through(&mut cache, f)
+    }
+
+    /// The same as [SmartHomeManager::commit], but instead of refreshing the stale state it
+    /// fails with [StoreError::ConcurrentModification]. It's used when the changes were
+    /// prepared by the caller from the entities read from this manager earlier.
+    pub(crate) fn commit_checked<R>(
+        &self,
+        f: impl FnOnce(&IndexedState) -> Result<(Vec<SmartHomeEvent>, R)>,
+    ) -> Result<R> {
+        let mut cache = self.cache.lock().unwrap();
+
+        if let Some(cached) = cache.as_ref() {
+            let revision = self.store.revision()?;
+            if cached.revision() != revision {
+                let error = StoreError::ConcurrentModification(cached.revision(), revision);
+                *cache = None;
+                return Err(error.into());
             }
-            Err(msg) => Err(anyhow!(format!("Unable list of devices: {msg}"))),
         }
+
+        self.refresh(&mut cache)?;
+        self.write_through(&mut cache, |state| {
+            f(state).map(|(events, result)| (EntryKind::Change, events, result))
+        })
     }
 
-    pub fn list_all_rooms(&self) -> Result<Vec<Room>> {
-        match self.read_smart_home_status() {
-            Ok(state) => {
-                let mut rooms: Vec<Room> = vec![];
-                if let Some(homes) = state {
-                    for home in homes {
-                        for room in home.rooms {
-                            rooms.push(room.clone())
-                        }
-                    }
-                }
-                Ok(rooms)
+    fn write_through<R>(
+        &self,
+        cache: &mut Option<IndexedState>,
+        f: impl FnOnce(&IndexedState) -> Result<(EntryKind, Vec<SmartHomeEvent>, R)>,
+    ) -> Result<R> {
+        // It's safe to unwrap, the cache is always populated by `refresh` function
+        let base = cache.as_ref().unwrap();
+        let (kind, events, result) = f(base)?;
+        if events.is_empty() {
+            return Ok(result);
+        }
+
+        let mut state = base.clone();
+        let mut entry = JournalEntry::new(base.revision() + 1, events).with_kind(kind);
+        entry.apply_recording(&mut state)?;
+
+        match Journal::new(self.store.as_ref()).save(base, &state, &entry) {
+            Ok(revision) => {
+                state.set_revision(revision);
+                *cache = Some(state);
+                Ok(result)
+            }
+            Err(error) => {
+                *cache = None;
+                Err(error)
             }
-            Err(msg) => Err(anyhow!(format!("Unable list of rooms: {msg}"))),
         }
     }
 
+    /// Gives an access to the journal of the changes made in the store of this manager
+    pub(crate) fn store(&self) -> &dyn StateStore {
+        self.store.as_ref()
+    }
+
+    pub(crate) fn journal(&self) -> Journal<'_> {
+        Journal::new(self.store.as_ref())
+    }
+
+    /// Makes a measurement by the device, all the values it measures at once. The reading is
+    /// kept as the last reading of the device.
+    pub fn make_measure(&self, device_id: &DeviceId) -> Result<Reading> {
+        self.commit(|state| {
+            let device = state
+                .device(device_id)
+                .ok_or_else(|| anyhow!("Not found"))?;
+            let measurements = device.measure().map_err(|msg| anyhow!(msg.to_string()))?;
+            if measurements.is_empty() {
+                return Err(anyhow!("N/A"));
+            }
+
+            let reading = Reading::new(measurements);
+            let event = SmartHomeEvent::DeviceMeasured {
+                device_id: device_id.clone(),
+                reading: Some(reading.clone()),
+            };
+            Ok((vec![event], reading))
+        })
+    }
+
+    pub fn list_all_devices(&self) -> Result<Vec<Device>> {
+        self.with_state(|state| state.devices().cloned().collect())
+            .map_err(|msg| anyhow!(format!("Unable list of devices: {msg}")))
+    }
+
+    pub fn list_all_rooms(&self) -> Result<Vec<Room>> {
+        self.with_state(|state| state.rooms().cloned().collect())
+            .map_err(|msg| anyhow!(format!("Unable list of rooms: {msg}")))
+    }
+
     pub fn list_all_homes(&self) -> Result<Vec<Home>> {
-        match self.read_smart_home_status() {
-            Ok(state) => match state {
-                None => Ok(vec![]),
-                Some(homes) => Ok(homes),
-            },
-            Err(msg) => Err(anyhow!(format!("Unable list of rooms: {msg}"))),
-        }
+        self.with_state(|state| state.homes().to_vec())
+            .map_err(|msg| anyhow!(format!("Unable list of homes: {msg}")))
     }
 }
@@ -0,0 +1,314 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use fs2::FileExt;
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::entities::generate_id;
+use crate::entities::manager::store::json_file::{LOCK_FILE, REPO_DIR, TEMP_FILE};
+use crate::entities::manager::store::{
+    copy_database, open_store, StoreError, StoreType, SMART_HOME_DB,
+};
+use crate::entities::manager::{SavedSmartHome, SmartHomeManager};
+
+/// The directory of the backups inside the `.smart-home` directory
+pub const BACKUP_DIR: &str = "backups";
+
+/// The number of the backups kept by default, the older ones are removed by the rotation
+pub const DEFAULT_BACKUP_KEEP: usize = 10;
+
+/// The description of the backup kept next to the copied files
+const MANIFEST_FILE: &str = "backup.json";
+
+/// A copied file of the repository
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupFile {
+    /// The path of the file relative to the `.smart-home` directory
+    pub name: String,
+    pub size: u64,
+    /// The hex encoded SHA-256 of the file content
+    pub sha256: String,
+}
+
+/// A snapshot of all the files of the repository: the state, the journal and whatever else the
+/// store keeps in the `.smart-home` directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Backup {
+    /// The id like `backup_01GNNA1J00C3FE9ZJ6S2JXNZ8K`, so the backups sort by the creation time
+    pub id: String,
+    pub label: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub files: Vec<BackupFile>,
+}
+
+impl Backup {
+    pub fn size(&self) -> u64 {
+        self.files.iter().map(|file| file.size).sum()
+    }
+}
+
+/// A one-line description, e.g.
+/// `backup_01GN... 2023-01-31 12:00:00 UTC, 2 file(s), 1024 bytes, before cleanup`
+impl Display for Backup {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            formatter,
+            "{} {}, {} file(s), {} bytes",
+            self.id,
+            self.created_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            self.files.len(),
+            self.size()
+        )?;
+        if let Some(label) = &self.label {
+            write!(formatter, ", {label}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The backups of the repository located in the `root` directory. Each backup is a directory in
+/// `.smart-home/backups` with the copies of the repository files and the manifest with their
+/// checksums, so the damaged backup is never restored. Only the latest [DEFAULT_BACKUP_KEEP]
+/// backups are kept, unless another number is given by [Backups::keep].
+///
+/// The backups work with the stores keeping their data in files only, so there are no backups
+/// of the [StoreType::Memory] store.
+#[derive(Debug, Clone)]
+pub struct Backups {
+    root: PathBuf,
+    store_type: StoreType,
+    keep: usize,
+}
+
+impl Backups {
+    pub fn new(root: PathBuf, store_type: StoreType) -> Self {
+        Self {
+            root,
+            store_type,
+            keep: DEFAULT_BACKUP_KEEP,
+        }
+    }
+
+    /// Sets the number of the backups kept by the rotation. Zero means no rotation at all.
+    pub fn keep(self, keep: usize) -> Self {
+        Self { keep, ..self }
+    }
+
+    fn repo_dir(&self) -> PathBuf {
+        self.root.join(REPO_DIR)
+    }
+
+    fn backup_dir(&self) -> PathBuf {
+        self.repo_dir().join(BACKUP_DIR)
+    }
+
+    /// Copies all the files of the repository into a new backup and removes the oldest backups
+    /// beyond the limit. The files are copied under the lock of the repository, so the json
+    /// file store never changes them in the middle of the copy. The SQLite store doesn't take
+    /// that lock, so its database is copied by SQLite itself in a single transaction instead.
+    pub fn create(&self, label: Option<String>) -> Result<Backup> {
+        if self.store_type == StoreType::Memory {
+            return Err(anyhow!("The in-memory store has no files to back up"));
+        }
+        let repo_dir = self.repo_dir();
+        if !repo_dir.is_dir() {
+            return Err(StoreError::NotInitialized.into());
+        }
+
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(repo_dir.join(LOCK_FILE))?;
+        lock.lock_exclusive()?;
+
+        let id = generate_id("backup");
+        let target = self.backup_dir().join(&id);
+        let sqlite = self.store_type == StoreType::Sqlite;
+        let copy = |from: &Path, to: &Path| match from.file_name() {
+            Some(name) if sqlite && name == SMART_HOME_DB => copy_database(from, to),
+            _ => fs::copy(from, to).map(|_| ()).map_err(Into::into),
+        };
+        let result = copy_files(&repo_dir, &target, &copy).and_then(|files| {
+            let backup = Backup {
+                id,
+                label,
+                created_at: Utc::now(),
+                files,
+            };
+            fs::write(
+                target.join(MANIFEST_FILE),
+                serde_json::to_string_pretty(&backup)?,
+            )?;
+            Ok(backup)
+        });
+        lock.unlock()?;
+
+        if result.is_err() {
+            let _ = fs::remove_dir_all(&target);
+        }
+        let backup = result.map_err(|msg| anyhow!("Unable create the backup: {msg}"))?;
+        self.rotate()?;
+        Ok(backup)
+    }
+
+    /// Lists the backups from the oldest to the latest one. The directories without the
+    /// manifest are not the backups, e.g. the ones left by a failed copy, so they are skipped.
+    pub fn list(&self) -> Result<Vec<Backup>> {
+        let dir = self.backup_dir();
+        if !dir.is_dir() {
+            return Ok(vec![]);
+        }
+
+        let mut backups = vec![];
+        for entry in fs::read_dir(dir)? {
+            let manifest = entry?.path().join(MANIFEST_FILE);
+            if let Ok(text) = fs::read_to_string(manifest) {
+                backups.push(serde_json::from_str::<Backup>(&text)?);
+            }
+        }
+        backups.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(backups)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Backup> {
+        self.list()?
+            .into_iter()
+            .find(|backup| backup.id == id)
+            .ok_or_else(|| anyhow!("Backup with id: {id} not found"))
+    }
+
+    /// Checks that all the files of the backup are in place and have the same content as they
+    /// had when the backup was taken
+    pub fn verify(&self, backup: &Backup) -> Result<()> {
+        let dir = self.backup_dir().join(&backup.id);
+        for file in &backup.files {
+            let path = dir.join(&file.name);
+            let checksum = sha256(&path)
+                .map_err(|e| anyhow!("The backup {} is damaged: {}: {e}", backup.id, file.name))?;
+            if checksum != file.sha256 {
+                return Err(anyhow!(
+                    "The backup {} is damaged: the checksum of {} doesn't match",
+                    backup.id,
+                    file.name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Verifies the backup and reads the smart home state kept by it. The backup is read by the
+    /// same kind of the store as the repository has, so the state is brought up to date with
+    /// the journal of the backup as well.
+    pub fn read_state(&self, id: &str) -> Result<(Backup, SavedSmartHome)> {
+        let backup = self.get(id)?;
+        self.verify(&backup)?;
+
+        // the store expects the files in the `.smart-home` directory of the repository root, and
+        // each read has its own root, so the reads of the same backup don't clobber each other
+        let root = self
+            .backup_dir()
+            .join(format!("{id}.{}", generate_id("restore")));
+        let copy = |from: &Path, to: &Path| fs::copy(from, to).map(|_| ()).map_err(Into::into);
+        let result =
+            copy_files(&self.backup_dir().join(id), &root.join(REPO_DIR), &copy).and_then(|_| {
+                SmartHomeManager::with_store(open_store(self.store_type, root.clone()))
+                    .read_smart_home_status()
+            });
+        let _ = fs::remove_dir_all(&root);
+
+        let state = result.map_err(|msg| anyhow!("Unable read the backup {id}: {msg}"))?;
+        Ok((backup, state))
+    }
+
+    /// Removes the oldest backups beyond the limit. Returns the removed ones.
+    pub fn rotate(&self) -> Result<Vec<Backup>> {
+        let backups = self.list()?;
+        if self.keep == 0 || backups.len() <= self.keep {
+            return Ok(vec![]);
+        }
+
+        let removed: Vec<Backup> = backups[..backups.len() - self.keep].to_vec();
+        for backup in &removed {
+            fs::remove_dir_all(self.backup_dir().join(&backup.id))?;
+        }
+        Ok(removed)
+    }
+}
+
+/// The function copying a single file from the first path to the second one
+type CopyFile<'a> = dyn Fn(&Path, &Path) -> Result<()> + 'a;
+
+/// Copies the files of the `from` directory with all its subdirectories, except the backups
+/// themselves, the lock, the temporary files and the journals of SQLite (the copy of its
+/// database has their content already). Each file is copied by the `copy` function.
+fn copy_files(from: &Path, to: &Path, copy: &CopyFile) -> Result<Vec<BackupFile>> {
+    fn walk(
+        from: &Path,
+        to: &Path,
+        prefix: &str,
+        copy: &CopyFile,
+        files: &mut Vec<BackupFile>,
+    ) -> Result<()> {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let relative = format!("{prefix}{name}");
+            if [BACKUP_DIR, LOCK_FILE, TEMP_FILE, MANIFEST_FILE].contains(&relative.as_str())
+                || name.ends_with(".tmp")
+                || name.starts_with(&format!("{SMART_HOME_DB}-"))
+            {
+                continue;
+            }
+
+            if entry.file_type()?.is_dir() {
+                walk(
+                    &entry.path(),
+                    &to.join(&name),
+                    &format!("{relative}/"),
+                    copy,
+                    files,
+                )?;
+            } else {
+                let target = to.join(&name);
+                copy(&entry.path(), &target)?;
+                files.push(BackupFile {
+                    size: fs::metadata(&target)?.len(),
+                    sha256: sha256(&target)?,
+                    name: relative,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = vec![];
+    walk(from, to, "", copy, &mut files)?;
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(files)
+}
+
+fn sha256(path: &Path) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 8192];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
@@ -0,0 +1,46 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+
+use crate::entities::manager::store::json_file::REPO_DIR;
+
+/// The environment variable with the root directory of the smart home repository. It's the
+/// same as the `--repo` option, but the option wins if both are given.
+pub const REPO_ENV: &str = "SMART_HOME_REPO";
+
+/// Looks for the nearest smart home repository the same way as git does: the `start` directory
+/// is checked first, then its parent, and so on up to the file system root. Returns the
+/// directory which contains the `.smart-home` subdirectory.
+pub fn find_repository(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| dir.join(REPO_DIR).is_dir())
+        .map(Path::to_path_buf)
+}
+
+/// Decides which directory is the root of the repository to work with:
+///
+/// * the `explicit` directory (the `--repo` option), if it's given
+/// * the directory from the [REPO_ENV] environment variable, if it's set
+/// * the nearest repository found by [find_repository] from the current directory, if the
+///   `discover` is on. It's off for the `init` command, which always creates a new repository
+///   right in the current directory
+/// * the current directory otherwise, so the commands report the repository is not initialized
+pub fn repository_root(explicit: Option<PathBuf>, discover: bool) -> Result<PathBuf> {
+    if let Some(root) = explicit {
+        return Ok(root);
+    }
+    if let Some(root) = env::var_os(REPO_ENV).filter(|root| !root.is_empty()) {
+        return Ok(PathBuf::from(root));
+    }
+
+    let current_dir =
+        env::current_dir().map_err(|e| anyhow!("Unable determine the current dir: {e}"))?;
+    if discover {
+        if let Some(root) = find_repository(&current_dir) {
+            return Ok(root);
+        }
+    }
+    Ok(current_dir)
+}
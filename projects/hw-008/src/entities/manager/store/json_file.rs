@@ -0,0 +1,385 @@
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use anyhow::{anyhow, Result};
+use fs2::FileExt;
+use serde_json::Value;
+
+use crate::entities::manager::store::{
+    schema, Revision, SchemaVersion, StateStore, StoreError, VersionedState, JOURNAL_LOG,
+};
+use crate::entities::manager::SavedSmartHome;
+
+const SMART_HOME_FILE: &str = "smart-home.json";
+
+pub(crate) const LOCK_FILE: &str = "smart-home.lock";
+
+pub(crate) const TEMP_FILE: &str = "smart-home.json.tmp";
+
+pub(crate) const REPO_DIR: &str = ".smart-home";
+
+/// The file system attributes of the state file, which are changed by every write. Each write
+/// replaces the file with a new one, so on the unix systems the inode number changes as well.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileStamp {
+    inode: u64,
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Result<Self> {
+        let metadata = fs::metadata(path)?;
+
+        #[cfg(unix)]
+        let inode = std::os::unix::fs::MetadataExt::ino(&metadata);
+        #[cfg(not(unix))]
+        let inode = 0;
+
+        Ok(FileStamp {
+            inode,
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        })
+    }
+}
+
+/// A [StateStore] which keeps the whole smart home as a single json document. The document is
+/// located in the `.smart-home/smart-home.json` file under the given root directory.
+///
+/// All the readers and writers (even from different processes) are synchronized by an advisory
+/// lock on the `.smart-home/smart-home.lock` file. The new state is written to a temporary file
+/// first and then renamed over the old one, so a crash in the middle of writing never leaves a
+/// half-written state behind.
+///
+/// The file keeps the versioned document described in [schema], the files written by the older
+/// versions of the smart home are upgraded on load.
+///
+/// The store remembers the revision of the last read or written file together with the file
+/// attributes, so it's able to tell the current revision without parsing the whole file again.
+pub struct JsonFileStore {
+    root: PathBuf,
+    last_seen: Mutex<Option<(FileStamp, Revision)>>,
+}
+
+impl JsonFileStore {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            last_seen: Mutex::new(None),
+        }
+    }
+
+    /// Remembers the attributes of the state file with the given revision. It must be called
+    /// under the repository lock, so nobody is able to replace the file in between.
+    fn remember(&self, revision: Revision) {
+        if let Ok(stamp) = FileStamp::of(&self.get_state_file()) {
+            *self.last_seen.lock().unwrap() = Some((stamp, revision));
+        }
+    }
+
+    fn repo_file(&self, name: &str) -> PathBuf {
+        let mut path = self.root.clone();
+        path.push(REPO_DIR);
+        path.push(name);
+        path
+    }
+
+    /// Returns the path of the json document with the smart home state
+    pub fn get_state_file(&self) -> PathBuf {
+        self.repo_file(SMART_HOME_FILE)
+    }
+
+    /// Opens the lock file of the repository. The returned file is not locked yet, so the caller
+    /// is responsible for taking a proper lock.
+    fn lock_file(&self) -> Result<File> {
+        if !self.is_initialized() {
+            return Err(StoreError::NotInitialized.into());
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.repo_file(LOCK_FILE))?;
+        Ok(file)
+    }
+
+    fn log_file(&self, log: &str) -> PathBuf {
+        self.repo_file(&format!("{log}.jsonl"))
+    }
+
+    /// Appends the records to the log file. It must be called under the exclusive lock.
+    fn write_log_file(&self, log: &str, records: &[String]) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.log_file(log))?;
+
+        let mut content = records.join("\n");
+        content.push('\n');
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        Ok(())
+    }
+
+    fn read_document(&self) -> Result<Value> {
+        let file = File::open(self.get_state_file())?;
+        let reader = BufReader::new(file);
+
+        let document = serde_json::from_reader(reader)
+            .map_err(|e| StoreError::MalformedState(e.to_string()))?;
+        Ok(document)
+    }
+
+    /// Reads the state file of any known version, see [schema]
+    fn read_state_file(&self) -> Result<VersionedState> {
+        Ok(schema::decode(self.read_document()?)?)
+    }
+
+    /// Reads the revision of the state file without reading the state itself, so the broken
+    /// state might be overwritten by the repaired one
+    fn read_revision(&self) -> Result<Revision> {
+        let document = schema::upgrade(self.read_document()?)?;
+        document["revision"]
+            .as_u64()
+            .ok_or_else(|| StoreError::MalformedState("invalid revision".into()).into())
+    }
+
+    /// Writes the state to the temporary file, flushes it to the disk and atomically replaces
+    /// the state file with it.
+    /// Replaces the records of the log file through a temporary file. It must be called under the
+    /// exclusive lock.
+    fn rewrite_log_file(
+        &self,
+        log: &str,
+        rewrite: &mut dyn FnMut(Vec<String>) -> Result<Vec<String>>,
+    ) -> Result<()> {
+        let path = self.log_file(log);
+        let records = if path.exists() {
+            BufReader::new(File::open(&path)?)
+                .lines()
+                .filter(|line| !matches!(line, Ok(line) if line.is_empty()))
+                .collect::<std::io::Result<Vec<String>>>()?
+        } else {
+            vec![]
+        };
+        let records = rewrite(records)?;
+
+        let temp_path = self.repo_file(&format!("{log}.jsonl.tmp"));
+        let mut temp = File::create(&temp_path)?;
+        for record in records {
+            temp.write_all(record.as_bytes())?;
+            temp.write_all(b"\n")?;
+        }
+        temp.sync_all()?;
+        drop(temp);
+
+        fs::rename(&temp_path, path)?;
+        if let Ok(dir) = File::open(self.repo_file("")) {
+            let _ = dir.sync_all();
+        }
+        Ok(())
+    }
+
+    fn write_state_file(&self, state: &SavedSmartHome, revision: Revision) -> Result<()> {
+        let content = serde_json::to_string(&schema::encode(state, revision)?)?;
+
+        let temp_path = self.repo_file(TEMP_FILE);
+        let mut temp = File::create(&temp_path)?;
+        temp.write_all(content.as_bytes())?;
+        temp.sync_all()?;
+        drop(temp);
+
+        fs::rename(&temp_path, self.get_state_file())?;
+
+        // Make the rename itself durable. Directories can't be opened for syncing on every
+        // platform, so it's done in the best effort manner.
+        if let Ok(dir) = File::open(self.repo_file("")) {
+            let _ = dir.sync_all();
+        }
+        Ok(())
+    }
+}
+
+impl StateStore for JsonFileStore {
+    fn initialize(&self) -> Result<()> {
+        let path = self.get_state_file();
+
+        if path.exists() {
+            return Err(anyhow!(format!(
+                "Repository {} already exists",
+                path.display()
+            )));
+        }
+
+        fs::create_dir_all(self.repo_file(""))?;
+        self.write_state_file(&None, 0)
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.get_state_file().exists()
+    }
+
+    fn schema_version(&self) -> Result<SchemaVersion> {
+        let lock = self.lock_file()?;
+        lock.lock_shared()?;
+        let document = self.read_document();
+        lock.unlock()?;
+
+        Ok(schema::detect_version(&document?)?)
+    }
+
+    fn load(&self) -> Result<VersionedState> {
+        let lock = self.lock_file()?;
+        lock.lock_shared()?;
+        let state = self.read_state_file();
+        if let Ok(ref state) = state {
+            self.remember(state.revision);
+        }
+        lock.unlock()?;
+
+        state
+    }
+
+    fn load_document(&self) -> Result<Value> {
+        let lock = self.lock_file()?;
+        lock.lock_shared()?;
+        let document = self.read_document();
+        lock.unlock()?;
+
+        document
+    }
+
+    fn revision(&self) -> Result<Revision> {
+        let stamp = FileStamp::of(&self.get_state_file())?;
+
+        if let Some((last_stamp, revision)) = self.last_seen.lock().unwrap().as_ref() {
+            if *last_stamp == stamp {
+                return Ok(*revision);
+            }
+        }
+
+        Ok(self.load()?.revision)
+    }
+
+    /// The journal records are appended before the state file is replaced, both under the
+    /// exclusive lock. A crash in between leaves the journal one step ahead of the state.
+    fn save_with_journal(
+        &self,
+        state: &SavedSmartHome,
+        base: Revision,
+        records: &[String],
+    ) -> Result<Revision> {
+        let lock = self.lock_file()?;
+        lock.lock_exclusive()?;
+
+        let result = self.read_revision().and_then(|current| {
+            if current != base {
+                return Err(StoreError::ConcurrentModification(base, current).into());
+            }
+
+            if !records.is_empty() {
+                self.write_log_file(JOURNAL_LOG, records)?;
+            }
+
+            let revision = current + 1;
+            self.write_state_file(state, revision)?;
+            self.remember(revision);
+            Ok(revision)
+        });
+
+        lock.unlock()?;
+        result
+    }
+
+    fn append_log(&self, log: &str, records: &[String]) -> Result<()> {
+        let lock = self.lock_file()?;
+        lock.lock_exclusive()?;
+        let result = self.write_log_file(log, records);
+        lock.unlock()?;
+        result
+    }
+
+    fn read_log(&self, log: &str) -> Result<Vec<String>> {
+        let path = self.log_file(log);
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+
+        let lock = self.lock_file()?;
+        lock.lock_shared()?;
+        let records = File::open(path).and_then(|file| {
+            BufReader::new(file)
+                .lines()
+                .filter(|line| !matches!(line, Ok(line) if line.is_empty()))
+                .collect::<std::io::Result<Vec<String>>>()
+        });
+        lock.unlock()?;
+
+        Ok(records?)
+    }
+
+    /// The log is written to a temporary file which then replaces the log file
+    fn rewrite_log(
+        &self,
+        log: &str,
+        rewrite: &mut dyn FnMut(Vec<String>) -> Result<Vec<String>>,
+    ) -> Result<()> {
+        let lock = self.lock_file()?;
+        lock.lock_exclusive()?;
+        let result = self.rewrite_log_file(log, rewrite);
+        lock.unlock()?;
+        result
+    }
+
+    /// Reads the log file backward by small chunks until the beginning of the last line is found
+    fn last_log_record(&self, log: &str) -> Result<Option<String>> {
+        const CHUNK: u64 = 4096;
+
+        let path = self.log_file(log);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let lock = self.lock_file()?;
+        lock.lock_shared()?;
+
+        let result = File::open(path).and_then(|mut file| {
+            let len = file.metadata()?.len();
+            let mut tail: Vec<u8> = vec![];
+            let mut position = len;
+
+            while position > 0 {
+                let start = position.saturating_sub(CHUNK);
+                let mut chunk = vec![0u8; (position - start) as usize];
+                file.seek(SeekFrom::Start(start))?;
+                file.read_exact(&mut chunk)?;
+                chunk.extend_from_slice(&tail);
+                tail = chunk;
+                position = start;
+
+                // the very last byte is the new-line symbol of the last record itself
+                let trimmed = tail.strip_suffix(b"\n").unwrap_or(&tail);
+                if trimmed.contains(&b'\n') {
+                    break;
+                }
+            }
+
+            let content = String::from_utf8_lossy(&tail);
+            Ok(content
+                .trim_end()
+                .rsplit('\n')
+                .next()
+                .filter(|line| !line.is_empty())
+                .map(|line| line.to_string()))
+        });
+
+        lock.unlock()?;
+        Ok(result?)
+    }
+}
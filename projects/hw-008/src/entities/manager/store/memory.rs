@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+use crate::entities::manager::store::{
+    schema, Revision, SchemaVersion, StateStore, StoreError, VersionedState, JOURNAL_LOG,
+    SCHEMA_VERSION,
+};
+use crate::entities::manager::SavedSmartHome;
+
+/// A [StateStore] which keeps the smart home in the memory of the current process. It's
+/// initialized right after creation, so it might be used in the tests without any preparation.
+pub struct InMemoryStore {
+    state: Mutex<VersionedState>,
+    logs: Mutex<HashMap<String, Vec<String>>>,
+}
+
+impl InMemoryStore {
+    /// Creates a store pre-populated with the given state
+    pub fn new(state: SavedSmartHome) -> Self {
+        Self {
+            state: Mutex::new(VersionedState { revision: 0, state }),
+            logs: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Default for InMemoryStore {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl StateStore for InMemoryStore {
+    fn initialize(&self) -> Result<()> {
+        Err(anyhow!("In-memory repository is always initialized"))
+    }
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn schema_version(&self) -> Result<SchemaVersion> {
+        Ok(SCHEMA_VERSION)
+    }
+
+    fn load(&self) -> Result<VersionedState> {
+        let state = self
+            .state
+            .lock()
+            .map_err(|e| anyhow!("Unable read the state: {e}"))?;
+        Ok(state.clone())
+    }
+
+    fn load_document(&self) -> Result<Value> {
+        let state = self.load()?;
+        schema::encode(&state.state, state.revision)
+    }
+
+    fn revision(&self) -> Result<Revision> {
+        let state = self
+            .state
+            .lock()
+            .map_err(|e| anyhow!("Unable read the state: {e}"))?;
+        Ok(state.revision)
+    }
+
+    fn save_with_journal(
+        &self,
+        state: &SavedSmartHome,
+        base: Revision,
+        records: &[String],
+    ) -> Result<Revision> {
+        let mut current = self
+            .state
+            .lock()
+            .map_err(|e| anyhow!("Unable write the state: {e}"))?;
+
+        if current.revision != base {
+            return Err(StoreError::ConcurrentModification(base, current.revision).into());
+        }
+
+        // the state lock is still held, so nobody is able to append a record in between
+        if !records.is_empty() {
+            self.append_log(JOURNAL_LOG, records)?;
+        }
+
+        current.revision += 1;
+        current.state = state.clone();
+        Ok(current.revision)
+    }
+
+    fn append_log(&self, log: &str, records: &[String]) -> Result<()> {
+        let mut logs = self
+            .logs
+            .lock()
+            .map_err(|e| anyhow!("Unable write the log: {e}"))?;
+        logs.entry(log.to_string())
+            .or_default()
+            .extend_from_slice(records);
+        Ok(())
+    }
+
+    fn read_log(&self, log: &str) -> Result<Vec<String>> {
+        let logs = self
+            .logs
+            .lock()
+            .map_err(|e| anyhow!("Unable read the log: {e}"))?;
+        Ok(logs.get(log).cloned().unwrap_or_default())
+    }
+
+    fn last_log_record(&self, log: &str) -> Result<Option<String>> {
+        let logs = self
+            .logs
+            .lock()
+            .map_err(|e| anyhow!("Unable read the log: {e}"))?;
+        Ok(logs.get(log).and_then(|records| records.last().cloned()))
+    }
+
+    fn rewrite_log(
+        &self,
+        log: &str,
+        rewrite: &mut dyn FnMut(Vec<String>) -> Result<Vec<String>>,
+    ) -> Result<()> {
+        let mut logs = self
+            .logs
+            .lock()
+            .map_err(|e| anyhow!("Unable write the log: {e}"))?;
+        let records = rewrite(logs.get(log).cloned().unwrap_or_default())?;
+        logs.insert(log.to_string(), records);
+        Ok(())
+    }
+}
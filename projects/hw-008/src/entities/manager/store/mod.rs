@@ -0,0 +1,195 @@
+//! A storage layer for the smart home state.
+//!
+//! The [SmartHomeManager](crate::entities::manager::SmartHomeManager) doesn't know where the
+//! state is kept. Instead, it talks to an implementation of the [StateStore] trait, so the same
+//! manager might work with a plain json file, with an embedded SQLite database, or with a
+//! temporary in-memory storage, which is super handy for the tests.
+//!
+//! Each stored state has a [Revision] number, which is incremented by every successful save. A
+//! writer must tell the store which revision its changes are based on, so the store is able to
+//! reject the changes made on top of an outdated state instead of silently overwriting
+//! somebody else's changes.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde_json::Value;
+
+use crate::entities::manager::SavedSmartHome;
+
+/// The default storage, which keeps the whole smart home in the `.smart-home/smart-home.json`
+/// file. It's the same format as the previous home works used.
+mod json_file;
+pub use json_file::JsonFileStore;
+
+/// A non-persistent storage. All the data will be lost as soon as the store is dropped.
+mod memory;
+pub use memory::InMemoryStore;
+
+/// An embedded SQLite database storage for larger installations.
+mod sqlite;
+pub use sqlite::SqliteStore;
+pub(crate) use sqlite::{copy_database, SMART_HOME_DB};
+
+/// Looking for the repository the store is located in
+mod discovery;
+pub use discovery::{find_repository, repository_root, REPO_ENV};
+
+/// The snapshots of the repository files with the checksums
+mod backup;
+pub use backup::{Backup, BackupFile, Backups, BACKUP_DIR, DEFAULT_BACKUP_KEEP};
+
+/// The versioned format of the stored state and the migrations between the versions
+mod schema;
+pub(crate) use schema::{decode as decode_state, detect_version, upgrade as upgrade_document};
+pub use schema::{Migration, MigrationPlan, SchemaVersion, MIGRATIONS, SCHEMA_VERSION};
+
+/// The name of the log with all the changes of the smart home, see [StateStore::save_with_journal]
+pub const JOURNAL_LOG: &str = "journal";
+
+/// The kinds of the storage the smart home might be kept in, see [open_store]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default, ValueEnum)]
+pub enum StoreType {
+    /// A single json file in the `.smart-home` directory
+    #[default]
+    Json,
+    /// An embedded SQLite database in the `.smart-home` directory
+    Sqlite,
+    /// A non-persistent in-memory storage, it lives as long as the server does
+    Memory,
+}
+
+/// A number of the saved state. The freshly initialized storage has the zero revision.
+pub type Revision = u64;
+
+/// The state of the smart home together with its [Revision] number
+#[derive(Debug, Clone)]
+pub struct VersionedState {
+    pub revision: Revision,
+    pub state: SavedSmartHome,
+}
+
+/// A list of errors specific for the storage layer. All other errors (I/O, serialization, etc.)
+/// are passed through as is.
+#[derive(Debug, PartialEq, Eq)]
+pub enum StoreError {
+    /// The repository was not initialized yet, so there is nothing to read or write
+    NotInitialized,
+    /// The state was modified by someone else after it had been read. The first parameter is
+    /// the revision the changes are based on, and the second one is the actual revision.
+    ConcurrentModification(Revision, Revision),
+    /// The state was written by a newer version of the smart home, so its format is unknown
+    UnsupportedSchema(SchemaVersion),
+    /// The stored state can't be read, the parameter describes the problem
+    MalformedState(String),
+}
+
+impl Error for StoreError {}
+
+impl Display for StoreError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            StoreError::NotInitialized => {
+                formatter.write_str("No repository found. Consider to init repository first")
+            }
+            StoreError::ConcurrentModification(expected, actual) => formatter.write_str(&format!(
+                "The smart home was modified concurrently: changes are based on revision \
+                    {expected}, but the current revision is {actual}. Please, retry the command"
+            )),
+            StoreError::UnsupportedSchema(version) => formatter.write_str(&format!(
+                "The smart home state has format version {version}, but only versions up to \
+                    {SCHEMA_VERSION} are supported. Please, upgrade the smart home"
+            )),
+            StoreError::MalformedState(reason) => formatter.write_str(&format!(
+                "Unable deserialize the smart-home state: {reason}"
+            )),
+        }
+    }
+}
+
+/// An interface for any storage, which is able to keep the state of the smart home. The store
+/// is shared between threads of the servers, so each implementation must be thread safe.
+pub trait StateStore: Send + Sync {
+    /// Prepare an empty storage. It should fail if the storage is already initialized, so the
+    /// existing data will never be overwritten by accident.
+    fn initialize(&self) -> Result<()>;
+
+    /// Returns `true` if the storage was initialized and ready for reading and writing.
+    fn is_initialized(&self) -> bool;
+
+    /// Returns the version of the format the state is actually stored in. The older formats are
+    /// upgraded by `load` on the fly, but they are written in the current format only by the
+    /// next `save`.
+    fn schema_version(&self) -> Result<SchemaVersion>;
+
+    /// Read the whole state of the smart home from the storage together with its revision.
+    fn load(&self) -> Result<VersionedState>;
+
+    /// Reads the stored state as a raw json document of the version it's stored in, see
+    /// [schema]. Unlike `load`, it doesn't fail on the entities which can't be deserialized, so
+    /// the integrity checker is able to look into the broken states as well.
+    fn load_document(&self) -> Result<Value>;
+
+    /// Returns the current revision of the stored state. The managers call it before each
+    /// access to their cached state, so the implementation must be much cheaper than `load`.
+    fn revision(&self) -> Result<Revision>;
+
+    /// Replace the whole state of the smart home in the storage with the given one. The write
+    /// must be atomic: either the whole new state is stored, or the old one is kept untouched.
+    ///
+    /// The `base` is the revision the new state is derived from. If the stored revision is
+    /// different, the store must reject the write with [StoreError::ConcurrentModification].
+    /// Returns the revision of the written state.
+    fn save(&self, state: &SavedSmartHome, base: Revision) -> Result<Revision> {
+        self.save_with_journal(state, base, &[])
+    }
+
+    /// The same as [StateStore::save], but the given records are appended to the
+    /// [JOURNAL_LOG] together with the state. The records are written only if the state is
+    /// accepted, and they must never be appended after the next accepted state, so the journal
+    /// always keeps the changes in the order of revisions.
+    ///
+    /// If the store is not able to write both things atomically, the journal must be written
+    /// first. A journal which is ahead of the state is fixed by replaying it on the next start,
+    /// whereas the lost journal records would be lost forever.
+    fn save_with_journal(
+        &self,
+        state: &SavedSmartHome,
+        base: Revision,
+        records: &[String],
+    ) -> Result<Revision>;
+
+    /// Appends the records to the end of the named append-only log. The logs are kept next to
+    /// the state, e.g. the journal of all the changes is such a log.
+    fn append_log(&self, log: &str, records: &[String]) -> Result<()>;
+
+    /// Reads all the records of the named log in the order they were appended. A log which was
+    /// never written is empty.
+    fn read_log(&self, log: &str) -> Result<Vec<String>>;
+
+    /// Returns the last record of the named log. It should be cheaper than reading the whole log.
+    fn last_log_record(&self, log: &str) -> Result<Option<String>>;
+
+    /// Replaces all the records of the named log with the ones returned by `rewrite`, e.g. to
+    /// compact the old records. Nothing might be appended to the log during the rewrite, and
+    /// the rewrite must be atomic: either all the records are replaced, or none of them.
+    fn rewrite_log(
+        &self,
+        log: &str,
+        rewrite: &mut dyn FnMut(Vec<String>) -> Result<Vec<String>>,
+    ) -> Result<()>;
+}
+
+/// Opens the storage of the given type located in the `root` directory. The json file store and
+/// the SQLite store keep their files in the `.smart-home` subdirectory of the `root`.
+pub fn open_store(store_type: StoreType, root: PathBuf) -> Arc<dyn StateStore> {
+    match store_type {
+        StoreType::Json => Arc::new(JsonFileStore::new(root)),
+        StoreType::Sqlite => Arc::new(SqliteStore::new(root)),
+        StoreType::Memory => Arc::new(InMemoryStore::default()),
+    }
+}
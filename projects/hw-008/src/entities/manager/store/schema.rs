@@ -0,0 +1,400 @@
+//! The versioned format of the stored smart home state.
+//!
+//! Each stored state is a json document (the SQLite store assembles the same document from its
+//! tables). The document is wrapped into an envelope with the version of its format:
+//!
+//! ```json
+//! {"schema_version": 2, "revision": 7, "homes": [...]}
+//! ```
+//!
+//! The documents of the older versions are upgraded on load by the chain of [MIGRATIONS], one
+//! step per version, so the rest of the code always works with the current format only. Each
+//! step works on the raw json value, so it doesn't depend on the current entity structs.
+
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+
+use anyhow::Result;
+use serde_json::{json, Map, Value};
+
+use crate::entities::devices::legacy_quantity;
+use crate::entities::manager::store::{Revision, StoreError, VersionedState};
+use crate::entities::manager::SavedSmartHome;
+
+/// The version of the state format written by this version of the smart home
+pub const SCHEMA_VERSION: SchemaVersion = 4;
+
+pub type SchemaVersion = u32;
+
+/// A single step of the migration chain. It upgrades the document of the `from` version to the
+/// next one.
+pub struct Migration {
+    pub from: SchemaVersion,
+    pub description: &'static str,
+    migrate: fn(Value) -> Result<Value, StoreError>,
+}
+
+impl Debug for Migration {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        write!(formatter, "Migration({}: {})", self.from, self.description)
+    }
+}
+
+/// The whole migration chain. The steps must be ordered by the `from` version, without gaps.
+///
+/// * version 0 is a bare list of homes (or `null`), written by the hw-006 and hw-007 crates and
+///   by the first versions of this crate
+/// * version 1 is the envelope with the revision number, but without the schema version
+/// * version 2 is the envelope with the schema version
+/// * version 3 keeps the runtime state of each device, the on/off status of the sockets is
+///   moved into it
+/// * version 4 keeps the typed measurements in the last readings of the devices instead of the
+///   bare values
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 0,
+        description: "wrap the bare list of homes into the envelope with zero revision",
+        migrate: |homes| Ok(json!({ "revision": 0, "homes": homes })),
+    },
+    Migration {
+        from: 1,
+        description: "add the schema version to the envelope",
+        migrate: |mut document| {
+            envelope(&mut document)?.insert("schema_version".into(), json!(2));
+            Ok(document)
+        },
+    },
+    Migration {
+        from: 2,
+        description: "move the status of the sockets into the device state",
+        migrate: |mut document| {
+            let fields = envelope(&mut document)?;
+            for device in devices_mut(fields.get_mut("homes")) {
+                if let Some(socket) = device.get_mut("Socket").and_then(Value::as_object_mut) {
+                    let on = socket.remove("status") == Some(json!("Enabled"));
+                    socket.insert("state".into(), device_state(on));
+                } else if let Some(ther) =
+                    device.get_mut("Thermometer").and_then(Value::as_object_mut)
+                {
+                    ther.insert("state".into(), device_state(true));
+                }
+            }
+            fields.insert("schema_version".into(), json!(3));
+            Ok(document)
+        },
+    },
+    Migration {
+        from: 3,
+        description: "turn the values of the last readings into the typed measurements",
+        migrate: |mut document| {
+            let fields = envelope(&mut document)?;
+            for device in devices_mut(fields.get_mut("homes")) {
+                let Some((tag, device)) = device.as_object_mut().and_then(|d| d.iter_mut().next())
+                else {
+                    continue;
+                };
+                let reading = device
+                    .get_mut("state")
+                    .and_then(|state| state.get_mut("last_reading"))
+                    .and_then(Value::as_object_mut);
+                if let Some(reading) = reading {
+                    if let Some(value) = reading.remove("value") {
+                        let measurement = measurement(tag, value, reading.get("taken_at"));
+                        reading.insert("measurements".into(), json!([measurement]));
+                    }
+                }
+            }
+            fields.insert("schema_version".into(), json!(4));
+            Ok(document)
+        },
+    },
+];
+
+/// The quantity is picked by the device tag the same way the old readings of the journal are
+/// upgraded on read, see [Reading](crate::entities::devices::Reading)
+fn measurement(tag: &str, value: Value, taken_at: Option<&Value>) -> Value {
+    let quantity = legacy_quantity(tag);
+    json!({"quantity": quantity, "value": value, "unit": quantity.unit(), "taken_at": taken_at,
+        "quality": "Good"})
+}
+
+fn device_state(on: bool) -> Value {
+    json!({ "on": on, "last_reading": null, "changed_at": null })
+}
+
+/// The devices of the raw document of any version, the version 0 is the list of homes itself
+fn devices(document: &Value) -> Vec<&Value> {
+    let homes = match document {
+        Value::Array(_) => Some(document),
+        _ => document.get("homes"),
+    };
+    homes
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|home| home.get("rooms").and_then(Value::as_array))
+        .flatten()
+        .filter_map(|room| room.get("devices").and_then(Value::as_array))
+        .flatten()
+        .collect()
+}
+
+/// Returns all the devices of the raw list of homes. The broken parts of the list are skipped,
+/// they are reported by the decoding anyway.
+fn devices_mut(homes: Option<&mut Value>) -> impl Iterator<Item = &mut Value> {
+    homes
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(|home| home.get_mut("rooms").and_then(Value::as_array_mut))
+        .flatten()
+        .filter_map(|room| room.get_mut("devices").and_then(Value::as_array_mut))
+        .flatten()
+}
+
+fn envelope(document: &mut Value) -> Result<&mut Map<String, Value>, StoreError> {
+    document
+        .as_object_mut()
+        .ok_or_else(|| StoreError::MalformedState("the state is not a json object".into()))
+}
+
+/// A description of the migration of the stored state to the current format
+#[derive(Debug)]
+pub struct MigrationPlan {
+    pub from: SchemaVersion,
+    pub to: SchemaVersion,
+    pub steps: Vec<&'static Migration>,
+    /// The number of the devices of each type rewritten by each step, it's known only for the
+    /// [previewed](MigrationPlan::preview) plans
+    pub rewritten: Option<Vec<BTreeMap<String, usize>>>,
+}
+
+impl MigrationPlan {
+    pub fn new(from: SchemaVersion) -> Result<Self, StoreError> {
+        Ok(MigrationPlan {
+            from,
+            to: SCHEMA_VERSION,
+            steps: migrations_from(from)?,
+            rewritten: None,
+        })
+    }
+
+    /// Runs the steps on the given document, which is a copy of the stored one, and counts the
+    /// devices each step rewrites. The migrations never add or remove the devices, so the
+    /// devices are compared by their positions.
+    pub fn preview(self, mut document: Value) -> Result<Self, StoreError> {
+        let mut rewritten = vec![];
+        for step in &self.steps {
+            let before: Vec<Value> = devices(&document).into_iter().cloned().collect();
+            document = (step.migrate)(document)?;
+            let mut counts = BTreeMap::new();
+            for (old, new) in before.iter().zip(devices(&document)) {
+                if old != new {
+                    let tag = new.as_object().and_then(|device| device.keys().next());
+                    let tag = tag.cloned().unwrap_or_else(|| "Unknown".into());
+                    *counts.entry(tag).or_default() += 1;
+                }
+            }
+            rewritten.push(counts);
+        }
+        Ok(Self {
+            rewritten: Some(rewritten),
+            ..self
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+impl Display for MigrationPlan {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        if self.is_empty() {
+            return write!(
+                formatter,
+                "The state format is up to date (version {})",
+                self.to
+            );
+        }
+
+        write!(
+            formatter,
+            "The state format is upgraded from version {} to {}:",
+            self.from, self.to
+        )?;
+        for (index, step) in self.steps.iter().enumerate() {
+            write!(
+                formatter,
+                "\n  {} -> {}: {}",
+                step.from,
+                step.from + 1,
+                step.description
+            )?;
+            let Some(counts) = self.rewritten.as_ref().and_then(|r| r.get(index)) else {
+                continue;
+            };
+            if counts.is_empty() {
+                write!(formatter, " (no devices are rewritten)")?;
+            } else {
+                let counts: Vec<String> = counts
+                    .iter()
+                    .map(|(tag, count)| format!("{count} {tag}"))
+                    .collect();
+                write!(formatter, " (rewrites {})", counts.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Detects the version of the state document
+pub fn detect_version(document: &Value) -> Result<SchemaVersion, StoreError> {
+    match document {
+        Value::Null | Value::Array(_) => Ok(0),
+        Value::Object(fields) => match fields.get("schema_version") {
+            Some(version) => version
+                .as_u64()
+                .map(|version| version as SchemaVersion)
+                .ok_or_else(|| StoreError::MalformedState("invalid schema version".into())),
+            None if fields.contains_key("revision") => Ok(1),
+            None => Err(StoreError::MalformedState(
+                "unknown format of the state".into(),
+            )),
+        },
+        _ => Err(StoreError::MalformedState(
+            "unknown format of the state".into(),
+        )),
+    }
+}
+
+/// Returns the migration steps needed to upgrade the document of the given version. Fails if
+/// the document was written by a newer version of the smart home.
+pub fn migrations_from(version: SchemaVersion) -> Result<Vec<&'static Migration>, StoreError> {
+    if version > SCHEMA_VERSION {
+        return Err(StoreError::UnsupportedSchema(version));
+    }
+    Ok(MIGRATIONS.iter().filter(|m| m.from >= version).collect())
+}
+
+/// Upgrades the document of any known version to the current version
+pub fn upgrade(mut document: Value) -> Result<Value, StoreError> {
+    let version = detect_version(&document)?;
+    for migration in migrations_from(version)? {
+        document = (migration.migrate)(document)?;
+    }
+    Ok(document)
+}
+
+/// Upgrades the document to the current version and reads the state from it
+pub fn decode(document: Value) -> Result<VersionedState, StoreError> {
+    let mut document = upgrade(document)?;
+
+    let fields = envelope(&mut document)?;
+    let revision = fields
+        .get("revision")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| StoreError::MalformedState("invalid revision".into()))?;
+    let homes = fields.remove("homes").unwrap_or(Value::Null);
+
+    let state: SavedSmartHome =
+        serde_json::from_value(homes).map_err(|e| StoreError::MalformedState(e.to_string()))?;
+
+    Ok(VersionedState { revision, state })
+}
+
+/// Writes the state into the document of the current version
+pub fn encode(state: &SavedSmartHome, revision: Revision) -> Result<Value> {
+    Ok(json!({
+        "schema_version": SCHEMA_VERSION,
+        "revision": revision,
+        "homes": serde_json::to_value(state)?,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{decode, detect_version, encode, SCHEMA_VERSION};
+    use crate::entities::manager::store::StoreError;
+    use crate::entities::Quantity;
+
+    #[test]
+    fn each_version_is_upgraded() {
+        let home = json!({"id": "home_1", "name": "Home", "description": null, "rooms": []});
+        let documents = [
+            json!([home]),
+            json!({"revision": 3, "homes": [home]}),
+            json!({"schema_version": 2, "revision": 3, "homes": [home]}),
+            json!({"schema_version": 3, "revision": 3, "homes": [home]}),
+            json!({"schema_version": 4, "revision": 3, "homes": [home]}),
+        ];
+
+        for (version, document) in documents.into_iter().enumerate() {
+            assert_eq!(detect_version(&document).unwrap(), version as u32);
+            let state = decode(document).unwrap();
+            assert_eq!(state.state.unwrap()[0].name, "Home");
+        }
+
+        let state = decode(json!(null)).unwrap();
+        assert_eq!(state.revision, 0);
+        assert!(state.state.is_none());
+
+        let encoded = encode(&None, 5).unwrap();
+        assert_eq!(detect_version(&encoded).unwrap(), SCHEMA_VERSION);
+        assert_eq!(decode(encoded).unwrap().revision, 5);
+    }
+
+    #[test]
+    fn socket_status_is_moved_into_device_state() {
+        let socket = json!({"Socket": {"id": "sock_1", "name": "Lamp", "description": null,
+            "power_consumption": 0.0, "status": "Enabled"}});
+        let thermometer =
+            json!({"Thermometer": {"id": "ther_1", "name": "T", "description": null}});
+        let room = json!({"id": "room_1", "name": "Hall", "description": null,
+            "devices": [socket, thermometer]});
+        let home = json!({"id": "home_1", "name": "Home", "description": null, "rooms": [room]});
+
+        let state = decode(json!({"schema_version": 2, "revision": 1, "homes": [home]})).unwrap();
+        let devices = &state.state.unwrap()[0].rooms[0].devices;
+        assert!(devices[0].state().on);
+        assert!(devices[1].state().on);
+        assert!(devices[0].state().changed_at.is_none());
+    }
+
+    #[test]
+    fn readings_get_typed_measurements() {
+        let reading = json!({"value": 0.5, "taken_at": "2023-01-31T10:00:00Z"});
+        let thermometer = json!({"Thermometer": {"id": "ther_1", "name": "T", "description": null,
+            "state": {"on": true, "last_reading": reading, "changed_at": null}}});
+        let room = json!({"id": "room_1", "name": "Hall", "description": null,
+            "devices": [thermometer]});
+        let home = json!({"id": "home_1", "name": "Home", "description": null, "rooms": [room]});
+
+        let state = decode(json!({"schema_version": 3, "revision": 1, "homes": [home]})).unwrap();
+        let devices = &state.state.unwrap()[0].rooms[0].devices;
+        let reading = devices[0].state().last_reading.clone().unwrap();
+        let temperature = reading.get(Quantity::Temperature).unwrap();
+        assert_eq!(temperature.value, 0.5);
+        assert_eq!(temperature.taken_at, reading.taken_at);
+    }
+
+    #[test]
+    fn broken_documents_are_rejected() {
+        let newer = json!({"schema_version": SCHEMA_VERSION + 1, "revision": 0, "homes": null});
+        assert_eq!(
+            decode(newer).unwrap_err(),
+            StoreError::UnsupportedSchema(SCHEMA_VERSION + 1)
+        );
+
+        assert!(matches!(
+            decode(json!([{"id": "home_1"}])),
+            Err(StoreError::MalformedState(_))
+        ));
+        assert!(matches!(
+            decode(json!("text")),
+            Err(StoreError::MalformedState(_))
+        ));
+    }
+}
@@ -0,0 +1,495 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use rusqlite::{
+    params, Connection, OpenFlags, OptionalExtension, Transaction, TransactionBehavior,
+};
+use serde_json::{json, Value};
+
+use crate::entities::manager::store::json_file::REPO_DIR;
+use crate::entities::manager::store::{
+    schema, Revision, SchemaVersion, StateStore, StoreError, VersionedState, JOURNAL_LOG,
+    SCHEMA_VERSION,
+};
+use crate::entities::manager::SavedSmartHome;
+use crate::entities::Labels;
+
+pub(crate) const SMART_HOME_DB: &str = "smart-home.db";
+
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The `meta` table keeps the revision of the state, and the `logs` table keeps the records of
+/// all the append-only logs. They are created on each connection, because the very first
+/// databases had no such tables.
+const META_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS meta (
+        key         TEXT PRIMARY KEY,
+        value       INTEGER NOT NULL
+    );
+    INSERT OR IGNORE INTO meta (key, value) VALUES ('revision', 0);
+    CREATE TABLE IF NOT EXISTS logs (
+        seq         INTEGER PRIMARY KEY AUTOINCREMENT,
+        log         TEXT NOT NULL,
+        record      TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS logs_by_name ON logs(log, seq);
+";
+
+const SCHEMA: &str = "
+    CREATE TABLE homes (
+        id          TEXT PRIMARY KEY,
+        position    INTEGER NOT NULL,
+        name        TEXT NOT NULL,
+        description TEXT,
+        labels      TEXT
+    );
+    CREATE TABLE rooms (
+        id          TEXT PRIMARY KEY,
+        home_id     TEXT NOT NULL REFERENCES homes(id) ON DELETE CASCADE,
+        position    INTEGER NOT NULL,
+        name        TEXT NOT NULL,
+        description TEXT,
+        labels      TEXT
+    );
+    CREATE TABLE devices (
+        id          TEXT PRIMARY KEY,
+        room_id     TEXT NOT NULL REFERENCES rooms(id) ON DELETE CASCADE,
+        position    INTEGER NOT NULL,
+        kind        TEXT NOT NULL,
+        name        TEXT NOT NULL,
+        data        TEXT NOT NULL
+    );
+    CREATE INDEX rooms_by_home ON rooms(home_id);
+    CREATE INDEX devices_by_room ON devices(room_id);
+";
+
+/// The columns added to the tables of [SCHEMA] after the first databases were created. The
+/// labels of the homes and the rooms are kept as json objects, the devices keep them in their
+/// data.
+const ADDED_COLUMNS: [(&str, &str, &str); 2] =
+    [("homes", "labels", "TEXT"), ("rooms", "labels", "TEXT")];
+
+/// Adds the [ADDED_COLUMNS] missing in the databases created earlier. It's done on each
+/// connection, the same as the [META_SCHEMA] tables are created.
+fn add_missing_columns(connection: &mut Connection) -> Result<()> {
+    let transaction = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    for (table, column, kind) in ADDED_COLUMNS {
+        let exists = transaction
+            .prepare(&format!(
+                "SELECT 1 FROM pragma_table_info('{table}') WHERE name = ?1"
+            ))?
+            .exists(params![column])?;
+        if !exists {
+            transaction
+                .execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {kind};"))?;
+        }
+    }
+    transaction.commit()?;
+    Ok(())
+}
+
+/// The labels are written as a json object, the empty ones aren't written at all
+fn labels_column(labels: &Labels) -> Result<Option<String>> {
+    if labels.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::to_string(labels)?))
+}
+
+/// Puts the labels of the column into the json document of the home or the room
+fn put_labels(entity: &mut Value, labels: Option<String>) -> Result<()> {
+    if let Some(labels) = labels {
+        let labels: Value =
+            serde_json::from_str(&labels).map_err(|e| StoreError::MalformedState(e.to_string()))?;
+        entity["labels"] = labels;
+    }
+    Ok(())
+}
+
+/// A [StateStore] backed by an embedded SQLite database. Homes, rooms and devices are stored in
+/// separate tables, so the database might be queried by any external tool as well. The
+/// database is located in the `.smart-home/smart-home.db` file under the given root directory.
+///
+/// Each save is done in a single immediate transaction, so concurrent writers (even from
+/// different processes) are serialized by SQLite itself.
+pub struct SqliteStore {
+    path: PathBuf,
+    connection: Mutex<Option<Connection>>,
+}
+
+impl SqliteStore {
+    pub fn new(root: PathBuf) -> Self {
+        let mut path = root;
+        path.push(REPO_DIR);
+        path.push(SMART_HOME_DB);
+
+        Self {
+            path,
+            connection: Mutex::new(None),
+        }
+    }
+
+    /// Returns a locked connection to the database. The connection is opened lazily on the
+    /// first call, and it never creates a new database file
+    fn connection(&self) -> Result<MutexGuard<'_, Option<Connection>>> {
+        let mut guard = self
+            .connection
+            .lock()
+            .map_err(|e| anyhow!("Unable lock the database connection: {e}"))?;
+
+        if guard.is_none() {
+            if !self.is_initialized() {
+                return Err(StoreError::NotInitialized.into());
+            }
+            let mut connection = Connection::open_with_flags(&self.path, OpenFlags::default())?;
+            connection.busy_timeout(BUSY_TIMEOUT)?;
+            connection.execute_batch("PRAGMA foreign_keys = ON;")?;
+            connection.execute_batch(META_SCHEMA)?;
+            add_missing_columns(&mut connection)?;
+            *guard = Some(connection);
+        }
+
+        Ok(guard)
+    }
+}
+
+/// Copies the database into the new file. The copy is made by `VACUUM INTO`, which reads the
+/// whole database in a single transaction, so a save made in the middle of the copy is either
+/// entirely in the copy or not there at all. A plain copy of the file might tear such save.
+pub(crate) fn copy_database(from: &Path, to: &Path) -> Result<()> {
+    let connection = Connection::open_with_flags(from, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    connection.busy_timeout(BUSY_TIMEOUT)?;
+    connection.execute("VACUUM INTO ?1", params![to.to_string_lossy()])?;
+    Ok(())
+}
+
+fn read_revision(transaction: &Transaction) -> Result<Revision> {
+    let revision =
+        transaction.query_row("SELECT value FROM meta WHERE key = 'revision'", [], |row| {
+            row.get::<_, i64>(0)
+        })?;
+    Ok(revision as Revision)
+}
+
+/// The databases created before the versioning was introduced have no schema version in the
+/// `meta` table. They have the same format as the version 1 documents.
+fn read_schema_version(transaction: &Transaction) -> Result<SchemaVersion> {
+    let version = transaction
+        .query_row(
+            "SELECT value FROM meta WHERE key = 'schema_version'",
+            [],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()?;
+    Ok(version.map_or(1, |version| version as SchemaVersion))
+}
+
+/// Assembles the json document of the stored version from the tables. The rooms and the devices
+/// which have no parent are not included.
+fn read_document(transaction: &Transaction) -> Result<Value> {
+    let revision = read_revision(transaction)?;
+    let version = read_schema_version(transaction)?;
+
+    let mut devices: HashMap<String, Vec<Value>> = HashMap::new();
+    let mut statement =
+        transaction.prepare("SELECT room_id, data FROM devices ORDER BY room_id, position")?;
+    let rows = statement.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?;
+    for row in rows {
+        let (room_id, data) = row?;
+        let device: Value =
+            serde_json::from_str(&data).map_err(|e| StoreError::MalformedState(e.to_string()))?;
+        devices.entry(room_id).or_default().push(device);
+    }
+
+    let mut rooms: HashMap<String, Vec<Value>> = HashMap::new();
+    let mut statement = transaction.prepare(
+        "SELECT id, home_id, name, description, labels FROM rooms ORDER BY home_id, position",
+    )?;
+    let rows = statement.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, Option<String>>(3)?,
+            row.get::<_, Option<String>>(4)?,
+        ))
+    })?;
+    for row in rows {
+        let (id, home_id, name, description, labels) = row?;
+        let mut room = json!({
+            "devices": devices.remove(&id).unwrap_or_default(),
+            "id": id,
+            "name": name,
+            "description": description,
+        });
+        put_labels(&mut room, labels)?;
+        rooms.entry(home_id).or_default().push(room);
+    }
+
+    let mut homes: Vec<Value> = vec![];
+    let mut statement =
+        transaction.prepare("SELECT id, name, description, labels FROM homes ORDER BY position")?;
+    let rows = statement.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<String>>(2)?,
+            row.get::<_, Option<String>>(3)?,
+        ))
+    })?;
+    for row in rows {
+        let (id, name, description, labels) = row?;
+        let mut home = json!({
+            "rooms": rooms.remove(&id).unwrap_or_default(),
+            "id": id,
+            "name": name,
+            "description": description,
+        });
+        put_labels(&mut home, labels)?;
+        homes.push(home);
+    }
+
+    let homes = if homes.is_empty() {
+        Value::Null
+    } else {
+        Value::Array(homes)
+    };
+    // the version 0 had no envelope at all
+    let document = match version {
+        0 => homes,
+        1 => json!({ "revision": revision, "homes": homes }),
+        _ => json!({ "schema_version": version, "revision": revision, "homes": homes }),
+    };
+    Ok(document)
+}
+
+impl StateStore for SqliteStore {
+    fn initialize(&self) -> Result<()> {
+        if self.is_initialized() {
+            return Err(anyhow!(format!(
+                "Repository {} already exists",
+                self.path.display()
+            )));
+        }
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let connection = Connection::open(&self.path)?;
+        connection.execute_batch(SCHEMA)?;
+        connection.execute_batch(META_SCHEMA)?;
+        connection.execute(
+            "INSERT INTO meta (key, value) VALUES ('schema_version', ?1)",
+            params![SCHEMA_VERSION],
+        )?;
+        Ok(())
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.path.exists()
+    }
+
+    fn schema_version(&self) -> Result<SchemaVersion> {
+        let mut guard = self.connection()?;
+        // It's safe to unwrap, the connection is always opened by `connection` function
+        let connection = guard.as_mut().unwrap();
+        let transaction = connection.transaction()?;
+        read_schema_version(&transaction)
+    }
+
+    /// The tables are read into the versioned json document, so the same migrations as for
+    /// the json file store are applied to the stored devices.
+    fn load(&self) -> Result<VersionedState> {
+        let mut guard = self.connection()?;
+        // It's safe to unwrap, the connection is always opened by `connection` function
+        let connection = guard.as_mut().unwrap();
+        let transaction = connection.transaction()?;
+        let revision = read_revision(&transaction)?;
+
+        let mut state = schema::decode(read_document(&transaction)?)?;
+        state.revision = revision;
+        Ok(state)
+    }
+
+    fn load_document(&self) -> Result<Value> {
+        let mut guard = self.connection()?;
+        // It's safe to unwrap, the connection is always opened by `connection` function
+        let connection = guard.as_mut().unwrap();
+        let transaction = connection.transaction()?;
+        read_document(&transaction)
+    }
+
+    fn revision(&self) -> Result<Revision> {
+        let mut guard = self.connection()?;
+        // It's safe to unwrap, the connection is always opened by `connection` function
+        let connection = guard.as_mut().unwrap();
+        let transaction = connection.transaction()?;
+        read_revision(&transaction)
+    }
+
+    /// The journal records are inserted in the same transaction as the state
+    fn save_with_journal(
+        &self,
+        state: &SavedSmartHome,
+        base: Revision,
+        records: &[String],
+    ) -> Result<Revision> {
+        let mut guard = self.connection()?;
+        // It's safe to unwrap, the connection is always opened by `connection` function
+        let connection = guard.as_mut().unwrap();
+        let transaction = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let current = read_revision(&transaction)?;
+        if current != base {
+            return Err(StoreError::ConcurrentModification(base, current).into());
+        }
+
+        for record in records {
+            transaction.execute(
+                "INSERT INTO logs (log, record) VALUES (?1, ?2)",
+                params![JOURNAL_LOG, record],
+            )?;
+        }
+
+        transaction.execute("DELETE FROM homes", [])?;
+
+        for (home_position, home) in state.iter().flatten().enumerate() {
+            transaction.execute(
+                "INSERT INTO homes (id, position, name, description, labels) \
+                    VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    home.id,
+                    home_position,
+                    home.name,
+                    home.description,
+                    labels_column(&home.labels)?
+                ],
+            )?;
+
+            for (room_position, room) in home.rooms.iter().enumerate() {
+                transaction.execute(
+                    "INSERT INTO rooms (id, home_id, position, name, description, labels) \
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        room.id,
+                        home.id,
+                        room_position,
+                        room.name,
+                        room.description,
+                        labels_column(&room.labels)?
+                    ],
+                )?;
+
+                for (device_position, device) in room.devices.iter().enumerate() {
+                    transaction.execute(
+                        "INSERT INTO devices (id, room_id, position, kind, name, data) \
+                            VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                        params![
+                            device.id(),
+                            room.id,
+                            device_position,
+                            device.device_type().tag(),
+                            device.name(),
+                            serde_json::to_string(device)?
+                        ],
+                    )?;
+                }
+            }
+        }
+
+        let revision = current + 1;
+        transaction.execute(
+            "UPDATE meta SET value = ?1 WHERE key = 'revision'",
+            params![revision as i64],
+        )?;
+        // the whole state is written in the current format
+        transaction.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', ?1)",
+            params![SCHEMA_VERSION],
+        )?;
+
+        transaction.commit()?;
+        Ok(revision)
+    }
+
+    fn append_log(&self, log: &str, records: &[String]) -> Result<()> {
+        let mut guard = self.connection()?;
+        // It's safe to unwrap, the connection is always opened by `connection` function
+        let connection = guard.as_mut().unwrap();
+        let transaction = connection.transaction()?;
+
+        for record in records {
+            transaction.execute(
+                "INSERT INTO logs (log, record) VALUES (?1, ?2)",
+                params![log, record],
+            )?;
+        }
+
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn read_log(&self, log: &str) -> Result<Vec<String>> {
+        let guard = self.connection()?;
+        // It's safe to unwrap, the connection is always opened by `connection` function
+        let connection = guard.as_ref().unwrap();
+
+        let mut statement =
+            connection.prepare("SELECT record FROM logs WHERE log = ?1 ORDER BY seq")?;
+        let records = statement
+            .query_map(params![log], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(records)
+    }
+
+    fn last_log_record(&self, log: &str) -> Result<Option<String>> {
+        let guard = self.connection()?;
+        // It's safe to unwrap, the connection is always opened by `connection` function
+        let connection = guard.as_ref().unwrap();
+
+        let record = connection
+            .query_row(
+                "SELECT record FROM logs WHERE log = ?1 ORDER BY seq DESC LIMIT 1",
+                params![log],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+        Ok(record)
+    }
+
+    /// The records are replaced in a single immediate transaction
+    fn rewrite_log(
+        &self,
+        log: &str,
+        rewrite: &mut dyn FnMut(Vec<String>) -> Result<Vec<String>>,
+    ) -> Result<()> {
+        let mut guard = self.connection()?;
+        // It's safe to unwrap, the connection is always opened by `connection` function
+        let connection = guard.as_mut().unwrap();
+        let transaction = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let records = transaction
+            .prepare("SELECT record FROM logs WHERE log = ?1 ORDER BY seq")?
+            .query_map(params![log], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        let records = rewrite(records)?;
+
+        transaction.execute("DELETE FROM logs WHERE log = ?1", params![log])?;
+        for record in records {
+            transaction.execute(
+                "INSERT INTO logs (log, record) VALUES (?1, ?2)",
+                params![log, record],
+            )?;
+        }
+
+        transaction.commit()?;
+        Ok(())
+    }
+}
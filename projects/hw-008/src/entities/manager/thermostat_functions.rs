@@ -0,0 +1,265 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+
+use crate::entities::devices::{
+    Decision, Device, DeviceId, EnvironmentSensor, RoomTemperature, Socket, Thermometer,
+    Thermostat, ThermostatMode,
+};
+use crate::entities::manager::events::SmartHomeEvent;
+use crate::entities::manager::indexed_state::IndexedState;
+use crate::entities::manager::journal::EntryKind;
+use crate::entities::manager::smart_home::SmartHomeManager;
+use crate::entities::Quantity;
+
+/// The target temperatures a thermostat might keep, in °C
+pub const TARGET_RANGE: (f32, f32) = (5.0, 35.0);
+
+/// The changes of a thermostat, the omitted ones are kept as is
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ThermostatSettings {
+    pub target: Option<f32>,
+    pub hysteresis: Option<f32>,
+    pub mode: Option<ThermostatMode>,
+    /// The thermometer or the environment sensor of the same room
+    pub thermometer: Option<DeviceId>,
+    /// The sockets of the same room, they replace the linked ones
+    pub heaters: Option<Vec<DeviceId>>,
+}
+
+impl ThermostatSettings {
+    fn is_empty(&self) -> bool {
+        *self == ThermostatSettings::default()
+    }
+}
+
+pub trait ThermostatFunctions {
+    /// Changes the target, the hysteresis, the mode or the links of the thermostat with the
+    /// given id. The linked devices must be in the same room as the thermostat. Returns the
+    /// thermostat after the change.
+    fn configure_thermostat(&self, id: &str, settings: ThermostatSettings) -> Result<Thermostat>;
+
+    /// Makes a single step of the control loop: each thermostat reads the temperature from its
+    /// thermometer, and switches its heaters if the temperature crossed the threshold. The
+    /// simulated room of the thermometer warms up or cools down by the heaters from then on,
+    /// see [RoomTemperature]. All the switches are made as a single automatic change, so it's
+    /// not undone by the users, and each decision is recorded by its thermostat.
+    /// Returns the decisions made by the thermostats with their ids.
+    fn regulate(&self) -> Result<Vec<(DeviceId, Decision)>>;
+}
+
+impl ThermostatFunctions for SmartHomeManager {
+    fn configure_thermostat(&self, id: &str, settings: ThermostatSettings) -> Result<Thermostat> {
+        if settings.is_empty() {
+            return Err(anyhow!(
+                "Nothing to change, provide the target, the hysteresis, the mode, the thermometer \
+                 or the heaters"
+            ));
+        }
+        let (lowest, highest) = TARGET_RANGE;
+        if let Some(target) = settings.target.filter(|t| !(lowest..=highest).contains(t)) {
+            return Err(anyhow!(
+                "The target must be from {lowest}°C to {highest}°C, got {target}°C"
+            ));
+        }
+        if let Some(hysteresis) = settings.hysteresis.filter(|h| !(0.0..=5.0).contains(h)) {
+            return Err(anyhow!(
+                "The hysteresis must be from 0°C to 5°C, got {hysteresis}°C"
+            ));
+        }
+
+        self.commit(|state| {
+            let id = id.to_string();
+            let mut config = thermostat(state, &id)?.config();
+            let mut events = vec![];
+            let room = state
+                .room_by_device_id(&id)
+                .ok_or_else(|| anyhow!("Unable find associated room for device: {id}"))?;
+            let in_room = |linked: &DeviceId| -> Result<&Device> {
+                room.devices
+                    .iter()
+                    .find(|device| device.id() == linked)
+                    .ok_or_else(|| anyhow!("The device {linked} is not in the room {}", room.id))
+            };
+
+            if let Some(linked) = &settings.thermometer {
+                let device = in_room(linked)?;
+                if device.downcast_ref::<Thermometer>().is_none()
+                    && device.downcast_ref::<EnvironmentSensor>().is_none()
+                {
+                    return Err(anyhow!(
+                        "The device {linked} is a {}, it doesn't measure the temperature",
+                        device.device_type()
+                    ));
+                }
+                config.thermometer = Some(linked.clone());
+                // the linked sensor measures the simulated room from now on
+                let mut sensor = device.clone();
+                if let Some(room @ None) = room_of(&mut sensor) {
+                    *room = Some(RoomTemperature::new(Utc::now()));
+                    events.push(SmartHomeEvent::DeviceReplaced { device: sensor });
+                }
+            }
+            if let Some(heaters) = &settings.heaters {
+                for linked in heaters {
+                    let device = in_room(linked)?;
+                    if device.downcast_ref::<Socket>().is_none() {
+                        return Err(anyhow!(
+                            "The device {linked} is a {}, not a socket",
+                            device.device_type()
+                        ));
+                    }
+                }
+                config.heaters = heaters.clone();
+            }
+            config.target = settings.target.unwrap_or(config.target);
+            config.hysteresis = settings.hysteresis.unwrap_or(config.hysteresis);
+            config.mode = settings.mode.unwrap_or(config.mode);
+
+            let mut thermostat = thermostat(state, &id)?;
+            thermostat.set_config(config.clone());
+            events.push(SmartHomeEvent::ThermostatConfigured {
+                device_id: id,
+                config,
+            });
+            Ok((events, thermostat))
+        })
+    }
+
+    fn regulate(&self) -> Result<Vec<(DeviceId, Decision)>> {
+        self.commit_as(EntryKind::Automatic, |state| {
+            let now = Utc::now();
+            let mut events = vec![];
+            let mut decisions = vec![];
+
+            let thermostats = state
+                .devices()
+                .filter_map(|device| device.downcast_ref::<Thermostat>());
+            for thermostat in thermostats {
+                // the missing or the switched off thermometer gives no temperature, so nothing
+                // is switched until it's back
+                let sensor = thermostat
+                    .thermometer
+                    .as_ref()
+                    .and_then(|id| state.device(id));
+                let temperature = sensor
+                    .and_then(|device| device.measure().ok())
+                    .and_then(|measurements| {
+                        measurements
+                            .into_iter()
+                            .find(|m| m.quantity == Quantity::Temperature)
+                    })
+                    .map(|measurement| measurement.value);
+                let heaters = || thermostat.heaters.iter().filter_map(|id| state.device(id));
+
+                let decision = thermostat.decide(temperature, now);
+                if let Some(decision) = decision {
+                    for heater in heaters() {
+                        if heater.state().on != decision.heating {
+                            events.push(SmartHomeEvent::DeviceStatusChanged {
+                                device_id: heater.id().clone(),
+                                status: decision.heating,
+                                at: Some(now),
+                            });
+                        }
+                    }
+                    let mut thermostat = thermostat.clone();
+                    thermostat.record(decision);
+                    decisions.push((thermostat.id.clone(), decision));
+                    events.push(SmartHomeEvent::DeviceReplaced {
+                        device: Device::new(thermostat),
+                    });
+                }
+
+                // the room warms up while any of the heaters is on, even the one switched by
+                // hand, and cools down otherwise
+                let heating = match decision {
+                    Some(decision) => decision.heating,
+                    None => heaters().any(|heater| heater.state().on),
+                };
+                let Some(mut sensor) = sensor.cloned() else {
+                    continue;
+                };
+                let changed = match room_of(&mut sensor) {
+                    Some(Some(room)) if room.heating != heating => {
+                        room.set_heating(heating, now);
+                        true
+                    }
+                    Some(room @ None) => {
+                        *room = Some(RoomTemperature {
+                            heating,
+                            ..RoomTemperature::new(now)
+                        });
+                        true
+                    }
+                    _ => false,
+                };
+                if changed {
+                    events.push(SmartHomeEvent::DeviceReplaced { device: sensor });
+                }
+            }
+            Ok((events, decisions))
+        })
+    }
+}
+
+/// Adds the events unlinking the devices, which the given events take away from the room of
+/// their thermostat, e.g. the heater moved to another room or the removed thermometer. This way
+/// the linked devices stay in the same room as the thermostat after the moves and the removals,
+/// the same as [ThermostatFunctions::configure_thermostat] requires.
+pub(crate) fn unlinking_thermostats(
+    state: &IndexedState,
+    mut events: Vec<SmartHomeEvent>,
+) -> Result<Vec<SmartHomeEvent>> {
+    let mut after = state.clone();
+    for event in &events {
+        event.apply(&mut after)?;
+    }
+
+    let mut unlinks = vec![];
+    for room in after.rooms() {
+        let in_room = |id: &DeviceId| room.devices.iter().any(|device| device.id() == id);
+        let thermostats = room
+            .devices
+            .iter()
+            .filter_map(|device| device.downcast_ref::<Thermostat>());
+        for thermostat in thermostats {
+            let mut config = thermostat.config();
+            config.thermometer = config.thermometer.filter(in_room);
+            config.heaters.retain(in_room);
+            if config != thermostat.config() {
+                unlinks.push(SmartHomeEvent::ThermostatConfigured {
+                    device_id: thermostat.id.clone(),
+                    config,
+                });
+            }
+        }
+    }
+    events.extend(unlinks);
+    Ok(events)
+}
+
+/// The simulated room measured by the thermometer or the environment sensor, the other devices
+/// have none
+fn room_of(device: &mut Device) -> Option<&mut Option<RoomTemperature>> {
+    if device.downcast_ref::<Thermometer>().is_some() {
+        device
+            .downcast_mut::<Thermometer>()
+            .map(|thermometer| &mut thermometer.room)
+    } else {
+        device
+            .downcast_mut::<EnvironmentSensor>()
+            .map(|sensor| &mut sensor.room)
+    }
+}
+
+fn thermostat(state: &IndexedState, id: &DeviceId) -> Result<Thermostat> {
+    let device = state
+        .device(id)
+        .ok_or_else(|| anyhow!("Device with id: {id} not found"))?;
+    device.downcast_ref::<Thermostat>().cloned().ok_or_else(|| {
+        anyhow!(
+            "The device {id} is a {}, not a thermostat",
+            device.device_type()
+        )
+    })
+}
@@ -0,0 +1,79 @@
+use std::ops::Deref;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+
+use crate::entities::manager::events::SmartHomeEvent;
+use crate::entities::manager::smart_home::SmartHomeManager;
+use crate::entities::manager::store::InMemoryStore;
+
+/// A set of changes, which are staged one by one, but written to the store all at once. The
+/// transaction is just a [SmartHomeManager] on top of a private in-memory copy of the smart
+/// home, so all the manager functions (creating, removing, finding, etc.) might be used with
+/// the transaction as is. Each staged change is validated against the copy right away, so the
+/// ids of the created entities are known before the commit.
+///
+/// Nobody else sees the staged changes until the transaction is committed by
+/// [TransactionFunctions::commit_transaction]. Dropping the transaction rolls it back.
+pub struct Transaction {
+    staged: Arc<SmartHomeManager>,
+}
+
+impl Transaction {
+    /// Returns the manager, which stages the changes of this transaction
+    pub fn manager(&self) -> Arc<SmartHomeManager> {
+        self.staged.clone()
+    }
+
+    /// Returns all the events staged by the transaction in the order they were made
+    pub fn staged_events(&self) -> Result<Vec<SmartHomeEvent>> {
+        let entries = self.staged.journal().entries()?;
+        Ok(entries.into_iter().flat_map(|entry| entry.events).collect())
+    }
+
+    /// Discards all the staged changes. It's the same as just dropping the transaction.
+    pub fn rollback(self) {}
+}
+
+impl Deref for Transaction {
+    type Target = SmartHomeManager;
+
+    fn deref(&self) -> &Self::Target {
+        &self.staged
+    }
+}
+
+pub trait TransactionFunctions {
+    /// Starts a new transaction based on the current state of the smart home
+    fn begin_transaction(&self) -> Result<Transaction>;
+
+    /// Writes all the changes staged by the transaction as a single change. Returns the number
+    /// of the written events.
+    fn commit_transaction(&self, transaction: Transaction) -> Result<usize>;
+}
+
+impl TransactionFunctions for SmartHomeManager {
+    fn begin_transaction(&self) -> Result<Transaction> {
+        let state = self.read_smart_home_status()?;
+        let store = Arc::new(InMemoryStore::new(state));
+
+        Ok(Transaction {
+            staged: Arc::new(
+                SmartHomeManager::with_store(store).with_naming_rules(self.naming_rules().clone()),
+            ),
+        })
+    }
+
+    /// The staged events are applied to the up-to-date state, not to the state the transaction
+    /// was started from. So, the changes made by others in the meantime are kept, and the whole
+    /// transaction fails if any of its events doesn't match the current state anymore, e.g. the
+    /// room was created in a home which was removed by someone else.
+    fn commit_transaction(&self, transaction: Transaction) -> Result<usize> {
+        let events = transaction.staged_events()?;
+        let count = events.len();
+
+        self.commit(|_| Ok((events, ())))
+            .map_err(|msg| anyhow!("Unable commit the transaction: {msg}"))?;
+        Ok(count)
+    }
+}
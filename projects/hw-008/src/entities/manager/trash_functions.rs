@@ -0,0 +1,508 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::entities::devices::Device;
+use crate::entities::house::{Home, HomeId, Room, RoomId};
+use crate::entities::manager::events::SmartHomeEvent;
+use crate::entities::manager::indexed_state::IndexedState;
+use crate::entities::manager::journal::{EntryKind, JournalEntry, Snapshot, SNAPSHOTS_LOG};
+use crate::entities::manager::smart_home::SmartHomeManager;
+use crate::entities::manager::store::JOURNAL_LOG;
+
+/// The name of the log with the entities hidden from the trash
+pub(crate) const TRASH_LOG: &str = "trash";
+
+/// The name of the log with the entities purged from the journal
+pub(crate) const PURGED_LOG: &str = "purged";
+
+/// The number of days the removed entities are kept in the trash, the older ones are purged
+pub const TRASH_RETENTION_DAYS: i64 = 30;
+
+/// The removed entity with all its content, together with the parent it was removed from
+#[derive(Debug, Clone)]
+pub enum Trashed {
+    Home(Home),
+    Room { home_id: HomeId, room: Room },
+    Device { room_id: RoomId, device: Device },
+}
+
+impl Trashed {
+    pub fn id(&self) -> &str {
+        match self {
+            Trashed::Home(home) => &home.id,
+            Trashed::Room { room, .. } => &room.id,
+            Trashed::Device { device, .. } => device.id(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Trashed::Home(home) => &home.name,
+            Trashed::Room { room, .. } => &room.name,
+            Trashed::Device { device, .. } => device.name(),
+        }
+    }
+
+    /// Either `home`, `room` or `device`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Trashed::Home(_) => "home",
+            Trashed::Room { .. } => "room",
+            Trashed::Device { .. } => "device",
+        }
+    }
+
+    /// The id of the home the room was removed from, or the id of the room the device was
+    /// removed from
+    pub fn parent_id(&self) -> Option<&str> {
+        match self {
+            Trashed::Home(_) => None,
+            Trashed::Room { home_id, .. } => Some(home_id),
+            Trashed::Device { room_id, .. } => Some(room_id),
+        }
+    }
+
+    /// Lists the entity and all its content as `(depth, kind, id, name)`, the entity itself has
+    /// the zero depth
+    pub fn affected(&self) -> Vec<(usize, &'static str, String, String)> {
+        fn room_tree(depth: usize, room: &Room) -> Vec<(usize, &'static str, String, String)> {
+            let mut affected = vec![(depth, "room", room.id.clone(), room.name.clone())];
+            for device in &room.devices {
+                let (id, name) = (device.id().clone(), device.name().to_string());
+                affected.push((depth + 1, "device", id, name));
+            }
+            affected
+        }
+
+        match self {
+            Trashed::Home(home) => {
+                let mut affected = vec![(0, "home", home.id.clone(), home.name.clone())];
+                for room in &home.rooms {
+                    affected.extend(room_tree(1, room));
+                }
+                affected
+            }
+            Trashed::Room { room, .. } => room_tree(0, room),
+            Trashed::Device { device, .. } => {
+                let (id, name) = (device.id().clone(), device.name().to_string());
+                vec![(0, "device", id, name)]
+            }
+        }
+    }
+
+    fn from_event(event: &SmartHomeEvent) -> Option<Self> {
+        match event {
+            SmartHomeEvent::HomeRemoved { home } => Some(Trashed::Home(home.clone())),
+            SmartHomeEvent::RoomRemoved { home_id, room } => Some(Trashed::Room {
+                home_id: home_id.clone(),
+                room: room.clone(),
+            }),
+            SmartHomeEvent::DeviceRemoved { room_id, device } => Some(Trashed::Device {
+                room_id: room_id.clone(),
+                device: device.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Writes the entity and its content as an indented tree, an entity per line, e.g.
+/// `home home_01GN... (My Home)` followed by `  room room_01GN... (Kitchen)`
+impl Display for Trashed {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        let lines: Vec<String> = self
+            .affected()
+            .into_iter()
+            .map(|(depth, kind, id, name)| format!("{}{kind} {id} ({name})", "  ".repeat(depth)))
+            .collect();
+        write!(formatter, "{}", lines.join("\n"))
+    }
+}
+
+/// An entity in the trash
+#[derive(Debug, Clone)]
+pub struct TrashEntry {
+    pub entity: Trashed,
+    pub removed_at: DateTime<Utc>,
+    pub removed_by: String,
+}
+
+/// A one-line description, e.g.
+/// `room room_01GN... (Kitchen) from home_01GN..., 2 nested, removed 2023-01-31 12:00:00 by bob`
+impl Display for TrashEntry {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        let entity = &self.entity;
+        write!(
+            formatter,
+            "{} {} ({})",
+            entity.kind(),
+            entity.id(),
+            entity.name()
+        )?;
+        if let Some(parent_id) = entity.parent_id() {
+            write!(formatter, " from {parent_id}")?;
+        }
+        let nested = entity.affected().len() - 1;
+        if nested > 0 {
+            write!(formatter, ", {nested} nested")?;
+        }
+        write!(
+            formatter,
+            ", removed {} by {}",
+            self.removed_at.format("%Y-%m-%d %H:%M:%S"),
+            self.removed_by
+        )
+    }
+}
+
+/// The entities hidden from the trash: the ones removed before the given moment, either all of
+/// them or the one with the given id only
+#[derive(Debug, Serialize, Deserialize)]
+struct Hide {
+    at: DateTime<Utc>,
+    before: DateTime<Utc>,
+    id: Option<String>,
+}
+
+impl Hide {
+    fn covers(&self, entry: &TrashEntry) -> bool {
+        entry.removed_at <= self.before
+            && self.id.as_deref().is_none_or(|id| id == entry.entity.id())
+    }
+}
+
+/// The removal of the entity which was purged, so it's not purged once again
+#[derive(Debug, Serialize, Deserialize)]
+struct Purge {
+    at: DateTime<Utc>,
+    id: String,
+    removed_at: DateTime<Utc>,
+}
+
+impl Purge {
+    fn covers(&self, entry: &TrashEntry) -> bool {
+        self.id == entry.entity.id() && self.removed_at == entry.removed_at
+    }
+}
+
+/// The removed homes, rooms and devices are not gone at once, they are kept in the trash for
+/// [TRASH_RETENTION_DAYS] days. The trash is not stored anywhere on its own: the journal keeps
+/// every removed entity with all its content already, so the trash is made of the entities
+/// removed by the journaled changes which are not back in the smart home. Thus, the undone
+/// removal takes the entity out of the trash as well, whereas the undone creation doesn't put
+/// the entity into the trash, it was never removed by anyone.
+///
+/// The hidden entities are just not listed in the trash anymore, but they are still in the
+/// journal and the snapshots, so they might be found by the `history` and restored from there.
+/// Once the retention period is over, the entities are purged: the journal and the snapshots
+/// keep only the ids, the names and the types of them, everything else is dropped. The removals
+/// of the purged entities can't be undone anymore.
+pub trait TrashFunctions {
+    /// Lists the entities in the trash from the oldest removal to the latest one
+    fn trash(&self) -> Result<Vec<TrashEntry>>;
+
+    /// Puts the entity from the trash back to the parent it was removed from, with the same id
+    /// and the same content. The parent must be in the smart home, so the home has to be
+    /// restored before its rooms. The restore is journaled, so it might be undone.
+    fn restore_from_trash(&self, id: &str) -> Result<TrashEntry>;
+
+    /// Hides the entity with the given id from the trash, or the whole trash. With `older_than`
+    /// only the entities removed earlier than that are hidden. Returns the hidden entries.
+    fn hide_from_trash(
+        &self,
+        id: Option<&str>,
+        older_than: Option<Duration>,
+    ) -> Result<Vec<TrashEntry>>;
+
+    /// Purges the entities removed more than [TRASH_RETENTION_DAYS] days before `now` from the
+    /// journal and the snapshots, unless they are back in the smart home. Returns the purged
+    /// entries, the ones purged already are not purged once again.
+    fn purge_trash(&self, now: DateTime<Utc>) -> Result<Vec<TrashEntry>>;
+}
+
+impl TrashFunctions for SmartHomeManager {
+    fn trash(&self) -> Result<Vec<TrashEntry>> {
+        let entries = self.journal().entries()?;
+        let hidden = self
+            .store()
+            .read_log(TRASH_LOG)?
+            .iter()
+            .map(|record| serde_json::from_str(record))
+            .collect::<serde_json::Result<Vec<Hide>>>()
+            .map_err(|e| anyhow!("Unable read the trash: {e}"))?;
+
+        self.with_state(|state| collect_trash(&entries, &hidden, state, Utc::now()))
+    }
+
+    fn restore_from_trash(&self, id: &str) -> Result<TrashEntry> {
+        let entry = self
+            .trash()?
+            .into_iter()
+            .find(|entry| entry.entity.id() == id)
+            .ok_or_else(|| anyhow!("Entity with id: {id} not found in the trash"))?;
+
+        self.commit(|state| {
+            let event = match &entry.entity {
+                Trashed::Home(home) => SmartHomeEvent::HomeCreated { home: home.clone() },
+                Trashed::Room { home_id, room } => {
+                    if state.home(home_id).is_none() {
+                        return Err(anyhow!(
+                            "The home {home_id} of the room is not there, restore it first"
+                        ));
+                    }
+                    SmartHomeEvent::RoomCreated {
+                        home_id: home_id.clone(),
+                        room: room.clone(),
+                    }
+                }
+                Trashed::Device { room_id, device } => {
+                    if state.room(room_id).is_none() {
+                        return Err(anyhow!(
+                            "The room {room_id} of the device is not there, restore it first"
+                        ));
+                    }
+                    SmartHomeEvent::DeviceCreated {
+                        room_id: room_id.clone(),
+                        device: device.clone(),
+                    }
+                }
+            };
+            Ok((vec![event.restamped(Utc::now())], entry.clone()))
+        })
+        .map_err(|msg| anyhow!("Unable restore {id} from the trash: {msg}"))
+    }
+
+    fn hide_from_trash(
+        &self,
+        id: Option<&str>,
+        older_than: Option<Duration>,
+    ) -> Result<Vec<TrashEntry>> {
+        let now = Utc::now();
+        let hide = Hide {
+            at: now,
+            before: now - older_than.unwrap_or_else(Duration::zero),
+            id: id.map(str::to_string),
+        };
+
+        let hidden: Vec<TrashEntry> = self
+            .trash()?
+            .into_iter()
+            .filter(|entry| hide.covers(entry))
+            .collect();
+        if let (Some(id), true) = (id, hidden.is_empty()) {
+            return Err(anyhow!("Entity with id: {id} not found in the trash"));
+        }
+
+        if !hidden.is_empty() {
+            self.store()
+                .append_log(TRASH_LOG, &[serde_json::to_string(&hide)?])?;
+        }
+        Ok(hidden)
+    }
+
+    fn purge_trash(&self, now: DateTime<Utc>) -> Result<Vec<TrashEntry>> {
+        let expired = now - Duration::days(TRASH_RETENTION_DAYS);
+        let purged = self
+            .store()
+            .read_log(PURGED_LOG)?
+            .iter()
+            .map(|record| serde_json::from_str(record))
+            .collect::<serde_json::Result<Vec<Purge>>>()
+            .map_err(|e| anyhow!("Unable read the purged entities: {e}"))?;
+
+        let entries = self.journal().entries()?;
+        let purging: Vec<TrashEntry> = self.with_state(|state| {
+            removals(&entries, state)
+                .into_iter()
+                .filter(|entry| entry.removed_at <= expired)
+                .filter(|entry| !purged.iter().any(|purge| purge.covers(entry)))
+                .collect()
+        })?;
+        if purging.is_empty() {
+            return Ok(purging);
+        }
+
+        let removed: HashSet<String> = purging
+            .iter()
+            .map(|entry| entry.entity.id().to_string())
+            .collect();
+        let ids: HashSet<String> = purging
+            .iter()
+            .flat_map(|entry| entry.entity.affected())
+            .map(|(_, _, id, _)| id)
+            .collect();
+
+        self.store().rewrite_log(JOURNAL_LOG, &mut |records| {
+            rewrite_records(records, |entry: &mut JournalEntry| {
+                // the removal can't be undone, there is nothing to restore anymore
+                if entry.events.iter().any(|event| {
+                    Trashed::from_event(event).is_some_and(|entity| removed.contains(entity.id()))
+                }) {
+                    entry.inverse.clear();
+                }
+                for event in entry.events.iter_mut().chain(entry.inverse.iter_mut()) {
+                    purge_event(event, &ids);
+                }
+            })
+        })?;
+        self.store().rewrite_log(SNAPSHOTS_LOG, &mut |records| {
+            rewrite_records(records, |snapshot: &mut Snapshot| {
+                for home in snapshot.state.iter_mut().flatten() {
+                    purge_home(home, &ids);
+                }
+            })
+        })?;
+
+        let records = purging
+            .iter()
+            .map(|entry| {
+                serde_json::to_string(&Purge {
+                    at: now,
+                    id: entry.entity.id().to_string(),
+                    removed_at: entry.removed_at,
+                })
+            })
+            .collect::<serde_json::Result<Vec<String>>>()?;
+        self.store().append_log(PURGED_LOG, &records)?;
+        Ok(purging)
+    }
+}
+
+/// Rewrites each record with the given function. The records it doesn't change are kept as
+/// they are, so the records written in the older formats are not touched.
+fn rewrite_records<T: serde::Serialize + serde::de::DeserializeOwned>(
+    records: Vec<String>,
+    rewrite: impl Fn(&mut T),
+) -> Result<Vec<String>> {
+    records
+        .into_iter()
+        .map(|record| {
+            let mut value: T = serde_json::from_str(&record)?;
+            let before = serde_json::to_string(&value)?;
+            rewrite(&mut value);
+            let after = serde_json::to_string(&value)?;
+            Ok(if after == before { record } else { after })
+        })
+        .collect()
+}
+
+/// Drops the content of the purged entities from the event. The ids are kept, so the event is
+/// still replayed the same way.
+fn purge_event(event: &mut SmartHomeEvent, ids: &HashSet<String>) {
+    match event {
+        SmartHomeEvent::HomeCreated { home }
+        | SmartHomeEvent::HomeRemoved { home }
+        | SmartHomeEvent::HomeReplaced { home } => purge_home(home, ids),
+        SmartHomeEvent::RoomCreated { room, .. } | SmartHomeEvent::RoomRemoved { room, .. } => {
+            purge_room(room, ids)
+        }
+        SmartHomeEvent::DeviceCreated { device, .. }
+        | SmartHomeEvent::DeviceRemoved { device, .. }
+        | SmartHomeEvent::DeviceReplaced { device } => purge_device(device, ids),
+        SmartHomeEvent::DeviceMeasured { device_id, reading } if ids.contains(device_id) => {
+            *reading = None
+        }
+        SmartHomeEvent::EntityEdited {
+            id, description, ..
+        } if ids.contains(id) => *description = None,
+        SmartHomeEvent::LabelsChanged { id, labels } if ids.contains(id) => labels.clear(),
+        SmartHomeEvent::StateReplaced { state } => {
+            for home in state.iter_mut().flatten() {
+                purge_home(home, ids);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn purge_home(home: &mut Home, ids: &HashSet<String>) {
+    if ids.contains(&home.id) {
+        home.description = None;
+        home.labels.clear();
+    }
+    for room in &mut home.rooms {
+        purge_room(room, ids);
+    }
+}
+
+fn purge_room(room: &mut Room, ids: &HashSet<String>) {
+    if ids.contains(&room.id) {
+        room.description = None;
+        room.labels.clear();
+    }
+    for device in &mut room.devices {
+        purge_device(device, ids);
+    }
+}
+
+/// Replaces the device with a new one of the same type, name and id, so its settings, state
+/// and readings are dropped
+fn purge_device(device: &mut Device, ids: &HashSet<String>) {
+    if !ids.contains(device.id()) {
+        return;
+    }
+    match device.device_type().create(device.name(), None) {
+        Ok(mut purged) => {
+            purged.set_id(device.id().clone());
+            *device = purged;
+        }
+        // the type is not registered anymore, so only the common content is dropped
+        Err(_) => {
+            device.edit(device.name().to_string(), None);
+            device.labels_mut().clear();
+            device.state_mut().last_reading = None;
+        }
+    }
+}
+
+/// Collects the latest removal of each entity which is neither back in the smart home, nor
+/// expired, nor hidden. The undone changes remove only the entities they created, so they are
+/// skipped.
+fn collect_trash(
+    entries: &[JournalEntry],
+    hidden: &[Hide],
+    state: &IndexedState,
+    now: DateTime<Utc>,
+) -> Vec<TrashEntry> {
+    let expired = now - Duration::days(TRASH_RETENTION_DAYS);
+    removals(entries, state)
+        .into_iter()
+        .filter(|entry| entry.removed_at > expired)
+        .filter(|entry| !hidden.iter().any(|hide| hide.covers(entry)))
+        .collect()
+}
+
+/// Collects the latest removal of each entity which is not back in the smart home, in the
+/// order of the journal
+fn removals(entries: &[JournalEntry], state: &IndexedState) -> Vec<TrashEntry> {
+    // the removals are numbered, so the trash keeps the order of the journal
+    let mut latest: HashMap<String, (usize, TrashEntry)> = HashMap::new();
+    let removals = entries
+        .iter()
+        .filter(|entry| !matches!(entry.kind, EntryKind::Undo(_)))
+        .flat_map(|entry| {
+            entry
+                .events
+                .iter()
+                .filter_map(Trashed::from_event)
+                .map(|entity| TrashEntry {
+                    entity,
+                    removed_at: entry.timestamp,
+                    removed_by: entry.actor.clone(),
+                })
+        });
+    for (n, trashed) in removals.enumerate() {
+        latest.insert(trashed.entity.id().to_string(), (n, trashed));
+    }
+
+    let mut trash: Vec<(usize, TrashEntry)> = latest
+        .into_values()
+        .filter(|(_, entry)| !state.contains_id(entry.entity.id()))
+        .collect();
+    trash.sort_by_key(|(n, _)| *n);
+    trash.into_iter().map(|(_, entry)| entry).collect()
+}
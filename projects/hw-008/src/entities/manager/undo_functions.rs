@@ -0,0 +1,57 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+
+use crate::entities::manager::events::SmartHomeEvent;
+use crate::entities::manager::journal::{EntryKind, JournalEntry};
+use crate::entities::manager::smart_home::SmartHomeManager;
+
+/// The undo and redo of the changes. The stacks of the changes are not kept anywhere, they are
+/// replayed from the journal each time, so they survive restarts and they are shared by all the
+/// clients of the same repository. The undo reverts the latest change made by anyone.
+pub trait UndoFunctions {
+    /// Reverts the latest change, which was not undone yet. Returns the reverted change.
+    fn undo(&self) -> Result<JournalEntry>;
+
+    /// Repeats the latest undone change. It's possible only until a new change is made.
+    /// Returns the repeated change.
+    fn redo(&self) -> Result<JournalEntry>;
+}
+
+// the stacks are replayed under the lock of the commit, so the picked change is the latest one
+// for the very state it's applied to, even if another session commits at the same time
+impl UndoFunctions for SmartHomeManager {
+    fn undo(&self) -> Result<JournalEntry> {
+        let mut picked = None;
+        self.commit_journaled(|_| {
+            let (mut undo, _) = self.journal().undo_stacks()?;
+            let target = undo.pop().ok_or_else(|| anyhow!("Nothing to undo"))?;
+            picked = Some(target.revision);
+            let kind = EntryKind::Undo(target.revision);
+            Ok((kind, restamped(&target.inverse), target))
+        })
+        .map_err(|msg| match picked {
+            Some(revision) => anyhow!("Unable undo change #{revision}: {msg}"),
+            None => msg,
+        })
+    }
+
+    fn redo(&self) -> Result<JournalEntry> {
+        let mut picked = None;
+        self.commit_journaled(|_| {
+            let (_, mut redo) = self.journal().undo_stacks()?;
+            let target = redo.pop().ok_or_else(|| anyhow!("Nothing to redo"))?;
+            picked = Some(target.revision);
+            let kind = EntryKind::Redo(target.revision);
+            Ok((kind, restamped(&target.events), target))
+        })
+        .map_err(|msg| match picked {
+            Some(revision) => anyhow!("Unable redo change #{revision}: {msg}"),
+            None => msg,
+        })
+    }
+}
+
+fn restamped(events: &[SmartHomeEvent]) -> Vec<SmartHomeEvent> {
+    let now: DateTime<Utc> = Utc::now();
+    events.iter().map(|event| event.restamped(now)).collect()
+}
@@ -1,83 +1,180 @@
 use anyhow::{anyhow, Result};
-use std::fs;
+use chrono::Utc;
 
-use crate::entities::devices::{Device, DeviceStatus, SocketStatus};
-use crate::entities::house::Home;
+use crate::entities::devices::{DeviceId, DeviceState, DeviceStatus};
+use crate::entities::house::{Home, HomeId, RoomId};
+use crate::entities::manager::events::SmartHomeEvent;
 use crate::entities::manager::smart_home::{SavedSmartHome, SmartHomeManager};
-use crate::entities::manager::FindFunctions;
+use crate::entities::manager::thermostat_functions::unlinking_thermostats;
+use crate::entities::ValidationError;
 
 pub trait UpdateFunctions {
-    fn change_device_status(&mut self, device_id: &str, status: DeviceStatus) -> Result<()>;
+    /// Switches the device on or off. Returns the new state of the device.
+    fn change_device_status(&self, device_id: &str, status: DeviceStatus) -> Result<DeviceState>;
     fn update_state(&self, home: SavedSmartHome) -> Result<()>;
     fn update_home_state(&self, home: Home) -> Result<()>;
+
+    /// Changes the name of the home, room or device with the given id
+    fn rename(&self, id: &str, name: String) -> Result<()>;
+
+    /// Changes the name and/or the description of the home, room or device with the given id.
+    /// The omitted fields are kept as is, and the empty description removes the description.
+    fn edit(&self, id: &str, name: Option<String>, description: Option<String>) -> Result<()>;
+
+    /// Moves the room with all its devices to another home
+    fn move_room(&self, room_id: &RoomId, home_id: &HomeId) -> Result<()>;
+
+    /// Moves the device to another room. The moved device is unlinked from the thermostat of its
+    /// previous room, and the moved thermostat is unlinked from the devices of that room.
+    fn move_device(&self, device_id: &DeviceId, room_id: &RoomId) -> Result<()>;
 }
 
 impl UpdateFunctions for SmartHomeManager {
-    fn change_device_status(&mut self, device_id: &str, status: DeviceStatus) -> Result<()> {
-        if let Some(room) = self.find_room_by_device_id(&device_id.to_string()) {
-            // It's safe to unwrap the result, because otherwise `find_room_by_device_id`
-            // will return None
-            let device = self.find_device_by_id(&device_id.to_string()).unwrap();
-
-            match device {
-                Device::Socket(mut socket) => {
-                    socket.status = SocketStatus::from_bool(status);
-                }
-                Device::Thermometer(_) => {}
-            }
+    fn change_device_status(&self, device_id: &str, status: DeviceStatus) -> Result<DeviceState> {
+        let device_id = device_id.to_string();
 
-            if let Some(mut home) = self.find_home_by_room_id(&room.id) {
-                home.rooms.retain(|r| r.id != room.id);
-                home.rooms.push(room);
+        self.commit(|state| {
+            let device = state.device(&device_id).ok_or_else(|| {
+                anyhow!(format!(
+                    "Unable find associated room for device: {device_id}"
+                ))
+            })?;
 
-                match self.update_home_state(home) {
-                    Ok(_) => Ok(()),
-                    Err(msg) => Err(anyhow!(msg.to_string())),
-                }
-            } else {
-                Err(anyhow!(format!(
-                    "Unable find associated home for room: {}",
-                    room.id
-                )))
+            let mut new_state = device.state().clone();
+            if new_state.on == status {
+                // nothing is changed, so nothing is journaled
+                return Ok((vec![], new_state));
             }
-        } else {
-            Err(anyhow!(format!(
-                "Unable find associated room for device: {device_id}"
-            )))
-        }
+            new_state.on = status;
+            new_state.changed_at = Some(Utc::now());
+
+            let event = SmartHomeEvent::DeviceStatusChanged {
+                device_id: device_id.clone(),
+                status,
+                at: new_state.changed_at,
+            };
+            Ok((vec![event], new_state))
+        })
     }
 
+    /// Replaces the whole state. The new state is expected to be derived from the state read
+    /// from this manager, so it fails if the stored state was changed since then.
     fn update_state(&self, home: SavedSmartHome) -> Result<()> {
-        if self.is_smart_home_repo_exists() {
-            let file = self.get_state_file()?;
-            let content = serde_json::to_string(&home).unwrap();
-            fs::write(file, content).expect("Unable write initial data");
-            Ok(())
-        } else {
-            Err(anyhow!(
-                "No repository found. Consider to init repository first"
-            ))
-        }
+        self.commit_checked(|_| Ok((vec![SmartHomeEvent::StateReplaced { state: home }], ())))
     }
 
+    /// Replaces the home with the same id, or adds a new one. The home is expected to be read
+    /// from this manager, so it fails if the stored state was changed since then.
     fn update_home_state(&self, home: Home) -> Result<()> {
-        let status = self.read_smart_home_status()?;
+        self.commit_checked(|_| Ok((vec![SmartHomeEvent::HomeReplaced { home }], ())))
+    }
 
-        match status {
-            None => {
-                let new_state: SavedSmartHome = Some(vec![home]);
-                self.update_state(new_state)
-            }
-            Some(old_home) => {
-                let mut homes: Vec<Home> = old_home
-                    .iter()
-                    .filter(|h| h.id != home.id)
-                    .cloned()
-                    .collect();
-
-                homes.push(home);
-                self.update_state(Some(homes))
+    fn rename(&self, id: &str, name: String) -> Result<()> {
+        self.edit(id, Some(name), None)
+    }
+
+    fn edit(&self, id: &str, name: Option<String>, description: Option<String>) -> Result<()> {
+        if name.is_none() && description.is_none() {
+            return Err(anyhow!(
+                "Nothing to edit, provide the name or the description"
+            ));
+        }
+        if let Some(name) = name.as_deref() {
+            // the empty name is rejected even by the permissive rules, the entity would be lost
+            if name.trim().is_empty() {
+                return Err(ValidationError::EmptyName.into());
             }
+            self.naming_rules().check_name(name)?;
         }
+
+        self.commit(|state| {
+            let id = id.to_string();
+            let (current_name, current_description) = if let Some(home) = state.home(&id) {
+                (home.name.clone(), home.description.clone())
+            } else if let Some(room) = state.room(&id) {
+                (room.name.clone(), room.description.clone())
+            } else if let Some(device) = state.device(&id) {
+                (
+                    device.name().to_string(),
+                    device.description().map(str::to_string),
+                )
+            } else {
+                return Err(anyhow!("Entity with id: {id} not found"));
+            };
+
+            if let Some(name) = name.as_deref().filter(|name| *name != current_name) {
+                let parent = if state.home(&id).is_some() {
+                    None
+                } else if let Some(home) = state.home_by_room_id(&id) {
+                    Some(home.id.as_str())
+                } else {
+                    state.room_by_device_id(&id).map(|room| room.id.as_str())
+                };
+                let siblings = state.child_names(parent).into_iter();
+                self.naming_rules().check_unique(name, siblings, parent)?;
+            }
+
+            let description = match description {
+                Some(description) if description.is_empty() => None,
+                Some(description) => Some(description),
+                None => current_description,
+            };
+            let event = SmartHomeEvent::EntityEdited {
+                id,
+                name: name.unwrap_or(current_name),
+                description,
+            };
+            Ok((vec![event], ()))
+        })
+    }
+
+    fn move_room(&self, room_id: &RoomId, home_id: &HomeId) -> Result<()> {
+        self.commit(|state| {
+            let current = state
+                .home_by_room_id(room_id)
+                .ok_or_else(|| anyhow!("Room with id: {room_id} not found"))?;
+            if state.home(home_id).is_none() {
+                return Err(anyhow!("Home with id: {home_id} not found"));
+            }
+            if &current.id == home_id {
+                return Ok((vec![], ()));
+            }
+            if let Some(room) = state.room(room_id) {
+                let siblings = state.child_names(Some(home_id)).into_iter();
+                self.naming_rules()
+                    .check_unique(&room.name, siblings, Some(home_id))?;
+            }
+
+            let event = SmartHomeEvent::RoomMoved {
+                room_id: room_id.clone(),
+                home_id: home_id.clone(),
+            };
+            Ok((unlinking_thermostats(state, vec![event])?, ()))
+        })
+    }
+
+    fn move_device(&self, device_id: &DeviceId, room_id: &RoomId) -> Result<()> {
+        self.commit(|state| {
+            let current = state
+                .room_by_device_id(device_id)
+                .ok_or_else(|| anyhow!("Unable find associated room for device: {device_id}"))?;
+            if state.room(room_id).is_none() {
+                return Err(anyhow!("Room with id: {room_id} not found"));
+            }
+            if &current.id == room_id {
+                return Ok((vec![], ()));
+            }
+            if let Some(device) = state.device(device_id) {
+                let siblings = state.child_names(Some(room_id)).into_iter();
+                self.naming_rules()
+                    .check_unique(device.name(), siblings, Some(room_id))?;
+            }
+
+            let event = SmartHomeEvent::DeviceMoved {
+                device_id: device_id.clone(),
+                room_id: room_id.clone(),
+            };
+            Ok((unlinking_thermostats(state, vec![event])?, ()))
+        })
     }
 }
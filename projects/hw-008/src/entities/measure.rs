@@ -1,13 +1,153 @@
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
 use std::error::Error;
 use std::fmt::{Display, Formatter, Result as FmtResult};
 
 /// An interface for any object, which want to declare that it can measure the surrounding
 /// environment. Current implementation is relatively simple and dummy, but in the future
 /// implementations it might be changed.
-pub trait Measure<T> {
+pub trait Measure {
     /// Make a measure. It's simple function for making measure of the env. It's a blocking
-    /// version, in the future updates it might be changed to the `async` version.
-    fn measure(&self) -> Result<Option<T>, MeasureError>;
+    /// version, in the future updates it might be changed to the `async` version. A single
+    /// measure might give several values at once, e.g. the temperature and the humidity, and
+    /// the empty list means there is nothing to measure right now.
+    fn measure(&self) -> Result<Vec<Measurement>, MeasureError>;
+}
+
+/// What exactly is measured. Each quantity has its own [Unit], see [Quantity::unit].
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Quantity {
+    Temperature,
+    Humidity,
+    Co2,
+    Pressure,
+    Brightness,
+    Power,
+    Energy,
+    /// The values measured before the quantities were introduced, nobody knows what they are
+    Unspecified,
+}
+
+impl Quantity {
+    /// The unit the values of this quantity are measured in
+    pub fn unit(&self) -> Unit {
+        match self {
+            Quantity::Temperature => Unit::Celsius,
+            Quantity::Humidity | Quantity::Brightness => Unit::Percent,
+            Quantity::Co2 => Unit::PartsPerMillion,
+            Quantity::Power => Unit::Watt,
+            Quantity::Energy => Unit::KilowattHour,
+            Quantity::Pressure => Unit::Hectopascal,
+            Quantity::Unspecified => Unit::None,
+        }
+    }
+}
+
+/// The name of the quantity in lowercase, e.g. `temperature` or `co2`
+impl Display for Quantity {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        let name = match self {
+            Quantity::Temperature => "temperature",
+            Quantity::Humidity => "humidity",
+            Quantity::Co2 => "co2",
+            Quantity::Pressure => "pressure",
+            Quantity::Brightness => "brightness",
+            Quantity::Power => "power",
+            Quantity::Energy => "energy",
+            Quantity::Unspecified => "value",
+        };
+        formatter.write_str(name)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Unit {
+    Celsius,
+    Percent,
+    PartsPerMillion,
+    Hectopascal,
+    Watt,
+    KilowattHour,
+    None,
+}
+
+/// The symbol of the unit, e.g. `°C`, written right after the value
+impl Display for Unit {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        let symbol = match self {
+            Unit::Celsius => "°C",
+            Unit::Percent => "%",
+            Unit::PartsPerMillion => " ppm",
+            Unit::Hectopascal => " hPa",
+            Unit::Watt => " W",
+            Unit::KilowattHour => " kWh",
+            Unit::None => "",
+        };
+        formatter.write_str(symbol)
+    }
+}
+
+/// How much the measured value might be trusted
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Quality {
+    #[default]
+    Good,
+    /// The value is measured, but it might be far from the real one, e.g. the sensor is still
+    /// warming up
+    Uncertain,
+    /// The value is measured, but it's known to be wrong
+    Bad,
+}
+
+impl Display for Quality {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Quality::Good => formatter.write_str("good"),
+            Quality::Uncertain => formatter.write_str("uncertain"),
+            Quality::Bad => formatter.write_str("bad"),
+        }
+    }
+}
+
+/// A single measured value together with what it is, in which units, when it was measured and
+/// how good it is
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct Measurement {
+    pub quantity: Quantity,
+    pub value: f32,
+    pub unit: Unit,
+    pub taken_at: DateTime<Utc>,
+    #[serde(default)]
+    pub quality: Quality,
+}
+
+impl Measurement {
+    /// A good measurement of the quantity in its own unit taken right now
+    pub fn new(quantity: Quantity, value: f32) -> Self {
+        Self {
+            quantity,
+            value,
+            unit: quantity.unit(),
+            taken_at: Utc::now(),
+            quality: Quality::Good,
+        }
+    }
+
+    pub fn with_quality(self, quality: Quality) -> Self {
+        Self { quality, ..self }
+    }
+}
+
+/// A short form of the measurement, e.g. `temperature 21.5°C`, the quality is written only when
+/// it's not good, e.g. `co2 800 ppm (uncertain)`
+impl Display for Measurement {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        write!(formatter, "{} {}{}", self.quantity, self.value, self.unit)?;
+        if self.quality != Quality::Good {
+            write!(formatter, " ({})", self.quality)?;
+        }
+        Ok(())
+    }
 }
 
 /// A list of errors, which may happen during the measurement. It's again super simple and dummy
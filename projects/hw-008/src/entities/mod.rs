@@ -21,20 +21,24 @@ pub use reportable::{ReportError, Reportable};
 /// is relatively simple interface object, which will allow to store group of devices in single
 /// container. All details of measurement should be hidden in the exact device implementation.
 mod measure;
-pub use measure::{Measure, MeasureError};
-
-pub(crate) fn generate_id(entity_type: &str) -> String {
-    use rand::distributions::Alphanumeric;
-    use rand::Rng;
-
-    let id: String = rand::thread_rng()
-        .sample_iter(&Alphanumeric)
-        .take(5)
-        .map(char::from)
-        .collect();
-
-    format!("{entity_type}_{id}")
-}
+pub use measure::{Measure, MeasureError, Measurement, Quality, Quantity, Unit};
+
+/// An [id] submodule generates the ids of the entities. The ids sort by the creation time, and
+/// the generator might be replaced, e.g. by a seeded one to get the same ids in the tests.
+mod id;
+pub use id::{generate_id, set_id_generator, IdGenerator, SeededIdGenerator, SortableIdGenerator};
+
+/// A [labels] submodule holds the key/value labels of the entities and the selectors, which
+/// pick the entities by their labels, e.g. `floor=2,!critical`
+mod labels;
+pub use labels::{format_labels, parse_label, LabelError, LabelSelector, Labels};
+
+/// A [validation] submodule holds the rules the names of the entities follow and the typed
+/// errors of the validation, each with a stable code for the clients
+mod validation;
+pub use validation::{
+    NamingRules, ValidationError, DEFAULT_ALLOWED_CHARACTERS, DEFAULT_MAX_NAME_LENGTH,
+};
 
 /// A manager submodule for storing management traits and structs for dealing with smart home.
 /// The [SmartHomeManager] should be the single entry point for the whole system.
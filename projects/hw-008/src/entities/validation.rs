@@ -0,0 +1,163 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// The maximum length of the name by the default [NamingRules], in characters
+pub const DEFAULT_MAX_NAME_LENGTH: usize = 64;
+
+/// The characters allowed in the names by the default [NamingRules] besides the letters, the
+/// digits and the spaces. The `/` is not there, since it separates the names in the paths like
+/// `My Home/Kitchen/Kettle`.
+pub const DEFAULT_ALLOWED_CHARACTERS: &str = "-_.,:'&()#+";
+
+/// The errors of the validation of the entities
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    /// The builder was not given any name
+    MissingName,
+    /// The name is empty or has the spaces only
+    EmptyName,
+    /// The name has more characters than the rules allow
+    NameTooLong { name: String, max_length: usize },
+    /// The name has a character which is not allowed by the rules
+    InvalidCharacter { name: String, character: char },
+    /// Another entity of the same parent has the same name. The parent is [None] for the homes.
+    DuplicateName {
+        name: String,
+        parent: Option<String>,
+    },
+}
+
+impl ValidationError {
+    /// The stable code of the error for the clients, e.g. the server replies with
+    /// `[duplicate-name] ...`, so the client doesn't have to parse the message
+    pub fn code(&self) -> &'static str {
+        match self {
+            ValidationError::MissingName => "missing-name",
+            ValidationError::EmptyName => "empty-name",
+            ValidationError::NameTooLong { .. } => "name-too-long",
+            ValidationError::InvalidCharacter { .. } => "invalid-character",
+            ValidationError::DuplicateName { .. } => "duplicate-name",
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+impl Display for ValidationError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            ValidationError::MissingName => write!(formatter, "Please, provide the name"),
+            ValidationError::EmptyName => write!(formatter, "The name must not be empty"),
+            ValidationError::NameTooLong { name, max_length } => write!(
+                formatter,
+                "The name '{name}' is too long, at most {max_length} characters are allowed"
+            ),
+            ValidationError::InvalidCharacter { name, character } => write!(
+                formatter,
+                "The name '{name}' has the character '{character}' which is not allowed"
+            ),
+            ValidationError::DuplicateName { name, parent: None } => {
+                write!(
+                    formatter,
+                    "The name '{name}' is used by another home already"
+                )
+            }
+            ValidationError::DuplicateName {
+                name,
+                parent: Some(parent),
+            } => write!(formatter, "The name '{name}' is used in {parent} already"),
+        }
+    }
+}
+
+/// The rules the names of the homes, rooms and devices have to follow. The default rules are
+/// strict enough to keep the names usable in the paths: a name is not empty, has at most
+/// [DEFAULT_MAX_NAME_LENGTH] characters, has only the letters, the digits, the spaces and
+/// [DEFAULT_ALLOWED_CHARACTERS], and no other entity of the same parent has the same name.
+///
+/// ```
+/// use hw_008::entities::NamingRules;
+/// let rules = NamingRules::default();
+///
+/// assert!(rules.check_name("Living room").is_ok());
+/// assert!(rules.check_name("Attic/Loft").is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamingRules {
+    pub non_empty: bool,
+    /// The maximum length in characters, [None] means any length
+    pub max_length: Option<usize>,
+    /// The characters allowed besides the letters, the digits and the spaces, [None] means any
+    /// characters
+    pub allowed_characters: Option<String>,
+    /// Whether the homes, the rooms of a home and the devices of a room have unique names
+    pub unique_names: bool,
+}
+
+impl Default for NamingRules {
+    fn default() -> Self {
+        Self {
+            non_empty: true,
+            max_length: Some(DEFAULT_MAX_NAME_LENGTH),
+            allowed_characters: Some(DEFAULT_ALLOWED_CHARACTERS.to_string()),
+            unique_names: true,
+        }
+    }
+}
+
+impl NamingRules {
+    /// The rules which accept any name, the way it was before the rules were introduced
+    pub fn permissive() -> Self {
+        Self {
+            non_empty: false,
+            max_length: None,
+            allowed_characters: None,
+            unique_names: false,
+        }
+    }
+
+    /// Checks the name on its own, without its siblings
+    pub fn check_name(&self, name: &str) -> Result<(), ValidationError> {
+        if self.non_empty && name.trim().is_empty() {
+            return Err(ValidationError::EmptyName);
+        }
+        if let Some(max_length) = self.max_length {
+            if name.chars().count() > max_length {
+                return Err(ValidationError::NameTooLong {
+                    name: name.to_string(),
+                    max_length,
+                });
+            }
+        }
+        if let Some(allowed) = &self.allowed_characters {
+            let invalid = name
+                .chars()
+                .find(|c| !(c.is_alphanumeric() || *c == ' ' || allowed.contains(*c)));
+            if let Some(character) = invalid {
+                return Err(ValidationError::InvalidCharacter {
+                    name: name.to_string(),
+                    character,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that none of the `siblings` has the same name, unless the names don't have to be
+    /// unique. The `parent` is the id of the home for the rooms, the id of the room for the
+    /// devices, and [None] for the homes.
+    pub fn check_unique<'a>(
+        &self,
+        name: &str,
+        mut siblings: impl Iterator<Item = &'a str>,
+        parent: Option<&str>,
+    ) -> Result<(), ValidationError> {
+        if self.unique_names && siblings.any(|sibling| sibling == name) {
+            return Err(ValidationError::DuplicateName {
+                name: name.to_string(),
+                parent: parent.map(str::to_string),
+            });
+        }
+        Ok(())
+    }
+}
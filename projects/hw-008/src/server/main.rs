@@ -1,5 +1,12 @@
 use clap::Parser;
-use hw_008::server::{TcpServer, UdpServer};
+use hw_008::cli::StoreType;
+use hw_008::entities::manager::{
+    open_store, repository_root, Backups, SmartHomeManager, DEFAULT_BACKUP_KEEP,
+};
+use hw_008::server::{BackupScheduler, TcpServer, ThermostatScheduler, TrashScheduler, UdpServer};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -12,6 +19,31 @@ pub struct ServerArgs {
     /// An optional server port. If no port provided it will be generated randomly
     #[arg(short, long, value_name = "port")]
     pub port: Option<u16>,
+
+    /// The storage type of the smart home repository
+    #[arg(long, value_name = "store", value_enum, default_value = "json")]
+    pub store: StoreType,
+
+    /// The root directory of the smart home repository. By default, it's taken from the
+    /// `SMART_HOME_REPO` environment variable, or the nearest repository is looked up from the
+    /// current directory
+    #[arg(long, value_name = "repo")]
+    pub repo: Option<PathBuf>,
+
+    /// Take a backup of the repository every given number of minutes. No backups are taken by
+    /// default
+    #[arg(long, value_name = "minutes")]
+    pub backup_every: Option<u64>,
+
+    /// The number of the latest scheduled backups to keep
+    #[arg(long, value_name = "count", default_value_t = DEFAULT_BACKUP_KEEP)]
+    pub backup_keep: usize,
+
+    /// Read the thermometers and switch the heaters of the thermostats every given number of
+    /// seconds
+    #[arg(long, value_name = "seconds", default_value_t = 30)]
+    #[arg(value_parser = clap::value_parser!(u64).range(1..))]
+    pub regulate_every: u64,
 }
 
 fn main() {
@@ -21,10 +53,22 @@ fn main() {
 
     let host = args.host.unwrap_or_else(|| "localhost".into());
     let port = args.port.unwrap_or(0u16);
-    let current_dir = std::env::current_dir().expect("Unable determine the current dir");
+    let root = repository_root(args.repo, true).expect("Unable determine the repository");
+    println!("Serving the smart home repository in {}", root.display());
+    let store = open_store(args.store, root.clone());
+    let manager = Arc::new(SmartHomeManager::with_store(store));
+
+    if let Some(minutes) = args.backup_every {
+        println!("Taking a backup every {minutes} minute(s)");
+        let backups = Backups::new(root, args.store).keep(args.backup_keep);
+        BackupScheduler::start(backups, Duration::from_secs(minutes * 60));
+    }
+
+    ThermostatScheduler::start(manager.clone(), Duration::from_secs(args.regulate_every));
+    TrashScheduler::start(manager.clone(), Duration::from_secs(24 * 60 * 60));
 
-    let tcp_server = TcpServer::start(host.clone(), port);
-    UdpServer::start(host, port + 1, current_dir);
+    let tcp_server = TcpServer::start(host.clone(), port, manager.clone());
+    UdpServer::start(host, port + 1, manager);
 
     tcp_server.join().unwrap()
 }
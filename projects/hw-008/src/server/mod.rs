@@ -1,6 +1,10 @@
+mod scheduler;
 mod tcp;
 mod udp;
 
+/// Taking the backups and running the thermostats of the served repository on a schedule
+pub use scheduler::{BackupScheduler, ThermostatScheduler, TrashScheduler};
+
 /// A package for storing TCP server related structs and logics
 pub use tcp::*;
 
@@ -0,0 +1,70 @@
+use crate::entities::manager::{
+    set_current_actor, Backups, SmartHomeManager, ThermostatFunctions, TrashFunctions,
+};
+use chrono::Utc;
+use std::sync::Arc;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Takes the backups of the served repository on a schedule. The rotation of the [Backups]
+/// keeps the number of the scheduled backups in check, so the scheduler never stops.
+pub struct BackupScheduler {}
+
+impl BackupScheduler {
+    /// Starts taking a backup every `interval`. The first backup is taken after the first
+    /// interval, not at the start. A failed backup is reported and retried with the next one.
+    pub fn start(backups: Backups, interval: Duration) -> JoinHandle<()> {
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            match backups.create(Some("scheduled".into())) {
+                Ok(backup) => println!("[BackupScheduler] Created {backup}"),
+                Err(e) => println!("[BackupScheduler] Error: {e}"),
+            }
+        })
+    }
+}
+
+/// Runs the control loop of the thermostats, see [ThermostatFunctions::regulate]. The heaters
+/// are switched by the server, so they are kept in check while no client is connected.
+pub struct ThermostatScheduler {}
+
+impl ThermostatScheduler {
+    /// Makes a step of the control loop every `interval`, each switching decision is reported.
+    /// A failed step is reported and retried with the next one.
+    pub fn start(manager: Arc<SmartHomeManager>, interval: Duration) -> JoinHandle<()> {
+        thread::spawn(move || {
+            // the changes of the control loop are journaled under its own name
+            set_current_actor("thermostat");
+            loop {
+                thread::sleep(interval);
+                match manager.regulate() {
+                    Ok(decisions) => {
+                        for (id, decision) in decisions {
+                            println!("[ThermostatScheduler] {id}: {decision}");
+                        }
+                    }
+                    Err(e) => println!("[ThermostatScheduler] Error: {e}"),
+                }
+            }
+        })
+    }
+}
+
+/// Purges the entities expired in the trash, see [TrashFunctions::purge_trash]
+pub struct TrashScheduler {}
+
+impl TrashScheduler {
+    /// Purges the trash at the start and then every `interval`. A failed purge is reported and
+    /// retried with the next one.
+    pub fn start(manager: Arc<SmartHomeManager>, interval: Duration) -> JoinHandle<()> {
+        thread::spawn(move || loop {
+            match manager.purge_trash(Utc::now()) {
+                Ok(purged) if purged.is_empty() => {}
+                Ok(purged) => println!("[TrashScheduler] Purged {} entities", purged.len()),
+                Err(e) => println!("[TrashScheduler] Error: {e}"),
+            }
+            thread::sleep(interval);
+        })
+    }
+}
@@ -12,19 +12,39 @@
 //! CommandReply------------------------>]
 //! <---------------------------------exit
 //! CloseConnection---------------------->
-
-use crate::cli::{Arguments as CliArguments, Command, CommandHandler};
+//!
+//! The commands sent between `begin` and `commit` are staged in a transaction and written all
+//! at once by the `commit`. The `rollback` discards the staged commands. The transaction which
+//! is not committed before the connection is closed is rolled back.
+//!
+//! The `list` commands are paged, a reply has at most [REMOTE_PAGE_SIZE] ids unless the client
+//! asks for another `--limit`. The last line of the paged reply tells the position of the page,
+//! e.g. `# 1-50 of 153`, and the client asks for the next page by the `--offset 50`.
+//!
+//! The reply to the command rejected by the validation starts with the code of the error in the
+//! square brackets, e.g. `[duplicate-name] The name 'Kitchen' is used in home_01GN... already`,
+//! see [ValidationError::code](crate::entities::ValidationError::code).
+
+use crate::cli::{
+    Arguments as CliArguments, Command, CommandHandler, ExportCommand, ListEntityCommand,
+};
+use crate::entities::manager::{
+    set_current_actor, SmartHomeManager, Transaction, TransactionFunctions,
+};
 use anyhow::{anyhow, Result};
 use clap::Parser;
-use std::env;
 use std::fmt::{Display, Formatter};
 use std::io::{Read, Write};
 use std::net::{Shutdown, TcpStream};
+use std::sync::Arc;
 use std::time::Duration;
 
 pub const DEFAULT_READ_TIMEOUT_IN_SECS: Duration = Duration::from_secs(u64::MAX);
 pub const DEFAULT_WRITE_TIMEOUT_IN_SECS: Duration = Duration::from_secs(10);
 
+/// The default number of the entities in the reply to the `list` command
+pub const REMOTE_PAGE_SIZE: usize = 50;
+
 struct Encoder<'a> {
     writer: &'a mut dyn Write,
 }
@@ -72,6 +92,8 @@ impl Display for ConnectionStatus {
 pub struct TcpSession {
     stream: TcpStream,
     status: ConnectionStatus,
+    manager: Arc<SmartHomeManager>,
+    transaction: Option<Transaction>,
 }
 
 impl TcpSession {
@@ -113,8 +135,7 @@ impl TcpSession {
                 };
 
                 let args = String::from_utf8(buf[0..last_index].to_vec()).unwrap();
-                let commands: Vec<String> = args.split(' ').map(|s| s.to_string()).collect();
-                Ok(commands)
+                Ok(split_command(&args))
             }
             Err(e) => Err(anyhow!("Unable read data from client: {e:?}")),
         }
@@ -150,12 +171,52 @@ impl TcpSession {
         self.status = status;
     }
 
+    fn begin_transaction(&mut self) {
+        if self.transaction.is_some() {
+            self.write_data("The transaction is already started\n");
+            return;
+        }
+
+        match self.manager.begin_transaction() {
+            Ok(transaction) => {
+                self.transaction = Some(transaction);
+                self.write_data("Transaction started\n");
+            }
+            Err(msg) => self.write_data(&format!("Unable start the transaction: {msg}\n")),
+        }
+    }
+
+    fn commit_transaction(&mut self) {
+        let Some(transaction) = self.transaction.take() else {
+            self.write_data("No transaction to commit\n");
+            return;
+        };
+
+        match self.manager.commit_transaction(transaction) {
+            Ok(count) => self.write_data(&format!("Transaction committed, {count} change(s)\n")),
+            Err(msg) => self.write_data(&format!("{msg}\n")),
+        }
+    }
+
+    fn rollback_transaction(&mut self) {
+        match self.transaction.take() {
+            Some(transaction) => {
+                transaction.rollback();
+                self.write_data("Transaction rolled back\n");
+            }
+            None => self.write_data("No transaction to rollback\n"),
+        }
+    }
+
     fn handle_command(&mut self, command: Vec<String>) -> bool {
         match &command[..] {
             [a] if (a.to_lowercase() == "exit" || a.to_lowercase() == "quit") => {
                 self.close_connection();
                 return true;
             }
+            [a] if a.to_lowercase() == "begin" => self.begin_transaction(),
+            [a] if a.to_lowercase() == "commit" => self.commit_transaction(),
+            [a] if a.to_lowercase() == "rollback" => self.rollback_transaction(),
             _ => {
                 // FIXME: A dirty hack for clap crate. The first arg in args should be the script
                 // FIXME: name. So, in our case we should give some fake script name
@@ -165,12 +226,36 @@ impl TcpSession {
                 let args = CliArguments::try_parse_from(command_args);
 
                 match args {
-                    Ok(args) => match &args.command {
-                        Command::Init => self.write_data("Not supported command in remote mode\n"),
+                    // the server works with its own repository and store only
+                    Ok(args) if args.repo.is_some() => {
+                        self.write_data("Not supported option in remote mode: --repo\n")
+                    }
+                    Ok(args) if args.store.is_some() => {
+                        self.write_data("Not supported option in remote mode: --store\n")
+                    }
+                    Ok(mut args) => match &args.command {
+                        // the migration, the import and the plan might read any file of the
+                        // server, as well as the export to a file might write one, so they are
+                        // local only. The server takes the backups on its own schedule.
+                        Command::Init
+                        | Command::Migrate(_)
+                        | Command::Import(_)
+                        | Command::Plan(_)
+                        | Command::Apply(_)
+                        | Command::Backup(_)
+                        | Command::Export(ExportCommand {
+                            output: Some(_), ..
+                        }) => self.write_data("Not supported command in remote mode\n"),
                         _ => {
+                            // while the transaction is open, all the changes are staged in it
+                            let manager = match &self.transaction {
+                                Some(transaction) => transaction.manager(),
+                                None => self.manager.clone(),
+                            };
+                            page_list_command(&mut args.command);
                             let mut writer = Encoder::new(&mut self.stream);
-                            let path = env::current_dir().unwrap();
-                            let mut handler = CommandHandler::new(&mut writer, path);
+                            let mut handler = CommandHandler::with_manager(&mut writer, manager)
+                                .with_error_codes();
                             handler.process(args.command);
                         }
                     },
@@ -185,13 +270,21 @@ impl TcpSession {
         false
     }
 
-    pub fn run(stream: TcpStream) -> Result<()> {
+    pub fn run(stream: TcpStream, manager: Arc<SmartHomeManager>) -> Result<()> {
         stream.set_read_timeout(Some(DEFAULT_READ_TIMEOUT_IN_SECS))?;
         stream.set_write_timeout(Some(DEFAULT_WRITE_TIMEOUT_IN_SECS))?;
 
+        // each session runs in its own thread, so all the changes made by the client are
+        // journaled on behalf of the client address
+        if let Ok(peer) = stream.peer_addr() {
+            set_current_actor(format!("tcp://{peer}"));
+        }
+
         let mut session = TcpSession {
             stream,
             status: ConnectionStatus::Connected,
+            manager,
+            transaction: None,
         };
 
         session.print_state();
@@ -226,8 +319,54 @@ impl TcpSession {
     }
 }
 
+/// Limits the reply of the `list` command to a single page, if the client didn't ask for
+/// another limit
+fn page_list_command(command: &mut Command) {
+    if let Command::List(wrapper) = command {
+        let options = match &mut wrapper.command {
+            ListEntityCommand::Homes(options) => options,
+            ListEntityCommand::Rooms(rooms) => &mut rooms.options,
+            ListEntityCommand::Devices(devices) => &mut devices.options,
+        };
+        options.limit.get_or_insert(REMOTE_PAGE_SIZE);
+    }
+}
+
+/// Splits the command line into the arguments by the spaces. The arguments with spaces, like
+/// the paths of the entities (`"My Home/Kitchen"`), should be quoted by the single or the double
+/// quotes, the same way as in the shell.
+fn split_command(line: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut quoted = false;
+
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                quoted = true;
+            }
+            (None, ' ') => {
+                if !current.is_empty() || quoted {
+                    args.push(std::mem::take(&mut current));
+                }
+                quoted = false;
+            }
+            (None, c) => current.push(c),
+        }
+    }
+    if !current.is_empty() || quoted {
+        args.push(current);
+    }
+    args
+}
+
 #[cfg(test)]
 mod tests {
+    use super::split_command;
     use crate::cli::Arguments as CliArguments;
     use clap::Parser;
 
@@ -257,4 +396,16 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn split_quoted_command() {
+        assert_eq!(
+            split_command(r#"status room --id "My Home/Kitchen""#),
+            vec!["status", "room", "--id", "My Home/Kitchen"]
+        );
+        assert_eq!(
+            split_command("edit -i 'Home/*' -d ''"),
+            vec!["edit", "-i", "Home/*", "-d", ""]
+        );
+    }
 }
@@ -1,10 +1,13 @@
+use crate::entities::manager::SmartHomeManager;
 use crate::server::TcpSession;
 use std::net::TcpListener;
+use std::sync::Arc;
 use std::thread;
 use std::thread::JoinHandle;
 
 /// A TCP Server for smart home project. The internal logic of the connection is hidden by
-/// [TcpSession] struct.
+/// [TcpSession] struct. All the sessions share the same [SmartHomeManager], so they share its
+/// cached state as well.
 #[allow(unused)]
 pub struct TcpServer {
     host: String,
@@ -12,7 +15,7 @@ pub struct TcpServer {
 }
 
 impl TcpServer {
-    pub fn start(host: String, port: u16) -> JoinHandle<()> {
+    pub fn start(host: String, port: u16, manager: Arc<SmartHomeManager>) -> JoinHandle<()> {
         let listener = TcpListener::bind((host, port)).unwrap();
         let addr = listener.local_addr().unwrap();
 
@@ -20,13 +23,14 @@ impl TcpServer {
             println!("Running Tcp server on {addr}");
 
             for stream in listener.incoming() {
+                let manager = manager.clone();
                 thread::spawn(move || {
                     let stream = stream.unwrap();
                     println!(
                         "[TcpServer] Connected with {:?}",
                         stream.local_addr().unwrap()
                     );
-                    let result = TcpSession::run(stream);
+                    let result = TcpSession::run(stream, manager);
                     if result.is_err() {
                         println!("[TcpServer] Error: {}", result.err().unwrap())
                     }
@@ -1,9 +1,7 @@
-use crate::entities::devices::Device;
+use crate::entities::devices::Reading;
 use crate::entities::manager::SmartHomeManager;
-use crate::entities::Measure;
 use chrono::Utc;
 use std::net::{SocketAddr, UdpSocket};
-use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
@@ -15,7 +13,7 @@ pub const SEND_INTERVAL: u64 = 2;
 pub struct UdpServer {
     active_connections: Mutex<Vec<SocketAddr>>,
     socket: Mutex<UdpSocket>,
-    manager: SmartHomeManager,
+    manager: Arc<SmartHomeManager>,
 }
 
 impl UdpServer {
@@ -49,18 +47,18 @@ impl UdpServer {
             }
 
             for device in devices.iter() {
-                match device {
-                    Device::Socket(_) => {}
-                    Device::Thermometer(therm) => {
-                        let socket = server.socket.lock().unwrap();
-                        for addr in connections.iter() {
-                            let id = therm.id.clone();
-                            let value = therm.measure().unwrap().unwrap();
-                            let measure = format!("[{}][{id}]: {value}\n", Utc::now());
+                let socket = server.socket.lock().unwrap();
+                for addr in connections.iter() {
+                    let id = device.id();
+                    // the devices measuring nothing and the switched off ones have nothing to send
+                    let measurements = match device.measure() {
+                        Ok(measurements) if !measurements.is_empty() => measurements,
+                        _ => continue,
+                    };
+                    let measure =
+                        format!("[{}][{id}]: {}\n", Utc::now(), Reading::new(measurements));
 
-                            socket.send_to(measure.as_bytes(), addr).unwrap();
-                        }
-                    }
+                    socket.send_to(measure.as_bytes(), addr).unwrap();
                 }
             }
             drop(connections);
@@ -68,12 +66,11 @@ impl UdpServer {
         });
     }
 
-    pub fn start(host: String, port: u16, repo: PathBuf) {
+    pub fn start(host: String, port: u16, manager: Arc<SmartHomeManager>) {
         let udp_socket_result = UdpSocket::bind((host, port));
         match udp_socket_result {
             Ok(socket) => {
                 let addr = socket.local_addr().unwrap();
-                let manager = SmartHomeManager::new(repo);
                 socket.set_nonblocking(true).unwrap();
 
                 let server = UdpServer {
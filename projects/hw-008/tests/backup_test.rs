@@ -0,0 +1,144 @@
+use std::fs;
+use std::sync::Arc;
+
+use hw_008::cli::{Arguments, CommandHandler, StoreType};
+use hw_008::entities::manager::{
+    open_store, BackupFunctions, Backups, CreateFunctions, RemoveFunctions, SmartHomeManager,
+    UndoFunctions, BACKUP_DIR,
+};
+
+use clap::Parser;
+
+fn repository(root: &std::path::Path, store_type: StoreType) -> Arc<SmartHomeManager> {
+    let manager = SmartHomeManager::with_store(open_store(store_type, root.to_path_buf()));
+    manager.initialize_smart_home().unwrap();
+    Arc::new(manager)
+}
+
+fn homes(manager: &SmartHomeManager) -> usize {
+    manager
+        .read_smart_home_status()
+        .unwrap()
+        .unwrap_or_default()
+        .len()
+}
+
+#[test]
+fn restore_brings_removed_home_back() {
+    for store_type in [StoreType::Json, StoreType::Sqlite] {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = repository(dir.path(), store_type);
+        let backups = Backups::new(dir.path().to_path_buf(), store_type);
+
+        let home = manager.create_home("Home".into(), None).unwrap();
+        manager
+            .create_room(home.clone(), "Hall".into(), None)
+            .unwrap();
+        let backup = backups.create(Some("before cleanup".into())).unwrap();
+        assert_eq!(backup.label.as_deref(), Some("before cleanup"));
+        assert!(!backup.files.is_empty());
+
+        manager.remove_home(&home).unwrap();
+        assert_eq!(homes(&manager), 0);
+
+        manager.restore_backup(&backups, &backup.id).unwrap();
+        let restored = manager.read_smart_home_status().unwrap().unwrap();
+        assert_eq!(restored[0].id, home, "{store_type:?}");
+        assert_eq!(restored[0].rooms.len(), 1);
+
+        // the restore is a regular change
+        manager.undo().unwrap();
+        assert_eq!(homes(&manager), 0);
+    }
+}
+
+#[test]
+fn backups_are_taken_and_read_while_repository_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let manager = repository(dir.path(), StoreType::Sqlite);
+    let backups = Backups::new(dir.path().to_path_buf(), StoreType::Sqlite).keep(0);
+
+    let writer = {
+        let manager = manager.clone();
+        std::thread::spawn(move || {
+            for n in 0..20 {
+                manager.create_home(format!("Home {n}"), None).unwrap();
+            }
+        })
+    };
+    let taken: Vec<_> = (0..5).map(|_| backups.create(None).unwrap()).collect();
+    writer.join().unwrap();
+
+    // each backup holds a whole state, and the same backup might be read by many at once
+    for backup in taken {
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let (backups, id) = (backups.clone(), backup.id.clone());
+                std::thread::spawn(move || backups.read_state(&id).map(|(_, state)| state))
+            })
+            .collect();
+        for reader in readers {
+            let state = reader.join().unwrap().unwrap().unwrap_or_default();
+            assert!(state.len() <= 20);
+        }
+    }
+}
+
+#[test]
+fn damaged_backup_is_not_restored() {
+    let dir = tempfile::tempdir().unwrap();
+    let manager = repository(dir.path(), StoreType::Json);
+    let backups = Backups::new(dir.path().to_path_buf(), StoreType::Json);
+
+    manager.create_home("Home".into(), None).unwrap();
+    let backup = backups.create(None).unwrap();
+    let file = dir
+        .path()
+        .join(".smart-home")
+        .join(BACKUP_DIR)
+        .join(&backup.id)
+        .join("smart-home.json");
+    fs::write(&file, "{}").unwrap();
+
+    let error = manager.restore_backup(&backups, &backup.id).unwrap_err();
+    assert!(error.to_string().contains("checksum"), "{error}");
+    assert_eq!(homes(&manager), 1);
+}
+
+#[test]
+fn old_backups_are_rotated() {
+    let dir = tempfile::tempdir().unwrap();
+    repository(dir.path(), StoreType::Json);
+    let backups = Backups::new(dir.path().to_path_buf(), StoreType::Json).keep(2);
+
+    let created: Vec<String> = (0..3).map(|_| backups.create(None).unwrap().id).collect();
+
+    let kept: Vec<String> = backups.list().unwrap().into_iter().map(|b| b.id).collect();
+    assert_eq!(kept, created[1..]);
+}
+
+#[test]
+fn backup_commands() {
+    let dir = tempfile::tempdir().unwrap();
+    let manager = repository(dir.path(), StoreType::Json);
+    let run = |args: &[&str]| {
+        let mut output = Vec::new();
+        let backups = Backups::new(dir.path().to_path_buf(), StoreType::Json);
+        let args = Arguments::parse_from(std::iter::once("cli").chain(args.iter().copied()));
+        CommandHandler::with_manager(&mut output, manager.clone())
+            .with_backups(backups)
+            .process(args.command);
+        String::from_utf8(output).unwrap()
+    };
+
+    assert_eq!(run(&["backup", "list"]), "No backups found");
+    let output = run(&["backup", "create", "--label", "nightly"]);
+    assert!(output.starts_with("Created backup_"), "{output}");
+    assert!(output.ends_with(", nightly"), "{output}");
+
+    let listed = run(&["backup", "list"]);
+    assert_eq!(listed.lines().count(), 1);
+    let id = listed.split(' ').next().unwrap();
+    let output = run(&["backup", "restore", id]);
+    assert!(output.starts_with(&format!("Restored {id}")), "{output}");
+}
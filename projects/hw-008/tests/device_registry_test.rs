@@ -0,0 +1,180 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::sync::{Arc, Once};
+
+use hw_008::cli::{Arguments, CommandHandler, DeviceType};
+use hw_008::entities::devices::{
+    register_device, Device, DeviceId, DeviceKind, DeviceRegistration, DeviceState, Socket,
+};
+use hw_008::entities::manager::{
+    CreateFunctions, FindFunctions, JsonFileStore, SmartHomeManager, StateStore,
+};
+use hw_008::entities::{generate_id, Labels, ReportError, Reportable};
+
+use clap::Parser;
+use serde_derive::{Deserialize, Serialize};
+
+/// A device brought by a downstream crate, the hw-008 knows nothing about it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Fan {
+    id: DeviceId,
+    name: String,
+    description: Option<String>,
+    #[serde(default)]
+    labels: Labels,
+    state: DeviceState,
+    speed: u8,
+}
+
+impl Display for Fan {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            formatter,
+            "Fan: {},\nId: {},\nSpeed: {}",
+            self.name, self.id, self.speed
+        )
+    }
+}
+
+impl Reportable for Fan {
+    fn report(&self) -> Result<String, ReportError> {
+        Ok(format!("Fan: {}, Speed: {}", self.name, self.speed))
+    }
+}
+
+impl DeviceKind for Fan {
+    fn tag(&self) -> &'static str {
+        "Fan"
+    }
+
+    fn id_prefix(&self) -> &'static str {
+        "fan"
+    }
+
+    fn id(&self) -> &DeviceId {
+        &self.id
+    }
+
+    fn set_id(&mut self, id: DeviceId) {
+        self.id = id;
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    fn labels(&self) -> &Labels {
+        &self.labels
+    }
+
+    fn labels_mut(&mut self) -> &mut Labels {
+        &mut self.labels
+    }
+
+    fn state(&self) -> &DeviceState {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut DeviceState {
+        &mut self.state
+    }
+
+    fn edit(&mut self, name: String, description: Option<String>) {
+        self.name = name;
+        self.description = description;
+    }
+}
+
+fn register_fan() {
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| {
+        let registration = DeviceRegistration::of("Fan", |name, description| Fan {
+            id: generate_id("fan"),
+            name: name.to_string(),
+            description: description.map(str::to_string),
+            labels: Labels::new(),
+            state: DeviceState::default(),
+            speed: 1,
+        });
+        register_device(registration).unwrap();
+    });
+}
+
+fn repository(root: &std::path::Path) -> Arc<SmartHomeManager> {
+    let store = JsonFileStore::new(root.to_path_buf());
+    store.initialize().unwrap();
+    Arc::new(SmartHomeManager::with_store(Arc::new(store)))
+}
+
+#[test]
+fn registered_device_is_stored_and_read_back() {
+    register_fan();
+    let dir = tempfile::tempdir().unwrap();
+    let manager = repository(dir.path());
+    let home = manager.create_home("Home".into(), None).unwrap();
+    let room = manager.create_room(home, "Hall".into(), None).unwrap();
+
+    let fan_type: DeviceType = "fan".parse().unwrap();
+    let fan = manager
+        .create_device(fan_type, room.clone(), "Ceiling".into(), None)
+        .unwrap();
+    assert!(fan.starts_with("fan_"));
+
+    // another manager reads the repository from scratch
+    let device = SmartHomeManager::new(dir.path().to_path_buf())
+        .find_device_by_id(&fan)
+        .unwrap();
+    assert_eq!(device.device_type(), fan_type);
+    assert_eq!(device.downcast_ref::<Fan>().unwrap().speed, 1);
+    assert!(device.downcast_ref::<Socket>().is_none());
+    assert!(manager.make_measure(&fan).is_err());
+
+    let mut output = Vec::new();
+    let args = Arguments::parse_from([
+        "cli",
+        "new",
+        "device",
+        "-t",
+        "Fan",
+        "--room-id",
+        &room,
+        "-n",
+        "Floor",
+    ]);
+    CommandHandler::with_manager(&mut output, manager.clone()).process(args.command);
+    assert!(String::from_utf8(output).unwrap().starts_with("fan_"));
+}
+
+#[test]
+fn devices_keep_externally_tagged_json() {
+    let json = r#"{"Socket": {"id": "sock_1", "name": "Lamp", "description": null,
+        "power_consumption": 0.0, "state": {"on": true, "last_reading": null, "changed_at": null}}}"#;
+    let device: Device = serde_json::from_str(json).unwrap();
+    assert_eq!(device.device_type(), DeviceType::SOCKET);
+    assert!(device.state().on);
+
+    let written = serde_json::to_value(&device).unwrap();
+    assert_eq!(written["Socket"]["name"], "Lamp");
+
+    let error = serde_json::from_str::<Device>(r#"{"Toaster": {}}"#).unwrap_err();
+    assert!(
+        error.to_string().contains("Unknown device type Toaster"),
+        "{error}"
+    );
+}
+
+#[test]
+fn device_types_are_registered_once() {
+    register_fan();
+    let registration = DeviceRegistration::of("socket", |name, _| Socket::new(name));
+    assert!(register_device(registration).is_err());
+
+    let error = "toaster".parse::<DeviceType>().unwrap_err();
+    assert!(
+        error.contains("socket, thermometer, light, environmentsensor, thermostat, fan"),
+        "{error}"
+    );
+}
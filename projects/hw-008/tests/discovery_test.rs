@@ -0,0 +1,48 @@
+use std::fs;
+use std::path::PathBuf;
+
+use hw_008::cli::{Command, CommandHandler};
+use hw_008::entities::manager::{find_repository, repository_root, SmartHomeManager};
+
+#[test]
+fn init_creates_repository_in_existing_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut output = Vec::new();
+
+    CommandHandler::new(&mut output, dir.path().to_path_buf()).process(Command::Init);
+    assert!(dir.path().join(".smart-home").is_dir());
+    assert!(SmartHomeManager::new(dir.path().to_path_buf()).is_smart_home_repo_exists());
+
+    let mut output = Vec::new();
+    CommandHandler::new(&mut output, dir.path().to_path_buf()).process(Command::Init);
+    assert!(String::from_utf8(output)
+        .unwrap()
+        .contains("Repository already exists"));
+}
+
+#[test]
+fn nearest_repository_is_found_from_nested_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let nested = dir.path().join("src").join("nested");
+    fs::create_dir_all(&nested).unwrap();
+
+    assert_eq!(find_repository(&nested), None);
+
+    let mut output = Vec::new();
+    CommandHandler::new(&mut output, dir.path().to_path_buf()).process(Command::Init);
+    assert_eq!(find_repository(&nested), Some(dir.path().to_path_buf()));
+
+    // the nearest one wins
+    let mut output = Vec::new();
+    CommandHandler::new(&mut output, nested.clone()).process(Command::Init);
+    assert_eq!(find_repository(&nested), Some(nested));
+}
+
+#[test]
+fn explicit_repository_wins() {
+    let explicit = PathBuf::from("/some/where");
+    assert_eq!(
+        repository_root(Some(explicit.clone()), true).unwrap(),
+        explicit
+    );
+}
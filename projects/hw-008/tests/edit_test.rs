@@ -0,0 +1,97 @@
+use std::sync::Arc;
+
+use hw_008::cli::DeviceType;
+use hw_008::entities::manager::{
+    CreateFunctions, FindFunctions, InMemoryStore, SmartHomeManager, UndoFunctions, UpdateFunctions,
+};
+
+fn manager() -> SmartHomeManager {
+    SmartHomeManager::with_store(Arc::new(InMemoryStore::default()))
+}
+
+#[test]
+fn entities_are_renamed_and_edited_in_place() {
+    let manager = manager();
+    let home_id = manager
+        .create_home("Home".into(), Some("Old".into()))
+        .unwrap();
+    let room_id = manager
+        .create_room(home_id.clone(), "Kitchen".into(), None)
+        .unwrap();
+    let socket_id = manager
+        .create_device(DeviceType::SOCKET, room_id.clone(), "Socket".into(), None)
+        .unwrap();
+
+    manager.rename(&home_id, "Cottage".into()).unwrap();
+    let home = manager.find_home_by_id(&home_id).unwrap();
+    assert_eq!(home.name, "Cottage");
+    assert_eq!(home.description.as_deref(), Some("Old"));
+
+    manager
+        .edit(&room_id, None, Some("With a window".into()))
+        .unwrap();
+    let room = manager.find_room_by_id(&room_id).unwrap();
+    assert_eq!(room.name, "Kitchen");
+    assert_eq!(room.description.as_deref(), Some("With a window"));
+
+    manager
+        .edit(&socket_id, Some("Kettle".into()), Some("".into()))
+        .unwrap();
+    let device = manager.find_device_by_id(&socket_id).unwrap();
+    assert_eq!(device.name(), "Kettle");
+    assert!(device.description().is_none());
+
+    assert!(manager.rename("unknown", "Name".into()).is_err());
+    assert!(manager.rename(&home_id, " ".into()).is_err());
+    assert!(manager.edit(&home_id, None, None).is_err());
+
+    manager.undo().unwrap();
+    assert_eq!(
+        manager.find_device_by_id(&socket_id).unwrap().name(),
+        "Socket"
+    );
+}
+
+#[test]
+fn entities_are_moved_with_their_ids() {
+    let manager = manager();
+    let home_id = manager.create_home("Home".into(), None).unwrap();
+    let other_id = manager.create_home("Other".into(), None).unwrap();
+    let kitchen_id = manager
+        .create_room(home_id.clone(), "Kitchen".into(), None)
+        .unwrap();
+    let hall_id = manager
+        .create_room(home_id.clone(), "Hall".into(), None)
+        .unwrap();
+    let socket_id = manager
+        .create_device(
+            DeviceType::SOCKET,
+            kitchen_id.clone(),
+            "Socket".into(),
+            None,
+        )
+        .unwrap();
+
+    manager.move_device(&socket_id, &hall_id).unwrap();
+    assert_eq!(
+        manager.find_room_by_device_id(&socket_id).unwrap().id,
+        hall_id
+    );
+    assert!(manager
+        .find_room_by_id(&kitchen_id)
+        .unwrap()
+        .devices
+        .is_empty());
+
+    manager.move_room(&hall_id, &other_id).unwrap();
+    let other = manager.find_home_by_id(&other_id).unwrap();
+    assert_eq!(other.rooms[0].id, hall_id);
+    assert_eq!(other.rooms[0].devices[0].id(), &socket_id);
+    assert_eq!(manager.find_home_by_id(&home_id).unwrap().rooms.len(), 1);
+
+    assert!(manager.move_room(&hall_id, &"unknown".into()).is_err());
+    assert!(manager.move_device(&socket_id, &"unknown".into()).is_err());
+
+    manager.undo().unwrap();
+    assert_eq!(manager.find_home_by_id(&home_id).unwrap().rooms.len(), 2);
+}
@@ -0,0 +1,234 @@
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use clap::Parser;
+
+use hw_008::cli::{Arguments, CommandHandler, DeviceType};
+use hw_008::entities::devices::{DeviceKind, Socket, SocketStatus};
+use hw_008::entities::manager::{
+    CreateFunctions, EnergyFunctions, FindFunctions, InMemoryStore, JsonFileStore, RemoveFunctions,
+    SmartHomeManager, StateStore, TrashFunctions, UndoFunctions, UpdateFunctions,
+};
+
+fn approx(value: f64, expected: f64) -> bool {
+    (value - expected).abs() < 0.001
+}
+
+#[test]
+fn energy_is_counted_only_while_enabled() {
+    let mut socket = Socket::new("Heater");
+    socket.power_consumption = 1000.0;
+    let start = Utc::now();
+
+    socket.switch(true, Some(start));
+    assert!(matches!(socket.status(), SocketStatus::Enabled));
+    assert!(approx(socket.energy_at(start + Duration::hours(1)), 1.0));
+
+    socket.set_power(500.0, start + Duration::hours(2));
+    assert!(approx(socket.energy_at(start + Duration::hours(4)), 3.0));
+
+    socket.switch(false, Some(start + Duration::hours(4)));
+    assert_eq!(socket.get_current_power_consumption(), 0.0);
+    assert!(approx(socket.energy_at(start + Duration::hours(10)), 3.0));
+}
+
+/// Puts the socket into the state it would have after an hour of work with the given power
+fn worked_for_an_hour(manager: &SmartHomeManager, socket_id: &String, watts: f32) {
+    let mut home = manager
+        .find_home_by_room_id(&manager.find_room_by_device_id(socket_id).unwrap().id)
+        .unwrap();
+    let device = home
+        .rooms
+        .iter_mut()
+        .flat_map(|room| room.devices.iter_mut())
+        .find(|device| device.id() == socket_id)
+        .unwrap();
+    device.downcast_mut::<Socket>().unwrap().power_consumption = watts;
+    device.switch(true, Some(Utc::now() - Duration::hours(1)));
+    manager.update_home_state(home).unwrap();
+}
+
+#[test]
+fn counters_survive_restarts() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = JsonFileStore::new(dir.path().to_path_buf());
+    store.initialize().unwrap();
+    let manager = SmartHomeManager::with_store(Arc::new(store));
+
+    let home = manager.create_home("Home".into(), None).unwrap();
+    let room = manager
+        .create_room(home.clone(), "Hall".into(), None)
+        .unwrap();
+    let kettle = manager
+        .create_device(DeviceType::SOCKET, room.clone(), "Kettle".into(), None)
+        .unwrap();
+    let lamp = manager
+        .create_device(DeviceType::SOCKET, room.clone(), "Lamp".into(), None)
+        .unwrap();
+    manager
+        .create_device(DeviceType::THERMOMETER, room.clone(), "T".into(), None)
+        .unwrap();
+    worked_for_an_hour(&manager, &kettle, 2000.0);
+    worked_for_an_hour(&manager, &lamp, 60.0);
+    manager.change_device_status(&lamp, false).unwrap();
+
+    let restarted = SmartHomeManager::new(dir.path().to_path_buf());
+    let usage = restarted.energy(&home, None).unwrap();
+    assert_eq!(usage.len(), 2);
+    assert!(approx(usage[0].kwh, 2.0), "{usage:?}");
+    assert!(approx(usage[1].kwh, 0.06), "{usage:?}");
+
+    let since_now = restarted.energy(&room, Some(Utc::now())).unwrap();
+    assert!(since_now.iter().all(|device| approx(device.kwh, 0.0)));
+
+    // the switched off lamp counts nothing anymore
+    let before = restarted.energy(&lamp, Some(Utc::now() - Duration::days(1)));
+    assert!(approx(before.unwrap()[0].kwh, 0.06));
+}
+
+#[test]
+fn undone_switch_keeps_the_counter() {
+    let manager = SmartHomeManager::with_store(Arc::new(InMemoryStore::default()));
+    let home = manager.create_home("Home".into(), None).unwrap();
+    let room = manager.create_room(home, "Hall".into(), None).unwrap();
+    let socket = manager
+        .create_device(DeviceType::SOCKET, room, "Kettle".into(), None)
+        .unwrap();
+    worked_for_an_hour(&manager, &socket, 1000.0);
+
+    manager.change_device_status(&socket, false).unwrap();
+    manager.undo().unwrap();
+    manager.change_device_status(&socket, false).unwrap();
+    let usage = manager.energy(&socket, None).unwrap();
+    assert!(approx(usage[0].kwh, 1.0), "{usage:?}");
+}
+
+#[test]
+fn undone_switch_counts_up_to_the_undo() {
+    let manager = SmartHomeManager::with_store(Arc::new(InMemoryStore::default()));
+    let home = manager.create_home("Home".into(), None).unwrap();
+    let room = manager.create_room(home, "Hall".into(), None).unwrap();
+    let socket = manager
+        .create_device(DeviceType::SOCKET, room, "Kettle".into(), None)
+        .unwrap();
+    manager.set_socket_power(&socket, 2000.0).unwrap();
+    let counter = || {
+        let device = manager.find_device_by_id(&socket).unwrap();
+        device.downcast_ref::<Socket>().unwrap().energy.clone()
+    };
+    let pause = || std::thread::sleep(std::time::Duration::from_millis(20));
+
+    // the socket worked until the switch on was undone
+    manager.change_device_status(&socket, true).unwrap();
+    pause();
+    manager.undo().unwrap();
+    let worked = counter().total_kwh;
+    assert!(worked > 0.0);
+
+    // the time it was switched off isn't counted when the switch off is undone
+    manager.change_device_status(&socket, true).unwrap();
+    pause();
+    manager.change_device_status(&socket, false).unwrap();
+    let switched_off = counter().total_kwh;
+    assert!(switched_off > worked);
+    pause();
+    let before_undo = Utc::now();
+    manager.undo().unwrap();
+    assert!(manager.find_device_by_id(&socket).unwrap().state().on);
+    assert!(approx(counter().total_kwh, switched_off));
+    assert!(counter().metered_at.unwrap() >= before_undo);
+}
+
+#[test]
+fn undone_power_change_never_takes_the_energy_back() {
+    let manager = SmartHomeManager::with_store(Arc::new(InMemoryStore::default()));
+    let home = manager.create_home("Home".into(), None).unwrap();
+    let room = manager.create_room(home, "Hall".into(), None).unwrap();
+    let socket = manager
+        .create_device(DeviceType::SOCKET, room, "Kettle".into(), None)
+        .unwrap();
+    let used = || manager.energy(&socket, None).unwrap()[0].kwh;
+    let pause = || std::thread::sleep(std::time::Duration::from_millis(20));
+
+    manager.set_socket_power(&socket, 1000.0).unwrap();
+    manager.change_device_status(&socket, true).unwrap();
+    pause();
+    manager.set_socket_power(&socket, 3_600_000.0).unwrap();
+    pause();
+    let before_undo = used();
+    manager.undo().unwrap();
+    assert!(used() >= before_undo, "{} < {before_undo}", used());
+
+    let device = manager.find_device_by_id(&socket).unwrap();
+    assert_eq!(
+        device.downcast_ref::<Socket>().unwrap().power_consumption,
+        1000.0
+    );
+    assert!(device.state().on);
+    pause();
+    assert!(used() >= before_undo);
+}
+
+#[test]
+fn removed_sockets_count_nothing_until_they_are_back() {
+    let manager = SmartHomeManager::with_store(Arc::new(InMemoryStore::default()));
+    let home = manager.create_home("Home".into(), None).unwrap();
+    let room = manager.create_room(home, "Hall".into(), None).unwrap();
+    let socket = manager
+        .create_device(DeviceType::SOCKET, room.clone(), "Kettle".into(), None)
+        .unwrap();
+    worked_for_an_hour(&manager, &socket, 1000.0);
+    let counted_since = |back| {
+        let device = manager.find_device_by_id(&socket).unwrap();
+        let energy = &device.downcast_ref::<Socket>().unwrap().energy;
+        assert!(approx(energy.total_kwh, 1.0), "{energy:?}");
+        assert!(energy.metered_at.unwrap() >= back, "{energy:?}");
+    };
+
+    manager.remove_device(&socket).unwrap();
+    let back = Utc::now();
+    manager.undo().unwrap();
+    counted_since(back);
+
+    manager.remove_room(&room).unwrap();
+    let back = Utc::now();
+    manager.restore_from_trash(&room).unwrap();
+    counted_since(back);
+}
+
+#[test]
+fn energy_command_writes_totals() {
+    let manager = Arc::new(SmartHomeManager::with_store(Arc::new(
+        InMemoryStore::default(),
+    )));
+    let home = manager.create_home("Home".into(), None).unwrap();
+    let room = manager.create_room(home, "Hall".into(), None).unwrap();
+    let socket = manager
+        .create_device(DeviceType::SOCKET, room.clone(), "Kettle".into(), None)
+        .unwrap();
+    manager
+        .create_device(DeviceType::THERMOMETER, room, "T".into(), None)
+        .unwrap();
+    worked_for_an_hour(&manager, &socket, 1500.0);
+    let run = |args: &[&str]| {
+        let mut output = Vec::new();
+        let args = Arguments::parse_from(std::iter::once("cli").chain(args.iter().copied()));
+        CommandHandler::with_manager(&mut output, manager.clone()).process(args.command);
+        String::from_utf8(output).unwrap()
+    };
+
+    let output = run(&["energy", "room", "-i", "Home/Hall"]);
+    assert!(
+        output.contains(&format!("{socket} (Kettle): 1.500 kWh")),
+        "{output}"
+    );
+    assert!(output.ends_with("Total: 1.500 kWh"), "{output}");
+
+    let output = run(&["energy", "device", "-i", "Home/Hall/T"]);
+    assert!(output.contains("has no energy meter"), "{output}");
+
+    let output = run(&["socket", "-i", "Home/Hall/Kettle", "-p", "100"]);
+    assert!(output.contains("Power consumption: 100"), "{output}");
+    let output = run(&["energy", "device", "-i", &socket, "--since", "2020-01-01"]);
+    assert_eq!(output, "1.500 kWh");
+}
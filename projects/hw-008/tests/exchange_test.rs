@@ -0,0 +1,236 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use serde_json::Value;
+
+use hw_008::cli::{Arguments, CommandHandler, DataFormat, DeviceType};
+use hw_008::entities::devices::{EnergyMeter, Socket, Thermostat};
+use hw_008::entities::manager::{
+    Change, CreateFunctions, EnergyFunctions, ExchangeFunctions, FindFunctions, ImportMode,
+    InMemoryStore, LabelFunctions, SmartHomeManager, ThermostatFunctions, ThermostatSettings,
+    UndoFunctions, UpdateFunctions,
+};
+use hw_008::entities::Labels;
+
+use clap::Parser;
+
+fn manager() -> Arc<SmartHomeManager> {
+    Arc::new(SmartHomeManager::with_store(Arc::new(
+        InMemoryStore::default(),
+    )))
+}
+
+/// A home with a room, a socket and a thermometer. Returns the ids of the home and the socket.
+fn populated() -> (Arc<SmartHomeManager>, String, String) {
+    let manager = manager();
+    let home = manager
+        .create_home("Home".into(), Some("The main one".into()))
+        .unwrap();
+    let room = manager
+        .create_room(home.clone(), "Hall".into(), None)
+        .unwrap();
+    let socket = manager
+        .create_device(DeviceType::SOCKET, room.clone(), "Lamp".into(), None)
+        .unwrap();
+    manager
+        .create_device(DeviceType::THERMOMETER, room, "Wall".into(), None)
+        .unwrap();
+    manager
+        .label(
+            &socket,
+            Labels::from([("floor".into(), "1".into()), ("critical".into(), "".into())]),
+            &[],
+        )
+        .unwrap();
+    manager.change_device_status(&socket, true).unwrap();
+    (manager, home, socket)
+}
+
+fn without_meter_moments(text: &str) -> String {
+    text.lines()
+        .filter(|line| !line.contains("metered_at"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn every_format_round_trips() {
+    let (source, _, _) = populated();
+
+    for format in [
+        DataFormat::Json,
+        DataFormat::Yaml,
+        DataFormat::Toml,
+        DataFormat::Csv,
+    ] {
+        let text = source.export(format).unwrap();
+
+        let target = manager();
+        let summary = target.import(&text, format, ImportMode::Merge).unwrap();
+        assert_eq!(summary.count(Change::Added), 4, "{format:?}: {summary}");
+
+        // the csv keeps less, so it's compared with the csv export only. The imported sockets
+        // are metered from the import, so the moments of their counters differ.
+        assert_eq!(
+            without_meter_moments(&target.export(format).unwrap()),
+            without_meter_moments(&text),
+            "{format:?}"
+        );
+
+        // importing the same homes once again changes nothing
+        let summary = target.import(&text, format, ImportMode::Merge).unwrap();
+        assert!(summary.entities.is_empty(), "{format:?}: {summary}");
+        assert_eq!(summary.unchanged, 4);
+    }
+}
+
+#[test]
+fn merge_keeps_and_replace_drops_other_homes() {
+    let (source, home, socket) = populated();
+    let exported = source.export(DataFormat::Json).unwrap();
+
+    let target = manager();
+    let other = target.create_home("Cottage".into(), None).unwrap();
+    target
+        .import(&exported, DataFormat::Json, ImportMode::Merge)
+        .unwrap();
+
+    source.rename(&socket, "Desk lamp".into()).unwrap();
+    let exported = source.export(DataFormat::Json).unwrap();
+
+    let summary = target
+        .import(&exported, DataFormat::Json, ImportMode::Merge)
+        .unwrap();
+    assert_eq!(
+        summary.to_string(),
+        format!("changed device {socket} (Desk lamp)\n0 added, 1 changed, 0 removed, 4 unchanged")
+    );
+
+    let summary = target
+        .import(&exported, DataFormat::Json, ImportMode::Replace)
+        .unwrap();
+    assert_eq!(
+        summary.to_string(),
+        format!("removed home {other} (Cottage)\n0 added, 0 changed, 1 removed, 4 unchanged")
+    );
+    let homes = target.read_smart_home_status().unwrap().unwrap();
+    assert_eq!(homes.len(), 1);
+    assert_eq!(homes[0].id, home);
+
+    // the import is a single change, so it's undone at once
+    target.undo().unwrap();
+    assert_eq!(target.read_smart_home_status().unwrap().unwrap().len(), 2);
+}
+
+#[test]
+fn invalid_imports_are_rejected() {
+    let (source, home, _) = populated();
+    let target = manager();
+
+    // the same room in two homes
+    let yaml = source.export(DataFormat::Yaml).unwrap();
+    let mut homes: serde_json::Value = serde_yaml::from_str(&yaml).unwrap();
+    let mut copy = homes["homes"][0].clone();
+    copy["id"] = "home_copy".into();
+    copy["rooms"][0]["name"] = "Another hall".into();
+    homes["homes"].as_array_mut().unwrap().push(copy);
+    let yaml = serde_yaml::to_string(&homes).unwrap();
+
+    let error = target
+        .import(&yaml, DataFormat::Yaml, ImportMode::Merge)
+        .unwrap_err();
+    assert!(error.to_string().contains("is already used by"), "{error}");
+
+    // the room of a missing home
+    let csv = format!("kind,id,parent,name,description,labels,on\nroom,room_1,{home},Hall,,,\n");
+    let error = target
+        .import(&csv, DataFormat::Csv, ImportMode::Merge)
+        .unwrap_err();
+    assert!(error.to_string().contains("has no home"), "{error}");
+
+    assert!(target.read_smart_home_status().unwrap().is_none());
+}
+
+#[test]
+fn export_and_import_files_from_cli() {
+    let (source, _, _) = populated();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("homes.toml");
+    let path = path.to_str().unwrap();
+
+    let mut output = Vec::new();
+    let args = Arguments::parse_from(["cli", "export", "-o", path]);
+    CommandHandler::with_manager(&mut output, source.clone()).process(args.command);
+    assert!(std::fs::read_to_string(path)
+        .unwrap()
+        .contains("schema_version = 4"));
+
+    let target = manager();
+    let mut output = Vec::new();
+    let args = Arguments::parse_from(["cli", "import", path, "--replace"]);
+    CommandHandler::with_manager(&mut output, target.clone()).process(args.command);
+    let output = String::from_utf8(output).unwrap();
+    assert!(
+        output.ends_with("4 added, 0 changed, 0 removed, 0 unchanged"),
+        "{output}"
+    );
+}
+
+#[test]
+fn imported_sockets_are_metered_from_the_import() {
+    let (source, _, socket) = populated();
+    source.set_socket_power(&socket, 3_600_000.0).unwrap();
+    let room = source.find_room_by_device_id(&socket).unwrap().id;
+    let stat = source
+        .create_device(DeviceType::THERMOSTAT, room, "Stat".into(), None)
+        .unwrap();
+    let settings = ThermostatSettings {
+        heaters: Some(vec![socket.clone()]),
+        ..ThermostatSettings::default()
+    };
+    source.configure_thermostat(&stat, settings).unwrap();
+    let exported = source.export(DataFormat::Json).unwrap();
+    let counted = |manager: &SmartHomeManager| {
+        let device = manager.find_device_by_id(&socket).unwrap();
+        device.downcast_ref::<Socket>().unwrap().energy.clone()
+    };
+
+    // the heater is dropped from the imported homes, so the thermostat is unlinked from it
+    let mut document: Value = serde_json::from_str(&exported).unwrap();
+    let devices = &mut document["homes"][0]["rooms"][0]["devices"];
+    let exported_socket = devices[0]["Socket"].clone();
+    let exported_energy: EnergyMeter =
+        serde_json::from_value(exported_socket["energy"].clone()).unwrap();
+    devices
+        .as_array_mut()
+        .unwrap()
+        .retain(|device| device.get("Socket").is_none());
+    let without_heater = document.to_string();
+
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    let target = manager();
+    let before_import = Utc::now();
+    target
+        .import(&exported, DataFormat::Json, ImportMode::Merge)
+        .unwrap();
+    let energy = counted(&target);
+    assert_eq!(energy.total_kwh, exported_energy.total_kwh);
+    assert!(energy.metered_at.unwrap() >= before_import);
+
+    // importing the same homes again keeps the counter going
+    let summary = target
+        .import(&exported, DataFormat::Json, ImportMode::Merge)
+        .unwrap();
+    assert!(summary.entities.is_empty(), "{summary}");
+    assert_eq!(counted(&target), energy);
+
+    target
+        .import(&without_heater, DataFormat::Json, ImportMode::Replace)
+        .unwrap();
+    let device = target.find_device_by_id(&stat).unwrap();
+    assert!(device
+        .downcast_ref::<Thermostat>()
+        .unwrap()
+        .heaters
+        .is_empty());
+}
@@ -0,0 +1,92 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use hw_008::cli::{Command, CommandHandler, FsckCommand};
+use hw_008::entities::manager::{
+    CheckFunctions, FindFunctions, InMemoryStore, Problem, SmartHomeManager,
+};
+
+/// The room `room_fghij` is stored in two homes, both homes have the same id, and the second
+/// device is of unknown type
+const BROKEN_STATE: &str = r#"{"schema_version":2,"revision":4,"homes":[
+    {"id":"home_abcde","name":"First","description":null,"rooms":[
+        {"id":"room_fghij","name":"Hall","description":null,"devices":[
+            {"Socket":{"id":"sock_klmno","name":"Lamp","description":null,"power_consumption":0.0,"status":"Disabled"}},
+            {"Kettle":{"id":"kett_pqrst","name":"Kettle"}}
+        ]}
+    ]},
+    {"id":"home_abcde","name":"Second","description":null,"rooms":[
+        {"id":"room_fghij","name":"Hall","description":null,"devices":[
+            {"Socket":{"id":"sock_klmno","name":"Lamp","description":null,"power_consumption":0.0,"status":"Disabled"}},
+            {"Kettle":{"id":"kett_pqrst","name":"Kettle"}}
+        ]}
+    ]}
+]}"#;
+
+fn write_state(root: &Path, content: &str) {
+    let repo = root.join(".smart-home");
+    fs::create_dir_all(&repo).unwrap();
+    fs::write(repo.join("smart-home.json"), content).unwrap();
+}
+
+#[test]
+fn healthy_state_has_no_problems() {
+    let manager = SmartHomeManager::with_store(Arc::new(InMemoryStore::default()));
+    let report = manager.check(true).unwrap();
+
+    assert!(report.is_clean());
+    assert_eq!(report.fixed, None);
+}
+
+#[test]
+fn broken_state_is_reported_and_repaired() {
+    let dir = tempfile::tempdir().unwrap();
+    write_state(dir.path(), BROKEN_STATE);
+    let manager = SmartHomeManager::new(dir.path().to_path_buf());
+    assert!(manager.read_smart_home_status().is_err());
+
+    let report = manager.check(false).unwrap();
+    assert_eq!(report.fixed, None);
+    assert!(matches!(
+        &report.problems[0],
+        Problem::Unreadable { location, .. } if location == "homes[0].rooms[0].devices[1]"
+    ));
+    assert!(matches!(
+        &report.problems[1],
+        Problem::DuplicateId { id, .. } if id == "home_abcde"
+    ));
+    assert!(matches!(
+        &report.problems[2],
+        Problem::DuplicateEntity { id, first, .. } if id == "room_fghij" && first == "homes[0].rooms[0]"
+    ));
+
+    let report = manager.check(true).unwrap();
+    assert_eq!(report.fixed, Some(5));
+
+    let homes = manager.list_all_homes().unwrap();
+    assert_eq!(homes.len(), 2);
+    assert_eq!(homes[0].rooms[0].devices.len(), 1);
+    assert!(homes[1].rooms.is_empty());
+    let ids: HashSet<_> = homes.iter().map(|home| home.id.clone()).collect();
+    assert_eq!(ids.len(), 2);
+    assert!(manager.find_home_by_id(&"home_abcde".into()).is_some());
+
+    assert!(manager.check(false).unwrap().is_clean());
+}
+
+#[test]
+fn problems_give_non_zero_exit_code() {
+    let dir = tempfile::tempdir().unwrap();
+    write_state(dir.path(), BROKEN_STATE);
+
+    let mut output = Vec::new();
+    let mut handler = CommandHandler::new(&mut output, dir.path().to_path_buf());
+    handler.process(Command::Fsck(FsckCommand { fix: false }));
+    assert_eq!(handler.exit_code(), 2);
+    handler.process(Command::Fsck(FsckCommand { fix: true }));
+    assert_eq!(handler.exit_code(), 1);
+    handler.process(Command::Fsck(FsckCommand { fix: false }));
+    assert_eq!(handler.exit_code(), 0);
+}
@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use hw_008::cli::DeviceType;
+use hw_008::entities::manager::{CreateFunctions, FindFunctions, InMemoryStore, SmartHomeManager};
+use hw_008::entities::{set_id_generator, SeededIdGenerator};
+
+#[test]
+fn seeded_generator_gives_the_same_ids() {
+    let create = || {
+        set_id_generator(SeededIdGenerator::new(42));
+        let manager = SmartHomeManager::with_store(Arc::new(InMemoryStore::default()));
+        let home_id = manager.create_home("Home".into(), None).unwrap();
+        let room_id = manager
+            .create_room(home_id.clone(), "Hall".into(), None)
+            .unwrap();
+        let device_id = manager
+            .create_device(DeviceType::THERMOMETER, room_id.clone(), "T".into(), None)
+            .unwrap();
+        (home_id, room_id, device_id)
+    };
+
+    let (home_id, room_id, device_id) = create();
+    assert_eq!(
+        create(),
+        (home_id.clone(), room_id.clone(), device_id.clone())
+    );
+
+    assert!(home_id.starts_with("home_"));
+    assert!(room_id.starts_with("room_"));
+    assert!(device_id.starts_with("ther_"));
+    // the ids sort by the creation time
+    assert!(home_id[5..] < room_id[5..] && room_id[5..] < device_id[5..]);
+}
+
+#[test]
+fn colliding_id_is_generated_again() {
+    let manager = SmartHomeManager::with_store(Arc::new(InMemoryStore::default()));
+
+    set_id_generator(SeededIdGenerator::new(7));
+    let first = manager.create_home("First".into(), None).unwrap();
+    // the same seed gives the same id once again
+    set_id_generator(SeededIdGenerator::new(7));
+    let second = manager.create_home("Second".into(), None).unwrap();
+
+    assert_ne!(first, second);
+    assert_eq!(manager.find_home_by_id(&first).unwrap().name, "First");
+    assert_eq!(manager.find_home_by_id(&second).unwrap().name, "Second");
+}
@@ -0,0 +1,245 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use chrono::Utc;
+use hw_008::cli::DeviceType;
+use hw_008::entities::manager::{
+    set_current_actor, CreateFunctions, FindFunctions, HistoryFunctions, InMemoryStore,
+    JournalEntry, JsonFileStore, RemoveFunctions, SmartHomeEvent, SmartHomeManager, StateStore,
+    UndoFunctions, UpdateFunctions, JOURNAL_LOG, SNAPSHOT_INTERVAL,
+};
+
+fn pause() {
+    // the timestamps of the changes must differ from the timestamps taken by the test
+    thread::sleep(Duration::from_millis(5));
+}
+
+#[test]
+fn every_change_is_journaled() {
+    set_current_actor("tester");
+    let manager = SmartHomeManager::with_store(Arc::new(InMemoryStore::default()));
+
+    let home_id = manager.create_home("Home".into(), None).unwrap();
+    let room_id = manager
+        .create_room(home_id.clone(), "Kitchen".into(), None)
+        .unwrap();
+    let socket_id = manager
+        .create_device(DeviceType::SOCKET, room_id.clone(), "Socket".into(), None)
+        .unwrap();
+    manager.change_device_status(&socket_id, true).unwrap();
+    manager.remove_room(&room_id).unwrap();
+
+    let history = manager.history().unwrap();
+    let events: Vec<String> = history
+        .iter()
+        .flat_map(|entry| entry.events.iter())
+        .map(|event| event.to_string())
+        .collect();
+
+    assert_eq!(history.len(), 5);
+    assert!(history.iter().all(|entry| entry.actor == "tester"));
+    assert_eq!(
+        history.iter().map(|e| e.revision).collect::<Vec<_>>(),
+        vec![1, 2, 3, 4, 5]
+    );
+    assert!(events[0].starts_with("HomeCreated"));
+    assert_eq!(
+        events[3],
+        format!("DeviceStatusChanged {socket_id} enabled")
+    );
+    assert!(events[4].starts_with(&format!("RoomRemoved {room_id}")));
+}
+
+#[test]
+fn home_is_shown_and_restored_at_point_in_time() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = JsonFileStore::new(dir.path().to_path_buf());
+    store.initialize().unwrap();
+    let manager = SmartHomeManager::with_store(Arc::new(store));
+
+    let home_id = manager.create_home("Home".into(), None).unwrap();
+    manager
+        .create_room(home_id.clone(), "Kitchen".into(), None)
+        .unwrap();
+    pause();
+    let before_mistake = Utc::now();
+    pause();
+
+    let hall_id = manager
+        .create_room(home_id.clone(), "Hall".into(), None)
+        .unwrap();
+    manager.remove_home(&home_id).unwrap();
+    assert!(manager.find_home_by_id(&home_id).is_none());
+
+    let past = manager.find_home_at(&home_id, before_mistake).unwrap();
+    assert_eq!(past.unwrap().rooms.len(), 1);
+
+    manager.restore_home(&home_id, before_mistake).unwrap();
+    let restored = manager.find_home_by_id(&home_id).unwrap();
+    assert_eq!(restored.rooms.len(), 1);
+    assert_eq!(restored.rooms[0].name, "Kitchen");
+    assert!(manager.find_room_by_id(&hall_id).is_none());
+
+    // the restoring is a change too
+    let history = manager.history().unwrap();
+    assert!(matches!(
+        history.last().unwrap().events[0],
+        SmartHomeEvent::HomeReplaced { .. }
+    ));
+}
+
+#[test]
+fn history_starts_at_the_first_journaled_change() {
+    let store = Arc::new(InMemoryStore::default());
+    store.save(&Some(vec![]), 0).unwrap();
+
+    let manager = SmartHomeManager::with_store(store);
+    let before = Utc::now();
+    pause();
+    let home_id = manager.create_home("Home".into(), None).unwrap();
+
+    // the state before the first change is taken as a snapshot
+    assert!(manager
+        .find_home_at(&home_id, Utc::now())
+        .unwrap()
+        .is_some());
+    assert!(manager.find_home_at(&home_id, before).is_err());
+}
+
+#[test]
+fn snapshots_are_taken_periodically() {
+    let store = Arc::new(InMemoryStore::default());
+    let manager = SmartHomeManager::with_store(store.clone());
+
+    let home_id = manager.create_home("Home".into(), None).unwrap();
+    let room_id = manager
+        .create_room(home_id.clone(), "Kitchen".into(), None)
+        .unwrap();
+    let socket_id = manager
+        .create_device(DeviceType::SOCKET, room_id, "Socket".into(), None)
+        .unwrap();
+
+    for i in 3..SNAPSHOT_INTERVAL + 10 {
+        manager
+            .change_device_status(&socket_id, i % 2 == 0)
+            .unwrap();
+    }
+
+    assert_eq!(store.read_log("snapshots").unwrap().len(), 1);
+    let home = manager.find_home_at(&home_id, Utc::now()).unwrap().unwrap();
+    assert_eq!(home.rooms[0].devices.len(), 1);
+}
+
+#[test]
+fn journal_ahead_of_state_is_replayed_on_start() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().to_path_buf();
+    let store = Arc::new(JsonFileStore::new(root.clone()));
+    store.initialize().unwrap();
+
+    let home_id = SmartHomeManager::with_store(store.clone())
+        .create_home("Home".into(), None)
+        .unwrap();
+    let home = SmartHomeManager::with_store(store.clone())
+        .find_home_by_id(&home_id)
+        .unwrap();
+
+    // imitate a writer which crashed right after writing the journal
+    let mut renamed = home;
+    renamed.description = Some("Written to the journal only".into());
+    let entry = JournalEntry::new(2, vec![SmartHomeEvent::HomeReplaced { home: renamed }]);
+    store
+        .append_log(JOURNAL_LOG, &[serde_json::to_string(&entry).unwrap()])
+        .unwrap();
+    assert_eq!(store.revision().unwrap(), 1);
+
+    let manager = SmartHomeManager::with_store(Arc::new(JsonFileStore::new(root)));
+    let home = manager.find_home_by_id(&home_id).unwrap();
+    assert_eq!(
+        home.description.as_deref(),
+        Some("Written to the journal only")
+    );
+    assert_eq!(store.revision().unwrap(), 2);
+
+    // the next change continues the journal
+    manager.create_room(home_id, "Hall".into(), None).unwrap();
+    let revisions: Vec<_> = manager
+        .history()
+        .unwrap()
+        .iter()
+        .map(|e| e.revision)
+        .collect();
+    assert_eq!(revisions, vec![1, 2, 3]);
+}
+
+#[test]
+fn removed_home_is_brought_back_by_undo() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().to_path_buf();
+    JsonFileStore::new(root.clone()).initialize().unwrap();
+    // each manager imitates a separate cli invocation
+    let manager = || SmartHomeManager::with_store(Arc::new(JsonFileStore::new(root.clone())));
+
+    let home_id = manager().create_home("Home".into(), None).unwrap();
+    let room_id = manager()
+        .create_room(home_id.clone(), "Kitchen".into(), None)
+        .unwrap();
+    let socket_id = manager()
+        .create_device(DeviceType::SOCKET, room_id.clone(), "Socket".into(), None)
+        .unwrap();
+    manager().change_device_status(&socket_id, true).unwrap();
+    manager().remove_home(&home_id).unwrap();
+
+    let undone = manager().undo().unwrap();
+    assert_eq!(undone.revision, 5);
+    let home = manager().find_home_by_id(&home_id).unwrap();
+    assert_eq!(home.rooms[0].devices[0].id(), &socket_id);
+
+    manager().undo().unwrap();
+    let device = manager().find_device_by_id(&socket_id).unwrap();
+    assert!(device.to_string().contains("Disabled"));
+
+    manager().redo().unwrap();
+    manager().redo().unwrap();
+    assert!(manager().find_home_by_id(&home_id).is_none());
+    assert!(manager().redo().is_err());
+
+    // a new change makes the undone changes impossible to redo
+    manager().undo().unwrap();
+    manager().create_home("Other".into(), None).unwrap();
+    assert!(manager().redo().is_err());
+
+    for _ in 0..5 {
+        manager().undo().unwrap();
+    }
+    assert!(manager().list_all_homes().unwrap().is_empty());
+    assert_eq!(manager().undo().unwrap_err().to_string(), "Nothing to undo");
+}
+
+#[test]
+fn measurements_are_not_undone() {
+    let manager = SmartHomeManager::with_store(Arc::new(InMemoryStore::default()));
+    let home_id = manager.create_home("Home".into(), None).unwrap();
+    let other_id = manager.create_home("Other".into(), None).unwrap();
+    let room_id = manager.create_room(home_id, "Hall".into(), None).unwrap();
+    let thermometer_id = manager
+        .create_device(DeviceType::THERMOMETER, room_id, "T".into(), None)
+        .unwrap();
+
+    manager.remove_home(&other_id).unwrap();
+    manager.make_measure(&thermometer_id).unwrap();
+    let undone = manager.undo().unwrap();
+    assert!(matches!(
+        undone.events[0],
+        SmartHomeEvent::HomeRemoved { .. }
+    ));
+    assert!(manager.find_home_by_id(&other_id).is_some());
+
+    // nor they make the undone changes impossible to redo
+    manager.make_measure(&thermometer_id).unwrap();
+    manager.redo().unwrap();
+    assert!(manager.find_home_by_id(&other_id).is_none());
+    let device = manager.find_device_by_id(&thermometer_id).unwrap();
+    assert!(device.state().last_reading.is_some());
+}
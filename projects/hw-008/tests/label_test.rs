@@ -0,0 +1,131 @@
+use std::sync::Arc;
+
+use hw_008::cli::{Arguments, CommandHandler, DeviceType};
+use hw_008::entities::manager::{
+    CreateFunctions, FindFunctions, InMemoryStore, LabelFunctions, SmartHomeManager, SqliteStore,
+    StateStore, UndoFunctions,
+};
+use hw_008::entities::{parse_label, LabelSelector, Labels};
+
+use clap::Parser;
+
+fn labels(texts: &[&str]) -> Labels {
+    texts
+        .iter()
+        .map(|text| parse_label(text).unwrap())
+        .collect()
+}
+
+fn run(manager: &Arc<SmartHomeManager>, args: &[&str]) -> String {
+    let mut output = Vec::new();
+    let mut handler = CommandHandler::with_manager(&mut output, manager.clone());
+    let args = Arguments::parse_from(std::iter::once("cli").chain(args.iter().copied()));
+    handler.process(args.command);
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn labels_are_set_removed_and_undone() {
+    let manager = SmartHomeManager::with_store(Arc::new(InMemoryStore::default()));
+    let home_id = manager.create_home("Home".into(), None).unwrap();
+
+    let result = manager
+        .label(&home_id, labels(&["floor=2", "critical"]), &[])
+        .unwrap();
+    assert_eq!(result, labels(&["critical", "floor=2"]));
+
+    let result = manager
+        .label(&home_id, labels(&["floor=3"]), &["critical".into()])
+        .unwrap();
+    assert_eq!(result, labels(&["floor=3"]));
+    assert_eq!(manager.find_home_by_id(&home_id).unwrap().labels, result);
+
+    manager.undo().unwrap();
+    let home = manager.find_home_by_id(&home_id).unwrap();
+    assert_eq!(home.labels, labels(&["critical", "floor=2"]));
+    assert!(home.to_string().contains("Labels: critical, floor=2"));
+
+    assert!(manager.label("unknown", Labels::new(), &[]).is_err());
+}
+
+#[test]
+fn labels_are_kept_by_sqlite() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = SqliteStore::new(dir.path().to_path_buf());
+    store.initialize().unwrap();
+    // the databases created before the labels had no columns for them
+    let connection =
+        rusqlite::Connection::open(dir.path().join(".smart-home/smart-home.db")).unwrap();
+    connection
+        .execute_batch(
+            "ALTER TABLE homes DROP COLUMN labels; ALTER TABLE rooms DROP COLUMN labels;",
+        )
+        .unwrap();
+    drop(connection);
+
+    let manager = SmartHomeManager::with_store(Arc::new(store));
+    let home = manager.create_home("Home".into(), None).unwrap();
+    let room = manager
+        .create_room(home.clone(), "Hall".into(), None)
+        .unwrap();
+    manager.label(&home, labels(&["floor=2"]), &[]).unwrap();
+    manager.label(&room, labels(&["critical"]), &[]).unwrap();
+
+    let store = SqliteStore::new(dir.path().to_path_buf());
+    let restarted = SmartHomeManager::with_store(Arc::new(store));
+    assert_eq!(
+        restarted.find_home_by_id(&home).unwrap().labels,
+        labels(&["floor=2"])
+    );
+    assert_eq!(
+        restarted.find_room_by_id(&room).unwrap().labels,
+        labels(&["critical"])
+    );
+}
+
+#[test]
+fn devices_are_selected_by_labels() {
+    let manager = Arc::new(SmartHomeManager::with_store(Arc::new(
+        InMemoryStore::default(),
+    )));
+    let home_id = manager.create_home("Home".into(), None).unwrap();
+    let room_id = manager.create_room(home_id, "Hall".into(), None).unwrap();
+    let create = |name: &str| {
+        manager
+            .create_device(DeviceType::SOCKET, room_id.clone(), name.into(), None)
+            .unwrap()
+    };
+    let lamp = create("Lamp");
+    let fridge = create("Fridge");
+    let heater = create("Heater");
+
+    run(&manager, &["label", "--id", &lamp, "floor=2"]);
+    run(&manager, &["label", "--id", &fridge, "floor=2", "critical"]);
+    run(&manager, &["label", "--id", &heater, "floor=1"]);
+
+    let selector: LabelSelector = "floor=2,!critical".parse().unwrap();
+    let selected = manager.select_devices(&selector).unwrap();
+    assert_eq!(selected.len(), 1);
+    assert_eq!(selected[0].id(), &lamp);
+
+    assert_eq!(
+        run(&manager, &["list", "devices", "-l", "floor"])
+            .lines()
+            .count(),
+        3
+    );
+    let output = run(
+        &manager,
+        &["status", "device", "-l", "floor=2", "--enable", "true"],
+    );
+    assert!(output.starts_with(&format!("{lamp}: on")));
+    assert!(output.contains(&format!("\n{fridge}: on")));
+    assert!(!output.contains(&heater));
+
+    let output = run(&manager, &["list", "devices", "-l", "floor=,"]);
+    assert!(output.contains("has no value"), "{output}");
+    assert_eq!(
+        run(&manager, &["status", "room", "-l", "zone"]),
+        "Not found"
+    );
+}
@@ -0,0 +1,144 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use clap::Parser;
+
+use hw_008::cli::{Arguments, CommandHandler, DeviceType};
+use hw_008::entities::devices::Light;
+use hw_008::entities::manager::{
+    CreateFunctions, FindFunctions, InMemoryStore, LightFunctions, LightSettings, SmartHomeManager,
+    UndoFunctions, UpdateFunctions,
+};
+
+fn manager() -> Arc<SmartHomeManager> {
+    Arc::new(SmartHomeManager::with_store(Arc::new(
+        InMemoryStore::default(),
+    )))
+}
+
+fn light(manager: &SmartHomeManager, id: &String) -> Light {
+    let device = manager.find_device_by_id(id).unwrap();
+    device.downcast_ref::<Light>().unwrap().clone()
+}
+
+#[test]
+fn transition_goes_gradually_to_the_new_brightness() {
+    let mut light = Light::new("Desk");
+    light.state.on = true;
+    let start = Utc::now();
+
+    light.set_brightness(0, Some(10_000), start);
+    assert_eq!(light.level_at(start), 100);
+    assert_eq!(light.level_at(start + chrono::Duration::seconds(5)), 50);
+    assert_eq!(light.level_at(start + chrono::Duration::seconds(20)), 0);
+
+    // the interrupted transition continues from the level it reached
+    light.set_brightness(80, Some(1_000), start + chrono::Duration::seconds(5));
+    assert_eq!(light.transition.unwrap().from, 50);
+
+    light.state.on = false;
+    assert_eq!(light.level_at(start + chrono::Duration::seconds(5)), 0);
+}
+
+#[test]
+fn light_is_adjusted_and_undone() {
+    let manager = manager();
+    let home = manager.create_home("Home".into(), None).unwrap();
+    let room = manager.create_room(home, "Hall".into(), None).unwrap();
+    let id = manager
+        .create_device(DeviceType::LIGHT, room, "Lamp".into(), None)
+        .unwrap();
+    assert!(id.starts_with("light_"));
+    assert_eq!(light(&manager, &id).brightness, 100);
+
+    let settings = LightSettings {
+        brightness: Some(30),
+        color_temperature: Some(2700),
+        transition: None,
+    };
+    manager.adjust_light(&id, settings).unwrap();
+    let adjusted = light(&manager, &id);
+    assert_eq!(adjusted.brightness, 30);
+    assert_eq!(adjusted.color_temperature, Some(2700));
+
+    // the switched off light measures the zero level
+    assert_eq!(
+        manager.make_measure(&id).unwrap().to_string(),
+        "brightness 0%"
+    );
+    manager.change_device_status(&id, true).unwrap();
+    assert_eq!(
+        manager.make_measure(&id).unwrap().to_string(),
+        "brightness 30%"
+    );
+
+    // the measurements are not undone, so the adjustment is the second change from the end
+    for _ in 0..2 {
+        manager.undo().unwrap();
+    }
+    assert_eq!(light(&manager, &id).brightness, 100);
+    assert_eq!(light(&manager, &id).color_temperature, None);
+}
+
+#[test]
+fn wrong_settings_are_rejected() {
+    let manager = manager();
+    let home = manager.create_home("Home".into(), None).unwrap();
+    let room = manager.create_room(home, "Hall".into(), None).unwrap();
+    let socket = manager
+        .create_device(DeviceType::SOCKET, room.clone(), "Kettle".into(), None)
+        .unwrap();
+    let lamp = manager
+        .create_device(DeviceType::LIGHT, room, "Lamp".into(), None)
+        .unwrap();
+
+    let brightness = |brightness| LightSettings {
+        brightness: Some(brightness),
+        transition: Some(Duration::from_secs(1)),
+        ..LightSettings::default()
+    };
+    let error = manager.adjust_light(&socket, brightness(50)).unwrap_err();
+    assert!(error.to_string().contains("not a light"), "{error}");
+    assert!(manager.adjust_light(&lamp, brightness(101)).is_err());
+
+    let too_cold = LightSettings {
+        color_temperature: Some(20_000),
+        ..LightSettings::default()
+    };
+    assert!(manager.adjust_light(&lamp, too_cold).is_err());
+    assert!(manager
+        .adjust_light(&lamp, LightSettings::default())
+        .is_err());
+}
+
+#[test]
+fn light_command_sets_brightness() {
+    let manager = manager();
+    let home = manager.create_home("Home".into(), None).unwrap();
+    let room = manager.create_room(home, "Hall".into(), None).unwrap();
+    let run = |args: &[&str]| {
+        let mut output = Vec::new();
+        let args = Arguments::parse_from(std::iter::once("cli").chain(args.iter().copied()));
+        CommandHandler::with_manager(&mut output, manager.clone()).process(args.command);
+        String::from_utf8(output).unwrap()
+    };
+
+    run(&[
+        "new",
+        "device",
+        "--room-id",
+        &room,
+        "-n",
+        "Lamp",
+        "--type",
+        "light",
+    ]);
+    let output = run(&["light", "-i", "Home/Hall/Lamp", "-b", "40", "-k", "3000"]);
+    assert!(output.starts_with("Light: Lamp"), "{output}");
+    assert!(output.contains("Brightness: 40%"), "{output}");
+    assert!(output.contains("Colour temperature: 3000K"), "{output}");
+
+    let parsed = Arguments::try_parse_from(["cli", "light", "-i", "Lamp", "-b", "150"]);
+    assert!(parsed.is_err());
+}
@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use hw_008::cli::{Arguments, CommandHandler, DeviceType, SortKey};
+use hw_008::entities::manager::{
+    CreateFunctions, InMemoryStore, ListFunctions, ListQuery, SmartHomeManager, UpdateFunctions,
+};
+
+use clap::Parser;
+
+struct Fixture {
+    manager: Arc<SmartHomeManager>,
+    home: String,
+    kitchen: String,
+    /// The devices in the order of creation
+    devices: Vec<String>,
+}
+
+fn fixture() -> Fixture {
+    let manager = Arc::new(SmartHomeManager::with_store(Arc::new(
+        InMemoryStore::default(),
+    )));
+    let home = manager.create_home("Home".into(), None).unwrap();
+    let cottage = manager.create_home("Cottage".into(), None).unwrap();
+    let kitchen = manager
+        .create_room(home.clone(), "Kitchen".into(), None)
+        .unwrap();
+    let garage = manager.create_room(cottage, "Garage".into(), None).unwrap();
+
+    let devices = [
+        (DeviceType::THERMOMETER, &kitchen, "Wall"),
+        (DeviceType::SOCKET, &kitchen, "Kettle"),
+        (DeviceType::THERMOMETER, &garage, "Floor"),
+        (DeviceType::SOCKET, &garage, "Charger"),
+        (DeviceType::THERMOMETER, &kitchen, "Fridge"),
+    ]
+    .into_iter()
+    .map(|(device_type, room, name)| {
+        manager
+            .create_device(device_type, room.clone(), name.into(), None)
+            .unwrap()
+    })
+    .collect();
+
+    Fixture {
+        manager,
+        home,
+        kitchen,
+        devices,
+    }
+}
+
+fn run(manager: &Arc<SmartHomeManager>, args: &[&str]) -> String {
+    let mut output = Vec::new();
+    let mut handler = CommandHandler::with_manager(&mut output, manager.clone());
+    let args = Arguments::parse_from(std::iter::once("cli").chain(args.iter().copied()));
+    handler.process(args.command);
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn devices_are_filtered_sorted_and_paged() {
+    let f = fixture();
+    let d = &f.devices;
+
+    let query = ListQuery {
+        device_type: Some(DeviceType::THERMOMETER),
+        sort: Some(SortKey::Name),
+        ..ListQuery::default()
+    };
+    let page = f.manager.query_devices(&query).unwrap();
+    let ids: Vec<&String> = page.items.iter().map(|device| device.id()).collect();
+    assert_eq!(ids, vec![&d[2], &d[4], &d[0]]);
+    assert_eq!(page.footer(), None);
+
+    let query = ListQuery {
+        home_id: Some(f.home.clone()),
+        offset: 1,
+        limit: Some(1),
+        ..ListQuery::default()
+    };
+    let page = f.manager.query_devices(&query).unwrap();
+    assert_eq!(page.items[0].id(), &d[1]);
+    assert_eq!(page.total, 3);
+    assert!(page.has_more());
+    assert_eq!(page.footer().unwrap(), "# 2-2 of 3");
+
+    f.manager.change_device_status(&d[3], true).unwrap();
+    let query = ListQuery {
+        status: Some(true),
+        ..ListQuery::default()
+    };
+    let page = f.manager.query_devices(&query).unwrap();
+    let ids: Vec<&String> = page.items.iter().map(|device| device.id()).collect();
+    // the thermometers are switched on since creation, and the devices are listed room by room
+    assert_eq!(ids, vec![&d[0], &d[4], &d[2], &d[3]]);
+}
+
+#[test]
+fn list_command_takes_filters() {
+    let f = fixture();
+    let d = &f.devices;
+
+    let output = run(
+        &f.manager,
+        &[
+            "list",
+            "devices",
+            "--type",
+            "thermometer",
+            "--room",
+            "Home/Kitchen",
+            "--sort",
+            "name",
+        ],
+    );
+    assert_eq!(output, format!("{}\n{}", d[4], d[0]));
+
+    let output = run(
+        &f.manager,
+        &["list", "devices", "--limit", "2", "--offset", "4"],
+    );
+    assert_eq!(output, format!("{}\n# 5-5 of 5", d[3]));
+
+    let output = run(&f.manager, &["list", "rooms", "--home", "Home"]);
+    assert_eq!(output, f.kitchen);
+
+    let output = run(&f.manager, &["list", "homes", "--offset", "5"]);
+    assert_eq!(output, "# nothing at offset 5 of 2");
+}
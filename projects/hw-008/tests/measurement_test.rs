@@ -0,0 +1,142 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use clap::Parser;
+
+use hw_008::cli::{Arguments, CommandHandler, DeviceType};
+use hw_008::entities::devices::{Device, EnvironmentSensor, Reading};
+use hw_008::entities::manager::{
+    CreateFunctions, FindFunctions, InMemoryStore, SmartHomeManager, StateStore, UpdateFunctions,
+    JOURNAL_LOG,
+};
+use hw_008::entities::{Measure, Quality, Quantity, Unit};
+
+fn manager() -> Arc<SmartHomeManager> {
+    Arc::new(SmartHomeManager::with_store(Arc::new(
+        InMemoryStore::default(),
+    )))
+}
+
+#[test]
+fn sensor_measures_everything_at_once() {
+    let mut sensor = EnvironmentSensor::new("Air");
+    let measurements = sensor.measure().unwrap();
+    let quantities: Vec<Quantity> = measurements.iter().map(|m| m.quantity).collect();
+    assert_eq!(
+        quantities,
+        [
+            Quantity::Temperature,
+            Quantity::Humidity,
+            Quantity::Co2,
+            Quantity::Pressure
+        ]
+    );
+    assert!(measurements.iter().all(|m| m.unit == m.quantity.unit()));
+    assert!(measurements.iter().all(|m| m.quality == Quality::Good));
+
+    // the freshly switched on sensor is still warming up
+    sensor.state.changed_at = Some(Utc::now());
+    let reading = Reading::new(sensor.measure().unwrap());
+    assert_eq!(
+        reading.get(Quantity::Co2).unwrap().quality,
+        Quality::Uncertain
+    );
+    assert_eq!(
+        reading.get(Quantity::Pressure).unwrap().unit,
+        Unit::Hectopascal
+    );
+
+    sensor.state.on = false;
+    assert!(sensor.measure().is_err());
+}
+
+#[test]
+fn reading_is_kept_as_last_reading() {
+    let manager = manager();
+    let home = manager.create_home("Home".into(), None).unwrap();
+    let room = manager.create_room(home, "Hall".into(), None).unwrap();
+    let sensor = manager
+        .create_device(DeviceType::ENVIRONMENT_SENSOR, room, "Air".into(), None)
+        .unwrap();
+    assert!(sensor.starts_with("env_"));
+
+    let reading = manager.make_measure(&sensor).unwrap();
+    assert_eq!(reading.measurements.len(), 4);
+    let device = manager.find_device_by_id(&sensor).unwrap();
+    assert_eq!(device.state().last_reading.as_ref(), Some(&reading));
+
+    manager.change_device_status(&sensor, false).unwrap();
+    assert!(manager.make_measure(&sensor).is_err());
+}
+
+#[test]
+fn measure_command_writes_all_values() {
+    let manager = manager();
+    let home = manager.create_home("Home".into(), None).unwrap();
+    let room = manager.create_room(home, "Hall".into(), None).unwrap();
+    let run = |args: &[&str]| {
+        let mut output = Vec::new();
+        let args = Arguments::parse_from(std::iter::once("cli").chain(args.iter().copied()));
+        CommandHandler::with_manager(&mut output, manager.clone()).process(args.command);
+        String::from_utf8(output).unwrap()
+    };
+
+    run(&[
+        "new",
+        "device",
+        "--room-id",
+        &room,
+        "-n",
+        "Air",
+        "--type",
+        "environment-sensor",
+    ]);
+    let output = run(&["measure", "-i", "Home/Hall/Air"]);
+    for quantity in ["temperature", "humidity", "co2", "pressure"] {
+        assert!(output.contains(quantity), "{output}");
+    }
+    assert!(output.contains(" ppm"), "{output}");
+}
+
+#[test]
+fn bare_values_are_read_as_unspecified_measurements() {
+    let reading: Reading =
+        serde_json::from_str(r#"{"value": 0.25, "taken_at": "2023-01-31T10:00:00Z"}"#).unwrap();
+    let measurement = reading.get(Quantity::Unspecified).unwrap();
+    assert_eq!(measurement.value, 0.25);
+    assert_eq!(measurement.unit, Unit::None);
+    assert_eq!(reading.to_string(), "value 0.25");
+}
+
+#[test]
+fn bare_values_get_the_quantity_of_their_device() {
+    let device: Device = serde_json::from_str(
+        r#"{"Thermometer": {"id": "ther_1", "name": "T", "description": null, "state": {"on": true,
+            "last_reading": {"value": 21.5, "taken_at": "2023-01-31T10:00:00Z"},
+            "changed_at": null}}}"#,
+    )
+    .unwrap();
+    let reading = device.state().last_reading.as_ref().unwrap();
+    assert_eq!(reading.to_string(), "temperature 21.5°C");
+
+    // the journal written before the measurements were introduced is replayed the same way
+    let store = Arc::new(InMemoryStore::default());
+    let manager = SmartHomeManager::with_store(store.clone());
+    let home = manager.create_home("Home".into(), None).unwrap();
+    let room = manager.create_room(home, "Hall".into(), None).unwrap();
+    let thermometer = manager
+        .create_device(DeviceType::THERMOMETER, room, "T".into(), None)
+        .unwrap();
+    let record = format!(
+        r#"{{"revision": 4, "timestamp": "{}", "actor": "old", "events": [{{"DeviceMeasured":
+            {{"device_id": "{thermometer}", "reading": {{"value": 19.0,
+            "taken_at": "2023-01-31T10:00:00Z"}}}}}}]}}"#,
+        Utc::now().to_rfc3339()
+    );
+    store.append_log(JOURNAL_LOG, &[record]).unwrap();
+
+    let restarted = SmartHomeManager::with_store(store);
+    let device = restarted.find_device_by_id(&thermometer).unwrap();
+    let reading = device.state().last_reading.as_ref().unwrap();
+    assert_eq!(reading.get(Quantity::Temperature).unwrap().value, 19.0);
+}
@@ -0,0 +1,113 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use hw_008::entities::manager::{
+    FindFunctions, HistoryFunctions, JsonFileStore, SmartHomeEvent, SmartHomeManager, SqliteStore,
+    StateStore, StoreError, SCHEMA_VERSION,
+};
+
+/// The state file exactly as the hw-006 and hw-007 crates write it
+const HW_007_STATE: &str = r#"[{"id":"home_abcde","name":"Legacy","description":null,"rooms":[{"id":"room_fghij","name":"Hall","description":"Big one","devices":[{"Socket":{"id":"sock_klmno","name":"Lamp","description":null,"power_consumption":0.0,"status":"Disabled"}}]}]}]"#;
+
+fn write_state(root: &Path, content: &str) -> std::path::PathBuf {
+    let repo = root.join(".smart-home");
+    fs::create_dir_all(&repo).unwrap();
+    let file = repo.join("smart-home.json");
+    fs::write(&file, content).unwrap();
+    file
+}
+
+#[test]
+fn legacy_state_file_is_migrated() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_state(dir.path(), HW_007_STATE);
+    let manager = SmartHomeManager::new(dir.path().to_path_buf());
+
+    let plan = manager.migrate(true).unwrap();
+    assert_eq!((plan.from, plan.to), (0, SCHEMA_VERSION));
+    assert_eq!(plan.steps.len(), SCHEMA_VERSION as usize);
+    let rewritten = plan.rewritten.as_ref().unwrap();
+    assert!(rewritten[0].is_empty());
+    assert_eq!(rewritten[2].get("Socket"), Some(&1));
+    assert!(plan.to_string().contains(
+        "2 -> 3: move the status of the sockets into the device state (rewrites 1 Socket)"
+    ));
+    assert_eq!(fs::read_to_string(&file).unwrap(), HW_007_STATE);
+
+    manager.migrate(false).unwrap();
+    let content = fs::read_to_string(&file).unwrap();
+    assert!(content.contains(&format!("\"schema_version\":{SCHEMA_VERSION}")));
+    assert!(manager.migrate(true).unwrap().is_empty());
+
+    let home = manager.find_home_by_id(&"home_abcde".to_string()).unwrap();
+    assert_eq!(home.rooms[0].devices[0].name(), "Lamp");
+    assert!(matches!(
+        manager.history().unwrap()[0].events[0],
+        SmartHomeEvent::SchemaMigrated { from: 0, .. }
+    ));
+}
+
+#[test]
+fn broken_state_is_reported_instead_of_panic() {
+    let dir = tempfile::tempdir().unwrap();
+    write_state(dir.path(), r#"[{"id":"home_abcde","name":"No rooms"}]"#);
+    let manager = SmartHomeManager::new(dir.path().to_path_buf());
+
+    let error = manager.read_smart_home_status().unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<StoreError>(),
+        Some(StoreError::MalformedState(_))
+    ));
+
+    write_state(
+        dir.path(),
+        r#"{"schema_version":999,"revision":1,"homes":null}"#,
+    );
+    let manager = SmartHomeManager::new(dir.path().to_path_buf());
+    let error = manager.list_all_homes().unwrap_err();
+    assert!(error.to_string().contains("999"));
+}
+
+#[test]
+fn state_of_previous_crates_is_imported() {
+    let dir = tempfile::tempdir().unwrap();
+    let legacy = dir.path().join("hw-007.json");
+    fs::write(&legacy, HW_007_STATE).unwrap();
+
+    let store = SqliteStore::new(dir.path().to_path_buf());
+    store.initialize().unwrap();
+    let manager = SmartHomeManager::with_store(Arc::new(store));
+
+    let plan = manager.import_state(&legacy).unwrap();
+    assert_eq!(plan.from, 0);
+    let room = manager
+        .find_room_by_device_id(&"sock_klmno".to_string())
+        .unwrap();
+    assert_eq!(room.description.as_deref(), Some("Big one"));
+}
+
+#[test]
+fn unversioned_database_is_migrated() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = SqliteStore::new(dir.path().to_path_buf());
+    store.initialize().unwrap();
+    assert_eq!(store.schema_version().unwrap(), SCHEMA_VERSION);
+
+    // the databases created before the versioning had no schema version
+    let connection =
+        rusqlite::Connection::open(dir.path().join(".smart-home/smart-home.db")).unwrap();
+    connection
+        .execute("DELETE FROM meta WHERE key = 'schema_version'", [])
+        .unwrap();
+    drop(connection);
+
+    let manager = SmartHomeManager::with_store(Arc::new(store));
+    assert_eq!(manager.migrate(false).unwrap().from, 1);
+
+    let store = SqliteStore::new(dir.path().to_path_buf());
+    assert_eq!(store.schema_version().unwrap(), SCHEMA_VERSION);
+    assert!(JsonFileStore::new(dir.path().to_path_buf())
+        .schema_version()
+        .is_err());
+}
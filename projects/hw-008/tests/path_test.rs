@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use hw_008::cli::{Arguments, CommandHandler, DeviceType};
+use hw_008::entities::manager::{CreateFunctions, FindFunctions, InMemoryStore, SmartHomeManager};
+
+use clap::Parser;
+
+struct Fixture {
+    manager: Arc<SmartHomeManager>,
+    main_light: String,
+    hall: String,
+}
+
+fn fixture() -> Fixture {
+    let manager = Arc::new(SmartHomeManager::with_store(Arc::new(
+        InMemoryStore::default(),
+    )));
+    let home = manager.create_home("My Home".into(), None).unwrap();
+    let cottage = manager.create_home("Cottage".into(), None).unwrap();
+
+    let kitchen = manager
+        .create_room(home.clone(), "Kitchen".into(), None)
+        .unwrap();
+    let hall = manager.create_room(home, "Hall".into(), None).unwrap();
+    let cottage_kitchen = manager
+        .create_room(cottage, "Kitchen".into(), None)
+        .unwrap();
+
+    let main_light = manager
+        .create_device(DeviceType::SOCKET, kitchen, "Main light".into(), None)
+        .unwrap();
+    manager
+        .create_device(DeviceType::SOCKET, cottage_kitchen, "Kettle".into(), None)
+        .unwrap();
+
+    Fixture {
+        manager,
+        main_light,
+        hall,
+    }
+}
+
+#[test]
+fn entities_are_resolved_by_path_and_pattern() {
+    let f = fixture();
+    let manager = &f.manager;
+
+    assert_eq!(
+        manager
+            .resolve_device("My Home/Kitchen/Main light")
+            .unwrap(),
+        f.main_light
+    );
+    assert_eq!(manager.resolve_device("*/*/Main*").unwrap(), f.main_light);
+    assert_eq!(manager.resolve_device(&f.main_light).unwrap(), f.main_light);
+    assert_eq!(manager.resolve_entity("My Home/Hall").unwrap(), f.hall);
+
+    let home_id = manager.resolve_home("My Home").unwrap();
+    assert_eq!(
+        manager.resolve_room(&format!("{home_id}/Hall")).unwrap(),
+        f.hall
+    );
+
+    assert_eq!(manager.find_by_path("*/Kitchen/*").unwrap().len(), 2);
+    assert_eq!(manager.find_by_path("*/Kitchen").unwrap().len(), 2);
+}
+
+#[test]
+fn ambiguous_and_unknown_references_are_rejected() {
+    let f = fixture();
+    let manager = &f.manager;
+
+    let error = manager.resolve_room("*/Kitchen").unwrap_err().to_string();
+    assert!(error.contains("is ambiguous"), "{error}");
+    assert!(error.contains("My Home/Kitchen"));
+    assert!(error.contains("Cottage/Kitchen"));
+
+    assert!(manager.resolve_room("My Home/Bedroom").is_err());
+    let error = manager.resolve_device("My Home/Kitchen").unwrap_err();
+    assert!(error.to_string().contains("<home>/<room>/<device>"));
+}
+
+#[test]
+fn commands_accept_paths_instead_of_ids() {
+    let f = fixture();
+    let mut output = Vec::new();
+    let mut handler = CommandHandler::with_manager(&mut output, f.manager.clone());
+
+    let args = Arguments::parse_from([
+        "cli",
+        "move",
+        "device",
+        "--id",
+        "My Home/Kitchen/Main light",
+        "--room-id",
+        "My Home/Hall",
+    ]);
+    handler.process(args.command);
+
+    let room = f.manager.find_room_by_device_id(&f.main_light).unwrap();
+    assert_eq!(room.id, f.hall);
+}
@@ -0,0 +1,179 @@
+use std::sync::Arc;
+
+use hw_008::cli::{Arguments, CommandHandler, DataFormat, DeviceType};
+use hw_008::entities::devices::Thermostat;
+use hw_008::entities::manager::{
+    Action, CreateFunctions, FindFunctions, HouseSpec, InMemoryStore, PlanFunctions,
+    SmartHomeManager, ThermostatFunctions, ThermostatSettings, UndoFunctions,
+};
+use hw_008::entities::NamingRules;
+
+use clap::Parser;
+
+const HOUSE: &str = r#"
+homes:
+  - name: My Home
+    rooms:
+      - name: Kitchen
+        description: The one with the fridge
+        devices:
+          - name: Kettle
+            type: socket
+            labels:
+              critical: ""
+          - name: Wall
+            type: thermometer
+      - name: Hall
+"#;
+
+fn manager() -> Arc<SmartHomeManager> {
+    Arc::new(SmartHomeManager::with_store(Arc::new(
+        InMemoryStore::default(),
+    )))
+}
+
+fn spec(text: &str) -> HouseSpec {
+    HouseSpec::parse(text, DataFormat::Yaml).unwrap()
+}
+
+#[test]
+fn apply_converges_and_reapply_changes_nothing() {
+    let manager = manager();
+
+    let plan = manager.plan(&spec(HOUSE)).unwrap();
+    assert_eq!(plan.count(Action::Create), 5);
+    assert!(manager.read_smart_home_status().unwrap().is_none());
+
+    manager.apply(&spec(HOUSE)).unwrap();
+    let kettle = manager.resolve_device("My Home/Kitchen/Kettle").unwrap();
+    assert!(kettle.starts_with("sock_"));
+
+    let plan = manager.apply(&spec(HOUSE)).unwrap();
+    assert!(plan.is_empty(), "{plan}");
+    assert_eq!(
+        manager.resolve_device("My Home/Kitchen/Kettle").unwrap(),
+        kettle
+    );
+}
+
+#[test]
+fn entities_are_matched_by_names() {
+    let manager = manager();
+    manager.apply(&spec(HOUSE)).unwrap();
+    let home = manager.resolve_home("My Home").unwrap();
+    let garage = manager.create_room(home, "Garage".into(), None).unwrap();
+    let wall = manager.resolve_device("My Home/Kitchen/Wall").unwrap();
+
+    let changed = HOUSE
+        .replace("The one with the fridge", "The one with the oven")
+        .replace("type: thermometer", "type: socket")
+        .replace("      - name: Hall\n", "");
+    let plan = manager.apply(&spec(&changed)).unwrap();
+    assert_eq!(
+        plan.to_string(),
+        [
+            "~ room My Home/Kitchen",
+            r#"    description: "The one with the fridge" -> "The one with the oven""#,
+            "-/+ device My Home/Kitchen/Wall",
+            "    type: thermometer -> socket",
+            "- room My Home/Hall",
+            "- room My Home/Garage",
+            "Plan: 1 to create, 1 to update, 3 to remove",
+        ]
+        .join("\n")
+    );
+
+    assert!(manager.find_room_by_id(&garage).is_none());
+    assert!(manager.find_device_by_id(&wall).is_none());
+    let device = manager
+        .find_device_by_id(&manager.resolve_device("My Home/Kitchen/Wall").unwrap())
+        .unwrap();
+    assert_eq!(device.device_type(), DeviceType::SOCKET);
+
+    // the whole apply is a single change
+    manager.undo().unwrap();
+    assert!(manager.find_room_by_id(&garage).is_some());
+    assert!(manager.find_device_by_id(&wall).is_some());
+}
+
+#[test]
+fn ambiguous_names_are_rejected() {
+    let duplicated = HOUSE.replace("name: Hall", "name: Kitchen");
+    let error = HouseSpec::parse(&duplicated, DataFormat::Yaml).unwrap_err();
+    assert_eq!(error.to_string(), "The name My Home/Kitchen is used twice");
+
+    // the duplicated names are only possible when the names don't have to be unique
+    let rules = NamingRules {
+        unique_names: false,
+        ..NamingRules::default()
+    };
+    let manager =
+        SmartHomeManager::with_store(Arc::new(InMemoryStore::default())).with_naming_rules(rules);
+    manager.create_home("My Home".into(), None).unwrap();
+    manager.create_home("My Home".into(), None).unwrap();
+    let error = manager.plan(&spec(HOUSE)).unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("Several entities are named My Home"));
+}
+
+#[test]
+fn plan_and_apply_from_cli() {
+    let manager = manager();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("house.yaml");
+    std::fs::write(&path, HOUSE).unwrap();
+    let path = path.to_str().unwrap();
+
+    let run = |args: &[&str]| {
+        let mut output = Vec::new();
+        let args = Arguments::parse_from(std::iter::once("cli").chain(args.iter().copied()));
+        CommandHandler::with_manager(&mut output, manager.clone()).process(args.command);
+        String::from_utf8(output).unwrap()
+    };
+
+    let output = run(&["plan", "-f", path]);
+    assert!(output.starts_with("+ home My Home\n"), "{output}");
+    assert!(output.ends_with("Plan: 5 to create, 0 to update, 0 to remove"));
+
+    run(&["apply", "-f", path]);
+    let output = run(&["plan", "-f", path]);
+    assert_eq!(output, "No changes, the smart home matches the description");
+}
+
+#[test]
+fn removed_devices_are_unlinked_from_thermostats() {
+    let manager = manager();
+    manager.apply(&spec(HOUSE)).unwrap();
+    let kitchen = manager.resolve_room("My Home/Kitchen").unwrap();
+    let stat = manager
+        .create_device(DeviceType::THERMOSTAT, kitchen, "Stat".into(), None)
+        .unwrap();
+    let kettle = manager.resolve_device("My Home/Kitchen/Kettle").unwrap();
+    let wall = manager.resolve_device("My Home/Kitchen/Wall").unwrap();
+    let settings = ThermostatSettings {
+        thermometer: Some(wall),
+        heaters: Some(vec![kettle]),
+        ..ThermostatSettings::default()
+    };
+    manager.configure_thermostat(&stat, settings).unwrap();
+
+    // the kettle is replaced with a light, and the thermometer is gone
+    let house = r#"
+homes:
+  - name: My Home
+    rooms:
+      - name: Kitchen
+        devices:
+          - name: Kettle
+            type: light
+          - name: Stat
+            type: thermostat
+      - name: Hall
+"#;
+    manager.apply(&spec(house)).unwrap();
+    let device = manager.find_device_by_id(&stat).unwrap();
+    let thermostat = device.downcast_ref::<Thermostat>().unwrap();
+    assert_eq!(thermostat.thermometer, None);
+    assert!(thermostat.heaters.is_empty());
+}
@@ -0,0 +1,196 @@
+use std::fs;
+use std::sync::Arc;
+use std::thread;
+
+use hw_008::cli::DeviceType;
+use hw_008::entities::manager::{
+    CreateFunctions, FindFunctions, InMemoryStore, JsonFileStore, RemoveFunctions,
+    SmartHomeManager, SqliteStore, StateStore, StoreError, UpdateFunctions,
+};
+
+/// The same scenario is executed against each store, so all of them must behave the same way
+fn create_find_and_remove(store: Arc<dyn StateStore>) {
+    let manager = SmartHomeManager::with_store(store.clone());
+
+    let home_id = manager
+        .create_home("Home".into(), Some("Test home".into()))
+        .expect("Unable create home");
+    let room_id = manager
+        .create_room(home_id.clone(), "Kitchen".into(), None)
+        .expect("Unable create room");
+    let device_id = manager
+        .create_device(
+            DeviceType::THERMOMETER,
+            room_id.clone(),
+            "Therm".into(),
+            None,
+        )
+        .expect("Unable create device");
+
+    // A fresh manager on top of the same store must see the changes
+    let manager = SmartHomeManager::with_store(store.clone());
+    let home = manager.find_home_by_id(&home_id).expect("Home not found");
+    assert_eq!(home.description, Some("Test home".to_string()));
+    assert_eq!(home.rooms.len(), 1);
+    assert_eq!(
+        manager.find_room_by_device_id(&device_id).unwrap().id,
+        room_id
+    );
+    assert_eq!(
+        manager.find_device_by_id(&device_id).unwrap().name(),
+        "Therm"
+    );
+
+    manager.remove_room(&room_id).expect("Unable remove room");
+    assert!(manager.find_device_by_id(&device_id).is_none());
+    assert_eq!(manager.list_all_homes().unwrap().len(), 1);
+
+    // the logs are rewritten as a whole, e.g. by the purge of the trash
+    let records = |texts: &[&str]| {
+        texts
+            .iter()
+            .map(|text| text.to_string())
+            .collect::<Vec<_>>()
+    };
+    store.append_log("test", &records(&["a", "b"])).unwrap();
+    store
+        .rewrite_log("test", &mut |log| {
+            assert_eq!(log, records(&["a", "b"]));
+            Ok(records(&["c"]))
+        })
+        .unwrap();
+    store.append_log("test", &records(&["d"])).unwrap();
+    assert_eq!(store.read_log("test").unwrap(), records(&["c", "d"]));
+    assert_eq!(store.last_log_record("test").unwrap(), Some("d".into()));
+}
+
+#[test]
+fn in_memory_store() {
+    create_find_and_remove(Arc::new(InMemoryStore::default()));
+}
+
+#[test]
+fn json_file_store() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = JsonFileStore::new(dir.path().to_path_buf());
+    assert!(!store.is_initialized());
+    store.initialize().expect("Unable initialize store");
+    assert!(store.initialize().is_err());
+
+    create_find_and_remove(Arc::new(store));
+}
+
+#[test]
+fn sqlite_store() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = SqliteStore::new(dir.path().to_path_buf());
+    assert!(store.load().is_err());
+    store.initialize().expect("Unable initialize store");
+    assert!(store.initialize().is_err());
+
+    create_find_and_remove(Arc::new(store));
+}
+
+#[test]
+fn outdated_changes_are_rejected() {
+    let store: Arc<dyn StateStore> = Arc::new(InMemoryStore::default());
+    let first = SmartHomeManager::with_store(store.clone());
+    let second = SmartHomeManager::with_store(store);
+
+    let home_id = first.create_home("Home".into(), None).unwrap();
+    let mut home = first.find_home_by_id(&home_id).unwrap();
+
+    second
+        .create_room(home_id.clone(), "Kitchen".into(), None)
+        .unwrap();
+
+    home.description = Some("Outdated change".into());
+    let error = first.update_home_state(home).unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<StoreError>(),
+        Some(&StoreError::ConcurrentModification(1, 2))
+    );
+
+    let home = second.find_home_by_id(&home_id).unwrap();
+    assert_eq!(home.rooms.len(), 1);
+    assert_eq!(home.description, None);
+}
+
+#[test]
+fn concurrent_writers_do_not_lose_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = Arc::new(JsonFileStore::new(dir.path().to_path_buf()));
+    store.initialize().unwrap();
+
+    let home_id = SmartHomeManager::with_store(store.clone())
+        .create_home("Home".into(), None)
+        .unwrap();
+
+    let handles: Vec<_> = (0..8)
+        .map(|thread| {
+            let store = store.clone();
+            let home_id = home_id.clone();
+            thread::spawn(move || {
+                let mut created = 0;
+                for room in 0..5 {
+                    let manager = SmartHomeManager::with_store(store.clone());
+                    let name = format!("Room {thread}-{room}");
+                    if manager.create_room(home_id.clone(), name, None).is_ok() {
+                        created += 1;
+                    }
+                }
+                created
+            })
+        })
+        .collect();
+
+    let created: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+    let home = SmartHomeManager::with_store(store.clone())
+        .find_home_by_id(&home_id)
+        .unwrap();
+
+    assert!(created > 0);
+    assert_eq!(home.rooms.len(), created);
+    assert!(!dir.path().join(".smart-home/smart-home.json.tmp").exists());
+}
+
+#[test]
+fn legacy_state_file_is_loaded() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = dir.path().join(".smart-home");
+    fs::create_dir_all(&repo).unwrap();
+    fs::write(
+        repo.join("smart-home.json"),
+        r#"[{"id":"home_abcde","name":"Legacy","description":null,"rooms":[]}]"#,
+    )
+    .unwrap();
+
+    let store = JsonFileStore::new(dir.path().to_path_buf());
+    let loaded = store.load().unwrap();
+    assert_eq!(loaded.revision, 0);
+    assert_eq!(loaded.state.as_ref().unwrap()[0].name, "Legacy");
+
+    assert_eq!(store.save(&loaded.state, 0).unwrap(), 1);
+    assert!(store.save(&loaded.state, 0).is_err());
+    assert_eq!(store.load().unwrap().revision, 1);
+}
+
+#[test]
+fn cached_state_is_reloaded_after_external_change() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().to_path_buf();
+    JsonFileStore::new(root.clone()).initialize().unwrap();
+
+    // Two managers with their own stores imitate two different processes
+    let reader = SmartHomeManager::with_store(Arc::new(JsonFileStore::new(root.clone())));
+    let writer = SmartHomeManager::with_store(Arc::new(JsonFileStore::new(root)));
+
+    let home_id = writer.create_home("Home".into(), None).unwrap();
+    assert_eq!(reader.find_home_by_id(&home_id).unwrap().rooms.len(), 0);
+
+    let room_id = writer
+        .create_room(home_id.clone(), "Hall".into(), None)
+        .unwrap();
+    assert_eq!(reader.find_home_by_room_id(&room_id).unwrap().id, home_id);
+    assert_eq!(reader.list_all_rooms().unwrap().len(), 1);
+}
@@ -0,0 +1,197 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use hw_008::cli::DeviceType;
+use hw_008::entities::manager::{
+    CreateFunctions, FindFunctions, InMemoryStore, JsonFileStore, SmartHomeManager, StateStore,
+};
+use hw_008::server::{TcpServer, REMOTE_PAGE_SIZE};
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+fn connect(port: u16) -> TcpStream {
+    for _ in 0..50 {
+        if let Ok(stream) = TcpStream::connect(("127.0.0.1", port)) {
+            return stream;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    panic!("Unable connect to the server");
+}
+
+/// Sends the command and reads the single reply framed by its length
+fn send(stream: &mut TcpStream, command: &str) -> String {
+    stream.write_all(command.as_bytes()).unwrap();
+
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len).unwrap();
+    let mut reply = vec![0u8; u32::from_be_bytes(len) as usize];
+    stream.read_exact(&mut reply).unwrap();
+    String::from_utf8(reply).unwrap()
+}
+
+#[test]
+fn device_state_change_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().to_path_buf();
+    let store = JsonFileStore::new(root.clone());
+    store.initialize().unwrap();
+
+    let manager = Arc::new(SmartHomeManager::with_store(Arc::new(store)));
+    let home_id = manager.create_home("Home".into(), None).unwrap();
+    let room_id = manager.create_room(home_id, "Hall".into(), None).unwrap();
+    let socket_id = manager
+        .create_device(DeviceType::SOCKET, room_id, "Lamp".into(), None)
+        .unwrap();
+
+    let port = free_port();
+    TcpServer::start("127.0.0.1".into(), port, manager);
+    let mut stream = connect(port);
+    assert_eq!(send(&mut stream, "handshake").trim(), "handshake");
+
+    let reply = send(
+        &mut stream,
+        &format!("status device -i {socket_id} --enable true"),
+    );
+    assert!(reply.starts_with(&format!("{socket_id}: on")), "{reply}");
+    send(&mut stream, "exit");
+
+    // the state is persisted, so it's seen by anyone who reads the repository later
+    let device = SmartHomeManager::new(root)
+        .find_device_by_id(&socket_id)
+        .unwrap();
+    assert!(device.state().on);
+    assert!(device.state().changed_at.is_some());
+    assert!(device.to_string().contains("Enabled"));
+}
+
+#[test]
+fn remote_lists_are_paged() {
+    let manager = Arc::new(SmartHomeManager::with_store(Arc::new(
+        InMemoryStore::default(),
+    )));
+    let homes: Vec<String> = (0..REMOTE_PAGE_SIZE + 5)
+        .map(|i| manager.create_home(format!("Home {i}"), None).unwrap())
+        .collect();
+
+    let port = free_port();
+    TcpServer::start("127.0.0.1".into(), port, manager);
+    let mut stream = connect(port);
+    send(&mut stream, "handshake");
+
+    let reply = send(&mut stream, "list homes");
+    let lines: Vec<&str> = reply.lines().collect();
+    assert_eq!(lines.len(), REMOTE_PAGE_SIZE + 1);
+    assert_eq!(lines[0], homes[0]);
+    assert_eq!(
+        lines[REMOTE_PAGE_SIZE],
+        format!("# 1-{REMOTE_PAGE_SIZE} of {}", homes.len())
+    );
+
+    let reply = send(
+        &mut stream,
+        &format!("list homes --offset {REMOTE_PAGE_SIZE}"),
+    );
+    let lines: Vec<&str> = reply.lines().collect();
+    assert_eq!(lines.len(), 6);
+    assert_eq!(lines[0], homes[REMOTE_PAGE_SIZE]);
+    send(&mut stream, "exit");
+}
+
+#[test]
+fn validation_errors_have_codes() {
+    let manager = Arc::new(SmartHomeManager::with_store(Arc::new(
+        InMemoryStore::default(),
+    )));
+    manager.create_home("Cottage".into(), None).unwrap();
+
+    let port = free_port();
+    TcpServer::start("127.0.0.1".into(), port, manager);
+    let mut stream = connect(port);
+    send(&mut stream, "handshake");
+
+    let reply = send(&mut stream, "new home -n Cottage");
+    assert_eq!(
+        reply,
+        "[duplicate-name] The name 'Cottage' is used by another home already"
+    );
+    let reply = send(&mut stream, "new home -n Attic/Loft");
+    assert!(reply.starts_with("[invalid-character] "), "{reply}");
+    send(&mut stream, "exit");
+}
+
+#[test]
+fn light_is_dimmed_remotely() {
+    let manager = Arc::new(SmartHomeManager::with_store(Arc::new(
+        InMemoryStore::default(),
+    )));
+    let home = manager.create_home("Flat".into(), None).unwrap();
+    let room = manager.create_room(home, "Hall".into(), None).unwrap();
+    manager
+        .create_device(DeviceType::LIGHT, room, "Lamp".into(), None)
+        .unwrap();
+
+    let port = free_port();
+    TcpServer::start("127.0.0.1".into(), port, manager);
+    let mut stream = connect(port);
+    send(&mut stream, "handshake");
+
+    let reply = send(&mut stream, "light -i Flat/Hall/Lamp -b 25 -t 2");
+    assert!(reply.contains("Brightness: 25%"), "{reply}");
+    send(&mut stream, "exit");
+}
+
+#[test]
+fn thermostat_target_is_set_remotely() {
+    let manager = Arc::new(SmartHomeManager::with_store(Arc::new(
+        InMemoryStore::default(),
+    )));
+    let home = manager.create_home("Flat".into(), None).unwrap();
+    let room = manager.create_room(home, "Hall".into(), None).unwrap();
+    manager
+        .create_device(DeviceType::THERMOSTAT, room, "Stat".into(), None)
+        .unwrap();
+
+    let port = free_port();
+    TcpServer::start("127.0.0.1".into(), port, manager);
+    let mut stream = connect(port);
+    send(&mut stream, "handshake");
+
+    let reply = send(&mut stream, "thermostat -i Flat/Hall/Stat -t 19 -m eco");
+    assert!(reply.contains("Target: 19°C"), "{reply}");
+    send(&mut stream, "exit");
+}
+
+#[test]
+fn server_options_are_rejected_remotely() {
+    let manager = Arc::new(SmartHomeManager::with_store(Arc::new(
+        InMemoryStore::default(),
+    )));
+    manager.create_home("Flat".into(), None).unwrap();
+
+    let port = free_port();
+    TcpServer::start("127.0.0.1".into(), port, manager);
+    let mut stream = connect(port);
+    send(&mut stream, "handshake");
+
+    let reply = send(&mut stream, "--store sqlite list homes");
+    assert!(
+        reply.contains("Not supported option in remote mode: --store"),
+        "{reply}"
+    );
+    let reply = send(&mut stream, "--repo /tmp list homes");
+    assert!(
+        reply.contains("Not supported option in remote mode: --repo"),
+        "{reply}"
+    );
+    send(&mut stream, "exit");
+}
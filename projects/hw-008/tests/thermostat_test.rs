@@ -0,0 +1,286 @@
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use clap::Parser;
+
+use hw_008::cli::{Arguments, CommandHandler, DeviceType};
+use hw_008::entities::devices::{
+    Decision, EnvironmentSensor, RoomTemperature, Thermostat, ThermostatMode, DECISION_LOG_SIZE,
+    ROOM_TEMPERATURE,
+};
+use hw_008::entities::manager::{
+    CreateFunctions, EntryKind, FindFunctions, HistoryFunctions, InMemoryStore, RemoveFunctions,
+    SmartHomeManager, ThermostatFunctions, ThermostatSettings, UndoFunctions, UpdateFunctions,
+};
+use hw_008::entities::Reportable;
+
+fn manager() -> Arc<SmartHomeManager> {
+    Arc::new(SmartHomeManager::with_store(Arc::new(
+        InMemoryStore::default(),
+    )))
+}
+
+#[test]
+fn decisions_follow_the_hysteresis() {
+    let mut thermostat = Thermostat::new("Hall");
+    let now = Utc::now();
+    assert_eq!(thermostat.decide(None, now), None);
+    assert_eq!(thermostat.decide(Some(20.7), now), None);
+
+    let decision = thermostat.decide(Some(20.4), now).unwrap();
+    assert!(decision.heating);
+    assert_eq!(decision.threshold, Some(20.5));
+    assert!(decision
+        .to_string()
+        .ends_with("20.4°C is below 20.5°C, the heaters are switched on"));
+    thermostat.record(decision);
+
+    // heating goes on up to the upper threshold
+    assert_eq!(thermostat.decide(Some(21.3), now), None);
+    let decision = thermostat.decide(Some(21.6), now).unwrap();
+    assert!(!decision.heating);
+    thermostat.record(decision);
+
+    // the eco mode keeps the temperature lower
+    thermostat.mode = ThermostatMode::Eco;
+    assert_eq!(thermostat.setpoint(), Some(18.0));
+    assert_eq!(thermostat.decide(Some(17.6), now), None);
+    thermostat.record(thermostat.decide(Some(17.4), now).unwrap());
+
+    // the switched off thermostat only switches the heaters off
+    thermostat.mode = ThermostatMode::Off;
+    let decision = thermostat.decide(Some(10.0), now).unwrap();
+    assert_eq!(decision.temperature, None);
+    assert!(!decision.heating);
+    thermostat.record(decision);
+    assert_eq!(thermostat.decide(Some(10.0), now), None);
+
+    for minutes in 0..DECISION_LOG_SIZE as i64 {
+        let at = now + Duration::minutes(minutes);
+        thermostat.record(Decision { at, ..decision });
+    }
+    assert_eq!(thermostat.decisions.len(), DECISION_LOG_SIZE);
+    assert!(thermostat
+        .report()
+        .unwrap()
+        .starts_with("Thermostat: Hall, Mode: off"));
+}
+
+#[test]
+fn thermostat_switches_heaters_of_its_room() {
+    let manager = manager();
+    let home = manager.create_home("Home".into(), None).unwrap();
+    let hall = manager
+        .create_room(home.clone(), "Hall".into(), None)
+        .unwrap();
+    let kitchen = manager.create_room(home, "Kitchen".into(), None).unwrap();
+    let create = |kind, room: &String, name: &str| {
+        manager
+            .create_device(kind, room.clone(), name.into(), None)
+            .unwrap()
+    };
+    let thermostat = create(DeviceType::THERMOSTAT, &hall, "Stat");
+    let thermometer = create(DeviceType::THERMOMETER, &hall, "T");
+    let heater = create(DeviceType::SOCKET, &hall, "Heater");
+    let kettle = create(DeviceType::SOCKET, &kitchen, "Kettle");
+    assert!(thermostat.starts_with("stat_"));
+
+    let link = |thermometer: &String, heater: &String| ThermostatSettings {
+        thermometer: Some(thermometer.clone()),
+        heaters: Some(vec![heater.clone()]),
+        ..ThermostatSettings::default()
+    };
+    assert!(manager
+        .configure_thermostat(&thermostat, link(&thermometer, &kettle))
+        .is_err());
+    assert!(manager
+        .configure_thermostat(&thermostat, link(&heater, &heater))
+        .is_err());
+    let target = ThermostatSettings {
+        target: Some(40.0),
+        ..ThermostatSettings::default()
+    };
+    assert!(manager.configure_thermostat(&thermostat, target).is_err());
+    let configured = manager
+        .configure_thermostat(&thermostat, link(&thermometer, &heater))
+        .unwrap();
+    assert_eq!(configured.heaters, vec![heater.clone()]);
+
+    // the simulated thermometer is always below the default target
+    let decisions = manager.regulate().unwrap();
+    assert_eq!(decisions.len(), 1);
+    assert!(decisions[0].1.heating);
+    assert!(manager.find_device_by_id(&heater).unwrap().state().on);
+    assert!(manager.regulate().unwrap().is_empty());
+
+    let off = ThermostatSettings {
+        mode: Some(ThermostatMode::Off),
+        ..ThermostatSettings::default()
+    };
+    manager.configure_thermostat(&thermostat, off).unwrap();
+    assert!(!manager.regulate().unwrap()[0].1.heating);
+    assert!(!manager.find_device_by_id(&heater).unwrap().state().on);
+
+    // the control loop isn't undone, the undo reverts the latest change of the user
+    let latest = manager.history().unwrap().pop().unwrap();
+    assert_eq!(latest.kind, EntryKind::Automatic);
+    manager.undo().unwrap();
+    assert!(!manager.find_device_by_id(&heater).unwrap().state().on);
+    let device = manager.find_device_by_id(&thermostat).unwrap();
+    let thermostat = device.downcast_ref::<Thermostat>().unwrap();
+    assert_eq!(thermostat.mode, ThermostatMode::Heat);
+    assert_eq!(thermostat.decisions.len(), 2);
+    manager.redo().unwrap();
+    assert!(manager.regulate().unwrap().is_empty());
+}
+
+#[test]
+fn room_follows_the_heaters() {
+    let start = Utc::now();
+    let mut room = RoomTemperature::new(start);
+    assert_eq!(room.celsius_at(start + Duration::hours(2)), 16.0);
+    room.set_heating(true, start + Duration::hours(2));
+    assert_eq!(room.celsius_at(start + Duration::hours(4)), 20.0);
+    assert_eq!(room.celsius_at(start + Duration::days(2)), 30.0);
+    room.heating = false;
+    assert_eq!(room.celsius_at(start + Duration::days(2)), 10.0);
+}
+
+#[test]
+fn heaters_are_switched_back_as_the_room_warms_up() {
+    let manager = manager();
+    let home = manager.create_home("Home".into(), None).unwrap();
+    let room = manager.create_room(home, "Hall".into(), None).unwrap();
+    let create = |kind, name: &str| {
+        manager
+            .create_device(kind, room.clone(), name.into(), None)
+            .unwrap()
+    };
+    let thermostat = create(DeviceType::THERMOSTAT, "Stat");
+    let sensor = create(DeviceType::ENVIRONMENT_SENSOR, "Air");
+    let heater = create(DeviceType::SOCKET, "Heater");
+    let settings = ThermostatSettings {
+        thermometer: Some(sensor.clone()),
+        heaters: Some(vec![heater.clone()]),
+        ..ThermostatSettings::default()
+    };
+    manager.configure_thermostat(&thermostat, settings).unwrap();
+    let measured = || {
+        let device = manager.find_device_by_id(&sensor).unwrap();
+        device.measure().unwrap()[0].value
+    };
+    assert!((measured() - ROOM_TEMPERATURE).abs() < 0.01);
+    // the simulated time is moved forward by moving the room back in time
+    let wait = |hours: i64| {
+        let mut home = manager.find_home_by_room_id(&room).unwrap();
+        let device = home.rooms[0]
+            .devices
+            .iter_mut()
+            .find(|device| device.id() == &sensor)
+            .unwrap();
+        let simulated = device.downcast_mut::<EnvironmentSensor>().unwrap();
+        simulated.room.as_mut().unwrap().at -= Duration::hours(hours);
+        manager.update_home_state(home).unwrap();
+    };
+    let heating = || manager.find_device_by_id(&heater).unwrap().state().on;
+
+    assert!(manager.regulate().unwrap()[0].1.heating);
+    assert!(heating());
+    wait(2);
+    assert!(measured() > 21.5);
+    assert!(!manager.regulate().unwrap()[0].1.heating);
+    assert!(!heating());
+    wait(2);
+    assert!(measured() < 20.5);
+    assert!(manager.regulate().unwrap()[0].1.heating);
+    assert!(heating());
+}
+
+#[test]
+fn devices_leaving_the_room_are_unlinked() {
+    let manager = manager();
+    let home = manager.create_home("Home".into(), None).unwrap();
+    let hall = manager
+        .create_room(home.clone(), "Hall".into(), None)
+        .unwrap();
+    let kitchen = manager.create_room(home, "Kitchen".into(), None).unwrap();
+    let create = |kind, name: &str| {
+        manager
+            .create_device(kind, hall.clone(), name.into(), None)
+            .unwrap()
+    };
+    let thermostat = create(DeviceType::THERMOSTAT, "Stat");
+    let thermometer = create(DeviceType::THERMOMETER, "T");
+    let left = create(DeviceType::SOCKET, "Left");
+    let right = create(DeviceType::SOCKET, "Right");
+    let settings = ThermostatSettings {
+        thermometer: Some(thermometer.clone()),
+        heaters: Some(vec![left.clone(), right.clone()]),
+        ..ThermostatSettings::default()
+    };
+    manager.configure_thermostat(&thermostat, settings).unwrap();
+    let links = || {
+        let device = manager.find_device_by_id(&thermostat).unwrap();
+        let thermostat = device.downcast_ref::<Thermostat>().unwrap();
+        (thermostat.thermometer.clone(), thermostat.heaters.clone())
+    };
+
+    manager.move_device(&left, &kitchen).unwrap();
+    assert_eq!(links(), (Some(thermometer.clone()), vec![right.clone()]));
+    manager.remove_device(&thermometer).unwrap();
+    assert_eq!(links(), (None, vec![right.clone()]));
+    // the undo of the removal brings the link back together with the device
+    manager.undo().unwrap();
+    assert_eq!(links(), (Some(thermometer.clone()), vec![right.clone()]));
+
+    // the moved thermostat keeps nothing of its previous room
+    manager.move_device(&thermostat, &kitchen).unwrap();
+    assert_eq!(links(), (None, vec![]));
+}
+
+#[test]
+fn thermostat_command_sets_target() {
+    let manager = manager();
+    let home = manager.create_home("Home".into(), None).unwrap();
+    let room = manager.create_room(home, "Hall".into(), None).unwrap();
+    for (kind, name) in [
+        (DeviceType::THERMOSTAT, "Stat"),
+        (DeviceType::ENVIRONMENT_SENSOR, "Air"),
+        (DeviceType::SOCKET, "Left"),
+        (DeviceType::SOCKET, "Right"),
+    ] {
+        manager
+            .create_device(kind, room.clone(), name.into(), None)
+            .unwrap();
+    }
+    let run = |args: &[&str]| {
+        let mut output = Vec::new();
+        let args = Arguments::parse_from(std::iter::once("cli").chain(args.iter().copied()));
+        CommandHandler::with_manager(&mut output, manager.clone()).process(args.command);
+        String::from_utf8(output).unwrap()
+    };
+
+    let output = run(&[
+        "thermostat",
+        "-i",
+        "Home/Hall/Stat",
+        "-t",
+        "22.5",
+        "-m",
+        "eco",
+        "--thermometer",
+        "Home/Hall/Air",
+        "--heater",
+        "Home/Hall/Left",
+        "--heater",
+        "Home/Hall/Right",
+    ]);
+    assert!(output.contains("Mode: eco"), "{output}");
+    assert!(output.contains("Target: 22.5°C ±0.5°C"), "{output}");
+    assert!(!output.contains("[Not linked]"), "{output}");
+
+    let output = run(&["thermostat", "-i", "Home/Hall/Stat"]);
+    assert!(output.starts_with("Nothing to change"), "{output}");
+    let output = run(&["thermostat", "-i", "Home/Hall/Air", "-t", "20"]);
+    assert!(output.contains("not a thermostat"), "{output}");
+}
@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use hw_008::cli::DeviceType;
+use hw_008::entities::manager::{
+    CreateFunctions, FindFunctions, HistoryFunctions, InMemoryStore, JsonFileStore,
+    RemoveFunctions, SmartHomeManager, StateStore, TransactionFunctions, UndoFunctions,
+};
+
+#[test]
+fn staged_changes_are_written_at_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = Arc::new(JsonFileStore::new(dir.path().to_path_buf()));
+    store.initialize().unwrap();
+    let manager = SmartHomeManager::with_store(store.clone());
+
+    let transaction = manager.begin_transaction().unwrap();
+    let home_id = transaction.create_home("Home".into(), None).unwrap();
+    let room_id = transaction
+        .create_room(home_id.clone(), "Kitchen".into(), None)
+        .unwrap();
+    transaction
+        .create_device(DeviceType::SOCKET, room_id.clone(), "Socket".into(), None)
+        .unwrap();
+
+    // the staged changes are visible inside the transaction only
+    assert!(transaction.find_room_by_id(&room_id).is_some());
+    assert!(manager.find_home_by_id(&home_id).is_none());
+    assert_eq!(store.revision().unwrap(), 0);
+
+    assert_eq!(manager.commit_transaction(transaction).unwrap(), 3);
+    assert_eq!(store.revision().unwrap(), 1);
+    let home = manager.find_home_by_id(&home_id).unwrap();
+    assert_eq!(home.rooms[0].devices.len(), 1);
+
+    // the whole transaction is a single change, so it's reverted by a single undo
+    assert_eq!(manager.history().unwrap().len(), 1);
+    manager.undo().unwrap();
+    assert!(manager.list_all_homes().unwrap().is_empty());
+}
+
+#[test]
+fn rolled_back_transaction_changes_nothing() {
+    let manager = SmartHomeManager::with_store(Arc::new(InMemoryStore::default()));
+
+    let transaction = manager.begin_transaction().unwrap();
+    transaction.create_home("Home".into(), None).unwrap();
+    // the invalid change is rejected right away, the transaction is still usable
+    assert!(transaction
+        .create_room("unknown".into(), "Kitchen".into(), None)
+        .is_err());
+    transaction.rollback();
+
+    assert!(manager.list_all_homes().unwrap().is_empty());
+    assert!(manager.history().unwrap().is_empty());
+}
+
+#[test]
+fn conflicting_transaction_is_not_written() {
+    let manager = SmartHomeManager::with_store(Arc::new(InMemoryStore::default()));
+    let home_id = manager.create_home("Home".into(), None).unwrap();
+    let other_id = manager.create_home("Other".into(), None).unwrap();
+
+    let transaction = manager.begin_transaction().unwrap();
+    transaction
+        .create_room(other_id.clone(), "Hall".into(), None)
+        .unwrap();
+    transaction
+        .create_room(home_id.clone(), "Kitchen".into(), None)
+        .unwrap();
+
+    // somebody else removes the home while the transaction is open
+    manager.remove_home(&home_id).unwrap();
+
+    let error = manager.commit_transaction(transaction).unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("Unable commit the transaction"));
+    assert!(manager.find_home_by_id(&other_id).unwrap().rooms.is_empty());
+    assert_eq!(manager.history().unwrap().len(), 3);
+}
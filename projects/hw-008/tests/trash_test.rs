@@ -0,0 +1,201 @@
+use std::sync::Arc;
+
+use hw_008::cli::{Arguments, CommandHandler, DeviceType};
+use hw_008::entities::manager::{
+    CreateFunctions, FindFunctions, HistoryFunctions, InMemoryStore, LabelFunctions,
+    RemoveFunctions, SmartHomeManager, TrashFunctions, UndoFunctions, SNAPSHOT_INTERVAL,
+    TRASH_RETENTION_DAYS,
+};
+use hw_008::entities::Labels;
+
+use clap::Parser;
+
+fn manager() -> Arc<SmartHomeManager> {
+    Arc::new(SmartHomeManager::with_store(Arc::new(
+        InMemoryStore::default(),
+    )))
+}
+
+#[test]
+fn removed_home_is_restored_with_same_ids() {
+    let manager = manager();
+    let home = manager.create_home("Home".into(), None).unwrap();
+    let room = manager
+        .create_room(home.clone(), "Kitchen".into(), None)
+        .unwrap();
+    let device = manager
+        .create_device(DeviceType::SOCKET, room.clone(), "Kettle".into(), None)
+        .unwrap();
+
+    manager.remove_home(&home).unwrap();
+    let trash = manager.trash().unwrap();
+    assert_eq!(trash.len(), 1);
+    assert_eq!(trash[0].entity.id(), home);
+    assert_eq!(trash[0].entity.affected().len(), 3);
+
+    manager.restore_from_trash(&home).unwrap();
+    assert!(manager.trash().unwrap().is_empty());
+    assert_eq!(
+        manager.find_device_by_id(&device).unwrap().id().clone(),
+        device
+    );
+    assert_eq!(manager.find_room_by_id(&room).unwrap().name, "Kitchen");
+}
+
+#[test]
+fn parent_is_restored_first() {
+    let manager = manager();
+    let home = manager.create_home("Home".into(), None).unwrap();
+    let room = manager
+        .create_room(home.clone(), "Kitchen".into(), None)
+        .unwrap();
+
+    manager.remove_room(&room).unwrap();
+    manager.remove_home(&home).unwrap();
+    assert_eq!(manager.trash().unwrap().len(), 2);
+
+    let error = manager.restore_from_trash(&room).unwrap_err();
+    assert!(error.to_string().contains("restore it first"), "{error}");
+
+    manager.restore_from_trash(&home).unwrap();
+    manager.restore_from_trash(&room).unwrap();
+    assert!(manager.find_room_by_id(&room).is_some());
+
+    // the undone removal takes the entity out of the trash as well
+    manager.remove_room(&room).unwrap();
+    manager.undo().unwrap();
+    assert!(manager.trash().unwrap().is_empty());
+
+    // the undone creation was never removed by anyone
+    let created = manager.create_home("Created".into(), None).unwrap();
+    manager.undo().unwrap();
+    assert!(manager.find_home_by_id(&created).is_none());
+    assert!(manager.trash().unwrap().is_empty());
+}
+
+#[test]
+fn hidden_entities_are_not_listed() {
+    let manager = manager();
+    let first = manager.create_home("First".into(), None).unwrap();
+    let second = manager.create_home("Second".into(), None).unwrap();
+    manager.remove_home(&first).unwrap();
+    manager.remove_home(&second).unwrap();
+
+    let hidden = manager
+        .hide_from_trash(None, Some(chrono::Duration::days(1)))
+        .unwrap();
+    assert!(hidden.is_empty());
+
+    let hidden = manager.hide_from_trash(Some(&first), None).unwrap();
+    assert_eq!(hidden.len(), 1);
+    let trash = manager.trash().unwrap();
+    assert_eq!(trash.len(), 1);
+    assert_eq!(trash[0].entity.id(), second);
+
+    assert!(manager.restore_from_trash(&first).is_err());
+    assert!(manager.hide_from_trash(Some(&first), None).is_err());
+    assert_eq!(manager.hide_from_trash(None, None).unwrap().len(), 1);
+    assert!(manager.trash().unwrap().is_empty());
+}
+
+#[test]
+fn expired_entities_are_purged() {
+    let manager = manager();
+    let home = manager
+        .create_home("Home".into(), Some("Secret home".into()))
+        .unwrap();
+    let room = manager
+        .create_room(home.clone(), "Kitchen".into(), Some("Secret room".into()))
+        .unwrap();
+    let device = manager
+        .create_device(DeviceType::SOCKET, room.clone(), "Kettle".into(), None)
+        .unwrap();
+    let kept = manager.create_home("Kept".into(), None).unwrap();
+    // the state with the home goes to a snapshot as well
+    for n in 0..SNAPSHOT_INTERVAL {
+        let labels = Labels::from([("secret".to_string(), n.to_string())]);
+        manager.label(&device, labels, &[]).unwrap();
+    }
+    let before_removal = chrono::Utc::now();
+    manager.remove_home(&home).unwrap();
+
+    let now = chrono::Utc::now();
+    assert!(manager.purge_trash(now).unwrap().is_empty());
+    let expired = now + chrono::Duration::days(TRASH_RETENTION_DAYS + 1);
+    let purged = manager.purge_trash(expired).unwrap();
+    assert_eq!(purged.len(), 1);
+    assert_eq!(purged[0].entity.id(), home);
+    assert!(manager.purge_trash(expired).unwrap().is_empty());
+
+    let history = serde_json::to_string(&manager.history().unwrap()).unwrap();
+    assert!(!history.contains("Secret"), "{history}");
+    assert!(!history.contains("secret"), "{history}");
+    assert!(manager
+        .history()
+        .unwrap()
+        .last()
+        .unwrap()
+        .inverse
+        .is_empty());
+
+    // the history is replayed the same way, only the content is gone
+    let purged = manager
+        .find_home_at(&home, before_removal)
+        .unwrap()
+        .unwrap();
+    assert_eq!(purged.name, "Home");
+    assert_eq!(purged.description, None);
+    assert_eq!(purged.rooms[0].description, None);
+    assert!(purged.rooms[0].devices[0].labels().is_empty());
+    assert!(manager.find_home_by_id(&kept).is_some());
+}
+
+#[test]
+fn trash_commands() {
+    let manager = manager();
+    let home = manager.create_home("Home".into(), None).unwrap();
+    let room = manager
+        .create_room(home.clone(), "Kitchen".into(), None)
+        .unwrap();
+    let device = manager
+        .create_device(DeviceType::SOCKET, room.clone(), "Kettle".into(), None)
+        .unwrap();
+    let run = |args: &[&str]| {
+        let mut output = Vec::new();
+        let args = Arguments::parse_from(std::iter::once("cli").chain(args.iter().copied()));
+        CommandHandler::with_manager(&mut output, manager.clone()).process(args.command);
+        String::from_utf8(output).unwrap()
+    };
+
+    let output = run(&["remove", "home", "-i", "Home", "--dry-run"]);
+    assert_eq!(
+        output,
+        [
+            format!("home {home} (Home)"),
+            format!("  room {room} (Kitchen)"),
+            format!("    device {device} (Kettle)"),
+            "Nothing is removed in the dry run mode".to_string(),
+        ]
+        .join("\n")
+    );
+    assert!(manager.find_home_by_id(&home).is_some());
+
+    assert_eq!(run(&["trash", "list"]), "The trash is empty");
+    run(&["remove", "home", "-i", "Home"]);
+    let output = run(&["trash", "list"]);
+    assert!(
+        output.starts_with(&format!("home {home} (Home), 2 nested, removed ")),
+        "{output}"
+    );
+
+    assert_eq!(
+        run(&["trash", "restore", &home]),
+        format!("Restored {home}")
+    );
+    assert!(manager.find_device_by_id(&device).is_some());
+
+    run(&["remove", "device", "-i", "Home/Kitchen/Kettle"]);
+    assert_eq!(run(&["trash", "hide"]), "Hidden 1 entities");
+    assert_eq!(run(&["trash", "list"]), "The trash is empty");
+    assert_eq!(run(&["trash", "purge"]), "Purged 0 entities");
+}
@@ -0,0 +1,163 @@
+use std::sync::Arc;
+
+use hw_008::cli::{Arguments, CommandHandler, DeviceType};
+use hw_008::entities::devices::{Device, Socket};
+use hw_008::entities::house::{Home, Room};
+use hw_008::entities::manager::{
+    CreateFunctions, InMemoryStore, SmartHomeManager, UpdateFunctions,
+};
+use hw_008::entities::{NamingRules, ValidationError};
+
+use clap::Parser;
+
+fn manager() -> Arc<SmartHomeManager> {
+    Arc::new(SmartHomeManager::with_store(Arc::new(
+        InMemoryStore::default(),
+    )))
+}
+
+fn validation_error(error: anyhow::Error) -> ValidationError {
+    error.downcast::<ValidationError>().unwrap()
+}
+
+#[test]
+fn builders_check_names() {
+    let error = Room::build().with_name("  ").build().unwrap_err();
+    assert_eq!(error, ValidationError::EmptyName);
+    assert_eq!(error.code(), "empty-name");
+
+    let long = "x".repeat(65);
+    let error = Home::build().with_name(&long).build().unwrap_err();
+    assert_eq!(error.code(), "name-too-long");
+
+    let error = Room::build()
+        .with_name("Kitchen")
+        .with_device(Device::new(Socket::new("Kettle")))
+        .with_device(Device::new(Socket::new("Kettle")))
+        .build()
+        .unwrap_err();
+    assert_eq!(
+        error,
+        ValidationError::DuplicateName {
+            name: "Kettle".into(),
+            parent: Some("Kitchen".into())
+        }
+    );
+
+    let error = Home::build()
+        .with_name("Home")
+        .with_room(Room::build().with_name("Hall").build().unwrap())
+        .with_room(Room::build().with_name("Hall").build().unwrap())
+        .build()
+        .unwrap_err();
+    assert_eq!(error.code(), "duplicate-name");
+}
+
+#[test]
+fn rooms_with_same_names_keep_their_devices() {
+    let hall = |device: &str| {
+        Room::build()
+            .with_name("Hall")
+            .with_device(Device::new(Socket::new(device)))
+            .build()
+            .unwrap()
+    };
+    let home = Home::build()
+        .with_name("Home")
+        .with_rooms(vec![hall("Lamp"), hall("Heater")])
+        .with_rules(NamingRules::permissive())
+        .build()
+        .unwrap();
+
+    let rooms = home.get_rooms();
+    let devices = home.get_devices(&rooms[1]).unwrap();
+    assert_eq!(devices[0].name(), "Heater");
+}
+
+#[test]
+fn create_functions_follow_rules() {
+    let manager = manager();
+    let home = manager.create_home("Home".into(), None).unwrap();
+    let room = manager
+        .create_room(home.clone(), "Hall".into(), None)
+        .unwrap();
+    manager
+        .create_device(DeviceType::SOCKET, room.clone(), "Lamp".into(), None)
+        .unwrap();
+
+    let error = manager.create_home("Home".into(), None).unwrap_err();
+    assert_eq!(validation_error(error).code(), "duplicate-name");
+    let error = manager
+        .create_room(home.clone(), "Hall".into(), None)
+        .unwrap_err();
+    assert_eq!(
+        validation_error(error),
+        ValidationError::DuplicateName {
+            name: "Hall".into(),
+            parent: Some(home.clone())
+        }
+    );
+    let error = manager
+        .create_device(DeviceType::THERMOMETER, room.clone(), "Lamp".into(), None)
+        .unwrap_err();
+    assert_eq!(validation_error(error).code(), "duplicate-name");
+    let error = manager
+        .create_device(DeviceType::SOCKET, room.clone(), "Lamp\t2".into(), None)
+        .unwrap_err();
+    assert_eq!(
+        validation_error(error),
+        ValidationError::InvalidCharacter {
+            name: "Lamp\t2".into(),
+            character: '\t'
+        }
+    );
+
+    // the same name is fine in another parent, and the renames follow the rules as well
+    let other = manager.create_home("Cottage".into(), None).unwrap();
+    manager
+        .create_room(other.clone(), "Hall".into(), None)
+        .unwrap();
+    let error = manager.rename(&other, "Home".into()).unwrap_err();
+    assert_eq!(validation_error(error).code(), "duplicate-name");
+    manager.rename(&other, "Cottage".into()).unwrap();
+}
+
+#[test]
+fn rules_are_configurable() {
+    let rules = NamingRules {
+        max_length: Some(8),
+        allowed_characters: Some("/".into()),
+        ..NamingRules::default()
+    };
+    let manager =
+        SmartHomeManager::with_store(Arc::new(InMemoryStore::default())).with_naming_rules(rules);
+
+    manager.create_home("Attic/1".into(), None).unwrap();
+    let error = manager.create_home("Attic 1 2 3".into(), None).unwrap_err();
+    assert_eq!(
+        validation_error(error),
+        ValidationError::NameTooLong {
+            name: "Attic 1 2 3".into(),
+            max_length: 8
+        }
+    );
+
+    let manager = SmartHomeManager::with_store(Arc::new(InMemoryStore::default()))
+        .with_naming_rules(NamingRules::permissive());
+    manager.create_home("Home".into(), None).unwrap();
+    manager.create_home("Home".into(), None).unwrap();
+}
+
+#[test]
+fn cli_prints_errors_without_codes() {
+    let manager = manager();
+    manager.create_home("Home".into(), None).unwrap();
+
+    let mut output = Vec::new();
+    let args = Arguments::parse_from(["cli", "new", "home", "-n", "Home"]);
+    CommandHandler::with_manager(&mut output, manager).process(args.command);
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "The name 'Home' is used by another home already"
+    );
+}